@@ -1,18 +1,263 @@
 use anyhow::{Context, Result, bail};
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
 /// Default timeout for Aseprite process execution (60 seconds).
-const PROCESS_TIMEOUT: Duration = Duration::from_secs(60);
+/// Overridden by the ASEPRITE_TIMEOUT_SECS env var.
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// Default number of Aseprite processes allowed to run at once.
+/// Overridden by the ASEPRITE_MAX_CONCURRENCY env var.
+const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Number of script lines included in a failed run's error result.
+const SCRIPT_EXCERPT_LINES: usize = 40;
+
+/// Upper bound for any process timeout, whether configured or passed per call (30 minutes).
+pub const MAX_TIMEOUT_SECS: u64 = 30 * 60;
+
+tokio::task_local! {
+    /// Per-call timeout set by tools that accept a `timeout_secs` parameter.
+    static CALL_TIMEOUT: Duration;
+    /// Cancellation token of the MCP request being served, set by the server's `call_tool`.
+    static CALL_CANCEL: CancellationToken;
+    /// Set during a dry run: collects the processes that would have been started.
+    static DRY_RUN: Arc<Mutex<Vec<serde_json::Value>>>;
+    /// Set while recording run metadata: collects exit code, duration and stderr of each process.
+    static RUN_LOG: Arc<Mutex<Vec<serde_json::Value>>>;
+}
+
+/// Run `fut` with its Aseprite processes limited to `secs` (1 to `MAX_TIMEOUT_SECS`) instead of
+/// the default timeout. With `None`, the default applies.
+pub async fn with_timeout<T, F: Future<Output = Result<T, String>>>(secs: Option<u64>, fut: F) -> Result<T, String> {
+    match secs {
+        Some(secs) => {
+            let secs = crate::utils::check_range("timeout_secs", secs, 1..=MAX_TIMEOUT_SECS, false)?;
+            CALL_TIMEOUT.scope(Duration::from_secs(secs), fut).await
+        }
+        None => fut.await,
+    }
+}
+
+/// Run `fut` so that cancelling `ct` kills any Aseprite process it is running (or waiting to
+/// start), making that run fail with a "cancelled" error.
+pub async fn with_cancellation<F: Future>(ct: CancellationToken, fut: F) -> F::Output {
+    CALL_CANCEL.scope(ct, fut).await
+}
+
+/// Run `fut` as a dry run: instead of starting Aseprite, each run is recorded (its Lua script or
+/// CLI arguments and target file) and fails with a "dry run" error, so the tool stops there.
+/// Returns the output of `fut` and the recorded runs.
+pub async fn dry_run<F: Future>(fut: F) -> (F::Output, Vec<serde_json::Value>) {
+    let planned = Arc::new(Mutex::new(Vec::new()));
+    let output = DRY_RUN.scope(planned.clone(), fut).await;
+    let planned = std::mem::take(&mut *planned.lock().unwrap_or_else(|e| e.into_inner()));
+    (output, planned)
+}
+
+/// Run `fut`, collecting the exit code, duration and stderr of every Aseprite process it starts.
+pub async fn record_runs<F: Future>(fut: F) -> (F::Output, Vec<serde_json::Value>) {
+    let runs = Arc::new(Mutex::new(Vec::new()));
+    let output = RUN_LOG.scope(runs.clone(), fut).await;
+    let runs = std::mem::take(&mut *runs.lock().unwrap_or_else(|e| e.into_inner()));
+    (output, runs)
+}
+
+/// Add the size of the script that ran to the record of the latest process (see `record_runs`).
+fn note_script_bytes(bytes: u64) {
+    RUN_LOG
+        .try_with(|runs| {
+            if let Some(last) = runs.lock().unwrap_or_else(|e| e.into_inner()).last_mut() {
+                last["script_bytes"] = serde_json::json!(bytes);
+            }
+        })
+        .ok();
+}
+
+/// Whether the current tool call is a dry run (see `dry_run`).
+pub fn is_dry_run() -> bool {
+    DRY_RUN.try_with(|_| ()).is_ok()
+}
+
+/// In a dry run, record `run` and return the error that stops the tool.
+fn plan_run(run: serde_json::Value) -> Result<()> {
+    match DRY_RUN.try_with(|planned| planned.lock().unwrap_or_else(|e| e.into_inner()).push(run)) {
+        Ok(()) => bail!("Dry run: Aseprite was not started"),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Temp files older than this are left over from crashed or killed runs and are deleted at startup.
+const TEMP_FILE_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Delete temp scripts and outputs (`mcp_*`) older than `TEMP_FILE_MAX_AGE`, including scripts
+/// kept by ASEPRITE_DEBUG_SCRIPTS. Other files in the directory are never touched.
+fn sweep_temp_dir(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut removed = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !entry.file_name().to_string_lossy().starts_with("mcp_") {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| m.modified().ok())
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > TEMP_FILE_MAX_AGE);
+        if stale && std::fs::remove_file(entry.path()).is_ok() {
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        info!("Removed {} stale temp files from {}", removed, dir.display());
+    }
+}
+
+/// Flatpak application ID of Aseprite.
+#[cfg(target_os = "linux")]
+const FLATPAK_APP_ID: &str = "org.aseprite.Aseprite";
+
+/// How to start Aseprite: a program plus fixed leading arguments, e.g. `flatpak run ...` or
+/// ASEPRITE_ARGS.
+#[derive(Debug, Clone)]
+pub struct AsepriteCommand {
+    pub program: PathBuf,
+    pub base_args: Vec<String>,
+}
+
+impl AsepriteCommand {
+    pub fn path(program: PathBuf) -> Self {
+        Self { program, base_args: Vec::new() }
+    }
+
+    /// A new process command with the base arguments already added.
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.base_args);
+        cmd
+    }
+}
+
+impl std::fmt::Display for AsepriteCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program.display())?;
+        for arg in &self.base_args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolve a macOS `.app` bundle to the executable inside `Contents/MacOS`; other paths are
+/// returned unchanged.
+fn app_bundle_executable(path: &Path) -> PathBuf {
+    if path.extension().is_some_and(|ext| ext == "app") && path.is_dir() {
+        let macos = path.join("Contents").join("MacOS");
+        let named = macos.join("aseprite");
+        if named.is_file() {
+            return named;
+        }
+        if let Ok(entries) = std::fs::read_dir(&macos)
+            && let Some(first) = entries.filter_map(|e| e.ok()).map(|e| e.path()).find(|p| p.is_file())
+        {
+            return first;
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Look up an executable by name in the directories on PATH.
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    let names: Vec<String> = if cfg!(windows) && Path::new(name).extension().is_none() {
+        vec![format!("{}.exe", name)]
+    } else {
+        vec![name.to_string()]
+    };
+    std::env::split_paths(&path_var)
+        .flat_map(|dir| names.iter().map(move |n| dir.join(n)).collect::<Vec<_>>())
+        .find(|candidate| candidate.is_file())
+}
+
+/// Common install locations for the current platform, in search order. Entries may be `.app`
+/// bundles (see `app_bundle_executable`).
+fn install_candidates() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let mut candidates: Vec<PathBuf> = Vec::new();
+
+    #[cfg(target_os = "windows")]
+    {
+        candidates.extend(
+            [
+                r"C:\Program Files\Aseprite\Aseprite.exe",
+                r"C:\Program Files (x86)\Aseprite\Aseprite.exe",
+                r"C:\Program Files (x86)\Steam\steamapps\common\Aseprite\Aseprite.exe",
+                r"C:\Program Files\Steam\steamapps\common\Aseprite\Aseprite.exe",
+            ]
+            .map(PathBuf::from),
+        );
+        if let Some(appdata) = std::env::var_os("APPDATA") {
+            candidates.push(PathBuf::from(appdata).join(r"itch\apps\aseprite\Aseprite.exe"));
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        candidates.extend([
+            PathBuf::from("/Applications/Aseprite.app"),
+            home.join("Applications/Aseprite.app"),
+            home.join("Library/Application Support/Steam/steamapps/common/Aseprite/Aseprite.app"),
+            home.join("Library/Application Support/itch/apps/aseprite/Aseprite.app"),
+            PathBuf::from("/opt/homebrew/bin/aseprite"),
+            PathBuf::from("/usr/local/bin/aseprite"),
+        ]);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        candidates.extend([
+            home.join(".steam/debian-installation/steamapps/common/Aseprite/aseprite"),
+            home.join(".steam/steam/steamapps/common/Aseprite/aseprite"),
+            home.join(".local/share/Steam/steamapps/common/Aseprite/aseprite"),
+            home.join(".config/itch/apps/aseprite/aseprite"),
+            PathBuf::from("/snap/bin/aseprite"),
+            PathBuf::from("/usr/local/bin/aseprite"),
+            PathBuf::from("/usr/bin/aseprite"),
+        ]);
+    }
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    let _ = &home;
+
+    candidates
+}
 
 /// Handles execution of Aseprite CLI commands and Lua scripts.
 #[derive(Debug)]
 pub struct AsepriteRunner {
-    exe_path: PathBuf,
+    exe: AsepriteCommand,
     temp_dir: PathBuf,
+    /// Default process timeout in seconds (ASEPRITE_TIMEOUT_SECS, or 60); changeable via `set_config`.
+    timeout_secs: AtomicU64,
+    /// Limits concurrent child processes (ASEPRITE_MAX_CONCURRENCY, or 4).
+    process_slots: Semaphore,
+    /// Keep the temp scripts of failed runs on disk (ASEPRITE_DEBUG_SCRIPTS=1).
+    debug_scripts: bool,
 }
 
 /// Output from an Aseprite CLI or script execution.
@@ -21,6 +266,14 @@ pub struct ScriptOutput {
     pub stdout: String,
     pub stderr: String,
     pub success: bool,
+    /// Process exit code (`None` if it was ended by a signal).
+    pub exit_code: Option<i32>,
+    /// Wall time of the process, excluding any wait for a free process slot.
+    pub duration: Duration,
+    /// For failed Lua runs: the first lines of the generated script, numbered.
+    pub script_excerpt: Option<String>,
+    /// For failed Lua runs with ASEPRITE_DEBUG_SCRIPTS=1: where the script was kept.
+    pub script_path: Option<PathBuf>,
 }
 
 impl ScriptOutput {
@@ -40,109 +293,199 @@ impl ScriptOutput {
             } else {
                 "Unknown error occurred"
             };
-            format!("Error: {}", err_msg)
+            let mut text = format!("Error: {}", err_msg);
+            let code = self.exit_code.map_or("none (killed)".to_string(), |c| c.to_string());
+            text.push_str(&format!(
+                "\n\nExit code: {}, after {:.2}s",
+                code,
+                self.duration.as_secs_f64()
+            ));
+            if let Some(ref path) = self.script_path {
+                text.push_str(&format!("\nScript kept at: {}", path.display()));
+            }
+            if let Some(ref excerpt) = self.script_excerpt {
+                text.push_str(&format!("\nGenerated script:\n{}", excerpt));
+            }
+            text
         }
     }
+
+    /// Attach the script that produced a failed run, so errors can be traced to a line.
+    fn attach_script(&mut self, lua_code: &str, kept_at: Option<PathBuf>) {
+        self.script_excerpt = Some(script_excerpt(lua_code));
+        self.script_path = kept_at;
+    }
+}
+
+/// The first `SCRIPT_EXCERPT_LINES` lines of a script, numbered.
+fn script_excerpt(lua_code: &str) -> String {
+    let total = lua_code.lines().count();
+    let mut excerpt: Vec<String> = lua_code
+        .lines()
+        .take(SCRIPT_EXCERPT_LINES)
+        .enumerate()
+        .map(|(i, line)| format!("{:>4} | {}", i + 1, line))
+        .collect();
+    if total > SCRIPT_EXCERPT_LINES {
+        excerpt.push(format!("     ... ({} more lines)", total - SCRIPT_EXCERPT_LINES));
+    }
+    excerpt.join("\n")
+}
+
+/// Add the script to a run that couldn't finish (it failed to start or timed out), as failed
+/// runs' outputs get it. Cancellations are left as they are.
+fn with_script(error: anyhow::Error, lua_code: &str, kept_at: Option<&Path>) -> anyhow::Error {
+    let message = format!("{:#}", error);
+    if message.starts_with("Cancelled") {
+        return error;
+    }
+    let kept = kept_at.map_or(String::new(), |path| format!("\nScript kept at: {}", path.display()));
+    anyhow::anyhow!("{}{}\nGenerated script:\n{}", message, kept, script_excerpt(lua_code))
 }
 
 impl AsepriteRunner {
     /// Create a new AsepriteRunner, locating the Aseprite executable.
     pub fn new() -> Result<Self> {
-        let exe_path = Self::find_aseprite()?;
-        let temp_dir = std::env::temp_dir().join("aseprite_mcp");
-        // Temp dir creation is fine synchronous — only runs once at startup
-        std::fs::create_dir_all(&temp_dir)
-            .context("Failed to create temp directory for Aseprite scripts")?;
-        info!("Aseprite MCP: using executable at {}", exe_path.display());
-        Ok(Self { exe_path, temp_dir })
-    }
-
-    /// Locate the Aseprite executable on the system.
-    fn find_aseprite() -> Result<PathBuf> {
-        // 1. Check ASEPRITE_PATH environment variable
-        if let Ok(path) = std::env::var("ASEPRITE_PATH") {
-            let path = PathBuf::from(&path);
-            if path.exists() {
-                return Ok(path);
-            }
-            debug!("ASEPRITE_PATH={} does not exist, searching...", path.display());
-        }
+        Self::with_exe(Self::find_aseprite()?)
+    }
 
-        // 2. Platform-specific common paths
-        #[cfg(target_os = "windows")]
-        {
-            let candidates = [
-                r"C:\Program Files\Aseprite\Aseprite.exe",
-                r"C:\Program Files (x86)\Steam\steamapps\common\Aseprite\Aseprite.exe",
-                r"C:\Program Files\Steam\steamapps\common\Aseprite\Aseprite.exe",
-            ];
-            for p in &candidates {
-                let path = PathBuf::from(p);
-                if path.exists() {
-                    return Ok(path);
+    /// Create a runner that starts Aseprite as `exe`, with the other settings from the environment.
+    pub fn with_exe(exe: AsepriteCommand) -> Result<Self> {
+        let temp_dir = match std::env::var("ASEPRITE_TEMP_DIR") {
+            Ok(dir) if !dir.trim().is_empty() => PathBuf::from(dir.trim()),
+            _ => std::env::temp_dir().join("aseprite_mcp"),
+        };
+        // Temp dir creation is fine synchronous — only runs once at startup
+        std::fs::create_dir_all(&temp_dir).with_context(|| {
+            format!("Failed to create temp directory {} for Aseprite scripts", temp_dir.display())
+        })?;
+        sweep_temp_dir(&temp_dir);
+        info!("Aseprite MCP: using executable at {}", exe);
+        let timeout_secs = match std::env::var("ASEPRITE_TIMEOUT_SECS") {
+            Ok(value) => match value.trim().parse::<u64>() {
+                Ok(secs) if secs > 0 => secs.min(MAX_TIMEOUT_SECS),
+                _ => {
+                    warn!("Ignoring invalid ASEPRITE_TIMEOUT_SECS={:?}", value);
+                    DEFAULT_TIMEOUT_SECS
                 }
-            }
-            // Try `where` command
-            if let Ok(output) = std::process::Command::new("where")
-                .arg("aseprite")
-                .output()
-            {
-                if output.status.success() {
-                    let path_str = String::from_utf8_lossy(&output.stdout);
-                    if let Some(first_line) = path_str.lines().next() {
-                        let p = PathBuf::from(first_line.trim());
-                        if p.exists() {
-                            return Ok(p);
-                        }
-                    }
+            },
+            Err(_) => DEFAULT_TIMEOUT_SECS,
+        };
+        info!("Aseprite process timeout: {} seconds", timeout_secs);
+        let max_concurrency = match std::env::var("ASEPRITE_MAX_CONCURRENCY") {
+            Ok(value) => match value.trim().parse::<usize>() {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    warn!("Ignoring invalid ASEPRITE_MAX_CONCURRENCY={:?}", value);
+                    DEFAULT_MAX_CONCURRENCY
                 }
+            },
+            Err(_) => DEFAULT_MAX_CONCURRENCY,
+        };
+        info!("Aseprite max concurrent processes: {}", max_concurrency);
+        let debug_scripts = std::env::var("ASEPRITE_DEBUG_SCRIPTS").is_ok_and(|v| v == "1");
+        Ok(Self {
+            exe,
+            temp_dir,
+            timeout_secs: AtomicU64::new(timeout_secs),
+            process_slots: Semaphore::new(max_concurrency),
+            debug_scripts,
+        })
+    }
+
+    /// Locate the Aseprite executable on the system. Fails with the list of locations checked.
+    fn find_aseprite() -> Result<AsepriteCommand> {
+        let mut checked: Vec<String> = Vec::new();
+        let base_args: Vec<String> = std::env::var("ASEPRITE_ARGS")
+            .map(|args| args.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        // 1. ASEPRITE_PATH: an executable, a .app bundle, or a command name looked up on PATH
+        if let Ok(value) = std::env::var("ASEPRITE_PATH") {
+            let path = PathBuf::from(&value);
+            let found = if path.components().count() == 1 && !path.exists() {
+                find_on_path(&value)
+            } else {
+                Some(app_bundle_executable(&path)).filter(|p| p.is_file())
+            };
+            if let Some(program) = found {
+                return Ok(AsepriteCommand { program, base_args });
             }
+            checked.push(format!("ASEPRITE_PATH={}", value));
+            debug!("ASEPRITE_PATH={} does not exist, searching...", value);
         }
 
-        #[cfg(target_os = "macos")]
-        {
-            let path = PathBuf::from("/Applications/Aseprite.app/Contents/MacOS/aseprite");
-            if path.exists() {
-                return Ok(path);
+        // 2. PATH
+        if let Some(program) = find_on_path("aseprite") {
+            return Ok(AsepriteCommand::path(program));
+        }
+        checked.push("aseprite on PATH".to_string());
+
+        // 3. Platform-specific install locations
+        for candidate in install_candidates() {
+            let path = app_bundle_executable(&candidate);
+            if path.is_file() {
+                return Ok(AsepriteCommand::path(path));
             }
+            checked.push(candidate.display().to_string());
         }
 
+        // 4. Flatpak, which has to be started through `flatpak run`
         #[cfg(target_os = "linux")]
         {
-            if let Ok(output) = std::process::Command::new("which")
-                .arg("aseprite")
-                .output()
-            {
-                if output.status.success() {
-                    let path =
-                        String::from_utf8_lossy(&output.stdout).trim().to_string();
-                    let p = PathBuf::from(&path);
-                    if p.exists() {
-                        return Ok(p);
-                    }
-                }
-            }
-            let home = std::env::var("HOME").unwrap_or_default();
-            let steam_path = format!(
-                "{}/.steam/debian-installation/steamapps/common/Aseprite/aseprite",
-                home
-            );
-            let p = PathBuf::from(&steam_path);
-            if p.exists() {
-                return Ok(p);
+            let installed = std::process::Command::new("flatpak")
+                .args(["info", FLATPAK_APP_ID])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if installed {
+                // Temp scripts live under /tmp, which Flatpak apps don't see by default
+                return Ok(AsepriteCommand {
+                    program: PathBuf::from("flatpak"),
+                    base_args: ["run", "--filesystem=host", "--filesystem=/tmp", FLATPAK_APP_ID]
+                        .map(str::to_string)
+                        .to_vec(),
+                });
             }
+            checked.push(format!("flatpak app {}", FLATPAK_APP_ID));
         }
 
         bail!(
-            "Could not find Aseprite executable. \
+            "Could not find Aseprite executable. Checked:\n  - {}\n\
              Please set the ASEPRITE_PATH environment variable to the full path \
-             of the Aseprite executable."
+             of the Aseprite executable (or its .app bundle on macOS).",
+            checked.join("\n  - ")
         )
     }
 
+    /// How Aseprite is started (program and fixed leading arguments).
+    pub fn exe(&self) -> &AsepriteCommand {
+        &self.exe
+    }
+
+    /// Default process timeout in seconds.
+    pub fn timeout_secs(&self) -> u64 {
+        self.timeout_secs.load(Ordering::Relaxed)
+    }
+
+    /// Change the default process timeout for later runs (clamped to 1..=`MAX_TIMEOUT_SECS`).
+    pub fn set_timeout_secs(&self, secs: u64) {
+        self.timeout_secs.store(secs.clamp(1, MAX_TIMEOUT_SECS), Ordering::Relaxed);
+    }
+
+    /// Directory used for temporary scripts and intermediate files.
+    pub fn temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
     /// Generate a unique temporary script file path.
     fn temp_script_path(&self) -> PathBuf {
-        use std::sync::atomic::{AtomicU64, Ordering};
+        self.temp_file_path("lua")
+    }
+
+    /// Generate a unique temporary file path with the given extension.
+    pub fn temp_file_path(&self, extension: &str) -> PathBuf {
         use std::time::{SystemTime, UNIX_EPOCH};
         static COUNTER: AtomicU64 = AtomicU64::new(0);
         let ts = SystemTime::now()
@@ -151,11 +494,12 @@ impl AsepriteRunner {
             .as_nanos();
         let count = COUNTER.fetch_add(1, Ordering::Relaxed);
         self.temp_dir
-            .join(format!("mcp_{}_{}.lua", ts, count))
+            .join(format!("mcp_{}_{}.{}", ts, count, extension))
     }
 
     /// Run a Lua script in batch mode (no file opened beforehand).
     pub async fn run_script(&self, lua_code: &str) -> Result<ScriptOutput> {
+        plan_run(serde_json::json!({ "file": null, "script": lua_code }))?;
         let script_path = self.temp_script_path();
         tokio::fs::write(&script_path, lua_code)
             .await
@@ -165,7 +509,7 @@ impl AsepriteRunner {
 
         let result = self
             .execute_with_timeout(
-                Command::new(&self.exe_path)
+                self.exe.command()
                     .args(["--batch", "--script"])
                     .arg(&script_path)
                     .stdout(Stdio::piped())
@@ -173,20 +517,18 @@ impl AsepriteRunner {
             )
             .await;
 
-        // Clean up temp file (best-effort)
-        if let Err(e) = tokio::fs::remove_file(&script_path).await {
-            warn!("Failed to clean up temp script {}: {}", script_path.display(), e);
-        }
-
-        result
+        self.finish_script(script_path, lua_code, result).await
     }
 
     /// Run a Lua script with a sprite file opened first.
+    /// Each `(key, value)` param is passed as `--script-param key=value`.
     pub async fn run_script_on_file(
         &self,
         file_path: &str,
         lua_code: &str,
+        params: &[(String, String)],
     ) -> Result<ScriptOutput> {
+        plan_run(serde_json::json!({ "file": file_path, "script": lua_code, "params": params }))?;
         let script_path = self.temp_script_path();
         tokio::fs::write(&script_path, lua_code)
             .await
@@ -198,79 +540,189 @@ impl AsepriteRunner {
             script_path.display()
         );
 
-        let result = self
-            .execute_with_timeout(
-                Command::new(&self.exe_path)
-                    .arg("--batch")
-                    .arg(file_path)
-                    .arg("--script")
-                    .arg(&script_path)
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped()),
-            )
-            .await;
+        let mut cmd = self.exe.command();
+        cmd.arg("--batch").arg(file_path);
+        for (key, value) in params {
+            cmd.arg("--script-param").arg(format!("{}={}", key, value));
+        }
+        cmd.arg("--script")
+            .arg(&script_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let result = self.execute_with_timeout(&mut cmd).await;
 
-        if let Err(e) = tokio::fs::remove_file(&script_path).await {
+        self.finish_script(script_path, lua_code, result).await
+    }
+
+    /// Remove a run's temp script (best-effort) and attach it to the output or error if the run
+    /// failed. With ASEPRITE_DEBUG_SCRIPTS=1 a failed run's script is kept on disk for inspection.
+    async fn finish_script(
+        &self,
+        script_path: PathBuf,
+        lua_code: &str,
+        result: Result<ScriptOutput>,
+    ) -> Result<ScriptOutput> {
+        if result.is_ok() {
+            note_script_bytes(lua_code.len() as u64);
+        }
+        let failed = result.as_ref().map_or(true, |output| !output.success);
+        let keep = failed && self.debug_scripts;
+        if !keep && let Err(e) = tokio::fs::remove_file(&script_path).await {
             warn!("Failed to clean up temp script {}: {}", script_path.display(), e);
         }
+        match result {
+            Ok(mut output) => {
+                if failed {
+                    output.attach_script(lua_code, keep.then_some(script_path));
+                }
+                Ok(output)
+            }
+            Err(e) => Err(with_script(e, lua_code, keep.then_some(script_path.as_path()))),
+        }
+    }
 
-        result
+    /// Whether failed runs' scripts are kept on disk (ASEPRITE_DEBUG_SCRIPTS=1). Callers that
+    /// write their own temp scripts leave them in place after a failed run when this is set.
+    pub fn keeps_failed_scripts(&self) -> bool {
+        self.debug_scripts
+    }
+
+    /// Run an existing Lua script file in batch mode, optionally opening a sprite first.
+    /// Each `(key, value)` param is passed as `--script-param key=value` (read via `app.params`).
+    pub async fn run_script_file(
+        &self,
+        script_path: &str,
+        file_path: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<ScriptOutput> {
+        plan_run(serde_json::json!({
+            "file": file_path,
+            "script_path": script_path,
+            "script": std::fs::read_to_string(script_path).ok(),
+            "script_params": params.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>(),
+        }))?;
+        debug!("Running Lua script file: {} (file: {:?})", script_path, file_path);
+
+        let mut cmd = self.exe.command();
+        cmd.arg("--batch");
+        if let Some(file_path) = file_path {
+            cmd.arg(file_path);
+        }
+        for (key, value) in params {
+            cmd.arg("--script-param").arg(format!("{}={}", key, value));
+        }
+        cmd.arg("--script")
+            .arg(script_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        // Temp scripts written by the caller are only kept under ASEPRITE_DEBUG_SCRIPTS
+        let kept_at = (self.debug_scripts || !Path::new(script_path).starts_with(&self.temp_dir))
+            .then(|| PathBuf::from(script_path));
+        let result = self.execute_with_timeout(&mut cmd).await;
+        let lua_code = tokio::fs::read_to_string(script_path).await.ok();
+        let mut output = match (result, lua_code.as_deref()) {
+            (Err(e), Some(lua_code)) => return Err(with_script(e, lua_code, kept_at.as_deref())),
+            (result, _) => result?,
+        };
+        if let Some(ref lua_code) = lua_code {
+            note_script_bytes(lua_code.len() as u64);
+            if !output.success {
+                output.attach_script(lua_code, kept_at);
+            }
+        }
+        Ok(output)
     }
 
     /// Run Aseprite with raw CLI arguments (batch mode).
-    pub async fn run_cli(&self, args: &[String]) -> Result<ScriptOutput> {
-        debug!("Running Aseprite CLI: {:?}", args);
-
-        self.execute_with_timeout(
-            Command::new(&self.exe_path)
-                .arg("--batch")
-                .args(args)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped()),
-        )
-        .await
+    /// Relative paths in `args` resolve against `working_dir` when given.
+    pub async fn run_cli(&self, args: &[String], working_dir: Option<&Path>) -> Result<ScriptOutput> {
+        plan_run(serde_json::json!({ "args": args, "working_dir": working_dir }))?;
+        debug!("Running Aseprite CLI: {:?} (in {:?})", args, working_dir);
+
+        let mut cmd = self.exe.command();
+        cmd.arg("--batch")
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
+        self.execute_with_timeout(&mut cmd).await
     }
 
-    /// Execute an Aseprite command with a timeout. Kills the process if it exceeds the limit.
+    /// Execute an Aseprite command with a timeout. Kills the process if it exceeds the limit or
+    /// the MCP request is cancelled (see `with_cancellation`).
+    /// Waits for a free process slot first; the timeout counts from when the slot is acquired.
     async fn execute_with_timeout(&self, cmd: &mut Command) -> Result<ScriptOutput> {
-        let mut child = cmd.spawn().context("Failed to spawn Aseprite process")?;
+        let cancel = CALL_CANCEL.try_with(|ct| ct.clone()).unwrap_or_default();
+        let queued = Instant::now();
+        let _permit = tokio::select! {
+            permit = self.process_slots.acquire() => permit.context("Aseprite process limiter was closed")?,
+            _ = cancel.cancelled() => bail!("Cancelled: the request was cancelled before Aseprite started"),
+        };
+        debug!("Waited {:?} for an Aseprite process slot", queued.elapsed());
 
-        // Take stdout/stderr handles before awaiting, so we can still kill the child on timeout
-        let stdout_handle = child.stdout.take();
-        let stderr_handle = child.stderr.take();
+        // Also kill the process if this future is dropped mid-run
+        cmd.kill_on_drop(true);
+        let mut child = cmd.spawn().context("Failed to spawn Aseprite process")?;
 
-        let status = match tokio::time::timeout(PROCESS_TIMEOUT, child.wait()).await {
+        // Drain stdout/stderr while the process runs: a full pipe buffer (~64 KiB) would
+        // otherwise block Aseprite until the timeout
+        let read_pipe = |pipe: Option<Box<dyn tokio::io::AsyncRead + Send + Unpin>>| {
+            tokio::spawn(async move {
+                use tokio::io::AsyncReadExt;
+                let mut buf = Vec::new();
+                if let Some(mut pipe) = pipe {
+                    pipe.read_to_end(&mut buf).await.ok();
+                }
+                String::from_utf8_lossy(&buf).to_string()
+            })
+        };
+        let stdout_reader = read_pipe(child.stdout.take().map(|p| Box::new(p) as _));
+        let stderr_reader = read_pipe(child.stderr.take().map(|p| Box::new(p) as _));
+        // Readers are stopped if the run ends early (timeout, cancellation)
+        let _abort_readers = AbortOnDrop([stdout_reader.abort_handle(), stderr_reader.abort_handle()]);
+
+        let started = Instant::now();
+        let call_timeout = CALL_TIMEOUT.try_with(|t| *t).ok();
+        let timeout = call_timeout.unwrap_or(Duration::from_secs(self.timeout_secs()));
+        let waited = tokio::select! {
+            waited = tokio::time::timeout(timeout, child.wait()) => waited,
+            _ = cancel.cancelled() => {
+                warn!("Request cancelled, killing Aseprite process...");
+                child.kill().await.ok();
+                bail!("Cancelled: the request was cancelled and the Aseprite process was stopped");
+            }
+        };
+        let status = match waited {
             Ok(result) => result.context("Failed to wait for Aseprite process")?,
             Err(_) => {
                 // Timeout — try to kill the process
-                warn!("Aseprite process timed out after {:?}, killing...", PROCESS_TIMEOUT);
+                warn!("Aseprite process timed out after {:?}, killing...", timeout);
                 child.kill().await.ok();
+                let limit = if call_timeout.is_some() {
+                    format!(
+                        "the timeout_secs limit for this call; pass a larger timeout_secs (max {})",
+                        MAX_TIMEOUT_SECS
+                    )
+                } else {
+                    format!(
+                        "the default limit; raise it with set_config or ASEPRITE_TIMEOUT_SECS, or pass \
+                         timeout_secs on run_lua_script, execute_cli, or export tools (max {})",
+                        MAX_TIMEOUT_SECS
+                    )
+                };
                 bail!(
-                    "Aseprite process timed out after {} seconds. \
+                    "Aseprite process timed out after {} seconds ({}). \
                      The operation may be too complex or Aseprite may be unresponsive.",
-                    PROCESS_TIMEOUT.as_secs()
+                    timeout.as_secs(),
+                    limit
                 );
             }
         };
 
-        // Read captured output
-        let stdout = if let Some(mut handle) = stdout_handle {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            handle.read_to_end(&mut buf).await.unwrap_or(0);
-            String::from_utf8_lossy(&buf).to_string()
-        } else {
-            String::new()
-        };
-
-        let stderr = if let Some(mut handle) = stderr_handle {
-            use tokio::io::AsyncReadExt;
-            let mut buf = Vec::new();
-            handle.read_to_end(&mut buf).await.unwrap_or(0);
-            String::from_utf8_lossy(&buf).to_string()
-        } else {
-            String::new()
-        };
+        let stdout = stdout_reader.await.unwrap_or_default();
+        let stderr = stderr_reader.await.unwrap_or_default();
 
         debug!(
             "Aseprite exit={} stdout_len={} stderr_len={}",
@@ -279,11 +731,25 @@ impl AsepriteRunner {
             stderr.len()
         );
 
-        Ok(ScriptOutput {
+        let output = ScriptOutput {
             stdout,
             stderr,
             success: status.success(),
-        })
+            exit_code: status.code(),
+            duration: started.elapsed(),
+            script_excerpt: None,
+            script_path: None,
+        };
+        RUN_LOG
+            .try_with(|runs| {
+                runs.lock().unwrap_or_else(|e| e.into_inner()).push(serde_json::json!({
+                    "exit_code": output.exit_code,
+                    "duration_ms": output.duration.as_millis() as u64,
+                    "stderr": output.stderr.lines().map(str::trim).filter(|l| !l.is_empty()).collect::<Vec<_>>(),
+                }))
+            })
+            .ok();
+        Ok(output)
     }
 }
 
@@ -312,3 +778,43 @@ pub fn normalize_path(path: &str) -> String {
 pub fn lua_path(path: &str) -> String {
     lua_string(&normalize_path(path))
 }
+
+/// Aborts the wrapped tasks when dropped.
+struct AbortOnDrop<const N: usize>([tokio::task::AbortHandle; N]);
+
+impl<const N: usize> Drop for AbortOnDrop<N> {
+    fn drop(&mut self) {
+        for handle in &self.0 {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, scratch_dir};
+
+    #[tokio::test]
+    async fn start_failures_show_the_script() {
+        // The test runner's executable doesn't exist, so the run can't start
+        let err = dry_server().execute_script("local spr = app.sprite\nprint(spr.width)").await.unwrap_err();
+        assert!(err.contains("Failed to spawn Aseprite process"), "{}", err);
+        assert!(err.contains("Generated script:\n   1 | local spr = app.sprite\n   2 | print(spr.width)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn zero_timeout_is_rejected() {
+        let err = with_timeout(Some(0), dry_server().execute_script("print(1)")).await.unwrap_err();
+        assert_eq!(err, format!("timeout_secs must be between 1 and {} (got 0)", MAX_TIMEOUT_SECS));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn timeouts_show_the_script() {
+        let server = crate::test_support::fake_server(&scratch_dir("timeout_script"), "sleep 5");
+        let err = with_timeout(Some(1), server.execute_script("print('slow')")).await.unwrap_err();
+        assert!(err.contains("timed out after 1 seconds"), "{}", err);
+        assert!(err.contains("   1 | print('slow')"), "{}", err);
+    }
+}
@@ -0,0 +1,114 @@
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory (next to the original file) holding its backups.
+pub const BACKUP_DIR: &str = ".mcp_backups";
+
+/// Default number of backups kept per file. Overridden by ASEPRITE_BACKUP_KEEP.
+pub const DEFAULT_BACKUP_KEEP: usize = 10;
+
+tokio::task_local! {
+    /// Per-call `backup` parameter, set by the server's `call_tool`.
+    static CALL_BACKUP: bool;
+}
+
+/// Run `fut` with backups before in-place saves turned on or off, overriding ASEPRITE_BACKUP.
+pub async fn with_backup<F: Future>(backup: Option<bool>, fut: F) -> F::Output {
+    match backup {
+        Some(backup) => CALL_BACKUP.scope(backup, fut).await,
+        None => fut.await,
+    }
+}
+
+/// The per-call backup setting, if the current tool call passed one.
+pub fn requested() -> Option<bool> {
+    CALL_BACKUP.try_with(|b| *b).ok()
+}
+
+/// Backup directory and file name prefix for `path`: `<dir>/.mcp_backups/<stem>.bak.`
+fn backup_location(path: &Path) -> (PathBuf, String) {
+    let dir = path.parent().unwrap_or(Path::new(".")).join(BACKUP_DIR);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    (dir, format!("{}.bak.", stem))
+}
+
+/// Copy `path` to `.mcp_backups/<stem>.bak.<unix millis>.<ext>` next to it (the stamp moved on
+/// until the name is unused), then delete all but the newest `keep` backups of that file.
+/// Returns the backup path.
+pub async fn backup_file(path: &Path, keep: usize) -> Result<PathBuf, String> {
+    let (dir, prefix) = backup_location(path);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| format!("Failed to create backup directory {}: {}", dir.display(), e))?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    // Two saves within the same millisecond get consecutive stamps, so neither backup is lost
+    let mut stamp = millis;
+    let backup = loop {
+        let candidate = dir.join(format!("{}{}{}", prefix, stamp, ext));
+        match tokio::fs::OpenOptions::new().write(true).create_new(true).open(&candidate).await {
+            Ok(_) => break candidate,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => stamp += 1,
+            Err(e) => return Err(format!("Failed to back up {}: {}", path.display(), e)),
+        }
+    };
+    tokio::fs::copy(path, &backup)
+        .await
+        .map_err(|e| format!("Failed to back up {}: {}", path.display(), e))?;
+
+    for old in list_backups(path).into_iter().skip(keep.max(1)) {
+        tokio::fs::remove_file(&old.0).await.ok();
+    }
+    Ok(backup)
+}
+
+/// Backups of `path` as `(backup path, unix millis)`, newest first.
+pub fn list_backups(path: &Path) -> Vec<(PathBuf, u128)> {
+    let (dir, prefix) = backup_location(path);
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let ext = path.extension().map(|e| e.to_string_lossy().to_string());
+    let mut backups: Vec<(PathBuf, u128)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let rest = name.strip_prefix(&prefix)?;
+            // Files with the same stem but another extension have their own backups
+            let (stamp, backup_ext) = match rest.split_once('.') {
+                Some((stamp, e)) => (stamp, Some(e.to_string())),
+                None => (rest, None),
+            };
+            (backup_ext == ext).then_some(())?;
+            Some((entry.path(), stamp.parse().ok()?))
+        })
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.1));
+    backups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    #[tokio::test]
+    async fn backups_in_quick_succession_get_distinct_names() {
+        let path = scratch_dir("backup_names").join("hero.aseprite");
+        std::fs::write(&path, b"v1").unwrap();
+        let first = backup_file(&path, 10).await.unwrap();
+        std::fs::write(&path, b"v2").unwrap();
+        let second = backup_file(&path, 10).await.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read(&first).unwrap(), b"v1");
+        assert_eq!(std::fs::read(&second).unwrap(), b"v2");
+        assert_eq!(list_backups(&path).len(), 2);
+    }
+}
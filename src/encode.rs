@@ -0,0 +1,242 @@
+use image_webp::{ColorType, WebPEncoder};
+
+/// One rendered animation frame: RGBA8888 pixels plus how long it is shown.
+pub struct RgbaFrame {
+    pub pixels: Vec<u8>,
+    pub duration_ms: u32,
+}
+
+/// Largest width/height a lossless WebP bitstream can store.
+const WEBP_MAX_SIDE: u32 = 16384;
+
+/// Encode frames as a lossless animated WebP. Each frame is a full-canvas VP8L bitstream wrapped
+/// in an ANMF chunk (no blending, no disposal). `loop_count` 0 loops forever.
+pub fn encode_webp_animation(frames: &[RgbaFrame], width: u32, height: u32, loop_count: u32) -> Result<Vec<u8>, String> {
+    if width == 0 || height == 0 || width > WEBP_MAX_SIDE || height > WEBP_MAX_SIDE {
+        return Err(format!("WebP frames must be 1-{} pixels on each side", WEBP_MAX_SIDE));
+    }
+
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0]; // animation + alpha flags, reserved
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+    let mut anim = vec![0; 4]; // transparent background
+    anim.extend_from_slice(&(loop_count.min(u16::MAX as u32) as u16).to_le_bytes());
+
+    let mut body = b"WEBP".to_vec();
+    push_riff_chunk(&mut body, b"VP8X", &vp8x);
+    push_riff_chunk(&mut body, b"ANIM", &anim);
+    for frame in frames {
+        if frame.pixels.len() != (width * height * 4) as usize {
+            return Err("Frame size does not match the animation size".to_string());
+        }
+        let mut still = Vec::new();
+        WebPEncoder::new(&mut still)
+            .encode(&frame.pixels, width, height, ColorType::Rgba8)
+            .map_err(|e| format!("WebP encoding failed: {}", e))?;
+        // A still image is "RIFF" size "WEBP" followed by its single VP8L chunk
+        let bitstream = still.get(12..).ok_or("WebP encoding failed: truncated output")?;
+
+        let mut anmf = vec![0; 6]; // frame x / 2 and y / 2
+        anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        anmf.extend_from_slice(&frame.duration_ms.min(0xFF_FFFF).to_le_bytes()[..3]);
+        anmf.push(0x02); // don't blend with the previous frame, don't dispose
+        anmf.extend_from_slice(bitstream);
+        push_riff_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// Append a RIFF chunk (fourcc, little-endian size, payload, pad byte to an even length).
+fn push_riff_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Encode frames as an APNG (animated PNG). `loop_count` 0 loops forever.
+pub fn encode_apng(frames: &[RgbaFrame], width: u32, height: u32, loop_count: u32) -> Result<Vec<u8>, String> {
+    let png_error = |e: png::EncodingError| format!("APNG encoding failed: {}", e);
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(frames.len() as u32, loop_count)
+            .map_err(png_error)?;
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        for frame in frames {
+            let delay = frame.duration_ms.min(u16::MAX as u32) as u16;
+            writer.set_frame_delay(delay, 1000).map_err(png_error)?;
+            writer.write_image_data(&frame.pixels).map_err(png_error)?;
+        }
+        writer.finish().map_err(png_error)?;
+    }
+    Ok(out)
+}
+
+/// Decode a PNG (any color type) into RGBA8888, returning `(width, height, pixels)`.
+pub fn decode_png_rgba(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let png_error = |e: png::DecodingError| format!("PNG decoding failed: {}", e);
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(png_error)?;
+    buf.truncate(info.buffer_size());
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        png::ColorType::Indexed => return Err("PNG palette was not expanded".to_string()),
+    };
+    Ok((info.width, info.height, rgba))
+}
+
+/// Encode RGBA8888 pixels as a PNG.
+pub fn encode_png_rgba(pixels: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let png_error = |e: png::EncodingError| format!("PNG encoding failed: {}", e);
+    let mut out = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut out, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(png_error)?;
+        writer.write_image_data(pixels).map_err(png_error)?;
+    }
+    Ok(out)
+}
+
+/// Result of shelf packing: each rectangle's top-left position (in input order) and the sheet size.
+pub struct ShelfLayout {
+    pub positions: Vec<(u32, u32)>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Place rectangles on horizontal shelves (tallest first) within `max_width` x `max_height`,
+/// keeping `padding` pixels around and between them. Returns `None` if they don't fit.
+pub fn shelf_pack(sizes: &[(u32, u32)], max_width: u32, max_height: u32, padding: u32) -> Option<ShelfLayout> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut x, mut y, mut shelf_height, mut used_width) = (padding, padding, 0, 0);
+    for i in order {
+        let (w, h) = sizes[i];
+        if w + 2 * padding > max_width {
+            return None;
+        }
+        if x + w + padding > max_width {
+            y += shelf_height + padding;
+            x = padding;
+            shelf_height = 0;
+        }
+        positions[i] = (x, y);
+        x += w + padding;
+        used_width = used_width.max(x);
+        shelf_height = shelf_height.max(h);
+    }
+    let height = y + shelf_height + padding;
+    if height > max_height {
+        return None;
+    }
+    Some(ShelfLayout {
+        positions,
+        width: used_width.max(1),
+        height: height.max(1),
+    })
+}
+
+/// Nearest-neighbor upscale of RGBA8888 pixels by an integer `factor`.
+pub fn upscale_nearest(rgba: &[u8], width: u32, height: u32, factor: u32) -> Vec<u8> {
+    if factor <= 1 {
+        return rgba.to_vec();
+    }
+    let row_len = (width * factor * 4) as usize;
+    let mut out = Vec::with_capacity(row_len * (height * factor) as usize);
+    for row in rgba.chunks_exact((width * 4) as usize).take(height as usize) {
+        let start = out.len();
+        for px in row.chunks_exact(4) {
+            for _ in 0..factor {
+                out.extend_from_slice(px);
+            }
+        }
+        for _ in 1..factor {
+            out.extend_from_within(start..start + row_len);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webp_animation_round_trips() {
+        let red = [255, 0, 0, 255].repeat(6);
+        let clear = [0, 0, 0, 0].repeat(6);
+        let frames = vec![
+            RgbaFrame { pixels: red.clone(), duration_ms: 100 },
+            RgbaFrame { pixels: clear.clone(), duration_ms: 250 },
+        ];
+        let bytes = encode_webp_animation(&frames, 3, 2, 0).unwrap();
+
+        let mut decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(bytes)).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (3, 2));
+        assert_eq!(decoder.num_frames(), 2);
+        let mut buf = vec![0; 24];
+        assert_eq!(decoder.read_frame(&mut buf).unwrap(), 100);
+        assert_eq!(buf, red);
+        assert_eq!(decoder.read_frame(&mut buf).unwrap(), 250);
+        assert_eq!(buf, clear);
+    }
+
+    #[test]
+    fn webp_animation_rejects_mismatched_frames() {
+        let frames = vec![RgbaFrame { pixels: vec![0; 8], duration_ms: 100 }];
+        assert!(encode_webp_animation(&frames, 3, 2, 0).is_err());
+    }
+
+    #[test]
+    fn shelf_pack_places_tallest_first_with_padding() {
+        let layout = shelf_pack(&[(4, 2), (4, 6), (4, 4)], 16, 100, 1).unwrap();
+        // One shelf: 6-high first, then 4, then 2, each followed by one pixel of padding
+        assert_eq!(layout.positions, [(11, 1), (1, 1), (6, 1)]);
+        assert_eq!((layout.width, layout.height), (16, 8));
+    }
+
+    #[test]
+    fn shelf_pack_starts_a_new_shelf_when_a_row_is_full() {
+        let layout = shelf_pack(&[(6, 4), (6, 3), (6, 2)], 14, 100, 0).unwrap();
+        assert_eq!(layout.positions, [(0, 0), (6, 0), (0, 4)]);
+        assert_eq!((layout.width, layout.height), (12, 6));
+    }
+
+    #[test]
+    fn shelf_pack_rejects_what_does_not_fit() {
+        assert!(shelf_pack(&[(20, 2)], 16, 100, 0).is_none());
+        assert!(shelf_pack(&[(15, 2)], 16, 100, 1).is_none());
+        assert!(shelf_pack(&[(8, 8), (8, 8), (8, 8)], 16, 12, 0).is_none());
+        assert!(shelf_pack(&[(8, 8), (8, 8)], 16, 8, 0).is_some());
+    }
+
+    #[test]
+    fn upscale_nearest_repeats_pixels_and_rows() {
+        let (a, b) = ([1, 2, 3, 4], [5, 6, 7, 8]);
+        let out = upscale_nearest(&[a, b].concat(), 2, 1, 2);
+        assert_eq!(out, [a, a, b, b, a, a, b, b].concat());
+        assert_eq!(upscale_nearest(&a, 1, 1, 1), a.to_vec());
+    }
+}
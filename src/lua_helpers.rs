@@ -1,38 +1,489 @@
-/// Reusable Lua function for finding a layer by name (searches groups recursively).
+use crate::aseprite::lua_path;
+use crate::server::AsepriteServer;
+use crate::utils::write_output;
+
+/// Reusable Lua function for finding a layer by name (searches groups recursively). A name no
+/// layer has that contains "/" is read as a group path, e.g. "Body/Arm" for the "Arm" layer in
+/// the "Body" group, so a layer literally named "a/b" still wins over the path.
 /// After including this snippet, call: `find_layer(spr.layers, "name")`
 pub const LUA_FIND_LAYER: &str = r#"
-local function find_layer(lyrs, name)
+local function find_layer_named(lyrs, name)
     for i, l in ipairs(lyrs) do
         if l.name == name then return l end
         if l.isGroup and l.layers then
-            local found = find_layer(l.layers, name)
+            local found = find_layer_named(l.layers, name)
             if found then return found end
         end
     end
     return nil
+end
+local function find_layer(lyrs, name)
+    local found = find_layer_named(lyrs, name)
+    if found or not name:find("/", 1, true) then return found end
+    for part in name:gmatch("[^/]+") do
+        local step = nil
+        for _, l in ipairs(lyrs or {}) do
+            if l.name == part then step = l break end
+        end
+        if not step then return nil end
+        found, lyrs = step, step.isGroup and step.layers or nil
+    end
+    return found
 end"#;
 
-/// Lua snippet to select a target layer by name. Uses `find_layer` (must include LUA_FIND_LAYER first).
-/// Sets `app.layer = target_layer` if found, otherwise prints error JSON and returns.
-pub fn lua_select_layer(layer_name: &str, error_on_missing: bool) -> String {
-    let name = crate::aseprite::lua_string(layer_name);
-    if error_on_missing {
+/// Reusable Lua function finding a tileset by name or 1-based index (a number or numeric string);
+/// `nil` selects the first tileset. Sprites from Aseprite versions without tilesets have none.
+/// After including this snippet, call: `find_tileset(spr, key)`
+pub const LUA_FIND_TILESET: &str = r#"
+local function find_tileset(spr, key)
+    local list = spr.tilesets or {}
+    if key == nil then return list[1] end
+    local n = tonumber(key)
+    if n then return list[n] end
+    for _, ts in ipairs(list) do
+        if ts.name == key then return ts end
+    end
+    return nil
+end"#;
+
+/// Reusable Lua function collecting all image layers (recursing into groups) into a list.
+/// After including this snippet, call: `collect_image_layers(spr.layers, {}, visibleOnly)`
+pub const LUA_COLLECT_IMAGE_LAYERS: &str = r#"
+local function collect_image_layers(lyrs, out, visible_only)
+    for _, l in ipairs(lyrs) do
+        if not visible_only or l.isVisible then
+            if l.isGroup then
+                collect_image_layers(l.layers, out, visible_only)
+            elseif l.isImage then
+                out[#out + 1] = l
+            end
+        end
+    end
+    return out
+end"#;
+
+/// Reusable Lua functions converting between RGB (0-255) and HSL (each 0-1).
+/// After including this snippet, call: `rgb_to_hsl(r, g, b)` / `hsl_to_rgb(h, s, l)` (returns floats)
+pub const LUA_HSL: &str = r#"
+local function rgb_to_hsl(r, g, b)
+    r, g, b = r / 255, g / 255, b / 255
+    local max, min = math.max(r, g, b), math.min(r, g, b)
+    local l = (max + min) / 2
+    local d = max - min
+    if d == 0 then return 0, 0, l end
+    local s = l > 0.5 and d / (2 - max - min) or d / (max + min)
+    local h
+    if max == r then
+        h = (g - b) / d + (g < b and 6 or 0)
+    elseif max == g then
+        h = (b - r) / d + 2
+    else
+        h = (r - g) / d + 4
+    end
+    return h / 6, s, l
+end
+local function hue_to_rgb(p, q, t)
+    t = t % 1
+    if t < 1 / 6 then return p + (q - p) * 6 * t end
+    if t < 1 / 2 then return q end
+    if t < 2 / 3 then return p + (q - p) * (2 / 3 - t) * 6 end
+    return p
+end
+local function hsl_to_rgb(h, s, l)
+    if s == 0 then return l * 255, l * 255, l * 255 end
+    local q = l < 0.5 and l * (1 + s) or l + s - l * s
+    local p = 2 * l - q
+    return hue_to_rgb(p, q, h + 1 / 3) * 255, hue_to_rgb(p, q, h) * 255, hue_to_rgb(p, q, h - 1 / 3) * 255
+end"#;
+
+/// Reusable Lua function returning a raw pixel's alpha (0-255) for the sprite's color mode.
+/// After including this snippet, call: `pixel_alpha(spr, pixelValue)`
+pub const LUA_PIXEL_ALPHA: &str = r#"
+local function pixel_alpha(s, pv)
+    if s.colorMode == ColorMode.RGB then return app.pixelColor.rgbaA(pv) end
+    if s.colorMode == ColorMode.GRAYSCALE then return app.pixelColor.grayaA(pv) end
+    if pv == s.transparentColor then return 0 end
+    return s.palettes[1]:getColor(pv).alpha
+end"#;
+
+/// Lua snippet that defines `img`, `offX`, and `offY` for reading pixels in canvas coordinates:
+/// the given layer's cel image at `frame`, or the flattened sprite when no layer is given.
+/// Prints an error JSON and returns if the layer, frame or cel doesn't exist.
+pub fn lua_image_source(layer_name: Option<&str>, frame: u32) -> String {
+    if let Some(layer_name) = layer_name {
         format!(
             r#"
-local target_layer = find_layer(spr.layers, {name})
-if not target_layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
+{find_layer}
+{require_layer}
+{check_frame}
+local cel = target_layer:cel(spr.frames[{frame}])
+if not cel then
+    print(json.encode({{error = "No cel at this frame"}}))
     return
 end
-app.layer = target_layer"#,
-            name = name
+local img = cel.image
+local offX = cel.position.x
+local offY = cel.position.y"#,
+            find_layer = LUA_FIND_LAYER,
+            require_layer = lua_require_layer("target_layer", layer_name),
+            check_frame = lua_check_frame(frame),
+            frame = frame
         )
     } else {
         format!(
             r#"
-local target_layer = find_layer(spr.layers, {name})
-if target_layer then app.layer = target_layer end"#,
-            name = name
+{check_frame}
+local flat = Image(spr.spec)
+flat:drawSprite(spr, {frame})
+local img = flat
+local offX = 0
+local offY = 0"#,
+            check_frame = lua_check_frame(frame),
+            frame = frame
+        )
+    }
+}
+
+/// Lua snippet to look up a slice by name into `target_slice`.
+/// Prints an error JSON listing the available slice names and returns if it doesn't exist.
+pub fn lua_find_slice(slice_name: &str) -> String {
+    let name = crate::aseprite::lua_string(slice_name);
+    format!(
+        r#"
+local target_slice = nil
+local slice_names = {{}}
+for i, s in ipairs(spr.slices) do
+    if s.name == {name} then target_slice = s end
+    table.insert(slice_names, s.name)
+end
+if not target_slice then
+    print(json.encode({{error = "Slice not found: " .. {name} .. ". Available slices: " .. (#slice_names > 0 and table.concat(slice_names, ", ") or "(none)")}}))
+    return
+end"#,
+        name = name
+    )
+}
+
+/// Lua pager for list tools. Call `in_page()` once per entry that passes the filters and only
+/// build the entry when it returns true; afterwards `matched` holds the number of matches.
+pub fn lua_pager(limit: Option<u32>, offset: Option<u32>) -> String {
+    let offset = offset.unwrap_or(0);
+    format!(
+        r#"
+local matched = 0
+local function in_page()
+    matched = matched + 1
+    return matched > {offset} and matched <= {last}
+end"#,
+        offset = offset,
+        last = limit.map_or("math.huge".to_string(), |l| (offset as u64 + l as u64).to_string())
+    )
+}
+
+/// Lua function `name_matches(name)`: a case-insensitive substring test, always true when
+/// `needle` is `None`.
+pub fn lua_name_filter(needle: Option<&str>) -> String {
+    format!(
+        r#"
+local name_needle = {needle}
+local function name_matches(name)
+    return not name_needle or string.find(string.lower(name), name_needle, 1, true) ~= nil
+end"#,
+        needle = needle.map_or("nil".to_string(), |n| crate::aseprite::lua_string(&n.to_ascii_lowercase()))
+    )
+}
+
+/// Reusable Lua function building a Selection from a polygon (even-odd rule, pixel centers).
+/// After including this snippet, call: `polygon_selection({Point(x, y), ...})`
+pub const LUA_POLYGON_SELECTION: &str = r#"
+local function polygon_selection(pts)
+    local sel = Selection()
+    local minY, maxY = pts[1].y, pts[1].y
+    for i, pt in ipairs(pts) do
+        minY = math.min(minY, pt.y)
+        maxY = math.max(maxY, pt.y)
+    end
+    for y = minY, maxY do
+        local cy = y + 0.5
+        local xs = {}
+        for i = 1, #pts do
+            local a = pts[i]
+            local b = pts[i % #pts + 1]
+            if (a.y <= cy and b.y > cy) or (b.y <= cy and a.y > cy) then
+                table.insert(xs, a.x + (cy - a.y) * (b.x - a.x) / (b.y - a.y))
+            end
+        end
+        table.sort(xs)
+        for i = 1, #xs - 1, 2 do
+            local x0 = math.ceil(xs[i] - 0.5)
+            local x1 = math.floor(xs[i + 1] - 0.5)
+            if x1 >= x0 then sel:add(Rectangle(x0, y, x1 - x0 + 1, 1)) end
+        end
+    end
+    return sel
+end"#;
+
+/// Reusable Lua function converting a `Color` to a raw pixel value for the sprite's color mode.
+/// After including this snippet, call: `color_to_pixel(spr, Color(r, g, b, a))`
+pub const LUA_COLOR_TO_PIXEL: &str = r#"
+local function color_to_pixel(s, c)
+    if s.colorMode == ColorMode.RGB then return c.rgbaPixel end
+    if s.colorMode == ColorMode.GRAYSCALE then return c.grayPixel end
+    return c.index
+end"#;
+
+/// Reusable Lua function finding the palette entry closest to an RGBA pixel value
+/// (squared distance with green weighted highest, then blue, then red; alpha counts too).
+/// After including this snippet, call: `nearest_palette_index(pal, pixel)`
+pub const LUA_NEAREST_PALETTE_INDEX: &str = r#"
+local function nearest_palette_index(pal, v)
+    local pc = app.pixelColor
+    local r, g, b, a = pc.rgbaR(v), pc.rgbaG(v), pc.rgbaB(v), pc.rgbaA(v)
+    local best, best_d = 0, math.huge
+    for i = 0, #pal - 1 do
+        local c = pal:getColor(i)
+        local dr, dg, db, da = c.red - r, c.green - g, c.blue - b, c.alpha - a
+        local d = 2 * dr * dr + 4 * dg * dg + 3 * db * db + da * da
+        if d < best_d then best, best_d = i, d end
+    end
+    return best
+end"#;
+
+/// Lua statements binding `var` to the layer named `layer_name` (found with `find_layer`, so
+/// include LUA_FIND_LAYER first), or printing the error JSON and returning when there's none.
+/// Every template reports a missing layer through this, with the same message.
+pub fn lua_require_layer(var: &str, layer_name: &str) -> String {
+    let name = crate::aseprite::lua_string(layer_name);
+    format!(
+        r#"local {var} = find_layer(spr.layers, {name})
+if not {var} then
+    print(json.encode({{error = "Layer not found: " .. {name}}}))
+    return
+end"#
+    )
+}
+
+/// Lua statements binding `var` to the tag named `tag_name`, or printing the error JSON (which
+/// lists the sprite's tags) and returning when there's none. Every template looks tags up through this.
+pub fn lua_require_tag(var: &str, tag_name: &str) -> String {
+    let name = crate::aseprite::lua_string(tag_name);
+    format!(
+        r#"local {var} = nil
+local tag_names = {{}}
+for _, t in ipairs(spr.tags) do
+    if not {var} and t.name == {name} then {var} = t end
+    table.insert(tag_names, t.name)
+end
+if not {var} then
+    print(json.encode({{error = "Tag not found: " .. {name} .. ". Available tags: " .. (#tag_names > 0 and table.concat(tag_names, ", ") or "(none)")}}))
+    return
+end"#
+    )
+}
+
+/// Lua statements printing the error JSON and returning unless the sprite has the 1-based frame
+/// `frame` (a Lua expression). Every template checks a single frame through this, so a frame
+/// past the end is always reported with the same message.
+pub fn lua_check_frame(frame: impl std::fmt::Display) -> String {
+    format!(
+        r#"if not spr.frames[{frame}] then
+    print(json.encode({{error = "Frame " .. {frame} .. " out of range (sprite has " .. #spr.frames .. " frames)"}}))
+    return
+end"#
+    )
+}
+
+/// Lua snippet to select a target layer by name. Uses `find_layer` (must include LUA_FIND_LAYER first).
+/// Sets `app.layer = target_layer` if found, otherwise prints error JSON and returns.
+pub fn lua_select_layer(layer_name: &str) -> String {
+    format!(
+        "\n{}\napp.layer = target_layer",
+        lua_require_layer("target_layer", layer_name)
+    )
+}
+
+/// Lua snippet that leaves only the named layer visible (together with its parent groups and
+/// its own children), so `drawSprite` renders just that layer. Visibility changes are in memory
+/// only — don't save the sprite afterwards. Prints an error JSON and returns if the layer doesn't exist.
+pub fn lua_solo_layer(layer_name: &str) -> String {
+    format!(
+        r#"
+{find_layer}
+{require_layer}
+local function solo_apply(lyrs, inside)
+    local found = false
+    for _, l in ipairs(lyrs) do
+        local isTarget = l == solo_layer
+        local containsTarget = false
+        if l.isGroup then
+            containsTarget = solo_apply(l.layers, inside or isTarget)
+        end
+        if isTarget or containsTarget then
+            l.isVisible = true
+            found = true
+        elseif not inside then
+            l.isVisible = false
+        end
+    end
+    return found
+end
+solo_apply(spr.layers, false)"#,
+        find_layer = LUA_FIND_LAYER,
+        require_layer = lua_require_layer("solo_layer", layer_name)
+    )
+}
+
+/// Lua snippet that adds an opaque "Matte" layer filled with the given color beneath every other
+/// layer in all frames of `spr`, so renders/exports have no transparent pixels. Does nothing when
+/// the sprite already has a background layer.
+pub fn lua_matte_layer(r: u8, g: u8, b: u8) -> String {
+    format!(
+        r#"
+{color_to_pixel}
+if not spr.backgroundLayer then
+    local matte = spr:newLayer()
+    matte.name = "Matte"
+    matte.stackIndex = 1
+    local mattePixel = color_to_pixel(spr, Color{{ r = {r}, g = {g}, b = {b}, a = 255 }})
+    for _, fr in ipairs(spr.frames) do
+        local matteImg = Image(spr.spec)
+        matteImg:clear(mattePixel)
+        spr:newCel(matte, fr, matteImg, Point(0, 0))
+    end
+end"#,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        r = r,
+        g = g,
+        b = b
+    )
+}
+
+/// Lua statement saving the open sprite `spr` over its file. It writes to a temp file next to the
+/// original, which the server renames over the original only once Aseprite exits successfully
+/// (see `AsepriteServer::execute_mutation_on_file`), so a killed process can't leave a
+/// half-written file. Scripts must run through `execute_script_on_file`, which supplies the path.
+/// The temp file keeps the original extension, so the sprite is encoded in the same format
+/// (and color mode) as a `saveAs` over the original would; every process opens the file afresh,
+/// so the sprite not being marked as saved afterwards has no effect.
+pub const LUA_SAVE_IN_PLACE: &str = "spr:saveCopyAs(app.params.mcp_save_path)";
+
+/// Lua statement saving the open sprite over its own file with `saveAs`, which (unlike
+/// `saveCopyAs`) marks the sprite saved and keeps the options it was loaded with. Scripts using it
+/// are run on a sibling temp copy of the file that is renamed over the original once Aseprite has
+/// exited successfully, like `LUA_SAVE_IN_PLACE` (see `AsepriteServer::execute_mutation_on_file`).
+pub const LUA_SAVE_AS_ORIGINAL: &str = "spr:saveAs(spr.filename)";
+
+/// Lua save statement and destination for mutating tools: the edited sprite is written to
+/// `output_path` (resolved like every other output path) leaving the input untouched, or saved
+/// in place when it's omitted or names the input itself.
+pub fn lua_save_target(
+    server: &AsepriteServer,
+    file_path: &str,
+    output_path: Option<&str>,
+) -> Result<(String, String), String> {
+    match output_path {
+        Some(output) => {
+            let output = server.resolve_output_path(output)?;
+            let input = server.resolve_input_path(file_path)?;
+            if same_file(&output, &input) {
+                return Ok((LUA_SAVE_IN_PLACE.to_string(), input));
+            }
+            Ok((format!("spr:saveCopyAs({})", lua_path(&output)), output))
+        }
+        None => Ok((LUA_SAVE_IN_PLACE.to_string(), server.resolve_input_path(file_path)?)),
+    }
+}
+
+/// Whether two resolved paths name the same file (one that exists, for differently spelled paths).
+pub fn same_file(a: &str, b: &str) -> bool {
+    a == b || std::fs::canonicalize(a).is_ok_and(|a| std::fs::canonicalize(b).is_ok_and(|b| a == b))
+}
+
+/// Like `lua_save_target`, but an in-place save uses `LUA_SAVE_AS_ORIGINAL`, for tools whose
+/// later steps (or the saved file's format options) depend on the sprite being properly saved.
+pub fn lua_save_as_target(
+    server: &AsepriteServer,
+    file_path: &str,
+    output_path: Option<&str>,
+) -> Result<(String, String), String> {
+    let (save, saved_to) = lua_save_target(server, file_path, output_path)?;
+    if save == LUA_SAVE_IN_PLACE {
+        return Ok((LUA_SAVE_AS_ORIGINAL.to_string(), saved_to));
+    }
+    Ok((save, saved_to))
+}
+
+/// Add the file a mutating tool wrote (see `lua_save_target`) to its JSON result as `saved_to`.
+pub fn with_saved_to(output: String, saved_to: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(&output) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            map.insert("saved_to".to_string(), saved_to.into());
+            serde_json::Value::Object(map).to_string()
+        }
+        _ => output,
+    }
+}
+
+/// Wrap an edit fragment into a standalone script. Fragments are Lua bodies that run with `spr`
+/// bound to the open sprite, report errors by printing `{error = ...}` JSON and returning, and
+/// leave their result table in `result`; the script runs `save` and prints `result`.
+/// The same fragments are combined by `batch_edit` to run several edits in one process.
+pub fn lua_fragment_script(body: &str, save: &str) -> String {
+    format!(
+        "local spr = app.sprite\nlocal result\n{}\n{}\nprint(json.encode(result))",
+        body, save
+    )
+}
+
+/// Temp file carrying bulk binary data (raw image bytes, usually) between the server and a
+/// script, so large payloads never go through Aseprite's stdout or the script source. A script
+/// opens it with `lua_open()` as `data_file`, writes with `data_file:write(img.bytes)` and closes
+/// it before printing its JSON summary; `read()` then returns everything it wrote. In the other
+/// direction, `with_contents` fills the file and `lua_read()` loads it into the script.
+/// The file is removed when this is dropped.
+pub struct DataFile {
+    path: std::path::PathBuf,
+}
+
+impl DataFile {
+    pub fn new(server: &AsepriteServer) -> Self {
+        Self { path: server.temp_file_path("bin") }
+    }
+
+    /// A data file holding `bytes`, for a script to load with `lua_read()`.
+    pub async fn with_contents(server: &AsepriteServer, bytes: &[u8]) -> Result<Self, String> {
+        let file = Self::new(server);
+        write_output(&file.path, bytes)
+            .await
+            .map_err(|e| format!("Failed to write temporary pixel data: {}", e))?;
+        Ok(file)
+    }
+
+    /// Lua statements reading the whole file into the string `data_bytes`.
+    pub fn lua_read(&self) -> String {
+        format!(
+            "local data_file = assert(io.open({}, \"rb\"))\nlocal data_bytes = data_file:read(\"a\")\ndata_file:close()",
+            lua_path(&self.path.to_string_lossy())
         )
     }
+
+    /// Lua statement opening the file for writing as `data_file`.
+    pub fn lua_open(&self) -> String {
+        format!(
+            "local data_file = assert(io.open({}, \"wb\"))",
+            lua_path(&self.path.to_string_lossy())
+        )
+    }
+
+    pub async fn read(&self) -> Result<Vec<u8>, String> {
+        tokio::fs::read(&self.path)
+            .await
+            .map_err(|e| format!("Failed to read the script's pixel data: {}", e))
+    }
+}
+
+impl Drop for DataFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
 }
@@ -1,29 +1,54 @@
 mod aseprite;
+mod backup;
+mod encode;
 mod lua_helpers;
+mod prompts;
+mod resources;
 mod server;
+#[cfg(test)]
+mod test_support;
 mod tools;
 mod utils;
 
 use anyhow::Result;
 use rmcp::ServiceExt;
 use server::AsepriteServer;
+use std::path::Path;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging to stderr (stdout is reserved for MCP JSON-RPC protocol)
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
-        )
-        .with_writer(std::io::stderr)
+    // Initialize logging to stderr (stdout is reserved for MCP JSON-RPC protocol), plus a daily
+    // rotated file when ASEPRITE_LOG_FILE is set, since many MCP hosts discard stderr
+    let log_file = std::env::var("ASEPRITE_LOG_FILE").ok().filter(|p| !p.trim().is_empty());
+    // The guard flushes the file writer's background thread on exit
+    let (file_layer, _log_guard) = match log_file {
+        Some(ref path) => {
+            let path = Path::new(path.trim());
+            let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let name = path.file_name().unwrap_or("aseprite_mcp.log".as_ref());
+            let appender = tracing_appender::rolling::daily(dir, name);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = tracing_subscriber::fmt::layer().with_ansi(false).with_writer(writer);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(file_layer)
         .init();
 
     info!(
         "Starting Aseprite MCP Server v{}",
         env!("CARGO_PKG_VERSION")
     );
+    if let Some(path) = log_file {
+        info!("Logging to {} (rotated daily)", path.trim());
+    }
 
     // Create the server (this also locates the Aseprite executable)
     let server = AsepriteServer::new()?;
@@ -0,0 +1,443 @@
+use std::cell::RefCell;
+
+use rmcp::model::{GetPromptResult, JsonObject, Prompt, PromptArgument, PromptMessage, PromptMessageRole, Tool};
+use serde_json::Value;
+
+use crate::server::AsepriteServer;
+
+/// A parameterized workflow prompt. Its steps name tools and parameters through a `ToolIndex`, and
+/// the signatures and descriptions of the tools they name are appended from the tool router.
+struct Workflow {
+    name: &'static str,
+    description: &'static str,
+    /// `(name, description, required)`
+    arguments: &'static [(&'static str, &'static str, bool)],
+    steps: fn(&JsonObject, &ToolIndex) -> Result<String, String>,
+}
+
+/// The tool router's tools, for writing the tool calls in a workflow's steps. Naming a tool or
+/// parameter the router doesn't have is an error rather than a silently wrong prompt, and the tools
+/// named are collected (in order) for the tool reference.
+struct ToolIndex {
+    tools: Vec<Tool>,
+    used: RefCell<Vec<String>>,
+}
+
+impl ToolIndex {
+    fn new(tools: Vec<Tool>) -> Self {
+        Self {
+            tools,
+            used: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<&Tool, String> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| format!("Prompt step refers to unknown tool '{}'", name))?;
+        let mut used = self.used.borrow_mut();
+        if !used.iter().any(|u| u == name) {
+            used.push(name.to_string());
+        }
+        Ok(tool)
+    }
+
+    /// `` `name` ``, checking that the tool exists.
+    fn tool(&self, name: &str) -> Result<String, String> {
+        self.lookup(name).map(|tool| format!("`{}`", tool.name))
+    }
+
+    /// `param`, checking that `tool` takes it.
+    fn param<'a>(&self, tool: &str, param: &'a str) -> Result<&'a str, String> {
+        let known = self
+            .lookup(tool)?
+            .input_schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .is_some_and(|properties| properties.contains_key(param));
+        if !known {
+            return Err(format!("Prompt step passes unknown parameter '{}' to '{}'", param, tool));
+        }
+        Ok(param)
+    }
+
+    /// `` `name` with a 1, b "x" `` for a call with these arguments (values already formatted).
+    fn call(&self, tool: &str, args: &[(&str, String)]) -> Result<String, String> {
+        let args = args
+            .iter()
+            .map(|(param, value)| Ok(format!("{} {}", self.param(tool, param)?, value)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let name = self.tool(tool)?;
+        Ok(format!("{} with {}", name, args.join(", ")))
+    }
+}
+
+const WORKFLOWS: &[Workflow] = &[
+    Workflow {
+        name: "create_character_sprite_sheet",
+        description: "Create an animated pixel-art character and export it as a tagged sprite sheet",
+        arguments: &[
+            ("size", "Frame size as WIDTHxHEIGHT, e.g. \"32x32\"", true),
+            ("frames", "Total number of animation frames, e.g. 8", true),
+            ("tags", "Comma-separated animation tags, e.g. \"idle, walk, attack\"", false),
+            ("output_path", "Sprite file to create (default: \"character.aseprite\")", false),
+        ],
+        steps: character_sheet_steps,
+    },
+    Workflow {
+        name: "export_for_godot",
+        description: "Clean up a sprite and export a scaled sprite sheet with JSON data for Godot",
+        arguments: &[
+            ("file", "Sprite file to export", true),
+            ("scale", "Integer upscale factor for the exported sheet (default: 1)", false),
+        ],
+        steps: godot_export_steps,
+    },
+    Workflow {
+        name: "recolor_to_palette",
+        description: "Remap a sprite's colors onto an existing palette file",
+        arguments: &[
+            ("file", "Sprite file to recolor", true),
+            ("palette", "Palette file (.gpl, .pal, .act, .col, .png, ...)", true),
+        ],
+        steps: recolor_steps,
+    },
+];
+
+/// Prompts advertised by `prompts/list`.
+pub fn list_prompts() -> Vec<Prompt> {
+    WORKFLOWS
+        .iter()
+        .map(|w| {
+            let arguments = w
+                .arguments
+                .iter()
+                .map(|(name, description, required)| PromptArgument {
+                    name: name.to_string(),
+                    description: Some(description.to_string()),
+                    required: Some(*required),
+                })
+                .collect();
+            Prompt::new(w.name, Some(w.description), Some(arguments))
+        })
+        .collect()
+}
+
+/// Expand a prompt into a single user message: the workflow steps, then a reference of the tools
+/// they use.
+pub fn get_prompt(
+    server: &AsepriteServer,
+    name: &str,
+    arguments: Option<JsonObject>,
+) -> Result<GetPromptResult, String> {
+    let workflow = WORKFLOWS
+        .iter()
+        .find(|w| w.name == name)
+        .ok_or_else(|| format!("Unknown prompt '{}'", name))?;
+    let arguments = arguments.unwrap_or_default();
+    for (arg, _, required) in workflow.arguments {
+        if *required && arg_string(&arguments, arg).is_none() {
+            return Err(format!("Prompt '{}' requires the '{}' argument", name, arg));
+        }
+    }
+
+    let tools = ToolIndex::new(server.tool_definitions());
+    let mut text = (workflow.steps)(&arguments, &tools)?;
+    text.push_str("\n\nTool reference (parameters marked ? are optional):\n");
+    for tool in tools.used.borrow().iter().filter_map(|name| tools.tools.iter().find(|t| t.name == *name)) {
+        text.push_str(&format!("- {}\n", tool_signature(tool)));
+    }
+    text.push_str(
+        "\nEvery tool also accepts dry_run: true to preview the Aseprite run without changing files.",
+    );
+    Ok(GetPromptResult {
+        description: Some(workflow.description.to_string()),
+        messages: vec![PromptMessage::new_text(PromptMessageRole::User, text)],
+    })
+}
+
+/// An argument as a string; clients may send numbers for numeric arguments.
+fn arg_string(args: &JsonObject, name: &str) -> Option<String> {
+    match args.get(name)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn arg_u32(args: &JsonObject, name: &str, default: u32) -> Result<u32, String> {
+    match arg_string(args, name) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("'{}' must be a positive integer, got '{}'", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// Render a tool as `` `name`(param: type, optional?: type) — description ``, with nested object
+/// parameters expanded one level.
+fn tool_signature(tool: &Tool) -> String {
+    let schema = &tool.input_schema;
+    let params = schema_params(schema, schema).join(", ");
+    let description = tool.description.as_deref().unwrap_or_default();
+    format!("`{}`({}) — {}", tool.name, params, description)
+}
+
+fn schema_params(object: &JsonObject, root: &JsonObject) -> Vec<String> {
+    let required: Vec<&str> = object
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let Some(properties) = object.get("properties").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    properties
+        .iter()
+        .filter(|(name, _)| name.as_str() != "timeout_secs")
+        .map(|(name, prop)| {
+            let marker = if required.contains(&name.as_str()) { "" } else { "?" };
+            format!("{}{}: {}", name, marker, schema_type(prop, root))
+        })
+        .collect()
+}
+
+fn schema_type(prop: &Value, root: &JsonObject) -> String {
+    // Nested structs are `$ref`s into `definitions`, optionally wrapped in `anyOf` with null
+    let reference = prop.get("$ref").or_else(|| {
+        prop.get("anyOf")?
+            .as_array()?
+            .iter()
+            .find_map(|variant| variant.get("$ref"))
+    });
+    if let Some(def) = reference
+        .and_then(Value::as_str)
+        .and_then(|r| r.strip_prefix("#/definitions/"))
+        .and_then(|name| root.get("definitions")?.get(name)?.as_object())
+    {
+        if def.contains_key("properties") {
+            return format!("{{{}}}", schema_params(def, root).join(", "));
+        }
+        return def.get("type").and_then(Value::as_str).unwrap_or("value").to_string();
+    }
+    match prop.get("type") {
+        Some(Value::String(t)) if t == "array" => {
+            let item = prop.get("items").map(|i| schema_type(i, root));
+            format!("{}[]", item.unwrap_or_else(|| "value".to_string()))
+        }
+        Some(Value::String(t)) => t.clone(),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null")
+            .unwrap_or("value")
+            .to_string(),
+        _ => "value".to_string(),
+    }
+}
+
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value)
+}
+
+fn character_sheet_steps(args: &JsonObject, tools: &ToolIndex) -> Result<String, String> {
+    let size = arg_string(args, "size").unwrap_or_default();
+    let (width, height) = size
+        .split_once(['x', 'X'])
+        .and_then(|(w, h)| Some((w.trim().parse::<u32>().ok()?, h.trim().parse::<u32>().ok()?)))
+        .ok_or_else(|| format!("'size' must look like 32x32, got '{}'", size))?;
+    let frames = arg_u32(args, "frames", 1)?;
+    let output = arg_string(args, "output_path").unwrap_or_else(|| "character.aseprite".to_string());
+    let stem = output.rsplit_once('.').map_or(output.as_str(), |(stem, _)| stem);
+    let tags: Vec<String> = arg_string(args, "tags")
+        .unwrap_or_default()
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let create = tools.call(
+        "create_sprite",
+        &[
+            ("width", width.to_string()),
+            ("height", height.to_string()),
+            ("output_path", quoted(&output)),
+        ],
+    )?;
+    let frames_step = if frames > 1 {
+        format!(
+            "Call {} so the sprite has {} frames.",
+            tools.call("add_frame", &[("file_path", quoted(&output)), ("count", (frames - 1).to_string())])?,
+            frames
+        )
+    } else {
+        "The sprite starts with one frame, so no frames need to be added.".to_string()
+    };
+    let tag_step = if tags.is_empty() {
+        format!(
+            "5. Call {} so engines see the animation.",
+            tools.call(
+                "create_tag",
+                &[
+                    ("name", quoted("default")),
+                    ("from_frame", "1".to_string()),
+                    ("to_frame", frames.to_string()),
+                ],
+            )?
+        )
+    } else {
+        format!(
+            "5. Split the {} frames across the tags {} in that order and call {} once per tag \
+             with its {}, {} and {} (frame numbers are 1-based and ranges must not overlap).",
+            frames,
+            tags.iter().map(|t| quoted(t)).collect::<Vec<_>>().join(", "),
+            tools.tool("create_tag")?,
+            tools.param("create_tag", "name")?,
+            tools.param("create_tag", "from_frame")?,
+            tools.param("create_tag", "to_frame")?
+        )
+    };
+    Ok(format!(
+        "Create a {width}x{height} pixel-art character with {frames} animation frames and export it as a \
+         sprite sheet.\n\n\
+         1. Call {create}.\n\
+         2. {frames_step}\n\
+         3. Optionally split the character into layers with {add_layer} (e.g. \"outline\", \"body\", \"shading\").\n\
+         4. Draw every frame with {draw_pixels} for individual pixels and {use_tool} for lines, \
+         rectangles and fills. Keep a small, consistent palette and a clear silhouette.\n\
+         {tag_step}\n\
+         6. Give frames their timing with {set_frame_duration} (around 100 ms per frame is a good start).\n\
+         7. Check the result with {export_preview} and fix anything that looks off.\n\
+         8. Call {export}.",
+        add_layer = tools.tool("add_layer")?,
+        draw_pixels = tools.tool("draw_pixels")?,
+        use_tool = tools.tool("use_tool")?,
+        set_frame_duration = tools.tool("set_frame_duration")?,
+        export_preview = tools.tool("export_preview")?,
+        export = tools.call(
+            "export_spritesheet",
+            &[
+                ("output_image", quoted(&format!("{}_sheet.png", stem))),
+                ("output_data", quoted(&format!("{}_sheet.json", stem))),
+                ("list_tags", "true".to_string()),
+            ],
+        )?,
+    ))
+}
+
+fn godot_export_steps(args: &JsonObject, tools: &ToolIndex) -> Result<String, String> {
+    let file = arg_string(args, "file").unwrap_or_default();
+    let scale = arg_u32(args, "scale", 1)?;
+    let stem = file.rsplit_once('.').map_or(file.as_str(), |(stem, _)| stem);
+    let scale_step = if scale > 1 {
+        format!(
+            "4. Copy the sprite with {duplicate} and call {resize} on the copy with {width} and {height} \
+             multiplied by {scale} (use the dimensions from step 2). Export from the copy in the next step \
+             so the original stays untouched.",
+            duplicate = tools.call(
+                "duplicate_sprite",
+                &[
+                    ("file_path", quoted(&file)),
+                    ("output_path", quoted(&format!("{}_x{}.aseprite", stem, scale))),
+                ],
+            )?,
+            resize = tools.tool("resize_sprite")?,
+            width = tools.param("resize_sprite", "width")?,
+            height = tools.param("resize_sprite", "height")?,
+        )
+    } else {
+        "4. No scaling was requested; export the cleaned sprite as-is.".to_string()
+    };
+    Ok(format!(
+        "Clean up \"{file}\" and export it for the Godot engine.\n\n\
+         1. Call {validate} and stop if it isn't readable.\n\
+         2. Call {info} to learn its size, frames and tags.\n\
+         3. Remove stray single pixels with {despeckle} only if the art looks noisy; skip this for clean pixel art.\n\
+         {scale_step}\n\
+         5. Call {export} so Godot's filtering doesn't bleed between frames.\n\
+         6. Report the sheet's frame size and tag ranges: in Godot, set the Sprite2D/AnimatedSprite2D \
+         texture filter to Nearest and use hframes/vframes or the JSON frame rectangles.",
+        validate = tools.call("validate_sprite", &[("file_path", quoted(&file))])?,
+        info = tools.tool("get_sprite_info")?,
+        despeckle = tools.tool("despeckle")?,
+        export = tools.call(
+            "export_spritesheet",
+            &[
+                ("output_image", quoted(&format!("{}_sheet.png", stem))),
+                ("output_data", quoted(&format!("{}_sheet.json", stem))),
+                ("sheet_type", quoted("rows")),
+                ("data_format", quoted("json-array")),
+                ("list_tags", "true".to_string()),
+                ("shape_padding", "1".to_string()),
+                ("extrude", "true".to_string()),
+            ],
+        )?,
+    ))
+}
+
+fn recolor_steps(args: &JsonObject, tools: &ToolIndex) -> Result<String, String> {
+    let file = arg_string(args, "file").unwrap_or_default();
+    let palette = arg_string(args, "palette").unwrap_or_default();
+    let stem = file.rsplit_once('.').map_or(file.as_str(), |(stem, _)| stem);
+    let copy = format!("{}_recolored.aseprite", stem);
+    Ok(format!(
+        "Recolor \"{file}\" to the colors of the palette \"{palette}\".\n\n\
+         1. Call {info} and {get_palette} on \"{file}\" to see its color mode and current colors.\n\
+         2. Work on a copy: call {duplicate} and use the copy below.\n\
+         3. If the copy is indexed, call {to_rgb} first so pixels keep their colors.\n\
+         4. Call {load_palette} on the copy.\n\
+         5. Call {to_indexed} to map every pixel to the nearest palette color.\n\
+         6. Compare the result with {export_preview} and tell the user which colors changed noticeably.",
+        info = tools.tool("get_sprite_info")?,
+        get_palette = tools.tool("get_palette")?,
+        duplicate = tools.call("duplicate_sprite", &[("file_path", quoted(&file)), ("output_path", quoted(&copy))])?,
+        to_rgb = tools.call("change_color_mode", &[("color_mode", quoted("rgb"))])?,
+        load_palette = tools.call("load_palette", &[("palette_path", quoted(&palette))])?,
+        to_indexed = tools.call("change_color_mode", &[("color_mode", quoted("indexed"))])?,
+        export_preview = tools.tool("export_preview")?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::dry_server;
+
+    fn args(value: serde_json::Value) -> Option<JsonObject> {
+        value.as_object().cloned()
+    }
+
+    #[test]
+    fn every_prompt_names_only_real_tools_and_parameters() {
+        let server = dry_server();
+        let cases = [
+            ("create_character_sprite_sheet", serde_json::json!({ "size": "32x32", "frames": 8 })),
+            ("create_character_sprite_sheet", serde_json::json!({ "size": "16x16", "frames": 6, "tags": "idle, walk" })),
+            ("export_for_godot", serde_json::json!({ "file": "hero.aseprite", "scale": 2 })),
+            ("recolor_to_palette", serde_json::json!({ "file": "hero.aseprite", "palette": "db16.gpl" })),
+        ];
+        for (name, arguments) in cases {
+            let result = get_prompt(&server, name, args(arguments)).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(result.messages.len(), 1);
+        }
+        assert_eq!(list_prompts().len(), WORKFLOWS.len());
+    }
+
+    #[test]
+    fn unknown_tools_and_parameters_are_errors() {
+        let tools = ToolIndex::new(dry_server().tool_definitions());
+        assert_eq!(
+            tools.call("create_sprite", &[("width", "8".to_string())]).unwrap(),
+            "`create_sprite` with width 8"
+        );
+        let err = tools.tool("draw_circle").unwrap_err();
+        assert_eq!(err, "Prompt step refers to unknown tool 'draw_circle'");
+        let err = tools.param("create_sprite", "depth").unwrap_err();
+        assert_eq!(err, "Prompt step passes unknown parameter 'depth' to 'create_sprite'");
+        assert_eq!(*tools.used.borrow(), ["create_sprite"]);
+    }
+}
@@ -0,0 +1,203 @@
+use base64::Engine;
+use rmcp::model::{AnnotateAble, RawResource, ReadResourceResult, Resource, ResourceContents};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::aseprite::lua_path;
+use crate::server::AsepriteServer;
+use crate::utils::{MAX_INLINE_IMAGE_BYTES, decode_hex, image_dimensions, image_mime_type};
+
+/// Resources returned per `resources/list` page.
+const RESOURCE_PAGE_SIZE: usize = 100;
+
+/// Extensions exposed as resources: raster images as-is, sprites via a rendered PNG preview.
+const RESOURCE_EXTENSIONS: &[&str] = &["png", "gif", "webp", "jpg", "jpeg", "bmp", "aseprite", "ase"];
+
+/// Directory whose files are exposed as resources: the output dir, else the workspace root.
+fn resource_root(server: &AsepriteServer) -> PathBuf {
+    server
+        .output_dir()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| server.workspace_root())
+}
+
+fn is_sprite(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "aseprite" | "ase"))
+}
+
+/// List image and sprite files under the resource root, `RESOURCE_PAGE_SIZE` at a time.
+/// The cursor is the offset of the next page.
+pub fn list_resources(server: &AsepriteServer, cursor: Option<&str>) -> Result<(Vec<Resource>, Option<String>), String> {
+    let offset: usize = match cursor {
+        Some(cursor) => cursor.parse().map_err(|_| format!("Invalid cursor '{}'", cursor))?,
+        None => 0,
+    };
+    let root = resource_root(server);
+    server.check_path_allowed(&root)?;
+    let Ok(root) = root.canonicalize() else {
+        return Ok((Vec::new(), None));
+    };
+    let pattern = root.join("**").join("*").to_string_lossy().to_string();
+    let mut files: Vec<PathBuf> = glob::glob(&pattern)
+        .map_err(|e| format!("Failed to list {}: {}", root.display(), e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| RESOURCE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        })
+        .filter(|path| !path.components().any(|c| c.as_os_str() == crate::backup::BACKUP_DIR))
+        .filter(|path| path.canonicalize().is_ok_and(|real| real.starts_with(&root) && real.is_file()))
+        .collect();
+    files.sort();
+
+    let resources = files
+        .iter()
+        .skip(offset)
+        .take(RESOURCE_PAGE_SIZE)
+        .map(|path| describe_resource(&root, path))
+        .collect();
+    let next = offset + RESOURCE_PAGE_SIZE;
+    Ok((resources, (next < files.len()).then(|| next.to_string())))
+}
+
+/// Build the resource entry for a file, reading its header for the dimensions.
+fn describe_resource(root: &Path, path: &Path) -> Resource {
+    let name = path
+        .strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/");
+    let mut header = Vec::new();
+    if let Ok(file) = std::fs::File::open(path) {
+        file.take(4096).read_to_end(&mut header).ok();
+    }
+    let dims = image_dimensions(&header);
+    let sprite = is_sprite(path);
+    let description = match (dims, sprite) {
+        (Some((w, h)), true) => format!("{}x{} Aseprite sprite (read as a PNG preview of frame 1)", w, h),
+        (Some((w, h)), false) => format!("{}x{} image", w, h),
+        (None, true) => "Aseprite sprite (read as a PNG preview of frame 1)".to_string(),
+        (None, false) => "Image".to_string(),
+    };
+    let mime = if sprite {
+        "image/png"
+    } else {
+        image_mime_type(&path.to_string_lossy()).unwrap_or("application/octet-stream")
+    };
+    RawResource {
+        uri: file_uri(path),
+        name,
+        description: Some(description),
+        mime_type: Some(mime.to_string()),
+        size: std::fs::metadata(path).ok().map(|m| m.len().min(u32::MAX as u64) as u32),
+    }
+    .no_annotation()
+}
+
+/// Read a `file://` resource as a base64 blob. Sprites are rendered to a PNG of frame 1 first.
+pub async fn read_resource(server: &AsepriteServer, uri: &str) -> Result<ReadResourceResult, String> {
+    let path = uri_path(uri).ok_or_else(|| format!("Unsupported resource URI: {}", uri))?;
+    let root = resource_root(server)
+        .canonicalize()
+        .map_err(|e| format!("Resource directory is not accessible: {}", e))?;
+    let real = path
+        .canonicalize()
+        .map_err(|_| format!("Resource not found: {}", uri))?;
+    if !real.starts_with(&root) || !real.is_file() {
+        return Err(format!("Resource not found: {}", uri));
+    }
+    server.check_path_allowed(&real)?;
+
+    let (bytes, mime) = if is_sprite(&real) {
+        (render_preview(server, &real).await?, "image/png")
+    } else {
+        let path_str = real.to_string_lossy();
+        let mime = image_mime_type(&path_str).ok_or_else(|| format!("Not an image resource: {}", uri))?;
+        check_size(std::fs::metadata(&real).map(|m| m.len()).unwrap_or(0), uri)?;
+        let bytes = tokio::fs::read(&real)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", uri, e))?;
+        (bytes, mime)
+    };
+    check_size(bytes.len() as u64, uri)?;
+    Ok(ReadResourceResult {
+        contents: vec![ResourceContents::BlobResourceContents {
+            uri: uri.to_string(),
+            mime_type: Some(mime.to_string()),
+            blob: base64::engine::general_purpose::STANDARD.encode(bytes),
+        }],
+    })
+}
+
+fn check_size(size: u64, uri: &str) -> Result<(), String> {
+    if size > MAX_INLINE_IMAGE_BYTES {
+        return Err(format!(
+            "{} is {} bytes, over the {} byte resource limit",
+            uri, size, MAX_INLINE_IMAGE_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Render frame 1 of a sprite (all visible layers) to PNG bytes.
+async fn render_preview(server: &AsepriteServer, path: &Path) -> Result<Vec<u8>, String> {
+    let preview = server.temp_file_path("png");
+    let script = format!(
+        r#"local spr = app.sprite
+local img = Image(spr.spec)
+img:drawSprite(spr, 1)
+img:saveAs{{ filename = {out}, palette = spr.palettes[1] }}
+print(json.encode({{status = "rendered"}}))"#,
+        out = lua_path(&preview.to_string_lossy())
+    );
+    let rendered = server
+        .execute_script_on_file(&path.to_string_lossy(), &script)
+        .await;
+    let bytes = tokio::fs::read(&preview).await;
+    tokio::fs::remove_file(&preview).await.ok();
+    rendered?;
+    bytes.map_err(|e| format!("Failed to render preview: {}", e))
+}
+
+/// Format an absolute path as a `file://` URI, percent-encoding characters that aren't URI-safe.
+fn file_uri(path: &Path) -> String {
+    let mut path = path.to_string_lossy().replace('\\', "/");
+    // Windows verbatim prefixes from canonicalize aren't part of a file URI
+    if let Some(stripped) = path.strip_prefix("//?/") {
+        path = stripped.to_string();
+    }
+    if !path.starts_with('/') {
+        path.insert(0, '/');
+    }
+    let mut uri = String::from("file://");
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~:".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Parse a `file://` URI back into a path, decoding percent escapes.
+fn uri_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.extend(decode_hex(std::str::from_utf8(&hex).ok()?).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    let path = String::from_utf8(bytes).ok()?;
+    // "/C:/dir" on Windows
+    if path.as_bytes().get(2) == Some(&b':') {
+        return Some(PathBuf::from(&path[1..]));
+    }
+    Some(PathBuf::from(path))
+}
@@ -6,12 +6,20 @@ use rmcp::{
     service::RequestContext,
 };
 use rmcp::handler::server::tool::Parameters;
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tracing::{error, info};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{OnceCell, OwnedMutexGuard};
+use tracing::{error, info, warn};
 
-use crate::aseprite::{AsepriteRunner, ScriptOutput};
+use crate::aseprite::{
+    AsepriteCommand, AsepriteRunner, ScriptOutput, dry_run, is_dry_run, record_runs, with_cancellation,
+    with_timeout,
+};
+use crate::backup;
+use crate::lua_helpers::{LUA_SAVE_AS_ORIGINAL, LUA_SAVE_IN_PLACE};
 use crate::tools;
+use crate::utils::script_error;
 
 // ============================================================================
 // AsepriteServer
@@ -23,9 +31,80 @@ pub struct AsepriteServer {
     /// Default output directory for generated files. Read from ASEPRITE_OUTPUT_DIR env var.
     /// When set, relative output paths are resolved against this directory.
     output_dir: Option<PathBuf>,
+    /// Workspace directory for relative paths. Read from ASEPRITE_WORKSPACE_DIR env var.
+    /// Relative inputs are looked up here first; relative outputs land here when no output dir is set.
+    workspace_dir: Option<PathBuf>,
+    /// Library of named Lua scripts (ASEPRITE_SCRIPTS_DIR) for `list_scripts`/`run_named_script`.
+    scripts_dir: Option<PathBuf>,
+    /// Canonical directories tools may read and write, from ASEPRITE_ALLOWED_DIRS. `None` means
+    /// unrestricted; when set, every input and output path must lie under one of them.
+    allowed_dirs: Option<Arc<Vec<PathBuf>>>,
+    /// Whether `run_lua_script`/`execute_cli` stay enabled in sandboxed mode
+    /// (ASEPRITE_ALLOW_ARBITRARY_SCRIPTS=1).
+    allow_arbitrary_scripts: bool,
+    /// One async lock per sprite file (keyed on the canonical path), so concurrent tool calls on
+    /// the same file run one at a time instead of overwriting each other's saves.
+    file_locks: Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>,
+    /// Settings the client may change at runtime (see `set_config`).
+    config: Arc<RwLock<ServerConfig>>,
+    /// Backups kept per file (ASEPRITE_BACKUP_KEEP, default 10).
+    backup_keep: usize,
+    /// When set (ASEPRITE_DRY_RUN=1), every tool call is a dry run (see `call_tool`).
+    dry_run: bool,
+    /// Add a `meta` object with each Aseprite run's exit code, duration and stderr to successful
+    /// results (ASEPRITE_VERBOSE_RESULTS=1).
+    verbose_results: bool,
+    /// The last `RECENT_OPERATIONS` tool calls, oldest first (see `get_recent_operations`).
+    recent_operations: Arc<Mutex<VecDeque<serde_json::Value>>>,
+    /// Aseprite version and feature probe, filled by the first `get_aseprite_info` call.
+    aseprite_info: Arc<OnceCell<serde_json::Value>>,
+    /// Fonts found by the first `list_fonts` call.
+    font_list: Arc<OnceCell<serde_json::Value>>,
     tool_router: ToolRouter<Self>,
 }
 
+/// Settings that `set_config` can change mid-session. Everything security-related (sandbox
+/// roots, script permissions, directories) is fixed at startup.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ServerConfig {
+    /// Back up files before tools save over them (ASEPRITE_BACKUP=1); calls can override it
+    /// with the per-call `backup` flag.
+    pub auto_backup: bool,
+    /// Color mode `create_sprite` uses when none is given.
+    pub default_color_mode: String,
+    /// Whether exports return the image inline when `return_base64` isn't passed.
+    pub return_base64: bool,
+    /// Largest region `get_pixel_data` reads in one call, in pixels (ASEPRITE_MAX_PIXEL_READ_AREA).
+    pub max_pixel_read_area: u64,
+    /// Most pixels `draw_pixels` (and points `use_tool`) take in one call, and in total across
+    /// a `batch_edit` (ASEPRITE_MAX_DRAW_PIXELS).
+    pub max_draw_pixels: u64,
+    /// Most entries `set_palette_color` takes in one call (ASEPRITE_MAX_PALETTE_ENTRIES).
+    pub max_palette_entries: u64,
+}
+
+/// Tool calls kept for `get_recent_operations`.
+const RECENT_OPERATIONS: usize = 100;
+
+/// Default `max_pixel_read_area`: a 256x256 region, which is already a large JSON result.
+const DEFAULT_MAX_PIXEL_READ_AREA: u64 = 256 * 256;
+
+/// Default `max_draw_pixels`: each pixel becomes a line of generated Lua, so larger edits make
+/// scripts that are slow to send and parse.
+const DEFAULT_MAX_DRAW_PIXELS: u64 = 5000;
+
+/// Default `max_palette_entries`: enough for any indexed palette several times over.
+const DEFAULT_MAX_PALETTE_ENTRIES: u64 = 1024;
+
+/// A positive integer limit from the environment, or `default` when unset or invalid.
+fn env_limit(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&n: &u64| n > 0)
+        .unwrap_or(default)
+}
+
 // ============================================================================
 // Tool Routing — thin wrappers that delegate to tool modules
 // ============================================================================
@@ -33,7 +112,12 @@ pub struct AsepriteServer {
 #[tool_router]
 impl AsepriteServer {
     pub fn new() -> anyhow::Result<Self> {
-        let runner = Arc::new(AsepriteRunner::new()?);
+        Ok(Self::with_runner(AsepriteRunner::new()?))
+    }
+
+    /// Create a server around `runner`, with the other settings from the environment.
+    pub fn with_runner(runner: AsepriteRunner) -> Self {
+        let runner = Arc::new(runner);
         let output_dir = std::env::var("ASEPRITE_OUTPUT_DIR").ok().map(|dir| {
             let path = PathBuf::from(&dir);
             if !path.exists() {
@@ -43,11 +127,71 @@ impl AsepriteServer {
             info!("Output directory set to: {}", path.display());
             path
         });
-        Ok(Self {
+        let workspace_dir = std::env::var("ASEPRITE_WORKSPACE_DIR").ok().map(|dir| {
+            let path = PathBuf::from(&dir);
+            info!("Workspace directory set to: {}", path.display());
+            path
+        });
+        let scripts_dir = std::env::var("ASEPRITE_SCRIPTS_DIR")
+            .ok()
+            .filter(|dir| !dir.trim().is_empty())
+            .map(|dir| PathBuf::from(dir.trim()));
+        let allowed_dirs = std::env::var("ASEPRITE_ALLOWED_DIRS").ok().map(|dirs| {
+            let mut roots: Vec<PathBuf> = dirs
+                .split(';')
+                .flat_map(|part| std::env::split_paths(part).collect::<Vec<_>>())
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .filter_map(|dir| match dir.canonicalize() {
+                    Ok(canonical) => Some(canonical),
+                    Err(e) => {
+                        warn!("Ignoring allowed directory {}: {}", dir.display(), e);
+                        None
+                    }
+                })
+                .collect();
+            // Temporary scripts and intermediate renders live in the runner's temp dir
+            if let Ok(temp_dir) = runner.temp_dir().canonicalize() {
+                roots.push(temp_dir);
+            }
+            info!("Sandboxed to directories: {:?}", roots);
+            Arc::new(roots)
+        });
+        let allow_arbitrary_scripts = std::env::var("ASEPRITE_ALLOW_ARBITRARY_SCRIPTS").is_ok_and(|v| v == "1");
+        let dry_run = std::env::var("ASEPRITE_DRY_RUN").is_ok_and(|v| v == "1");
+        let config = ServerConfig {
+            auto_backup: std::env::var("ASEPRITE_BACKUP").is_ok_and(|v| v == "1"),
+            default_color_mode: "rgb".to_string(),
+            return_base64: false,
+            max_pixel_read_area: env_limit("ASEPRITE_MAX_PIXEL_READ_AREA", DEFAULT_MAX_PIXEL_READ_AREA),
+            max_draw_pixels: env_limit("ASEPRITE_MAX_DRAW_PIXELS", DEFAULT_MAX_DRAW_PIXELS),
+            max_palette_entries: env_limit("ASEPRITE_MAX_PALETTE_ENTRIES", DEFAULT_MAX_PALETTE_ENTRIES),
+        };
+        let verbose_results = std::env::var("ASEPRITE_VERBOSE_RESULTS").is_ok_and(|v| v == "1");
+        let backup_keep = std::env::var("ASEPRITE_BACKUP_KEEP")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|&n: &usize| n > 0)
+            .unwrap_or(backup::DEFAULT_BACKUP_KEEP);
+        if dry_run {
+            info!("Dry-run mode: tools report their Lua/CLI runs without starting Aseprite");
+        }
+        Self {
             runner,
             output_dir,
+            workspace_dir,
+            scripts_dir,
+            allowed_dirs,
+            allow_arbitrary_scripts,
+            config: Arc::new(RwLock::new(config)),
+            backup_keep,
+            dry_run,
+            verbose_results,
+            recent_operations: Arc::default(),
+            file_locks: Arc::default(),
+            aseprite_info: Arc::default(),
+            font_list: Arc::default(),
             tool_router: Self::tool_router(),
-        })
+        }
     }
 
     // ========================================================================
@@ -70,6 +214,30 @@ impl AsepriteServer {
         tools::sprite::get_sprite_info(self, params.0).await
     }
 
+    #[tool(description = "Describe an unfamiliar sprite in one call: a compact text summary (canvas size, color mode, frame count and total duration, content bounds, the 5 dominant colors with percentages, the layer tree as an indented list, tags with frame counts, durations, and direction) plus a small inline thumbnail of frame 1.")]
+    async fn describe_sprite(
+        &self,
+        params: Parameters<tools::sprite::DescribeSpriteParams>,
+    ) -> Result<CallToolResult, McpError> {
+        rich_result(tools::sprite::describe_sprite(self, params.0).await)
+    }
+
+    #[tool(description = "Look at an animation without exporting anything: returns small inline PNG thumbnails of a sprite's frames (all frames, one tag, or a from_frame/to_frame range), downscaled with nearest-neighbor so pixel art stays legible, either one image per frame or a single side-by-side strip. At most 32 frames per call; page longer ranges with offset (next_offset is reported).")]
+    async fn get_frame_thumbnails(
+        &self,
+        params: Parameters<tools::sprite::GetFrameThumbnailsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        rich_result(tools::sprite::get_frame_thumbnails(self, params.0).await)
+    }
+
+    #[tool(description = "Report what makes a sprite big: file size, per-layer cel counts and image bytes, empty cels, duplicate cel images that could be linked, already-linked cels, unused palette entries, and canvas utilization (content bounds vs canvas area).")]
+    async fn get_sprite_stats(
+        &self,
+        params: Parameters<tools::sprite::SpriteFileParams>,
+    ) -> Result<String, String> {
+        tools::sprite::get_sprite_stats(self, params.0).await
+    }
+
     #[tool(description = "Resize a sprite to specified width and height in pixels.")]
     async fn resize_sprite(
         &self,
@@ -86,6 +254,14 @@ impl AsepriteServer {
         tools::sprite::crop_sprite(self, params.0).await
     }
 
+    #[tool(description = "Crop a sprite to the bounds of its stored selection, or to an explicit region. The selection is cleared afterwards.")]
+    async fn crop_to_selection(
+        &self,
+        params: Parameters<tools::sprite::CropToSelectionParams>,
+    ) -> Result<String, String> {
+        tools::sprite::crop_to_selection(self, params.0).await
+    }
+
     #[tool(description = "Flip a sprite horizontally or vertically.")]
     async fn flip_sprite(
         &self,
@@ -110,6 +286,14 @@ impl AsepriteServer {
         tools::sprite::canvas_size(self, params.0).await
     }
 
+    #[tool(description = "Pad the canvas so both sides reach the next power of two (mode \"pot\") or the next multiple of N (mode \"multiple\"), e.g. for GPU-friendly textures. Content stays at the top-left or is centered (anchor); sizes that already fit are left unchanged. Reports the old and new dimensions and the padding added.")]
+    async fn pad_canvas_to(
+        &self,
+        params: Parameters<tools::sprite::PadCanvasToParams>,
+    ) -> Result<String, String> {
+        tools::sprite::pad_canvas_to(self, params.0).await
+    }
+
     #[tool(description = "Duplicate a sprite to a new file, preserving all layers, frames, tags, and slices.")]
     async fn duplicate_sprite(
         &self,
@@ -118,7 +302,15 @@ impl AsepriteServer {
         tools::sprite::duplicate_sprite(self, params.0).await
     }
 
-    #[tool(description = "Auto-crop a sprite, trimming transparent borders to fit the content tightly. Essential for optimizing game sprite sizes.")]
+    #[tool(description = "Create a new sprite file containing only a region (or a slice's bounds) of an existing sprite. Optionally keep just one layer or frame, and keep the layer structure instead of flattening.")]
+    async fn extract_region_to_sprite(
+        &self,
+        params: Parameters<tools::sprite::ExtractRegionParams>,
+    ) -> Result<String, String> {
+        tools::sprite::extract_region_to_sprite(self, params.0).await
+    }
+
+    #[tool(description = "Auto-crop a sprite, trimming transparent borders to fit the content tightly. Essential for optimizing game sprite sizes. Pass file_paths or glob instead of file_path to crop several files in turn; each is saved in place and reported separately, and a failure doesn't stop the rest.")]
     async fn auto_crop_sprite(
         &self,
         params: Parameters<tools::sprite::AutoCropParams>,
@@ -142,16 +334,32 @@ impl AsepriteServer {
         tools::sprite::reverse_frames(self, params.0).await
     }
 
+    #[tool(description = "Check that a sprite file opens cleanly. Returns status 'ok', 'opened_with_warnings' (e.g. truncated file, cels without images, Aseprite warnings), 'unreadable' (with Aseprite's error), or 'missing', plus dimensions and layer/frame/cel counts. With repair=true, a readable but damaged file is re-saved to output_path.")]
+    async fn validate_sprite(
+        &self,
+        params: Parameters<tools::sprite::ValidateSpriteParams>,
+    ) -> Result<String, String> {
+        tools::sprite::validate_sprite(self, params.0).await
+    }
+
+    #[tool(description = "Report cels, slices, and tilemap cels whose origin isn't aligned to the grid (the sprite's grid, or a caller-supplied {x, y, width, height}; tilemap cels are checked against their tileset grid). Each entry gives its offset past the nearest grid lines.")]
+    async fn check_grid_alignment(
+        &self,
+        params: Parameters<tools::sprite::CheckGridAlignmentParams>,
+    ) -> Result<String, String> {
+        tools::sprite::check_grid_alignment(self, params.0).await
+    }
+
     // ========================================================================
     // Layer Management Tools
     // ========================================================================
 
-    #[tool(description = "List all layers in a sprite file with name, visibility, opacity, blend mode, and hierarchy information.")]
+    #[tool(description = "List all layers in a sprite file with name, visibility, opacity, blend mode, and hierarchy information. Filter with name_contains and page large files with limit/offset; the result has total and returned counts.")]
     async fn list_layers(
         &self,
-        params: Parameters<tools::sprite::SpriteFileParams>,
+        params: Parameters<tools::layer::ListLayersParams>,
     ) -> Result<String, String> {
-        tools::layer::list_layers(self, &params.0.file_path).await
+        tools::layer::list_layers(self, params.0).await
     }
 
     #[tool(description = "Add a new layer or group layer to a sprite. Optionally specify where to insert it.")]
@@ -206,12 +414,12 @@ impl AsepriteServer {
     // Frame Management Tools
     // ========================================================================
 
-    #[tool(description = "List all frames in a sprite with frame numbers and durations in seconds.")]
+    #[tool(description = "List all frames in a sprite with frame numbers and durations in seconds. Filter with from_frame/to_frame and page with limit/offset; the result has total and returned counts.")]
     async fn list_frames(
         &self,
-        params: Parameters<tools::sprite::SpriteFileParams>,
+        params: Parameters<tools::frame::ListFramesParams>,
     ) -> Result<String, String> {
-        tools::frame::list_frames(self, &params.0.file_path).await
+        tools::frame::list_frames(self, params.0).await
     }
 
     #[tool(description = "Add one or more frames to a sprite. Can create copies of the current frame or empty frames.")]
@@ -238,6 +446,14 @@ impl AsepriteServer {
         tools::frame::set_frame_duration(self, params.0).await
     }
 
+    #[tool(description = "Find frames with identical flattened images (or within a per-channel tolerance, compared on a downsampled copy) in the whole sprite, a tag, or a frame range. Returns groups of matching frame numbers. apply=\"merge_durations\" deletes duplicates that directly repeat the previous frame and adds their duration to it.")]
+    async fn find_duplicate_frames(
+        &self,
+        params: Parameters<tools::frame::FindDuplicateFramesParams>,
+    ) -> Result<String, String> {
+        tools::frame::find_duplicate_frames(self, params.0).await
+    }
+
     // ========================================================================
     // Tag Management Tools
     // ========================================================================
@@ -270,12 +486,12 @@ impl AsepriteServer {
     // Slice Management Tools (Game Dev — hitboxes, 9-slice UI, pivots)
     // ========================================================================
 
-    #[tool(description = "List all slices in a sprite with bounds, 9-slice center, pivot point, and user data. Slices define named regions for game engines (hitboxes, UI elements, anchors).")]
+    #[tool(description = "List all slices in a sprite with bounds, 9-slice center, pivot point, and user data. Slices define named regions for game engines (hitboxes, UI elements, anchors). Filter with name_contains and page with limit/offset.")]
     async fn list_slices(
         &self,
-        params: Parameters<tools::sprite::SpriteFileParams>,
+        params: Parameters<tools::slice::ListSlicesParams>,
     ) -> Result<String, String> {
-        tools::slice::list_slices(self, &params.0.file_path).await
+        tools::slice::list_slices(self, params.0).await
     }
 
     #[tool(description = "Create a new slice (named region) in a sprite. Supports 9-slice center rect for UI scaling, pivot point for anchor/origin, and custom data for game metadata.")]
@@ -294,11 +510,83 @@ impl AsepriteServer {
         tools::slice::delete_slice(self, params.0).await
     }
 
+    // ========================================================================
+    // Tileset Tools
+    // ========================================================================
+
+    #[tool(description = "List a sprite's tilesets with name, tile size, tile count (including the empty tile 0), and base index. Sprites without tilesets return an empty list.")]
+    async fn list_tilesets(
+        &self,
+        params: Parameters<tools::sprite::SpriteFileParams>,
+    ) -> Result<String, String> {
+        tools::tileset::list_tilesets(self, &params.0.file_path).await
+    }
+
+    #[tool(description = "Return one tile of a tileset as an inline PNG image, selected by tileset (name or 1-based index, default first) and tile_index. Optionally also save it to output_path.")]
+    async fn get_tile_image(
+        &self,
+        params: Parameters<tools::tileset::GetTileImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        rich_result(tools::tileset::get_tile_image(self, params.0).await)
+    }
+
+    #[tool(description = "Create a tileset with the given tile size. With from_layer, that image layer is converted to a tilemap layer: it is cut into tiles on the grid and identical tiles are stored once. Reports the number of unique tiles. Requires an .aseprite file and Aseprite 1.3+.")]
+    async fn create_tileset(
+        &self,
+        params: Parameters<tools::tileset::CreateTilesetParams>,
+    ) -> Result<String, String> {
+        tools::tileset::create_tileset(self, params.0).await
+    }
+
+    #[tool(description = "Lay out a tilemap layer from a 2D array of tile indices (tiles[row][column], 0 = empty tile), with optional flip flags per tile (\"x\", \"y\", \"d\"). The layer is created as a tilemap layer if missing. The map must fit the canvas; out-of-range tile indices are reported. Requires an .aseprite file and Aseprite 1.3+.")]
+    async fn set_tilemap(
+        &self,
+        params: Parameters<tools::tileset::SetTilemapParams>,
+    ) -> Result<String, String> {
+        tools::tileset::set_tilemap(self, params.0).await
+    }
+
+    #[tool(description = "Read a tilemap layer as a 2D array of tile indices (tiles[row][column], 0 = empty) plus flip flags when any tile is flipped, with the grid size. Use region (in tile coordinates) to read part of a large map.")]
+    async fn get_tilemap(
+        &self,
+        params: Parameters<tools::tileset::GetTilemapParams>,
+    ) -> Result<String, String> {
+        tools::tileset::get_tilemap(self, params.0).await
+    }
+
+    #[tool(description = "Export a tileset as an image of its tiles on a grid (columns, padding between tiles) plus a JSON sidecar with tile size, count, columns, and rows for Godot/Tiled importers. Tile i of the image is tileset index i, including the empty tile 0.")]
+    async fn export_tileset(
+        &self,
+        params: Parameters<tools::tileset::ExportTilesetParams>,
+    ) -> Result<String, String> {
+        tools::tileset::export_tileset(self, params.0).await
+    }
+
+    // ========================================================================
+    // User Data Tools
+    // ========================================================================
+
+    #[tool(description = "Read the user data of a sprite, layer, tag, slice, or cel: data string, color, and Aseprite 1.3 typed properties. Select by name (layer/tag/slice) or layer + frame (cel).")]
+    async fn get_user_data(
+        &self,
+        params: Parameters<tools::user_data::GetUserDataParams>,
+    ) -> Result<String, String> {
+        tools::user_data::get_user_data(self, params.0).await
+    }
+
+    #[tool(description = "Set the user data of a sprite, layer, tag, slice, or cel: data string, color, and/or typed properties. Properties go into Aseprite 1.3's properties table, or JSON-encoded into the data string on older versions; the result's `storage` says which was used.")]
+    async fn set_user_data(
+        &self,
+        params: Parameters<tools::user_data::SetUserDataParams>,
+    ) -> Result<String, String> {
+        tools::user_data::set_user_data(self, params.0).await
+    }
+
     // ========================================================================
     // Cel Management Tools
     // ========================================================================
 
-    #[tool(description = "List all cels in a sprite with layer, frame, position, size, and opacity. Optionally filter by layer name or frame number.")]
+    #[tool(description = "List all cels in a sprite with layer, frame, position, size, and opacity. Optionally filter by layer name, frame, or from_frame/to_frame range, and page with limit/offset; the result has total and returned counts.")]
     async fn list_cels(
         &self,
         params: Parameters<tools::cel::ListCelsParams>,
@@ -330,6 +618,14 @@ impl AsepriteServer {
         tools::cel::clear_cel(self, params.0).await
     }
 
+    #[tool(description = "Delete every cel in a frame range on one or more layers in a single transaction, e.g. to wipe a scratch layer. Reports how many cels were deleted and how many frames were already empty, per layer.")]
+    async fn clear_cels_range(
+        &self,
+        params: Parameters<tools::cel::ClearCelsRangeParams>,
+    ) -> Result<String, String> {
+        tools::cel::clear_cels_range(self, params.0).await
+    }
+
     #[tool(description = "Create a new empty cel at a specific layer and frame.")]
     async fn new_cel(
         &self,
@@ -338,11 +634,43 @@ impl AsepriteServer {
         tools::cel::new_cel(self, params.0).await
     }
 
+    #[tool(description = "Move a layer's cels along an eased path between two keyframes (e.g. a bobbing pickup or a travelling projectile). Start and end positions default to the cels already at the keyframes. Frames without a cel get an unlinked copy of the start frame's image, and linked cels in the range are unlinked: Aseprite stores one position for all cels in a link, so linked cels couldn't move independently.")]
+    async fn tween_cel_positions(
+        &self,
+        params: Parameters<tools::cel::TweenCelPositionsParams>,
+    ) -> Result<String, String> {
+        tools::cel::tween_cel_positions(self, params.0).await
+    }
+
+    #[tool(description = "Scale and/or rotate the contents of a layer's cels across a frame range around a pivot that stays fixed on the canvas, for squash-and-stretch or spin animations without redrawing. Scaling uses Aseprite's resize (\"nearest\" or \"rotsprite\"); rotation takes any angle. Returns each cel's new position and size; linked cels are transformed once.")]
+    async fn transform_cel_contents(
+        &self,
+        params: Parameters<tools::cel::TransformCelContentsParams>,
+    ) -> Result<String, String> {
+        tools::cel::transform_cel_contents(self, params.0).await
+    }
+
+    #[tool(description = "Copy every cel of one layer onto another, shifted in time by frame_offset and on the canvas by position_offset (e.g. the same flame on a second torch, two frames out of phase). Frames are added as needed and the target layer is created if missing. Existing target cels are kept unless overwrite is set; link keeps linked source cels linked on the target.")]
+    async fn copy_layer_animation(
+        &self,
+        params: Parameters<tools::cel::CopyLayerAnimationParams>,
+    ) -> Result<String, String> {
+        tools::cel::copy_layer_animation(self, params.0).await
+    }
+
+    #[tool(description = "Bake an opacity fade into a layer's cels across a frame range (start_opacity to end_opacity with optional easing), for engines that only play flat sprite sheets. fill_missing gives frames without a cel a copy of the nearest earlier cel so the fade is continuous. Returns the opacity applied per frame.")]
+    async fn fade_layer(
+        &self,
+        params: Parameters<tools::cel::FadeLayerParams>,
+    ) -> Result<String, String> {
+        tools::cel::fade_layer(self, params.0).await
+    }
+
     // ========================================================================
     // Drawing Tools
     // ========================================================================
 
-    #[tool(description = "Draw individual pixels on a sprite at specified coordinates with given colors (hex format like '#ff0000'). Optionally target a specific layer and frame.")]
+    #[tool(description = "Draw individual pixels on a sprite at specified coordinates with given colors (hex format like '#ff0000'). Optionally target a specific layer and frame. At most 5000 pixels per call by default (ASEPRITE_MAX_DRAW_PIXELS / set_config max_draw_pixels); split larger edits into chunks or use fill_region for solid areas.")]
     async fn draw_pixels(
         &self,
         params: Parameters<tools::drawing::DrawPixelsParams>,
@@ -350,7 +678,7 @@ impl AsepriteServer {
         tools::drawing::draw_pixels(self, params.0).await
     }
 
-    #[tool(description = "Use an Aseprite drawing tool (pencil, line, rectangle, filled_rectangle, ellipse, filled_ellipse, paint_bucket, spray, eraser) with specified points, color, brush size, and opacity.")]
+    #[tool(description = "Use an Aseprite drawing tool (pencil, line, rectangle, filled_rectangle, ellipse, filled_ellipse, paint_bucket, spray, eraser) with specified points, color, brush size, and opacity. At most 5000 points per call by default (max_draw_pixels).")]
     async fn use_tool(
         &self,
         params: Parameters<tools::drawing::UseToolParams>,
@@ -358,7 +686,7 @@ impl AsepriteServer {
         tools::drawing::use_tool(self, params.0).await
     }
 
-    #[tool(description = "Read pixel color data from a rectangular region of a sprite. Returns an array of pixel colors in hex format.")]
+    #[tool(description = "Read pixel color data from a rectangular region of a sprite. Returns an array of pixel colors in hex format. Coordinates may be negative (e.g. over a moved cel); pixels outside the image read as transparent. At most 65536 pixels (width * height) per call by default (ASEPRITE_MAX_PIXEL_READ_AREA / set_config max_pixel_read_area).")]
     async fn get_pixel_data(
         &self,
         params: Parameters<tools::drawing::GetPixelDataParams>,
@@ -366,6 +694,30 @@ impl AsepriteServer {
         tools::drawing::get_pixel_data(self, params.0).await
     }
 
+    #[tool(description = "Find stray pixels on a layer (or the flattened image): mode \"isolated\" finds small opaque islands floating apart from the rest, mode \"holes\" finds small transparent gaps enclosed by opaque pixels. Returns each island's pixel coordinates and colors; fix=true erases floaters or fills holes with the surrounding color.")]
+    async fn find_stray_pixels(
+        &self,
+        params: Parameters<tools::drawing::FindStrayPixelsParams>,
+    ) -> Result<String, String> {
+        tools::drawing::find_stray_pixels(self, params.0).await
+    }
+
+    #[tool(description = "Fill every pixel of a rectangle (or the sprite's active selection) with a color, regardless of existing content. A fully transparent color (e.g. '#00000000') erases the region.")]
+    async fn fill_region(
+        &self,
+        params: Parameters<tools::drawing::FillRegionParams>,
+    ) -> Result<String, String> {
+        tools::drawing::fill_region(self, params.0).await
+    }
+
+    #[tool(description = "Skew (shear) a layer's cel or a rectangle of it: shear_x shifts each row sideways (italics, wind-blown grass, fake perspective) and shear_y shifts each column up or down, in pixels per row/column or degrees. The cel grows to fit moved content unless wrap=true keeps it inside the region. Returns the new content bounds.")]
+    async fn skew_region(
+        &self,
+        params: Parameters<tools::drawing::SkewRegionParams>,
+    ) -> Result<String, String> {
+        tools::drawing::skew_region(self, params.0).await
+    }
+
     // ========================================================================
     // Palette Tools
     // ========================================================================
@@ -378,7 +730,7 @@ impl AsepriteServer {
         tools::palette::get_palette(self, params.0).await
     }
 
-    #[tool(description = "Set one or more colors in the sprite's palette by index. Colors should be hex strings like '#ff0000'.")]
+    #[tool(description = "Set one or more colors in the sprite's palette by index. Colors should be hex strings like '#ff0000'. At most 1024 entries per call by default (ASEPRITE_MAX_PALETTE_ENTRIES / set_config max_palette_entries); use load_palette for whole palettes.")]
     async fn set_palette_color(
         &self,
         params: Parameters<tools::palette::SetPaletteColorParams>,
@@ -410,7 +762,7 @@ impl AsepriteServer {
         tools::palette::save_palette(self, params.0).await
     }
 
-    #[tool(description = "Automatically generate an optimized palette from sprite colors using color quantization. Great for reducing color count for indexed-mode game sprites.")]
+    #[tool(description = "Automatically generate an optimized palette from sprite colors using color quantization. Great for reducing color count for indexed-mode game sprites. Pass file_paths or glob instead of file_path to quantize several files in turn; each is saved in place and reported separately, and a failure doesn't stop the rest.")]
     async fn color_quantization(
         &self,
         params: Parameters<tools::palette::ColorQuantizationParams>,
@@ -418,6 +770,22 @@ impl AsepriteServer {
         tools::palette::color_quantization(self, params.0).await
     }
 
+    #[tool(description = "Check whether an RGB sprite can become indexed without changing any pixel: unique color count, off-palette colors (with pixel counts and an example location), and whether a lossless conversion is possible. fix=true snaps off-palette pixels to their nearest palette color.")]
+    async fn check_indexed_compliance(
+        &self,
+        params: Parameters<tools::palette::CheckIndexedComplianceParams>,
+    ) -> Result<String, String> {
+        tools::palette::check_indexed_compliance(self, params.0).await
+    }
+
+    #[tool(description = "Bake a palette-cycling animation (water, fire, glowing runes) from one painted frame of an indexed sprite: the frame is duplicated and each copy has the start_index..end_index span rotated one more step. Returns the number of frames created and the cycled index range.")]
+    async fn generate_palette_cycle(
+        &self,
+        params: Parameters<tools::palette::GeneratePaletteCycleParams>,
+    ) -> Result<String, String> {
+        tools::palette::generate_palette_cycle(self, params.0).await
+    }
+
     // ========================================================================
     // Selection Tools
     // ========================================================================
@@ -462,31 +830,163 @@ impl AsepriteServer {
         tools::selection::select_by_color(self, params.0).await
     }
 
+    #[tool(description = "Get information about the sprite's stored selection: whether it exists, its bounds, optionally the selected pixel count, and whether a given point is inside it.")]
+    async fn get_selection_info(
+        &self,
+        params: Parameters<tools::selection::GetSelectionInfoParams>,
+    ) -> Result<String, String> {
+        tools::selection::get_selection_info(self, params.0).await
+    }
+
+    #[tool(description = "Set the sprite selection to a slice's bounds (or its key bounds at a given frame). Mode can be 'replace', 'add', 'subtract', or 'intersect'.")]
+    async fn select_from_slice(
+        &self,
+        params: Parameters<tools::selection::SelectFromSliceParams>,
+    ) -> Result<String, String> {
+        tools::selection::select_from_slice(self, params.0).await
+    }
+
+    #[tool(description = "Select every non-transparent pixel (alpha at or above a threshold) of a layer or the flattened sprite. Returns the selection bounds and pixel count.")]
+    async fn select_opaque(
+        &self,
+        params: Parameters<tools::selection::SelectOpaqueParams>,
+    ) -> Result<String, String> {
+        tools::selection::select_opaque(self, params.0).await
+    }
+
+    #[tool(description = "Copy or cut pixels from a region (or the sprite's active selection) on a source layer into a new layer at the same canvas position. Useful for separating a drawn element onto its own layer.")]
+    async fn selection_to_layer(
+        &self,
+        params: Parameters<tools::selection::SelectionToLayerParams>,
+    ) -> Result<String, String> {
+        tools::selection::selection_to_layer(self, params.0).await
+    }
+
     // ========================================================================
     // Export Tools
     // ========================================================================
 
-    #[tool(description = "Export a sprite to a different format (png, gif, jpg, bmp, webp, etc.) with optional scale factor and layer/tag filtering.")]
+    #[tool(description = "Export a sprite to a different format (png, gif, jpg, bmp, webp, etc.) with optional scale factor, layer/tag filtering, frame range (from_frame/to_frame), and filename_format placeholders ({title}, {layer}, {tag}, {frame}). Set split_layers / split_tags to write one file per layer / tag (files written are listed). Set return_base64 to also receive the image inline.")]
     async fn export_sprite(
         &self,
         params: Parameters<tools::export::ExportSpriteParams>,
-    ) -> Result<String, String> {
-        tools::export::export_sprite(self, params.0).await
+    ) -> Result<CallToolResult, McpError> {
+        let timeout = params.0.timeout_secs;
+        rich_result(with_timeout(timeout, tools::export::export_sprite(self, params.0)).await)
     }
 
-    #[tool(description = "Export a sprite as a spritesheet image with optional JSON metadata. Supports horizontal, vertical, rows, columns, and packed layouts.")]
+    #[tool(description = "Export a sprite as a spritesheet image with optional JSON metadata. Supports horizontal, vertical, rows, columns, and packed layouts, plus border/shape/inner padding, edge extrusion, json-hash/json-array data with tags/layers/slices, and a summary of the written data.")]
     async fn export_spritesheet(
         &self,
         params: Parameters<tools::export::ExportSpritesheetParams>,
     ) -> Result<String, String> {
-        tools::export::export_spritesheet(self, params.0).await
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_spritesheet(self, params.0)).await
+    }
+
+    #[tool(description = "Export a packed spritesheet PNG plus a Unity-ready JSON describing each frame as a sprite: name, rect (bottom-left origin), normalized pivot (from a slice pivot or a default), duration, and pixelsPerUnit.")]
+    async fn export_unity_meta(
+        &self,
+        params: Parameters<tools::export::ExportUnityMetaParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_unity_meta(self, params.0)).await
+    }
+
+    #[tool(description = "Export a spritesheet plus a libGDX .atlas (or TexturePacker JSON hash) with regions named {title}_{tag} indexed per frame, including correct offsets for trimmed frames. Accepts the same layout/padding options as export_spritesheet.")]
+    async fn export_atlas(
+        &self,
+        params: Parameters<tools::export::ExportAtlasParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_atlas(self, params.0)).await
+    }
+
+    #[tool(description = "Export an animated GIF from a copy of the sprite (source untouched) with loop count, transparency, dithering, and palette size control. Returns the file size and palette size used.")]
+    async fn export_gif(
+        &self,
+        params: Parameters<tools::export::ExportGifParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_gif(self, params.0)).await
+    }
+
+    #[tool(description = "Export a single frame (or a frame range) to an image, optionally scaled and limited to one layer. Ranges become an animation for gif/webp outputs, otherwise numbered files.")]
+    async fn export_frame(
+        &self,
+        params: Parameters<tools::export::ExportFrameParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_frame(self, params.0)).await
+    }
+
+    #[tool(description = "Export a frame's raw pixel data for embedded targets as rgba8888, rgb565, or indexed8 bytes (with palette), either as a binary file or a C array source file with width/height defines.")]
+    async fn export_raw(
+        &self,
+        params: Parameters<tools::export::ExportRawParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_raw(self, params.0)).await
+    }
+
+    #[tool(description = "Export a frame as a Windows .ico (and optionally macOS .icns) containing nearest-neighbor renders at several sizes (default 16-256).")]
+    async fn export_icon(
+        &self,
+        params: Parameters<tools::export::ExportIconParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_icon(self, params.0)).await
+    }
+
+    #[tool(description = "Export an animation as lossless animated WebP or APNG, without GIF's 256-color limit. Honors frame durations and tag direction; returns the encoder used and output size.")]
+    async fn export_animation(
+        &self,
+        params: Parameters<tools::export::ExportAnimationParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::export_animation(self, params.0)).await
+    }
+
+    #[tool(description = "Pack several sprite/image files (paths or glob patterns) into one atlas image with a JSON mapping names to rects. Uses Aseprite's packer, falling back to shelf packing within max_width/max_height.")]
+    async fn pack_atlas(
+        &self,
+        params: Parameters<tools::export::PackAtlasParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::export::pack_atlas(self, params.0)).await
+    }
+
+    #[tool(description = "Make a small looping preview GIF (optionally just one tag) from a copy of the sprite for reviewing animation timing. Scaled (default 2x) but capped by max_dimension; set return_base64 to get it inline.")]
+    async fn export_preview(
+        &self,
+        params: Parameters<tools::export::ExportPreviewParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let timeout = params.0.timeout_secs;
+        rich_result(with_timeout(timeout, tools::export::export_preview(self, params.0)).await)
+    }
+
+    #[tool(description = "Render every frame (or one tag's frames) into a single PNG grid for review, with frame numbers burned in under each frame and tag boundaries marked with bars in the tag's color. Returns the grid layout and each frame's cell position; set return_base64 to get the sheet inline.")]
+    async fn export_contact_sheet(
+        &self,
+        params: Parameters<tools::export::ExportContactSheetParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let timeout = params.0.timeout_secs;
+        rich_result(with_timeout(timeout, tools::export::export_contact_sheet(self, params.0)).await)
+    }
+
+    #[tool(description = "Compare a flattened frame pixel-for-pixel against a reference PNG (e.g. a golden image in an art test). Reports match percentage, differing pixel count, and the bounding box of the differences; a size mismatch is reported with both sizes. Optionally writes a diff PNG (matches dimmed, differences magenta) and returns it inline.")]
+    async fn compare_to_image(
+        &self,
+        params: Parameters<tools::export::CompareToImageParams>,
+    ) -> Result<CallToolResult, McpError> {
+        rich_result(tools::export::compare_to_image(self, params.0).await)
     }
 
     // ========================================================================
     // Color Operations
     // ========================================================================
 
-    #[tool(description = "Replace all occurrences of one color with another color throughout the sprite, with optional tolerance.")]
+    #[tool(description = "Replace colors throughout the sprite, with optional tolerance. Give a single from_color/to_color pair or a mappings array of {from, to} pairs applied together in one pass (no cascading). Optional layer, frame or from_frame/to_frame, selection targeting, and output_path.")]
     async fn replace_color(
         &self,
         params: Parameters<tools::effects::ReplaceColorParams>,
@@ -494,7 +994,15 @@ impl AsepriteServer {
         tools::effects::replace_color(self, params.0).await
     }
 
-    #[tool(description = "Apply an outline effect around non-transparent pixels with a specified color.")]
+    #[tool(description = "Recolor by mapping between two palettes of equal length (each a palette file or an inline color array): every pixel matching from_palette color i becomes to_palette color i, in one pass with no cascading. Works on RGB sprites. Optional layer, frame or from_frame/to_frame, and output_path. Reports matched, remapped, and unmatched pixel counts plus the most common unmatched colors.")]
+    async fn remap_colors(
+        &self,
+        params: Parameters<tools::effects::RemapColorsParams>,
+    ) -> Result<String, String> {
+        tools::effects::remap_colors(self, params.0).await
+    }
+
+    #[tool(description = "Apply an outline effect around non-transparent pixels with a specified color. Supports thickness, inside/outside placement, circle/square corners, outlining every layer separately (all_layers), every frame of a tag, limiting it to a region, slice, or selection so other content is untouched, and saving to output_path. Returns the layers and frames outlined and the masked region.")]
     async fn outline(
         &self,
         params: Parameters<tools::effects::OutlineParams>,
@@ -502,6 +1010,38 @@ impl AsepriteServer {
         tools::effects::outline(self, params.0).await
     }
 
+    #[tool(description = "Add a drop shadow beneath a layer's content: its silhouette filled with the shadow color (alpha kept as a mask) and offset by offset_x/offset_y. Merged into the same cel, or placed on a new \"Shadow\" layer with separate_layer. RGB sprites only.")]
+    async fn drop_shadow(
+        &self,
+        params: Parameters<tools::effects::DropShadowParams>,
+    ) -> Result<String, String> {
+        tools::effects::drop_shadow(self, params.0).await
+    }
+
+    #[tool(description = "Automatic first-pass shading for flat-colored sprites: edge pixels of each color region facing the light (light_direction) are shifted up a color ramp and edges facing away are shifted down. ramp is an array of hex colors (dark to light) or \"derive\" to build +/-2 hue-shifted steps per base color. strength is the number of ramp steps (1-2). RGB sprites only.")]
+    async fn auto_shade(
+        &self,
+        params: Parameters<tools::effects::AutoShadeParams>,
+    ) -> Result<String, String> {
+        tools::effects::auto_shade(self, params.0).await
+    }
+
+    #[tool(description = "Scanline/CRT overlay: darken every Nth row (horizontal) or column (vertical) by darkness 0-100 across a frame range. Darkens pixels in place, or with separate_layer draws the lines on a new \"Scanlines\" multiply layer that can be toggled.")]
+    async fn scanlines(
+        &self,
+        params: Parameters<tools::effects::ScanlinesParams>,
+    ) -> Result<String, String> {
+        tools::effects::scanlines(self, params.0).await
+    }
+
+    #[tool(description = "Extract a silhouette/alpha mask: every pixel with alpha >= alpha_threshold becomes the fill color (default black), everything else transparent. Source is a layer or the flattened sprite at a frame. Writes a new image (output_path) or adds it as a layer (new_layer_name). Reports the silhouette's bounding box.")]
+    async fn extract_silhouette(
+        &self,
+        params: Parameters<tools::effects::ExtractSilhouetteParams>,
+    ) -> Result<String, String> {
+        tools::effects::extract_silhouette(self, params.0).await
+    }
+
     // ========================================================================
     // Filter Tools
     // ========================================================================
@@ -530,7 +1070,7 @@ impl AsepriteServer {
         tools::filter::invert_color(self, params.0).await
     }
 
-    #[tool(description = "Apply a despeckle (median) filter to reduce noise in pixel art. Adjustable matrix size.")]
+    #[tool(description = "Apply a despeckle (median) filter to reduce noise in pixel art. Adjustable matrix size. Pass file_paths or glob instead of file_path to clean several files in turn; each is saved in place and reported separately, and a failure doesn't stop the rest.")]
     async fn despeckle(
         &self,
         params: Parameters<tools::filter::DespeckleParams>,
@@ -538,24 +1078,162 @@ impl AsepriteServer {
         tools::filter::despeckle(self, params.0).await
     }
 
+    #[tool(description = "Adjust tones with a color curve or levels. Give curve points (input -> output, 0-255) or the levels form black_point/white_point/gamma, for channel rgb, r, g, b, or alpha. Optional layer, frame, and selection targeting (defaults: all image layers, all frames). RGB sprites only.")]
+    async fn color_curve(
+        &self,
+        params: Parameters<tools::filter::ColorCurveParams>,
+    ) -> Result<String, String> {
+        tools::filter::color_curve(self, params.0).await
+    }
+
+    #[tool(description = "Posterize a sprite: reduce each RGB channel to N evenly spaced values (levels 2-64), preserving alpha. Reports the number of unique colors before and after. Optional layer, frame, and selection targeting. RGB sprites only.")]
+    async fn posterize(
+        &self,
+        params: Parameters<tools::filter::PosterizeParams>,
+    ) -> Result<String, String> {
+        tools::filter::posterize(self, params.0).await
+    }
+
+    #[tool(description = "Add noise/grain: random per-pixel RGB offsets scaled by amount (0-100), clamped to 0-255, skipping fully transparent pixels. monochrome uses one offset for all channels. Deterministic: the same seed gives identical output. Optional layer, frame, and selection targeting. RGB sprites only.")]
+    async fn add_noise(
+        &self,
+        params: Parameters<tools::filter::AddNoiseParams>,
+    ) -> Result<String, String> {
+        tools::filter::add_noise(self, params.0).await
+    }
+
+    #[tool(description = "Tint pixels toward a color (sepia, damage flashes, team colors) with strength 0-100 and mode multiply, overlay, or colorize (tint hue/saturation, pixel lightness kept). Alpha is preserved. Returns the effective parameters and number of pixels modified. Optional layer, frame, and selection targeting. RGB sprites only.")]
+    async fn tint(
+        &self,
+        params: Parameters<tools::filter::TintParams>,
+    ) -> Result<String, String> {
+        tools::filter::tint(self, params.0).await
+    }
+
+    #[tool(description = "Desaturate pixels toward gray in place while the sprite stays in RGB mode (unlike change_color_mode). method: luminance, average, or lightness; amount 0-100 for partial desaturation. Optional layer, frame, and selection targeting.")]
+    async fn desaturate(
+        &self,
+        params: Parameters<tools::filter::DesaturateParams>,
+    ) -> Result<String, String> {
+        tools::filter::desaturate(self, params.0).await
+    }
+
+    #[tool(description = "Shift hue/saturation/lightness only for pixels whose hue lies within hue_range degrees of target_hue (wrapping around 0/360), e.g. push only the greens toward teal. Gray pixels never match. Reports how many pixels matched. Optional layer, frame, and selection targeting. RGB sprites only.")]
+    async fn shift_hue_range(
+        &self,
+        params: Parameters<tools::filter::ShiftHueRangeParams>,
+    ) -> Result<String, String> {
+        tools::filter::shift_hue_range(self, params.0).await
+    }
+
+    // ========================================================================
+    // System
+    // ========================================================================
+
+    #[tool(description = "Health check: report the Aseprite version and API version, the executable path, output and temp directories, and which optional features are available (tilemaps, WebP export, CLI flags such as --extrude). Probed once and cached.")]
+    async fn get_aseprite_info(&self) -> Result<String, String> {
+        tools::system::get_aseprite_info(self).await
+    }
+
+    #[tool(description = "List fonts for drawing text (e.g. from run_lua_script): the fonts Aseprite ships with or has installed in its extensions, plus the system font directories, with names, file paths, and formats. pixel_only keeps bitmap formats and well-known pixel fonts. Scanned once per server run.")]
+    async fn list_fonts(
+        &self,
+        params: Parameters<tools::system::ListFontsParams>,
+    ) -> Result<String, String> {
+        tools::system::list_fonts(self, params.0).await
+    }
+
+    #[tool(description = "Show the server's effective settings: the ones set_config can change (default timeout, backups, create_sprite's default color mode, inline export images) and the locked ones fixed at startup (sandbox roots, script permissions, directories).")]
+    async fn get_config(&self) -> Result<String, String> {
+        tools::system::get_config(self).await
+    }
+
+    #[tool(description = "Change runtime settings for the rest of the session: timeout_secs (default Aseprite timeout, 1-1800), auto_backup (back up files before in-place saves), default_color_mode for create_sprite (rgb, grayscale, indexed), return_base64 (exports return images inline by default), max_pixel_read_area (largest get_pixel_data region in pixels), max_draw_pixels (most pixels/points per draw_pixels, use_tool, or batch_edit call), max_palette_entries (most set_palette_color entries per call). Sandbox and directory settings are locked. Returns the effective config.")]
+    async fn set_config(
+        &self,
+        params: Parameters<tools::system::SetConfigParams>,
+    ) -> Result<String, String> {
+        tools::system::set_config(self, params.0).await
+    }
+
+    #[tool(description = "Review what this session did: the most recent tool calls (newest first, up to 100 kept) with their target file, duration, number of Aseprite runs, generated script size, and success or error.")]
+    async fn get_recent_operations(
+        &self,
+        params: Parameters<tools::system::RecentOperationsParams>,
+    ) -> Result<String, String> {
+        tools::system::get_recent_operations(self, params.0).await
+    }
+
+    // ========================================================================
+    // Workspace Tools
+    // ========================================================================
+
+    #[tool(description = "List files under the workspace root (ASEPRITE_WORKSPACE_DIR, else ASEPRITE_OUTPUT_DIR, else the working directory) with relative paths, sizes, and modification times (Unix seconds). Filter with a glob pattern and/or extensions; page with limit and offset.")]
+    async fn list_files(
+        &self,
+        params: Parameters<tools::workspace::ListFilesParams>,
+    ) -> Result<String, String> {
+        tools::workspace::list_files(self, params.0).await
+    }
+
+    #[tool(description = "List the automatic backups of a file (newest first), or restore one by passing its name or path as backup. Restoring first backs up the current file, so it can be undone. Backups are made before in-place saves when ASEPRITE_BACKUP=1 or a call passes backup=true.")]
+    async fn restore_backup(
+        &self,
+        params: Parameters<tools::workspace::RestoreBackupParams>,
+    ) -> Result<String, String> {
+        tools::workspace::restore_backup(self, params.0).await
+    }
+
+    #[tool(description = "Check whether a file or directory exists, resolving relative paths the same way tool inputs are resolved. Returns the resolved path, size, and modification time, or the directories searched when not found.")]
+    async fn stat_file(
+        &self,
+        params: Parameters<tools::workspace::StatFileParams>,
+    ) -> Result<String, String> {
+        tools::workspace::stat_file(self, params.0).await
+    }
+
     // ========================================================================
     // Script & Command Execution
     // ========================================================================
 
-    #[tool(description = "Execute arbitrary Lua code in Aseprite's scripting environment. The script has full access to the Aseprite API. Use print() to return data. Optionally specify a sprite file to open first.")]
+    #[tool(description = "Apply several edits to one sprite in a single Aseprite run with one save at the end, avoiding per-call startup and save overhead. operations is an ordered array of {\"op\": name, ...params} using the parameters of draw_pixels, use_tool, fill_region, add_layer, set_frame_duration, or create_tag (without file_path). Reports per-operation results; if any operation fails, all are rolled back and the file is left unchanged. draw_pixels pixels and use_tool points count toward max_draw_pixels (default 5000) across the whole batch.")]
+    async fn batch_edit(
+        &self,
+        params: Parameters<tools::batch::BatchEditParams>,
+    ) -> Result<String, String> {
+        tools::batch::batch_edit(self, params.0).await
+    }
+
+    #[tool(description = "Execute arbitrary Lua code in Aseprite's scripting environment. The script has full access to the Aseprite API. Return structured data with mcp_return(table), which the tool returns as JSON; otherwise printed output is returned. Provide the code inline (script) or a path to a .lua file (script_path). Optionally specify a sprite file to open first and params passed as --script-param (read via app.params).")]
     async fn run_lua_script(
         &self,
         params: Parameters<tools::scripting::RunLuaScriptParams>,
     ) -> Result<String, String> {
-        tools::scripting::run_lua_script(self, params.0).await
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::scripting::run_lua_script(self, params.0)).await
     }
 
-    #[tool(description = "Run Aseprite in batch mode with custom CLI arguments. Useful for complex export operations, format conversions, and operations best expressed as CLI commands.")]
+    #[tool(description = "List the named Lua scripts in the script library (ASEPRITE_SCRIPTS_DIR) with the description from each script's header comment. Run them with run_named_script.")]
+    async fn list_scripts(&self) -> Result<String, String> {
+        tools::scripting::list_scripts(self).await
+    }
+
+    #[tool(description = "Run a script from the script library by name (see list_scripts), optionally opening a sprite first, with params passed as --script-param (read via app.params). Like run_lua_script, mcp_return(table) returns structured JSON. Library scripts stay available when sandboxing disables run_lua_script.")]
+    async fn run_named_script(
+        &self,
+        params: Parameters<tools::scripting::RunNamedScriptParams>,
+    ) -> Result<String, String> {
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::scripting::run_named_script(self, params.0)).await
+    }
+
+    #[tool(description = "Run Aseprite in batch mode with custom CLI arguments. Useful for complex export operations, format conversions, and operations best expressed as CLI commands. Relative paths resolve against working_dir (default: the workspace root). Interactive flags such as --shell are rejected. Long exports can raise the process timeout with timeout_secs.")]
     async fn execute_cli(
         &self,
         params: Parameters<tools::scripting::ExecuteCliParams>,
     ) -> Result<String, String> {
-        tools::scripting::execute_cli(self, params.0).await
+        let timeout = params.0.timeout_secs;
+        with_timeout(timeout, tools::scripting::execute_cli(self, params.0)).await
     }
 }
 
@@ -569,7 +1247,7 @@ impl AsepriteServer {
         match self.runner.run_script(script).await {
             Ok(output) => {
                 if output.success {
-                    Ok(output.result_text())
+                    Self::script_result(output.result_text())
                 } else {
                     error!("Script error: {}", output.stderr);
                     Err(output.result_text())
@@ -588,10 +1266,78 @@ impl AsepriteServer {
         file_path: &str,
         script: &str,
     ) -> Result<String, String> {
-        match self.runner.run_script_on_file(file_path, script).await {
+        let file_path = &self.resolve_input_path(file_path)?;
+        let _guards = self.lock_files([file_path]).await;
+        self.execute_script_on_locked_file(file_path, script).await
+    }
+
+    /// Like `execute_script_on_file`, for callers already holding the file's lock (see
+    /// `lock_files`) so that a run reading the sprite and the run writing back what was computed
+    /// from it can't interleave with other calls on the same file.
+    pub async fn execute_script_on_locked_file(
+        &self,
+        file_path: &str,
+        script: &str,
+    ) -> Result<String, String> {
+        let file_path = &self.resolve_input_path(file_path)?;
+        // Saves over the input always go through one of the in-place statements (an output path
+        // equal to the input is mapped to them by `lua_save_target`)
+        let overwrites = script.contains(LUA_SAVE_IN_PLACE) || script.contains(LUA_SAVE_AS_ORIGINAL);
+        if overwrites {
+            self.backup_before_save(file_path).await?;
+            return self.execute_mutation_on_file(file_path, script).await;
+        }
+        self.run_on_file(file_path, script, &[]).await
+    }
+
+    /// Run a script that saves `file_path` in place. The script writes to a temp file next to
+    /// the original (`app.params.mcp_save_path`), which is renamed over it only once Aseprite has
+    /// exited successfully, so a crash or timeout mid-save never leaves a truncated sprite.
+    /// Scripts saving with `LUA_SAVE_AS_ORIGINAL` are instead run on a copy of the file at that
+    /// temp path, which they save over.
+    async fn execute_mutation_on_file(&self, file_path: &str, script: &str) -> Result<String, String> {
+        let path = Path::new(file_path);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let temp = match path.extension() {
+            Some(ext) => path.with_file_name(format!("{}.tmp.{}", stem, ext.to_string_lossy())),
+            None => path.with_file_name(format!("{}.tmp", stem)),
+        };
+        let temp_path = temp.to_string_lossy().to_string();
+        let result = if !script.contains(LUA_SAVE_AS_ORIGINAL) {
+            let params = [("mcp_save_path".to_string(), temp_path)];
+            self.run_on_file(file_path, script, &params).await
+        } else if is_dry_run() {
+            self.run_on_file(file_path, script, &[]).await
+        } else {
+            tokio::fs::copy(path, &temp)
+                .await
+                .map_err(|e| format!("Failed to copy {} for saving: {}", file_path, e))?;
+            self.run_on_file(&temp_path, script, &[]).await
+        };
+        if is_dry_run() {
+            return result;
+        }
+        if result.is_ok() && temp.is_file() {
+            if let Err(e) = tokio::fs::rename(&temp, path).await {
+                tokio::fs::remove_file(&temp).await.ok();
+                return Err(format!("Failed to replace {} with the saved copy: {}", file_path, e));
+            }
+        } else {
+            tokio::fs::remove_file(&temp).await.ok();
+        }
+        result
+    }
+
+    async fn run_on_file(
+        &self,
+        file_path: &str,
+        script: &str,
+        params: &[(String, String)],
+    ) -> Result<String, String> {
+        match self.runner.run_script_on_file(file_path, script, params).await {
             Ok(output) => {
                 if output.success {
-                    Ok(output.result_text())
+                    Self::script_result(output.result_text())
                 } else {
                     error!("Script error on {}: {}", file_path, output.stderr);
                     Err(output.result_text())
@@ -604,23 +1350,525 @@ impl AsepriteServer {
         }
     }
 
+    /// Execute a Lua script file, optionally with a sprite file loaded first and script params.
+    pub async fn execute_script_file(
+        &self,
+        script_path: &str,
+        file_path: Option<&str>,
+        params: &[(String, String)],
+    ) -> Result<String, String> {
+        let script_path = &self.resolve_input_path(script_path)?;
+        let file_path = file_path.map(|f| self.resolve_input_path(f)).transpose()?;
+        let file_path = file_path.as_deref();
+        let _guards = self.lock_files(file_path).await;
+        match self.runner.run_script_file(script_path, file_path, params).await {
+            Ok(output) => {
+                if output.success {
+                    Self::script_result(output.result_text())
+                } else {
+                    error!("Script error in {}: {}", script_path, output.stderr);
+                    Err(output.result_text())
+                }
+            }
+            Err(e) => {
+                error!("Failed to run script {}: {}", script_path, e);
+                Err(format!("Failed to execute script: {}", e))
+            }
+        }
+    }
+
+    /// If backups are on for this call, copy `file_path` aside before a run that saves over it.
+    async fn backup_before_save(&self, file_path: &str) -> Result<(), String> {
+        let enabled = backup::requested().unwrap_or(self.config().auto_backup);
+        if !enabled || is_dry_run() || !Path::new(file_path).is_file() {
+            return Ok(());
+        }
+        let saved = backup::backup_file(Path::new(file_path), self.backup_keep).await?;
+        info!("Backed up {} to {}", file_path, saved.display());
+        Ok(())
+    }
+
+    /// Definitions of every registered tool, without the common parameters.
+    pub fn tool_definitions(&self) -> Vec<Tool> {
+        self.tool_router.list_all()
+    }
+
+    /// Backups kept per file (ASEPRITE_BACKUP_KEEP).
+    pub fn backup_keep(&self) -> usize {
+        self.backup_keep
+    }
+
+    /// Turn the output of a successful script run into a tool result. Scripts report failures by
+    /// printing `{"error": ...}` and exiting normally, so those become `Err`.
+    fn script_result(text: String) -> Result<String, String> {
+        match script_error(&text) {
+            Some(message) => Err(message),
+            None => Ok(text),
+        }
+    }
+
     /// Resolve an output path against the configured output directory.
-    /// If `ASEPRITE_OUTPUT_DIR` is set and `path` is relative, it's joined with the output dir.
-    /// If `path` is absolute or no output dir is set, returns the path as-is.
-    pub fn resolve_output_path(&self, path: &str) -> String {
-        if let Some(ref output_dir) = self.output_dir {
-            let p = Path::new(path);
-            if p.is_relative() {
-                return output_dir.join(p).to_string_lossy().to_string();
+    /// If `path` is relative, it's joined with `ASEPRITE_OUTPUT_DIR`, or with
+    /// `ASEPRITE_WORKSPACE_DIR` when no output dir is set.
+    /// If `path` is absolute or neither is set, returns the path as-is.
+    /// Fails if the resolved path is outside the allowed directories (see `check_path_allowed`).
+    pub fn resolve_output_path(&self, path: &str) -> Result<String, String> {
+        let base = self.output_dir.as_ref().or(self.workspace_dir.as_ref());
+        let resolved = match base {
+            Some(base) if Path::new(path).is_relative() => base.join(path).to_string_lossy().to_string(),
+            _ => path.to_string(),
+        };
+        self.check_path_allowed(Path::new(&resolved))?;
+        Ok(resolved)
+    }
+
+    /// Resolve an existing input file. Relative paths are looked up in the workspace dir, then the
+    /// output dir (where relative outputs were written), then the working directory; the first
+    /// match wins. Fails, listing the searched directories, if the file isn't found, or if it is
+    /// outside the allowed directories.
+    pub fn resolve_input_path(&self, path: &str) -> Result<String, String> {
+        let resolved = if Path::new(path).is_relative() {
+            let dirs = self.input_search_dirs();
+            dirs.iter()
+                .map(|dir| dir.join(path))
+                .find(|candidate| candidate.exists())
+                .map(|found| found.to_string_lossy().to_string())
+                .ok_or_else(|| {
+                    let searched: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+                    format!("File not found: {} (searched: {})", path, searched.join(", "))
+                })?
+        } else {
+            path.to_string()
+        };
+        self.check_path_allowed(Path::new(&resolved))?;
+        Ok(resolved)
+    }
+
+    /// Root directory listed by `list_files`: the first input search directory.
+    pub fn workspace_root(&self) -> PathBuf {
+        self.input_search_dirs().swap_remove(0)
+    }
+
+    /// Directories searched for relative input paths, in order (see `resolve_input_path`).
+    pub fn input_search_dirs(&self) -> Vec<PathBuf> {
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for dir in [self.workspace_dir.as_ref(), self.output_dir.as_ref(), Some(&cwd)].into_iter().flatten() {
+            if !dirs.contains(dir) {
+                dirs.push(dir.clone());
             }
         }
-        path.to_string()
+        dirs
+    }
+
+    /// In sandboxed mode (ASEPRITE_ALLOWED_DIRS set), reject paths that don't resolve to a
+    /// location under an allowed directory. Symlinks are resolved first, so a link inside an
+    /// allowed directory can't point outside it.
+    pub fn check_path_allowed(&self, path: &Path) -> Result<(), String> {
+        let Some(ref roots) = self.allowed_dirs else {
+            return Ok(());
+        };
+        let canonical = canonicalize_lenient(path)
+            .map_err(|e| format!("Access denied: {}: {}", path.display(), e))?;
+        if roots.iter().any(|root| canonical.starts_with(root)) {
+            Ok(())
+        } else {
+            let roots: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+            Err(format!(
+                "Access denied: {} is outside the allowed directories ({})",
+                path.display(),
+                roots.join(", ")
+            ))
+        }
+    }
+
+    /// Whether tools that run arbitrary Lua or CLI arguments are enabled. They can reach any
+    /// file, so sandboxed mode disables them unless ASEPRITE_ALLOW_ARBITRARY_SCRIPTS=1.
+    pub fn arbitrary_scripts_allowed(&self) -> bool {
+        self.allowed_dirs.is_none() || self.allow_arbitrary_scripts
+    }
+
+    /// Configured default output directory (ASEPRITE_OUTPUT_DIR), if any.
+    pub fn output_dir(&self) -> Option<&Path> {
+        self.output_dir.as_deref()
+    }
+
+    /// How Aseprite is started (program and fixed leading arguments).
+    pub fn exe(&self) -> &AsepriteCommand {
+        self.runner.exe()
+    }
+
+    /// Directory used for temporary scripts and intermediate files.
+    pub fn temp_dir(&self) -> &Path {
+        self.runner.temp_dir()
+    }
+
+    /// Named script library (ASEPRITE_SCRIPTS_DIR), if configured.
+    pub fn scripts_dir(&self) -> Option<&Path> {
+        self.scripts_dir.as_deref()
+    }
+
+    /// Current runtime settings.
+    pub fn config(&self) -> ServerConfig {
+        self.config.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Apply `update` to the runtime settings.
+    pub fn update_config(&self, update: impl FnOnce(&mut ServerConfig)) {
+        update(&mut self.config.write().unwrap_or_else(|e| e.into_inner()));
+    }
+
+    /// Default Aseprite process timeout in seconds.
+    pub fn timeout_secs(&self) -> u64 {
+        self.runner.timeout_secs()
+    }
+
+    /// Change the default Aseprite process timeout for later calls.
+    pub fn set_timeout_secs(&self, secs: u64) {
+        self.runner.set_timeout_secs(secs);
+    }
+
+    /// Sandbox roots (ASEPRITE_ALLOWED_DIRS), if sandboxed.
+    pub fn allowed_dirs(&self) -> Option<&[PathBuf]> {
+        self.allowed_dirs.as_deref().map(Vec::as_slice)
+    }
+
+    /// Sandbox file access to `roots`, as ASEPRITE_ALLOWED_DIRS would.
+    #[cfg(test)]
+    pub fn set_allowed_dirs(&mut self, roots: Vec<PathBuf>) {
+        self.allowed_dirs = Some(Arc::new(roots));
+    }
+
+    /// Whether every call is forced to be a dry run (ASEPRITE_DRY_RUN=1).
+    pub fn dry_run_forced(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Log a finished tool call and keep it for `get_recent_operations`.
+    fn record_operation(
+        &self,
+        tool: String,
+        file: Option<String>,
+        duration: std::time::Duration,
+        result: &Result<CallToolResult, McpError>,
+        runs: &[serde_json::Value],
+    ) {
+        let error = match result {
+            Ok(result) if result.is_error == Some(true) => result
+                .content
+                .iter()
+                .find_map(|c| c.as_text())
+                .map(|t| t.text.lines().next().unwrap_or_default().to_string()),
+            Ok(_) => None,
+            Err(e) => Some(e.message.to_string()),
+        };
+        let success = error.is_none();
+        let script_bytes: u64 = runs.iter().filter_map(|r| r["script_bytes"].as_u64()).sum();
+        let duration_ms = duration.as_millis() as u64;
+        info!(
+            tool = %tool,
+            file = file.as_deref().unwrap_or("-"),
+            script_bytes,
+            aseprite_runs = runs.len(),
+            duration_ms,
+            success,
+            "Tool call finished"
+        );
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut operations = self.recent_operations.lock().unwrap_or_else(|e| e.into_inner());
+        if operations.len() == RECENT_OPERATIONS {
+            operations.pop_front();
+        }
+        operations.push_back(serde_json::json!({
+            "time": timestamp,
+            "tool": tool,
+            "file": file,
+            "duration_ms": duration_ms,
+            "aseprite_runs": runs.len(),
+            "script_bytes": script_bytes,
+            "success": success,
+            "error": error,
+        }));
+    }
+
+    /// The most recent `limit` tool calls, newest first.
+    pub fn recent_operations(&self, limit: usize) -> Vec<serde_json::Value> {
+        let operations = self.recent_operations.lock().unwrap_or_else(|e| e.into_inner());
+        operations.iter().rev().take(limit).cloned().collect()
+    }
+
+    /// Cache for the Aseprite version/feature probe (see `get_aseprite_info`).
+    pub fn aseprite_info_cache(&self) -> &OnceCell<serde_json::Value> {
+        &self.aseprite_info
+    }
+
+    /// Cache for the font directory scan (see `list_fonts`).
+    pub fn font_list_cache(&self) -> &OnceCell<serde_json::Value> {
+        &self.font_list
+    }
+
+    /// Whether failed runs' scripts are kept on disk (ASEPRITE_DEBUG_SCRIPTS=1).
+    pub fn keeps_failed_scripts(&self) -> bool {
+        self.runner.keeps_failed_scripts()
+    }
+
+    /// Generate a unique path in the server's temp directory for intermediate files.
+    pub fn temp_file_path(&self, extension: &str) -> PathBuf {
+        self.runner.temp_file_path(extension)
     }
 
     /// Run Aseprite with raw CLI arguments (batch mode). Exposed for tool modules.
     pub async fn run_cli(&self, args: &[String]) -> anyhow::Result<ScriptOutput> {
-        self.runner.run_cli(args).await
+        self.run_cli_in(args, None).await
+    }
+
+    /// Run Aseprite with raw CLI arguments, resolving relative paths in them against `working_dir`.
+    pub async fn run_cli_in(&self, args: &[String], working_dir: Option<&Path>) -> anyhow::Result<ScriptOutput> {
+        // Any argument naming an existing file may be read or rewritten by this run
+        let files: Vec<PathBuf> = args
+            .iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .map(|arg| working_dir.map_or_else(|| PathBuf::from(arg), |dir| dir.join(arg)))
+            .filter(|path| path.is_file())
+            .collect();
+        for file in &files {
+            self.check_path_allowed(file).map_err(anyhow::Error::msg)?;
+        }
+        // Outputs usually don't exist yet, so they're found by the flag in front of them
+        let mut outputs: Vec<&str> = Vec::new();
+        for (i, arg) in args.iter().enumerate() {
+            if let Some((flag, value)) = arg.split_once('=')
+                && CLI_OUTPUT_FLAGS.contains(&flag)
+            {
+                outputs.push(value);
+            } else if CLI_OUTPUT_FLAGS.contains(&arg.as_str()) {
+                let value = args.get(i + 1).ok_or_else(|| anyhow::anyhow!("{} needs a value", arg))?;
+                outputs.push(value);
+            }
+        }
+        for output in outputs {
+            let path = working_dir.map_or_else(|| PathBuf::from(output), |dir| dir.join(output));
+            self.check_path_allowed(&path).map_err(anyhow::Error::msg)?;
+        }
+        let _guards = self.lock_files(&files).await;
+        self.runner.run_cli(args, working_dir).await
+    }
+
+    /// Acquire the per-file locks for `paths`. Locks are taken in sorted order so that two calls
+    /// locking overlapping sets of files can't deadlock; they are released when the guards drop.
+    pub async fn lock_files<I, P>(&self, paths: I) -> Vec<FileLockGuard>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut keys: Vec<PathBuf> = paths
+            .into_iter()
+            .map(|p| {
+                let p = p.as_ref();
+                p.canonicalize()
+                    .or_else(|_| std::path::absolute(p))
+                    .unwrap_or_else(|_| p.to_path_buf())
+            })
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        let locks: Vec<_> = {
+            let mut map = self.file_locks.lock().unwrap_or_else(|e| e.into_inner());
+            keys.into_iter()
+                .map(|key| (map.entry(key.clone()).or_default().clone(), key))
+                .collect()
+        };
+        let mut guards = Vec::with_capacity(locks.len());
+        for (lock, key) in locks {
+            guards.push(FileLockGuard {
+                guard: Some(lock.lock_owned().await),
+                key,
+                file_locks: self.file_locks.clone(),
+            });
+        }
+        guards
+    }
+}
+
+/// Holds one file's lock (see `AsepriteServer::lock_files`). Dropping the last guard of a file
+/// that no other call is waiting on removes its entry from the lock map.
+pub struct FileLockGuard {
+    guard: Option<OwnedMutexGuard<()>>,
+    key: PathBuf,
+    file_locks: Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl Drop for FileLockGuard {
+    fn drop(&mut self) {
+        self.guard.take();
+        // New lockers clone the entry under the map lock, so a count of 1 (the map's own
+        // reference) means nobody holds or waits for this file
+        let mut map = self.file_locks.lock().unwrap_or_else(|e| e.into_inner());
+        if map.get(&self.key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+            map.remove(&self.key);
+        }
+    }
+}
+
+/// Aseprite CLI options whose value is a path the run writes to.
+const CLI_OUTPUT_FLAGS: &[&str] = &["--save-as", "--sheet", "--data", "--filename-format"];
+
+/// Canonicalize a path that may not exist yet: the deepest existing ancestor is canonicalized
+/// (resolving symlinks) and the remaining components are appended. A `..` below a missing
+/// directory can't be resolved, so such paths fail to canonicalize.
+fn canonicalize_lenient(path: &Path) -> Result<PathBuf, String> {
+    let absolute = std::path::absolute(path).map_err(|e| e.to_string())?;
+    let mut existing = absolute.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        let Some(name) = existing.file_name() else { break };
+        rest.push(name.to_os_string());
+        existing = existing.parent().unwrap_or(existing);
+    }
+    let mut canonical = existing.canonicalize().map_err(|e| e.to_string())?;
+    canonical.extend(rest.iter().rev());
+    Ok(canonical)
+}
+
+/// Tools that only read sprites or report state.
+const READ_ONLY_TOOLS: &[&str] = &[
+    "get_sprite_info",
+    "describe_sprite",
+    "get_frame_thumbnails",
+    "get_sprite_stats",
+    "list_layers",
+    "list_frames",
+    "list_tags",
+    "list_slices",
+    "list_tilesets",
+    "get_user_data",
+    "get_tilemap",
+    "list_cels",
+    "get_pixel_data",
+    "get_palette",
+    "get_selection_info",
+    "get_aseprite_info",
+    "list_fonts",
+    "get_recent_operations",
+    "get_config",
+    "list_files",
+    "stat_file",
+    "list_scripts",
+];
+
+/// Tools that leave their input untouched and (re)write only the requested output files, so
+/// repeating a call gives the same result.
+const EXPORT_TOOLS: &[&str] = &[
+    "export_sprite",
+    "export_spritesheet",
+    "export_unity_meta",
+    "export_atlas",
+    "export_gif",
+    "export_frame",
+    "export_raw",
+    "export_icon",
+    "export_animation",
+    "pack_atlas",
+    "export_preview",
+    "export_contact_sheet",
+    "compare_to_image",
+    "get_tile_image",
+    "export_tileset",
+    "save_palette",
+    "validate_sprite",
+    "check_grid_alignment",
+];
+
+/// Tools that create a new sprite file from their arguments.
+const CREATE_TOOLS: &[&str] = &["create_sprite", "duplicate_sprite", "extract_region_to_sprite"];
+
+/// Tools that run caller-supplied Lua or CLI arguments, which can do anything.
+const OPEN_WORLD_TOOLS: &[&str] = &["run_lua_script", "execute_cli"];
+
+/// Annotation hints for a tool. Anything not in the tables above edits a sprite in place
+/// (unless given an `output_path`), which is destructive and not idempotent.
+fn tool_annotations(name: &str) -> ToolAnnotations {
+    let annotations = ToolAnnotations::new().open_world(false);
+    if READ_ONLY_TOOLS.contains(&name) || EXPORT_TOOLS.contains(&name) {
+        annotations.read_only(true).idempotent(true)
+    } else if CREATE_TOOLS.contains(&name) {
+        annotations.read_only(false).destructive(false).idempotent(true)
+    } else if OPEN_WORLD_TOOLS.contains(&name) {
+        annotations.read_only(false).destructive(true).idempotent(false).open_world(true)
+    } else {
+        annotations.read_only(false).destructive(true).idempotent(false)
+    }
+}
+
+/// Fill in the annotation hints clients use to decide which calls need confirmation.
+fn with_annotations(mut tool: Tool) -> Tool {
+    tool.annotations = Some(tool_annotations(&tool.name));
+    tool
+}
+
+/// Advertise the `dry_run` and `backup` parameters that `call_tool` accepts for every tool.
+fn with_common_params(mut tool: Tool) -> Tool {
+    let mut schema = (*tool.input_schema).clone();
+    if let serde_json::Value::Object(props) = schema
+        .entry("properties")
+        .or_insert_with(|| serde_json::json!({}))
+    {
+        props.insert(
+            "dry_run".to_string(),
+            serde_json::json!({
+                "type": "boolean",
+                "description": "Return the Lua script / CLI arguments this call would run, and the file it \
+                                would target, without starting Aseprite"
+            }),
+        );
+        props.insert(
+            "backup".to_string(),
+            serde_json::json!({
+                "type": "boolean",
+                "description": "Back up the file before this call saves over it (default: ASEPRITE_BACKUP or set_config auto_backup); \
+                                see restore_backup"
+            }),
+        );
+    }
+    tool.input_schema = Arc::new(schema);
+    tool
+}
+
+/// Attach a `meta` object describing the Aseprite runs to a successful result: inserted into a
+/// JSON object result that has no `meta` key of its own, otherwise appended as its own text item.
+fn with_run_meta(mut result: CallToolResult, runs: Vec<serde_json::Value>) -> CallToolResult {
+    if result.is_error == Some(true) || runs.is_empty() {
+        return result;
+    }
+    let total_ms: u64 = runs.iter().filter_map(|r| r["duration_ms"].as_u64()).sum();
+    let meta = serde_json::json!({ "duration_ms": total_ms, "runs": runs });
+    let object_text = result.content.iter_mut().find_map(|content| match &mut content.raw {
+        RawContent::Text(text) => serde_json::from_str::<serde_json::Value>(&text.text)
+            .ok()
+            .filter(|v| v.is_object() && v.get("meta").is_none())
+            .map(|v| (text, v)),
+        _ => None,
+    });
+    match object_text {
+        Some((text, mut value)) => {
+            value["meta"] = meta;
+            text.text = value.to_string();
+        }
+        None => result
+            .content
+            .push(Content::text(serde_json::json!({ "meta": meta }).to_string())),
     }
+    result
+}
+
+/// Convert a tool result carrying rich content (e.g. images) into an MCP result,
+/// reporting `Err` messages as error content like the plain-text tools do.
+fn rich_result(result: Result<CallToolResult, String>) -> Result<CallToolResult, McpError> {
+    Ok(result.unwrap_or_else(|e| CallToolResult::error(vec![Content::text(e)])))
 }
 
 // ============================================================================
@@ -638,22 +1886,111 @@ impl ServerHandler for AsepriteServer {
             instructions: Some(
                 "Aseprite MCP Server - Bridge AI assistants with the Aseprite pixel art editor. \
                  Control Aseprite via CLI batch mode to create, edit, and export pixel art sprites \
-                 and animations. All file paths should be absolute or relative to the working directory. \
+                 and animations. All file paths should be absolute or relative; relative inputs are \
+                 looked up in the workspace directory, output directory, and working directory. \
+                 Images and sprites in the output directory are also available as resources, and \
+                 prompts describe common workflows step by step. \
                  Colors use hex format: '#rrggbb' or '#rrggbbaa'."
                     .into(),
             ),
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
-            ..Default::default()
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .enable_prompts()
+                .build(),
         }
     }
 
     fn call_tool(
         &self,
-        request: CallToolRequestParam,
+        mut request: CallToolRequestParam,
         context: RequestContext<RoleServer>,
     ) -> impl std::future::Future<Output = Result<CallToolResult, McpError>> + Send + '_ {
+        // `dry_run` and `backup` are accepted by every tool, so they're taken out before the
+        // tool's own parsing
+        let mut take_flag = |name: &str| {
+            request
+                .arguments
+                .as_mut()
+                .and_then(|args| args.remove(name))
+                .and_then(|v| v.as_bool())
+        };
+        let dry = take_flag("dry_run").unwrap_or(self.dry_run);
+        let backup = take_flag("backup");
+        // Cancelling the request (or the client disconnecting) kills the running Aseprite process
+        let ct = context.ct.clone();
+        let tool = request.name.to_string();
+        let target = request.arguments.as_ref().and_then(|args| {
+            ["file_path", "output_path", "script_path", "glob"]
+                .iter()
+                .find_map(|key| args.get(*key)?.as_str().map(str::to_string))
+        });
         let ctx = ToolCallContext::new(self, request, context);
-        async move { self.tool_router.call(ctx).await }
+        async move {
+            let call = with_cancellation(ct, backup::with_backup(backup, self.tool_router.call(ctx)));
+            if !dry {
+                let started = std::time::Instant::now();
+                let (result, runs) = record_runs(call).await;
+                self.record_operation(tool, target, started.elapsed(), &result, &runs);
+                if !self.verbose_results {
+                    return result;
+                }
+                return result.map(|result| with_run_meta(result, runs));
+            }
+            let (result, planned) = dry_run(call).await;
+            if planned.is_empty() {
+                // Nothing to run (validation error, or a tool that doesn't use Aseprite)
+                return result;
+            }
+            let report = serde_json::json!({
+                "dry_run": true,
+                "runs": planned,
+                "note": "Aseprite was not started and no sprite or output files were written. \
+                         Only the first run is shown when later steps depend on its output.",
+            });
+            Ok(CallToolResult::success(vec![Content::text(report.to_string())]))
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let cursor = request.and_then(|r| r.cursor);
+        let (resources, next_cursor) = crate::resources::list_resources(self, cursor.as_deref())
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        Ok(ListResourcesResult { resources, next_cursor })
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        crate::resources::read_resource(self, &request.uri)
+            .await
+            .map_err(|e| McpError::resource_not_found(e, None))
+    }
+
+    async fn list_prompts(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListPromptsResult, McpError> {
+        Ok(ListPromptsResult {
+            prompts: crate::prompts::list_prompts(),
+            next_cursor: None,
+        })
+    }
+
+    async fn get_prompt(
+        &self,
+        request: GetPromptRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<GetPromptResult, McpError> {
+        crate::prompts::get_prompt(self, &request.name, request.arguments)
+            .map_err(|e| McpError::invalid_params(e, None))
     }
 
     fn list_tools(
@@ -661,9 +1998,104 @@ impl ServerHandler for AsepriteServer {
         _request: Option<PaginatedRequestParam>,
         _context: RequestContext<RoleServer>,
     ) -> impl std::future::Future<Output = Result<ListToolsResult, McpError>> + Send + '_ {
+        let tools = self
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(with_annotations)
+            .map(with_common_params)
+            .collect();
         std::future::ready(Ok(ListToolsResult {
-            tools: self.tool_router.list_all(),
+            tools,
             next_cursor: None,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, scratch_dir};
+
+    #[test]
+    fn run_meta_never_replaces_a_results_own_meta() {
+        let runs = vec![serde_json::json!({ "exit_code": 0, "duration_ms": 12 })];
+        let text = |result: &CallToolResult, i: usize| match &result.content[i].raw {
+            RawContent::Text(text) => serde_json::from_str::<serde_json::Value>(&text.text).unwrap(),
+            _ => panic!("expected text content"),
+        };
+
+        let plain = with_run_meta(CallToolResult::success(vec![Content::text(r#"{"status":"ok"}"#)]), runs.clone());
+        assert_eq!(plain.content.len(), 1);
+        assert_eq!(text(&plain, 0)["meta"]["duration_ms"], 12);
+
+        let sheet = r#"{"frames":[],"meta":{"size":{"w":16,"h":16}}}"#;
+        let sheet = with_run_meta(CallToolResult::success(vec![Content::text(sheet)]), runs);
+        assert_eq!(text(&sheet, 0)["meta"]["size"]["w"], 16);
+        assert_eq!(text(&sheet, 1)["meta"]["duration_ms"], 12);
+    }
+
+    #[test]
+    fn listed_tools_carry_their_annotations() {
+        let server = dry_server();
+        let tools: HashMap<String, ToolAnnotations> = server
+            .tool_router
+            .list_all()
+            .into_iter()
+            .map(with_annotations)
+            .map(|t| (t.name.to_string(), t.annotations.unwrap()))
+            .collect();
+        let hints = |name: &str| {
+            let a = &tools[name];
+            (a.read_only_hint, a.destructive_hint, a.idempotent_hint, a.open_world_hint)
+        };
+        assert_eq!(hints("get_sprite_info"), (Some(true), None, Some(true), Some(false)));
+        assert_eq!(hints("export_gif"), (Some(true), None, Some(true), Some(false)));
+        assert_eq!(hints("create_sprite"), (Some(false), Some(false), Some(true), Some(false)));
+        assert_eq!(hints("draw_pixels"), (Some(false), Some(true), Some(false), Some(false)));
+        assert_eq!(hints("run_lua_script"), (Some(false), Some(true), Some(false), Some(true)));
+
+        // A renamed tool would otherwise silently fall back to "destructive"
+        for name in READ_ONLY_TOOLS.iter().chain(EXPORT_TOOLS).chain(CREATE_TOOLS).chain(OPEN_WORLD_TOOLS) {
+            assert!(tools.contains_key(*name), "{} is not a tool", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn file_locks_are_dropped_once_released() {
+        let server = dry_server();
+        let guards = server.lock_files(["/art/a.aseprite", "/art/b.aseprite"]).await;
+        assert_eq!(server.file_locks.lock().unwrap().len(), 2);
+        let waiter = {
+            let server = server.clone();
+            tokio::spawn(async move { server.lock_files(["/art/a.aseprite"]).await.len() })
+        };
+        tokio::task::yield_now().await;
+        drop(guards);
+        assert_eq!(waiter.await.unwrap(), 1);
+        assert!(server.file_locks.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn cli_outputs_outside_the_sandbox_are_rejected() {
+        let dir = scratch_dir("cli_outputs").canonicalize().unwrap();
+        let mut server = dry_server();
+        server.allowed_dirs = Some(Arc::new(vec![dir.clone()]));
+        let inside = dir.join("out.png").to_string_lossy().to_string();
+        let outside = std::env::temp_dir().join("elsewhere").join("out.png").to_string_lossy().to_string();
+
+        for flag in CLI_OUTPUT_FLAGS {
+            let args = vec![flag.to_string(), outside.clone()];
+            let err = server.run_cli_in(&args, None).await.unwrap_err().to_string();
+            assert!(err.starts_with("Access denied"), "{}: {}", flag, err);
+            let joined = vec![format!("{}={}", flag, outside)];
+            assert!(server.run_cli_in(&joined, None).await.is_err());
+        }
+        let relative = vec!["--sheet".to_string(), "../escape.png".to_string()];
+        assert!(server.run_cli_in(&relative, Some(&dir)).await.unwrap_err().to_string().starts_with("Access denied"));
+
+        let args = vec!["--save-as".to_string(), inside];
+        let (_, planned) = dry_run(server.run_cli_in(&args, None)).await;
+        assert_eq!(planned.len(), 1);
+    }
+}
@@ -0,0 +1,74 @@
+//! Helpers for unit tests that go through the tools' full code path.
+//!
+//! `dry_server` never starts Aseprite: tools run as dry runs (see `aseprite::dry_run`), so tests
+//! can check the scripts they generate. `real_server` uses the installed Aseprite, and tests that
+//! need it return early when it isn't found. `fake_server` stands in a shell script for Aseprite,
+//! for tests about how runs are started and ordered.
+
+use crate::aseprite::{AsepriteCommand, AsepriteRunner, dry_run};
+use crate::server::AsepriteServer;
+use std::future::Future;
+use std::path::PathBuf;
+
+/// A server whose runs are only ever planned, never started.
+pub fn dry_server() -> AsepriteServer {
+    let exe = AsepriteCommand::path(PathBuf::from("aseprite-not-installed"));
+    AsepriteServer::with_runner(AsepriteRunner::with_exe(exe).expect("test runner"))
+}
+
+/// A server whose "Aseprite" is the shell script `body`, written to `dir`. Scripts on a file are
+/// started as `aseprite --batch FILE [--script-param K=V]... --script SCRIPT`.
+#[cfg(unix)]
+pub fn fake_server(dir: &std::path::Path, body: &str) -> AsepriteServer {
+    use std::os::unix::fs::PermissionsExt;
+    let exe = dir.join("fake-aseprite");
+    std::fs::write(&exe, format!("#!/bin/sh\n{}\n", body)).expect("write fake executable");
+    std::fs::set_permissions(&exe, std::fs::Permissions::from_mode(0o755)).expect("make it executable");
+    AsepriteServer::with_runner(AsepriteRunner::with_exe(AsepriteCommand::path(exe)).expect("test runner"))
+}
+
+/// A server using the installed Aseprite, or `None` (with a note) when it isn't installed.
+pub fn real_server() -> Option<AsepriteServer> {
+    match AsepriteRunner::new() {
+        Ok(runner) => Some(AsepriteServer::with_runner(runner)),
+        Err(_) => {
+            eprintln!("Aseprite not found, skipping");
+            None
+        }
+    }
+}
+
+/// Run `fut` as a dry run and return the Lua script of its first planned run.
+pub async fn planned_script<F: Future>(fut: F) -> String {
+    let (_, planned) = dry_run(fut).await;
+    let run = planned.first().expect("the tool should plan an Aseprite run");
+    run["script"].as_str().expect("a Lua script run").to_string()
+}
+
+/// A fresh, empty directory under the system temp dir for files a test writes.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join("aseprite_mcp_tests")
+        .join(format!("{}-{}", name, std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+/// Save an 8x8 RGB sprite at `path` with two frames whose cels are linked (one shared image),
+/// holding a single pixel of `color` (hex) at (2, 2).
+pub async fn save_linked_frames(server: &AsepriteServer, path: &str, color: &str) {
+    let (r, g, b, a) = crate::utils::parse_color(color).expect("test color");
+    let script = format!(
+        r#"local spr = Sprite(8, 8)
+spr.cels[1].image:drawPixel(2, 2, Color{{ r = {r}, g = {g}, b = {b}, a = {a} }})
+spr:newFrame()
+app.range.layers = {{ spr.layers[1] }}
+app.range.frames = {{ 1, 2 }}
+app.command.LinkCels()
+app.range:clear()
+spr:saveAs({path})"#,
+        path = crate::aseprite::lua_path(path)
+    );
+    server.execute_script(&script).await.expect("save linked frames");
+}
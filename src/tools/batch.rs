@@ -0,0 +1,142 @@
+use rmcp::schemars;
+use serde::Deserialize;
+
+use crate::lua_helpers::LUA_SAVE_IN_PLACE;
+use crate::server::AsepriteServer;
+use crate::tools::drawing::{self, DrawPixelsOp, FillRegionOp, UseToolOp};
+use crate::tools::frame::{self, SetFrameDurationOp, resolve_optional_frame};
+use crate::tools::layer::{self, AddLayerOp};
+use crate::tools::tag::{self, CreateTagOp};
+
+// ============================================================================
+// Parameter Structs
+// ============================================================================
+
+/// One edit in a batch. Takes the same parameters as the tool of the same name, minus file_path.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    DrawPixels(DrawPixelsOp),
+    UseTool(UseToolOp),
+    FillRegion(FillRegionOp),
+    AddLayer(AddLayerOp),
+    SetFrameDuration(SetFrameDurationOp),
+    CreateTag(CreateTagOp),
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct BatchEditParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Operations to apply in order, e.g. [{"op": "add_layer", "name": "Shading"},
+    /// {"op": "draw_pixels", "layer": "Shading", "pixels": [...]}]
+    pub operations: Vec<BatchOperation>,
+}
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+pub async fn batch_edit(server: &AsepriteServer, p: BatchEditParams) -> Result<String, String> {
+    if p.operations.is_empty() {
+        return Err("operations cannot be empty".to_string());
+    }
+    let drawn: usize = p
+        .operations
+        .iter()
+        .map(|op| match op {
+            BatchOperation::DrawPixels(op) => op.pixels.len(),
+            BatchOperation::UseTool(op) => op.points.len(),
+            _ => 0,
+        })
+        .sum();
+    let max_drawn = server.config().max_draw_pixels;
+    if drawn as u64 > max_drawn {
+        return Err(format!(
+            "The operations draw {} pixels and stroke points in total, more than the {} allowed per batch_edit (max_draw_pixels); split them across several batch_edit calls of at most {} each",
+            drawn, max_drawn, max_drawn
+        ));
+    }
+
+    let mut ops = String::new();
+    for (i, op) in p.operations.iter().enumerate() {
+        let (name, fragment) = match op {
+            BatchOperation::DrawPixels(op) => {
+                let frame = resolve_optional_frame(server, &p.file_path, op.frame.as_ref()).await;
+                ("draw_pixels", frame.and_then(|f| drawing::draw_pixels_fragment(op, f)))
+            }
+            BatchOperation::UseTool(op) => {
+                let frame = resolve_optional_frame(server, &p.file_path, op.frame.as_ref()).await;
+                ("use_tool", frame.and_then(|f| drawing::use_tool_fragment(op, f)))
+            }
+            BatchOperation::FillRegion(op) => {
+                let frame = resolve_optional_frame(server, &p.file_path, op.frame.as_ref()).await;
+                ("fill_region", frame.and_then(|f| drawing::fill_region_fragment(op, f)))
+            }
+            BatchOperation::AddLayer(op) => ("add_layer", Ok(layer::add_layer_fragment(op))),
+            BatchOperation::SetFrameDuration(op) => {
+                ("set_frame_duration", Ok(frame::set_frame_duration_fragment(op)))
+            }
+            BatchOperation::CreateTag(op) => ("create_tag", tag::create_tag_fragment(op)),
+        };
+        let fragment = fragment.map_err(|e| format!("Operation {} ({}): {}", i + 1, name, e))?;
+        // The `print` parameter shadows the global inside the fragment, capturing its error output
+        ops.push_str(&format!(
+            r#"{{name = "{name}", run = function(print)
+local result
+{fragment}
+return result
+end}},
+"#,
+            name = name,
+            fragment = fragment
+        ));
+    }
+
+    let script = format!(
+        r#"local spr = app.sprite
+local ops = {{
+{ops}}}
+local results = {{}}
+local failure = nil
+pcall(function()
+    app.transaction("Batch Edit", function()
+        for i, op in ipairs(ops) do
+            local output = {{}}
+            local ok, res = pcall(op.run, function(...)
+                local parts = {{}}
+                for k, v in ipairs({{...}}) do parts[k] = tostring(v) end
+                output[#output + 1] = table.concat(parts, "\t")
+            end)
+            local op_error = nil
+            if not ok then
+                op_error = tostring(res)
+            else
+                for _, line in ipairs(output) do
+                    local decoded_ok, decoded = pcall(json.decode, line)
+                    if decoded_ok and type(decoded) == "table" and decoded.error then
+                        op_error = tostring(decoded.error)
+                    end
+                end
+            end
+            if op_error then
+                results[#results + 1] = {{index = i, op = op.name, success = false, error = op_error}}
+                failure = "Operation " .. i .. " (" .. op.name .. ") failed: " .. op_error
+                -- Raising inside the transaction rolls back every operation applied so far
+                error(failure)
+            end
+            results[#results + 1] = {{index = i, op = op.name, success = true, result = res}}
+        end
+    end)
+end)
+if failure then
+    print(json.encode({{error = failure .. ". No changes were saved.", results = results}}))
+    return
+end
+{save}
+print(json.encode({{status = "applied", operations = #ops, results = results}}))"#,
+        ops = ops,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
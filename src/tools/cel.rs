@@ -2,8 +2,11 @@ use rmcp::schemars;
 use serde::Deserialize;
 
 use crate::aseprite::lua_string;
-use crate::lua_helpers::LUA_FIND_LAYER;
+use crate::lua_helpers::{LUA_FIND_LAYER, lua_check_frame, lua_pager, lua_require_layer, lua_save_target, with_saved_to};
 use crate::server::AsepriteServer;
+use crate::tools::drawing::PointData;
+use crate::tools::frame::{FrameRef, lua_frame_range, resolve_frame, resolve_optional_frame};
+use crate::utils::{Easing, check_choice, check_range};
 
 // ============================================================================
 // Parameter Structs
@@ -15,8 +18,16 @@ pub struct ListCelsParams {
     pub file_path: String,
     /// Filter by layer name (optional)
     pub layer: Option<String>,
-    /// Filter by frame number, 1-based (optional)
-    pub frame: Option<u32>,
+    /// Filter by frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (optional)
+    pub frame: Option<FrameRef>,
+    /// First frame to include: 1-based number or "tag:n" / "tag:start" / "tag:end" (default: 1)
+    pub from_frame: Option<FrameRef>,
+    /// Last frame to include, same format (default: last frame)
+    pub to_frame: Option<FrameRef>,
+    /// Maximum number of entries to return (default: all)
+    pub limit: Option<u32>,
+    /// Number of matching entries to skip before returning (default: 0)
+    pub offset: Option<u32>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -25,12 +36,14 @@ pub struct MoveCelParams {
     pub file_path: String,
     /// Layer name of the cel to move
     pub layer: String,
-    /// Frame number (1-based) of the cel to move
-    pub frame: u32,
+    /// Frame of the cel to move: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub frame: FrameRef,
     /// New X position on the canvas
     pub x: i32,
     /// New Y position on the canvas
     pub y: i32,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -39,10 +52,12 @@ pub struct SetCelOpacityParams {
     pub file_path: String,
     /// Layer name of the cel
     pub layer: String,
-    /// Frame number (1-based) of the cel
-    pub frame: u32,
+    /// Frame of the cel: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub frame: FrameRef,
     /// Opacity value (0-255)
     pub opacity: u32,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -51,8 +66,26 @@ pub struct ClearCelParams {
     pub file_path: String,
     /// Layer name of the cel to clear
     pub layer: String,
-    /// Frame number (1-based) of the cel to clear
-    pub frame: u32,
+    /// Frame of the cel to clear: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub frame: FrameRef,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ClearCelsRangeParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer name whose cels to clear
+    pub layer: Option<String>,
+    /// Several layers to clear the same frame range on (combined with layer if both are given)
+    pub layers: Option<Vec<String>>,
+    /// First frame to clear: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub from_frame: FrameRef,
+    /// Last frame to clear, inclusive (clamped to the sprite's frame count)
+    pub to_frame: FrameRef,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -61,8 +94,98 @@ pub struct NewCelParams {
     pub file_path: String,
     /// Layer name where the new cel should be created
     pub layer: String,
-    /// Frame number (1-based) for the new cel
-    pub frame: u32,
+    /// Frame for the new cel: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub frame: FrameRef,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TweenCelPositionsParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer whose cels to move
+    pub layer: String,
+    /// First keyframe: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub from_frame: FrameRef,
+    /// Last keyframe, same format (must be after from_frame)
+    pub to_frame: FrameRef,
+    /// Position at from_frame (if omitted, the cel's current position there)
+    pub start: Option<PointData>,
+    /// Position at to_frame (if omitted, the cel's current position there)
+    pub end: Option<PointData>,
+    /// "linear" (default), "ease_in", "ease_out", or "ease_in_out"
+    pub easing: Option<String>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TransformCelContentsParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer whose cels to transform
+    pub layer: String,
+    /// First frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (default: 1)
+    pub from_frame: Option<FrameRef>,
+    /// Last frame, same format (default: last frame)
+    pub to_frame: Option<FrameRef>,
+    /// Horizontal scale factor (0.01-16, default: 1)
+    pub scale_x: Option<f64>,
+    /// Vertical scale factor (0.01-16, default: 1)
+    pub scale_y: Option<f64>,
+    /// Clockwise rotation in degrees, any angle (default: 0)
+    pub rotate_degrees: Option<f64>,
+    /// Canvas point that stays fixed (default: the center of each cel)
+    pub pivot: Option<PointData>,
+    /// "nearest" (default) or "rotsprite" (smoother edges when scaling pixel art)
+    pub interpolation: Option<String>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CopyLayerAnimationParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer to copy cels from
+    pub source_layer: String,
+    /// Layer to copy cels to (created on top if it doesn't exist)
+    pub target_layer: String,
+    /// Frames to shift the copy by, may be negative (default: 0). Frames are added as needed.
+    pub frame_offset: Option<i32>,
+    /// Position shift of the copied cels as {"x": dx, "y": dy} (default: no shift)
+    pub position_offset: Option<PointData>,
+    /// Link target cels whose source cels are linked, instead of copying each image separately
+    /// (Aseprite only links cels within one layer; default: false)
+    pub link: Option<bool>,
+    /// Replace cels that already exist on the target layer (default: false, they're skipped)
+    pub overwrite: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FadeLayerParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer whose cels to fade
+    pub layer: String,
+    /// First frame of the fade: 1-based number or "tag:n" / "tag:start" / "tag:end"
+    pub from_frame: FrameRef,
+    /// Last frame of the fade, same format (must be after from_frame)
+    pub to_frame: FrameRef,
+    /// Cel opacity at from_frame (0-255)
+    pub start_opacity: u32,
+    /// Cel opacity at to_frame (0-255)
+    pub end_opacity: u32,
+    /// "linear" (default), "ease_in", "ease_out", or "ease_in_out"
+    pub easing: Option<String>,
+    /// Give frames without a cel a copy of the nearest earlier cel so the fade is continuous
+    /// (default: false, such frames are skipped)
+    pub fill_missing: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
@@ -74,34 +197,35 @@ pub async fn list_cels(server: &AsepriteServer, p: ListCelsParams) -> Result<Str
         format!(
             r#"
 {find_layer}
-local target_layer = find_layer(spr.layers, {name})
-if not target_layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end"#,
+{require_layer}"#,
+            require_layer = lua_require_layer("target_layer", layer),
             find_layer = LUA_FIND_LAYER,
-            name = lua_string(layer)
         )
     } else {
         String::new()
     };
 
-    let frame_filter = if let Some(frame) = p.frame {
+    let frame_filter = if let Some(frame) = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await? {
         format!("local target_frame = {}", frame)
     } else {
         "local target_frame = nil".to_string()
     };
 
+    let range = lua_frame_range(server, &p.file_path, p.from_frame.as_ref(), p.to_frame.as_ref()).await?;
+
     let script = format!(
         r#"local spr = app.sprite
 {filter_code}
 {frame_filter}
+{range}
+{pager}
 local cels = {{}}
 for i, cel in ipairs(spr.cels) do
     local include = true
     if target_layer and cel.layer ~= target_layer then include = false end
     if target_frame and cel.frameNumber ~= target_frame then include = false end
-    if include then
+    if cel.frameNumber < from_f or cel.frameNumber > to_f then include = false end
+    if include and in_page() then
         local c = {{}}
         c.layer = cel.layer.name
         c.frame = cel.frameNumber
@@ -115,29 +239,29 @@ for i, cel in ipairs(spr.cels) do
         table.insert(cels, c)
     end
 end
-print(json.encode({{cels = cels, total = #cels}}))"#,
+print(json.encode({{cels = cels, total = matched, returned = #cels}}))"#,
         filter_code = filter_code,
-        frame_filter = frame_filter
+        frame_filter = frame_filter,
+        range = range,
+        pager = lua_pager(p.limit, p.offset)
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn move_cel(server: &AsepriteServer, p: MoveCelParams) -> Result<String, String> {
+    let frame = resolve_frame(server, &p.file_path, &p.frame).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end
+{require_layer}
 local cel = layer:cel({frame})
 if not cel then
     print(json.encode({{error = "No cel at frame " .. {frame} .. " on layer " .. {name}}}))
     return
 end
 cel.position = Point({x}, {y})
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.layer = cel.layer.name
 result.frame = cel.frameNumber
@@ -145,84 +269,152 @@ result.x = cel.position.x
 result.y = cel.position.y
 result.status = "moved"
 print(json.encode(result))"#,
+        require_layer = lua_require_layer("layer", &p.layer),
         find_layer = LUA_FIND_LAYER,
         name = lua_string(&p.layer),
-        frame = p.frame,
+        frame = frame,
         x = p.x,
-        y = p.y
+        y = p.y,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn set_cel_opacity(server: &AsepriteServer, p: SetCelOpacityParams) -> Result<String, String> {
-    let opacity = p.opacity.min(255);
+    let frame = resolve_frame(server, &p.file_path, &p.frame).await?;
+    let opacity = check_range("opacity", p.opacity, 0..=255, false)?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end
+{require_layer}
 local cel = layer:cel({frame})
 if not cel then
     print(json.encode({{error = "No cel at frame " .. {frame} .. " on layer " .. {name}}}))
     return
 end
 cel.opacity = {opacity}
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.layer = cel.layer.name
 result.frame = cel.frameNumber
 result.opacity = cel.opacity
 result.status = "updated"
 print(json.encode(result))"#,
+        require_layer = lua_require_layer("layer", &p.layer),
         find_layer = LUA_FIND_LAYER,
         name = lua_string(&p.layer),
-        frame = p.frame,
-        opacity = opacity
+        frame = frame,
+        opacity = opacity,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn clear_cel(server: &AsepriteServer, p: ClearCelParams) -> Result<String, String> {
+    let frame = resolve_frame(server, &p.file_path, &p.frame).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end
+{require_layer}
 local cel = layer:cel({frame})
 if cel then
     spr:deleteCel(cel)
 end
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "cleared", layer = {name}, frame = {frame}}}))"#,
+        require_layer = lua_require_layer("layer", &p.layer),
         find_layer = LUA_FIND_LAYER,
         name = lua_string(&p.layer),
-        frame = p.frame
+        frame = frame,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
-pub async fn new_cel(server: &AsepriteServer, p: NewCelParams) -> Result<String, String> {
+pub async fn clear_cels_range(server: &AsepriteServer, p: ClearCelsRangeParams) -> Result<String, String> {
+    let mut names: Vec<&str> = p.layer.iter().chain(p.layers.iter().flatten()).map(String::as_str).collect();
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|name| seen.insert(*name));
+    if names.is_empty() {
+        return Err("Provide layer or layers".to_string());
+    }
+    let range = lua_frame_range(server, &p.file_path, Some(&p.from_frame), Some(&p.to_frame)).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
+local layers = {{}}
+for _, name in ipairs({{ {names} }}) do
+    local layer = find_layer(spr.layers, name)
+    if not layer then
+        print(json.encode({{error = "Layer not found: " .. name}}))
+        return
+    end
+    if layer.isGroup then
+        print(json.encode({{error = "Layer " .. name .. " is a group and has no cels; clear its child layers instead"}}))
+        return
+    end
+    layers[#layers + 1] = layer
 end
-local frame = spr.frames[{frame}]
-if not frame then
-    print(json.encode({{error = "Frame {frame} does not exist"}}))
+{range}
+if from_f > #spr.frames then
+    print(json.encode({{error = "Frame " .. from_f .. " out of range (sprite has " .. #spr.frames .. " frames)"}}))
     return
 end
+to_f = math.min(to_f, #spr.frames)
+local per_layer = {{}}
+local deleted, empty = 0, 0
+app.transaction("Clear Cels", function()
+    for _, layer in ipairs(layers) do
+        local n = 0
+        for f = from_f, to_f do
+            local cel = layer:cel(f)
+            if cel then
+                spr:deleteCel(cel)
+                n = n + 1
+            end
+        end
+        local already_empty = to_f - from_f + 1 - n
+        per_layer[#per_layer + 1] = {{layer = layer.name, deleted = n, already_empty = already_empty}}
+        deleted = deleted + n
+        empty = empty + already_empty
+    end
+end)
+{save}
+print(json.encode({{status = "cleared", from_frame = from_f, to_frame = to_f, deleted = deleted, already_empty = empty, layers = per_layer}}))"#,
+        find_layer = LUA_FIND_LAYER,
+        names = names.iter().map(|name| lua_string(name)).collect::<Vec<_>>().join(", "),
+        range = range,
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn new_cel(server: &AsepriteServer, p: NewCelParams) -> Result<String, String> {
+    let frame = resolve_frame(server, &p.file_path, &p.frame).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+{require_layer}
+{check_frame}
+local frame = spr.frames[{frame}]
 local cel = spr:newCel(layer, frame)
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.layer = cel.layer.name
 result.frame = cel.frameNumber
@@ -233,9 +425,452 @@ result.height = cel.image.height
 result.opacity = cel.opacity
 result.status = "created"
 print(json.encode(result))"#,
+        check_frame = lua_check_frame(frame),
+        require_layer = lua_require_layer("layer", &p.layer),
+        find_layer = LUA_FIND_LAYER,
+        frame = frame,
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn tween_cel_positions(server: &AsepriteServer, p: TweenCelPositionsParams) -> Result<String, String> {
+    let easing = Easing::parse(p.easing.as_deref().unwrap_or("linear"))?;
+    let from = resolve_frame(server, &p.file_path, &p.from_frame).await?;
+    let to = resolve_frame(server, &p.file_path, &p.to_frame).await?;
+    if from == 0 {
+        return Err("from_frame is 1-based and must be at least 1 (got 0)".to_string());
+    }
+    if to <= from {
+        return Err(format!("to_frame must be after from_frame {} (got {})", from, to));
+    }
+
+    let (start, end) = match (p.start, p.end) {
+        (Some(start), Some(end)) => ((start.x, start.y), (end.x, end.y)),
+        (start, end) => {
+            let script = format!(
+                r#"local spr = app.sprite
+{find_layer}
+{require_layer}
+local result = {{}}
+for key, f in pairs({{start = {from}, ["end"] = {to}}}) do
+    local cel = layer:cel(f)
+    if cel then result[key] = {{x = cel.position.x, y = cel.position.y}} end
+end
+print(json.encode(result))"#,
+                require_layer = lua_require_layer("layer", &p.layer),
+                find_layer = LUA_FIND_LAYER,
+                from = from,
+                to = to
+            );
+            let output = server.execute_script_on_file(&p.file_path, &script).await?;
+            let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+            let existing = |key: &str, frame: u32| -> Result<(i32, i32), String> {
+                let pos = &json[key];
+                match (pos["x"].as_i64(), pos["y"].as_i64()) {
+                    (Some(x), Some(y)) => Ok((x as i32, y as i32)),
+                    _ => Err(format!(
+                        "No cel at frame {} on layer {} to take the {} position from; pass {} explicitly",
+                        frame, p.layer, key, key
+                    )),
+                }
+            };
+            (
+                match start {
+                    Some(pt) => (pt.x, pt.y),
+                    None => existing("start", from)?,
+                },
+                match end {
+                    Some(pt) => (pt.x, pt.y),
+                    None => existing("end", to)?,
+                },
+            )
+        }
+    };
+
+    let positions: Vec<String> = (from..=to)
+        .map(|f| {
+            let t = easing.apply((f - from) as f64 / (to - from) as f64);
+            let x = (start.0 as f64 + (end.0 - start.0) as f64 * t).round() as i32;
+            let y = (start.1 as f64 + (end.1 - start.1) as f64 * t).round() as i32;
+            format!("{{{}, {}, {}}}", f, x, y)
+        })
+        .collect();
+
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+local layer = find_layer(spr.layers, {name})
+if not layer or not layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+if {to} > #spr.frames then
+    print(json.encode({{error = "to_frame must be at most " .. #spr.frames .. ", the sprite's frame count (got {to})"}}))
+    return
+end
+local source = layer:cel({from})
+if not source then
+    print(json.encode({{error = "No cel at frame {from} on layer " .. {name} .. " to tween"}}))
+    return
+end
+local source_image = source.image:clone()
+local frames = {{}}
+local seen = {{}}
+app.transaction("Tween Cel Positions", function()
+    for _, p in ipairs({{ {positions} }}) do
+        local cel = layer:cel(p[1])
+        local created = false
+        if not cel then
+            -- A copy rather than a linked cel: linked cels share one position
+            cel = spr:newCel(layer, p[1], source_image, Point(p[2], p[3]))
+            created = true
+        elseif seen[cel.image.id] then
+            -- Linked cels share one position, so this frame gets its own copy of the image
+            local img = cel.image:clone()
+            spr:deleteCel(cel)
+            cel = spr:newCel(layer, p[1], img, Point(p[2], p[3]))
+        else
+            cel.position = Point(p[2], p[3])
+        end
+        seen[cel.image.id] = true
+        frames[#frames + 1] = {{frame = p[1], x = cel.position.x, y = cel.position.y, created = created}}
+    end
+end)
+{save}
+print(json.encode({{status = "tweened", layer = layer.name, easing = {easing}, frames = frames}}))"#,
         find_layer = LUA_FIND_LAYER,
         name = lua_string(&p.layer),
-        frame = p.frame
+        from = from,
+        to = to,
+        positions = positions.join(", "),
+        save = save,
+        easing = lua_string(&p.easing.as_deref().unwrap_or("linear").to_ascii_lowercase())
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn transform_cel_contents(server: &AsepriteServer, p: TransformCelContentsParams) -> Result<String, String> {
+    let scale_x = check_range("scale_x", p.scale_x.unwrap_or(1.0), 0.01..=16.0, false)?;
+    let scale_y = check_range("scale_y", p.scale_y.unwrap_or(1.0), 0.01..=16.0, false)?;
+    let degrees = p.rotate_degrees.unwrap_or(0.0);
+    if !degrees.is_finite() {
+        return Err(format!("rotate_degrees must be a finite number (got {})", degrees));
+    }
+    let method = check_choice(
+        "interpolation",
+        p.interpolation.as_deref().unwrap_or("nearest"),
+        &[("nearest", "nearest"), ("rotsprite", "rotsprite")],
+        false,
+    )?;
+    if scale_x == 1.0 && scale_y == 1.0 && degrees.rem_euclid(360.0) == 0.0 {
+        return Err("Nothing to do: pass scale_x, scale_y, or rotate_degrees".to_string());
+    }
+    let range = lua_frame_range(server, &p.file_path, p.from_frame.as_ref(), p.to_frame.as_ref()).await?;
+    let pivot = match p.pivot {
+        Some(ref pt) => format!("local fixed_pivot = {{x = {}, y = {}}}", pt.x, pt.y),
+        None => "local fixed_pivot = nil".to_string(),
+    };
+
+    // Scaling goes through Aseprite's resize (so "rotsprite" applies); Aseprite's scripting API
+    // has no arbitrary-angle rotation, so the rotation step samples the nearest source pixel.
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+local layer = find_layer(spr.layers, {name})
+if not layer or not layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+{range}
+to_f = math.min(to_f, #spr.frames)
+{pivot}
+local sx, sy = {scale_x}, {scale_y}
+local theta = math.rad({degrees})
+local cos_t, sin_t = math.cos(theta), math.sin(theta)
+local eps = 1e-6
+
+local function rotate(img, x0, y0, px, py)
+    local minx, miny, maxx, maxy = math.huge, math.huge, -math.huge, -math.huge
+    for _, c in ipairs({{ {{x0, y0}}, {{x0 + img.width, y0}}, {{x0, y0 + img.height}}, {{x0 + img.width, y0 + img.height}} }}) do
+        local dx, dy = c[1] - px, c[2] - py
+        local rx, ry = px + dx * cos_t - dy * sin_t, py + dx * sin_t + dy * cos_t
+        minx, miny = math.min(minx, rx), math.min(miny, ry)
+        maxx, maxy = math.max(maxx, rx), math.max(maxy, ry)
+    end
+    local bx, by = math.floor(minx + eps), math.floor(miny + eps)
+    local bw, bh = math.max(1, math.ceil(maxx - eps) - bx), math.max(1, math.ceil(maxy - eps) - by)
+    local out = Image(bw, bh, img.colorMode)
+    out:clear(img.spec.transparentColor)
+    for j = 0, bh - 1 do
+        for i = 0, bw - 1 do
+            local dx, dy = bx + i + 0.5 - px, by + j + 0.5 - py
+            local srcx = math.floor(px + dx * cos_t + dy * sin_t - x0)
+            local srcy = math.floor(py - dx * sin_t + dy * cos_t - y0)
+            if srcx >= 0 and srcy >= 0 and srcx < img.width and srcy < img.height then
+                out:drawPixel(i, j, img:getPixel(srcx, srcy))
+            end
+        end
+    end
+    return out, bx, by
+end
+
+local cels, linked = {{}}, {{}}
+local seen = {{}}
+app.transaction("Transform Cel Contents", function()
+    for f = from_f, to_f do
+        local cel = layer:cel(f)
+        if cel and seen[cel.image.id] then
+            -- Linked cels share their image and position, so they're already transformed
+            linked[#linked + 1] = f
+        elseif cel then
+            seen[cel.image.id] = true
+            local img = cel.image:clone()
+            local x0, y0 = cel.position.x, cel.position.y
+            local px = fixed_pivot and fixed_pivot.x or x0 + img.width / 2
+            local py = fixed_pivot and fixed_pivot.y or y0 + img.height / 2
+            if sx ~= 1 or sy ~= 1 then
+                local w = math.max(1, math.floor(img.width * sx + 0.5))
+                local h = math.max(1, math.floor(img.height * sy + 0.5))
+                img:resize{{width = w, height = h, method = "{method}"}}
+                x0 = math.floor(px + (x0 - px) * sx + 0.5)
+                y0 = math.floor(py + (y0 - py) * sy + 0.5)
+            end
+            if theta ~= 0 then
+                img, x0, y0 = rotate(img, x0, y0, px, py)
+            end
+            cel.image = img
+            cel.position = Point(x0, y0)
+            cels[#cels + 1] = {{frame = f, x = x0, y = y0, width = img.width, height = img.height}}
+        end
+    end
+end)
+if #cels == 0 then
+    print(json.encode({{error = "Layer " .. {name} .. " has no cels in frames " .. from_f .. "-" .. to_f}}))
+    return
+end
+{save}
+local result = {{status = "transformed", layer = layer.name, cels = cels}}
+if #linked > 0 then result.linked_frames = linked end
+print(json.encode(result))"#,
+        find_layer = LUA_FIND_LAYER,
+        name = lua_string(&p.layer),
+        range = range,
+        pivot = pivot,
+        scale_x = scale_x,
+        scale_y = scale_y,
+        degrees = degrees,
+        method = method,
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn copy_layer_animation(server: &AsepriteServer, p: CopyLayerAnimationParams) -> Result<String, String> {
+    if p.source_layer == p.target_layer {
+        return Err("source_layer and target_layer must be different layers".to_string());
+    }
+    let (dx, dy) = p.position_offset.as_ref().map_or((0, 0), |o| (o.x, o.y));
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+local src = find_layer(spr.layers, {source})
+if not src or not src.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {source}}}))
+    return
+end
+local dst = find_layer(spr.layers, {target})
+local created_layer = false
+if dst and not dst.isImage then
+    print(json.encode({{error = "Target layer " .. {target} .. " is not an image layer"}}))
+    return
+end
+local sources = {{}}
+for _, cel in ipairs(src.cels) do
+    sources[#sources + 1] = {{frame = cel.frameNumber, image = cel.image, x = cel.position.x, y = cel.position.y, opacity = cel.opacity, id = cel.image.id}}
+end
+if #sources == 0 then
+    print(json.encode({{error = "Layer " .. {source} .. " has no cels to copy"}}))
+    return
+end
+local copied, skipped, groups = {{}}, {{}}, {{}}
+local added_frames = 0
+app.transaction("Copy Layer Animation", function()
+    if not dst then
+        dst = spr:newLayer()
+        dst.name = {target}
+        created_layer = true
+    end
+    for _, s in ipairs(sources) do
+        local f = s.frame + {frame_offset}
+        local existing = f >= 1 and dst:cel(f)
+        if f < 1 then
+            skipped[#skipped + 1] = {{source_frame = s.frame, frame = f, reason = "before frame 1"}}
+        elseif existing and not {overwrite} then
+            skipped[#skipped + 1] = {{source_frame = s.frame, frame = f, reason = "target cel exists"}}
+        else
+            while #spr.frames < f do
+                spr:newEmptyFrame(#spr.frames + 1)
+                added_frames = added_frames + 1
+            end
+            if existing then spr:deleteCel(existing) end
+            local cel = spr:newCel(dst, f, s.image, Point(s.x + {dx}, s.y + {dy}))
+            cel.opacity = s.opacity
+            copied[#copied + 1] = {{source_frame = s.frame, frame = f, x = cel.position.x, y = cel.position.y}}
+            groups[s.id] = groups[s.id] or {{}}
+            table.insert(groups[s.id], f)
+        end
+    end
+end)
+local links = 0
+if {link} then
+    for _, frames in pairs(groups) do
+        if #frames > 1 then
+            local ok = pcall(function()
+                app.range.layers = {{ dst }}
+                app.range.frames = frames
+                app.command.LinkCels()
+            end)
+            if ok then links = links + #frames - 1 end
+        end
+    end
+    app.range:clear()
+end
+{save}
+local result = {{status = "copied", source_layer = src.name, target_layer = dst.name, created_layer = created_layer, copied = copied, added_frames = added_frames}}
+if #skipped > 0 then result.skipped = skipped end
+if {link} then result.linked_cels = links end
+print(json.encode(result))"#,
+        find_layer = LUA_FIND_LAYER,
+        source = lua_string(&p.source_layer),
+        target = lua_string(&p.target_layer),
+        frame_offset = p.frame_offset.unwrap_or(0),
+        dx = dx,
+        dy = dy,
+        overwrite = p.overwrite.unwrap_or(false),
+        link = p.link.unwrap_or(false),
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn fade_layer(server: &AsepriteServer, p: FadeLayerParams) -> Result<String, String> {
+    let easing = Easing::parse(p.easing.as_deref().unwrap_or("linear"))?;
+    let start = check_range("start_opacity", p.start_opacity, 0..=255, false)?;
+    let end = check_range("end_opacity", p.end_opacity, 0..=255, false)?;
+    let from = resolve_frame(server, &p.file_path, &p.from_frame).await?;
+    let to = resolve_frame(server, &p.file_path, &p.to_frame).await?;
+    if from == 0 {
+        return Err("from_frame is 1-based and must be at least 1 (got 0)".to_string());
+    }
+    if to <= from {
+        return Err(format!("to_frame must be after from_frame {} (got {})", from, to));
+    }
+
+    let opacities: Vec<String> = (from..=to)
+        .map(|f| {
+            let t = easing.apply((f - from) as f64 / (to - from) as f64);
+            let opacity = (start as f64 + (end as f64 - start as f64) * t).round() as u32;
+            format!("{{{}, {}}}", f, opacity)
+        })
+        .collect();
+
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+local layer = find_layer(spr.layers, {name})
+if not layer or not layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+if {to} > #spr.frames then
+    print(json.encode({{error = "to_frame must be at most " .. #spr.frames .. ", the sprite's frame count (got {to})"}}))
+    return
+end
+-- The cel before the range (if any) seeds fill_missing for the first frames
+local previous = nil
+for f = {from} - 1, 1, -1 do
+    previous = layer:cel(f)
+    if previous then break end
+end
+local frames, skipped = {{}}, {{}}
+local seen = {{}}
+app.transaction("Fade Layer", function()
+    for _, p in ipairs({{ {opacities} }}) do
+        local cel = layer:cel(p[1])
+        local created = false
+        if not cel and {fill_missing} and previous then
+            cel = spr:newCel(layer, p[1], previous.image, previous.position)
+            created = true
+        elseif cel and seen[cel.image.id] then
+            -- Linked cels share one opacity, so this frame gets its own copy of the image
+            local img, pos = cel.image:clone(), cel.position
+            spr:deleteCel(cel)
+            cel = spr:newCel(layer, p[1], img, pos)
+        end
+        if cel then
+            cel.opacity = p[2]
+            seen[cel.image.id] = true
+            previous = cel
+            frames[#frames + 1] = {{frame = p[1], opacity = cel.opacity, created = created}}
+        else
+            skipped[#skipped + 1] = p[1]
+        end
+    end
+end)
+if #frames == 0 then
+    print(json.encode({{error = "Layer " .. {name} .. " has no cels in frames {from}-{to}"}}))
+    return
+end
+{save}
+local result = {{status = "faded", layer = layer.name, easing = {easing}, frames = frames}}
+if #skipped > 0 then result.skipped_frames = skipped end
+print(json.encode(result))"#,
+        find_layer = LUA_FIND_LAYER,
+        name = lua_string(&p.layer),
+        from = from,
+        to = to,
+        opacities = opacities.join(", "),
+        fill_missing = p.fill_missing.unwrap_or(false),
+        easing = lua_string(&p.easing.as_deref().unwrap_or("linear").to_ascii_lowercase()),
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn opacity_above_255_is_rejected() {
+        let params: SetCelOpacityParams = serde_json::from_value(serde_json::json!({
+            "file_path": "/art/in.aseprite",
+            "layer": "Layer 1",
+            "frame": 1,
+            "opacity": 300,
+        }))
+        .unwrap();
+        let err = set_cel_opacity(&crate::test_support::dry_server(), params).await.unwrap_err();
+        assert_eq!(err, "opacity must be between 0 and 255 (got 300)");
+    }
 }
@@ -2,9 +2,15 @@ use rmcp::schemars;
 use serde::{Deserialize, Serialize};
 
 use crate::aseprite::lua_string;
-use crate::lua_helpers::{LUA_FIND_LAYER, lua_select_layer};
+use crate::lua_helpers::{
+    DataFile, LUA_COLOR_TO_PIXEL, LUA_FIND_LAYER, LUA_SAVE_IN_PLACE, lua_check_frame, lua_fragment_script,
+    lua_image_source, lua_require_layer, lua_save_target, lua_select_layer, with_saved_to,
+};
 use crate::server::AsepriteServer;
-use crate::utils::{parse_hex_color_with_alpha, validate_hex_color};
+use crate::tools::frame::{FrameRef, resolve_optional_frame};
+use crate::tools::selection::{RectData, SelectionSpec, lua_apply_selection, lua_region_or_selection};
+use crate::tools::sprite::snap_grid;
+use crate::utils::{check_choice, check_range, parse_color};
 
 // ============================================================================
 // Parameter Structs
@@ -14,12 +20,20 @@ use crate::utils::{parse_hex_color_with_alpha, validate_hex_color};
 pub struct DrawPixelsParams {
     /// Path to the sprite file
     pub file_path: String,
+    #[serde(flatten)]
+    pub op: DrawPixelsOp,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DrawPixelsOp {
     /// Array of pixel data: [{"x": 0, "y": 0, "color": "#ff0000"}, ...]
     pub pixels: Vec<PixelData>,
     /// Target layer name (if omitted, uses active layer)
     pub layer: Option<String>,
-    /// Target frame number, 1-based (if omitted, uses frame 1)
-    pub frame: Option<u32>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
@@ -28,7 +42,7 @@ pub struct PixelData {
     pub x: i32,
     /// Y coordinate
     pub y: i32,
-    /// Color as hex string (e.g. "#ff0000", "#ff000080" with alpha)
+    /// Color as hex, CSS name, or rgb()/rgba() (e.g. "#ff0000", "#ff000080" with alpha, "red")
     pub color: String,
 }
 
@@ -36,21 +50,35 @@ pub struct PixelData {
 pub struct UseToolParams {
     /// Path to the sprite file
     pub file_path: String,
+    #[serde(flatten)]
+    pub op: UseToolOp,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+    /// Snap each point to the nearest grid intersection before drawing (default: false)
+    pub snap_to_grid: Option<bool>,
+    /// Grid for snap_to_grid as {x, y, width, height}: origin and cell size (if omitted, uses the sprite's grid)
+    pub grid: Option<RectData>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct UseToolOp {
     /// Tool name: "pencil", "line", "rectangle", "filled_rectangle", "ellipse",
     /// "filled_ellipse", "paint_bucket", "spray", "eraser", "contour", "polygon"
     pub tool: String,
     /// Array of points: [{"x": 0, "y": 0}, ...] defining the tool stroke
     pub points: Vec<PointData>,
-    /// Foreground color as hex string (e.g. "#ff0000")
+    /// Foreground color as hex, CSS name, or rgb()/rgba() (e.g. "#ff0000", "rgb(255, 0, 0)")
     pub color: String,
-    /// Brush size (default: 1)
+    /// Brush size in pixels (1-64, default: 1)
     pub brush_size: Option<u32>,
     /// Opacity 0-255 (default: 255)
     pub opacity: Option<u32>,
     /// Target layer name (if omitted, uses active layer)
     pub layer: Option<String>,
-    /// Target frame number, 1-based (if omitted, uses frame 1)
-    pub frame: Option<u32>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
@@ -65,36 +93,132 @@ pub struct PointData {
 pub struct GetPixelDataParams {
     /// Path to the sprite file
     pub file_path: String,
-    /// X coordinate of the region start
-    pub x: u32,
-    /// Y coordinate of the region start
-    pub y: u32,
-    /// Width of the region to read
+    /// X coordinate of the region start (may be negative; pixels outside the image read as transparent)
+    pub x: i32,
+    /// Y coordinate of the region start (may be negative)
+    pub y: i32,
+    /// Width of the region to read (width * height is limited by the max_pixel_read_area setting)
     pub width: u32,
     /// Height of the region to read
     pub height: u32,
     /// Target layer name (if omitted, uses flattened image)
     pub layer: Option<String>,
-    /// Target frame number, 1-based (if omitted, uses frame 1)
-    pub frame: Option<u32>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FindStrayPixelsParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer to check (if omitted, checks the flattened image; required with fix)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
+    /// "isolated" (default): small opaque islands floating apart from other pixels;
+    /// "holes": small transparent gaps fully surrounded by opaque pixels
+    pub mode: Option<String>,
+    /// Largest island or hole, in pixels, that counts as stray (1-65536, default: 2)
+    pub max_island_size: Option<u32>,
+    /// Erase the isolated pixels, or fill each hole with its most common surrounding color (default: false)
+    pub fix: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FillRegionParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    #[serde(flatten)]
+    pub op: FillRegionOp,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+    /// Snap the region's edges to the nearest grid lines before filling (default: false)
+    pub snap_to_grid: Option<bool>,
+    /// Grid for snap_to_grid as {x, y, width, height}: origin and cell size (if omitted, uses the sprite's grid)
+    pub grid: Option<RectData>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FillRegionOp {
+    /// Fill color as hex, CSS name, or rgb()/rgba() (e.g. "#ff0000"; "#00000000" or "transparent" to erase)
+    pub color: String,
+    /// Explicit region to fill: {x, y, width, height}. Required unless use_active_selection is true.
+    pub region: Option<RectData>,
+    /// Fill the selection stored in the sprite file instead of an explicit region (default: false)
+    pub use_active_selection: Option<bool>,
+    /// Selection to fill: a rectangle, a polygon point list, or "from_slice:<name>". Implies use_active_selection.
+    pub selection: Option<SelectionSpec>,
+    /// Target layer name (if omitted, uses active layer)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SkewRegionParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer whose cel is skewed
+    pub layer: String,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses frame 1)
+    pub frame: Option<FrameRef>,
+    /// Rectangle to skew, in canvas coordinates (if omitted, skews the whole cel)
+    pub region: Option<RectData>,
+    /// Horizontal shear: each row above the region's bottom row moves this much further right
+    /// (negative moves left). Positive values lean content like italics.
+    pub shear_x: Option<f64>,
+    /// Vertical shear: each column right of the region's left column moves this much further down
+    /// (negative moves up)
+    pub shear_y: Option<f64>,
+    /// "pixels" (default): shear values are pixels of offset per row/column; "degrees": shear angles
+    pub unit: Option<String>,
+    /// Wrap pixels leaving the region around to its opposite side instead of growing the cel (default: false)
+    pub wrap: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
+/// Reject more than `max_draw_pixels` pixels (or stroke points) in one call, with a chunk size
+/// the caller can retry with.
+pub fn check_draw_limit(server: &AsepriteServer, count: usize, what: &str) -> Result<(), String> {
+    let max = server.config().max_draw_pixels;
+    if count as u64 > max {
+        return Err(format!(
+            "Got {} {}, more than the {} allowed per call (max_draw_pixels); split them into chunks of at most {} and send each in its own call, or use fill_region for solid areas",
+            count, what, max, max
+        ));
+    }
+    Ok(())
+}
+
 pub async fn draw_pixels(server: &AsepriteServer, p: DrawPixelsParams) -> Result<String, String> {
+    check_draw_limit(server, p.op.pixels.len(), "pixels")?;
+    let frame = resolve_optional_frame(server, &p.file_path, p.op.frame.as_ref()).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = lua_fragment_script(&draw_pixels_fragment(&p.op, frame)?, &save);
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Lua fragment drawing the pixels (see `lua_fragment_script`).
+pub fn draw_pixels_fragment(p: &DrawPixelsOp, frame: Option<u32>) -> Result<String, String> {
     if p.pixels.is_empty() {
         return Err("Pixels array cannot be empty".to_string());
     }
+    let mut colors = Vec::with_capacity(p.pixels.len());
     for px in &p.pixels {
-        validate_hex_color(&px.color)
-            .map_err(|e| format!("Invalid pixel color '{}': {}", px.color, e))?;
+        colors.push(parse_color(&px.color).map_err(|e| format!("Invalid pixel color '{}': {}", px.color, e))?);
     }
-    let frame_num = p.frame.unwrap_or(1);
+    let frame_num = frame.unwrap_or(1);
 
     let layer_select = if let Some(ref layer_name) = p.layer {
-        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name, true))
+        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name))
     } else {
         String::new()
     };
@@ -102,16 +226,15 @@ pub async fn draw_pixels(server: &AsepriteServer, p: DrawPixelsParams) -> Result
     // Build pixel drawing code using Image:drawPixel for much better performance
     // than calling app.useTool per pixel
     let mut pixel_code = String::new();
-    for px in &p.pixels {
-        let (r, g, b, a) = parse_hex_color_with_alpha(&px.color);
+    for (px, (r, g, b, a)) in p.pixels.iter().zip(colors) {
         pixel_code.push_str(&format!(
             "    img:drawPixel({}, {}, app.pixelColor.rgba({}, {}, {}, {}))\n",
             px.x, px.y, r, g, b, a
         ));
     }
 
-    let script = format!(
-        r#"local spr = app.sprite
+    Ok(format!(
+        r#"{check_frame}
 app.frame = spr.frames[{frame}]
 {layer_select}
 
@@ -124,25 +247,44 @@ app.transaction("Draw Pixels", function()
     local pos = cel.position
 {pixel_code}
 end)
-spr:saveAs(spr.filename)
-print(json.encode({{status = "drawn", pixelCount = {count}}}))"#,
+result = {{status = "drawn", pixelCount = {count}}}"#,
+        check_frame = lua_check_frame(frame_num),
         frame = frame_num,
         layer_select = layer_select,
         pixel_code = pixel_code,
         count = p.pixels.len()
-    );
-    server.execute_script_on_file(&p.file_path, &script).await
+    ))
 }
 
-pub async fn use_tool(server: &AsepriteServer, p: UseToolParams) -> Result<String, String> {
+pub async fn use_tool(server: &AsepriteServer, mut p: UseToolParams) -> Result<String, String> {
+    check_draw_limit(server, p.op.points.len(), "points")?;
+    if let Some(grid) = snap_grid(server, &p.file_path, p.snap_to_grid, p.grid.as_ref()).await? {
+        for point in &mut p.op.points {
+            (point.x, point.y) = grid.snap_point(point.x, point.y);
+        }
+    }
+    let frame = resolve_optional_frame(server, &p.file_path, p.op.frame.as_ref()).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = lua_fragment_script(&use_tool_fragment(&p.op, frame)?, &save);
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Largest brush Aseprite supports.
+const MAX_BRUSH_SIZE: u32 = 64;
+
+/// Lua fragment applying the tool stroke (see `lua_fragment_script`).
+pub fn use_tool_fragment(p: &UseToolOp, frame: Option<u32>) -> Result<String, String> {
     if p.points.is_empty() {
         return Err("Points array cannot be empty".to_string());
     }
-    validate_hex_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
-    let frame_num = p.frame.unwrap_or(1);
-    let brush_size = p.brush_size.unwrap_or(1);
-    let opacity = p.opacity.unwrap_or(255).min(255);
-    let (r, g, b, a) = parse_hex_color_with_alpha(&p.color);
+    let (r, g, b, a) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let frame_num = frame.unwrap_or(1);
+    let lenient = p.lenient.unwrap_or(false);
+    let brush_size = check_range("brush_size", p.brush_size.unwrap_or(1), 1..=MAX_BRUSH_SIZE, lenient)?;
+    let opacity = check_range("opacity", p.opacity.unwrap_or(255), 0..=255, lenient)?;
 
     let points_lua: Vec<String> = p
         .points
@@ -152,13 +294,13 @@ pub async fn use_tool(server: &AsepriteServer, p: UseToolParams) -> Result<Strin
     let points_str = points_lua.join(", ");
 
     let layer_select = if let Some(ref layer_name) = p.layer {
-        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name, false))
+        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name))
     } else {
         String::new()
     };
 
-    let script = format!(
-        r#"local spr = app.sprite
+    Ok(format!(
+        r#"{check_frame}
 app.frame = spr.frames[{frame}]
 {layer_select}
 
@@ -172,8 +314,8 @@ app.transaction("Use Tool", function()
         cel = app.cel
     }}
 end)
-spr:saveAs(spr.filename)
-print(json.encode({{status = "drawn", tool = {tool}}}))"#,
+result = {{status = "drawn", tool = {tool}}}"#,
+        check_frame = lua_check_frame(frame_num),
         frame = frame_num,
         layer_select = layer_select,
         tool = lua_string(&p.tool),
@@ -184,45 +326,28 @@ print(json.encode({{status = "drawn", tool = {tool}}}))"#,
         bs = brush_size,
         points = points_str,
         opacity = opacity
-    );
-    server.execute_script_on_file(&p.file_path, &script).await
+    ))
 }
 
 pub async fn get_pixel_data(server: &AsepriteServer, p: GetPixelDataParams) -> Result<String, String> {
-    let frame_num = p.frame.unwrap_or(1);
+    if p.width == 0 || p.height == 0 {
+        return Err(format!("width and height must be at least 1 (got {}x{})", p.width, p.height));
+    }
+    let max_area = server.config().max_pixel_read_area;
+    let area = p.width as u64 * p.height as u64;
+    if area > max_area {
+        return Err(format!(
+            "Region {}x{} is {} pixels, more than the {} allowed per call (max_pixel_read_area); read it in strips of at most {} rows at this width, or use export_raw for whole images",
+            p.width,
+            p.height,
+            area,
+            max_area,
+            (max_area / p.width as u64).max(1)
+        ));
+    }
+    let frame_num = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?.unwrap_or(1);
 
-    let image_source = if let Some(ref layer_name) = p.layer {
-        format!(
-            r#"
-{find_layer}
-local target_layer = find_layer(spr.layers, {name})
-if not target_layer then
-    print(json.encode({{error = "Layer not found"}}))
-    return
-end
-local cel = target_layer:cel(spr.frames[{frame}])
-if not cel then
-    print(json.encode({{error = "No cel at this frame"}}))
-    return
-end
-local img = cel.image
-local offX = cel.position.x
-local offY = cel.position.y"#,
-            find_layer = LUA_FIND_LAYER,
-            name = lua_string(layer_name),
-            frame = frame_num
-        )
-    } else {
-        format!(
-            r#"
-local flat = Image(spr.spec)
-flat:drawSprite(spr, {frame})
-local img = flat
-local offX = 0
-local offY = 0"#,
-            frame = frame_num
-        )
-    };
+    let image_source = lua_image_source(p.layer.as_deref(), frame_num);
 
     let script = format!(
         r##"local spr = app.sprite
@@ -258,3 +383,782 @@ print(json.encode({{pixels = pixels, width = {w}, height = {h}}}))"##,
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
+
+pub async fn find_stray_pixels(server: &AsepriteServer, p: FindStrayPixelsParams) -> Result<String, String> {
+    let holes = match p.mode.as_deref().unwrap_or("isolated") {
+        "isolated" => false,
+        "holes" => true,
+        other => return Err(format!("Unknown mode '{}'. Use \"isolated\" or \"holes\"", other)),
+    };
+    let fix = p.fix.unwrap_or(false);
+    if fix && p.layer.is_none() {
+        return Err("fix requires a layer (the flattened image can't be edited)".to_string());
+    }
+    let max_size = check_range("max_island_size", p.max_island_size.unwrap_or(2), 1..=65536, false)? as usize;
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?.unwrap_or(1);
+
+    // The pixels are written to the data file as RGBA; the fix run stays under the same lock
+    let file_path = server.resolve_input_path(&p.file_path)?;
+    let _guards = server.lock_files([&file_path]).await;
+    let pixel_file = DataFile::new(server);
+    let script = format!(
+        r##"local spr = app.sprite
+{check_frame}
+{image_source}
+local pc = app.pixelColor
+local pal = spr.palettes[1]
+local mode = img.colorMode
+local rgba = {{}}
+for it in img:pixels() do
+    local v = it()
+    local r, g, b, a
+    if mode == ColorMode.RGB then
+        r, g, b, a = pc.rgbaR(v), pc.rgbaG(v), pc.rgbaB(v), pc.rgbaA(v)
+    elseif mode == ColorMode.GRAYSCALE then
+        r, g, b, a = pc.grayaV(v), pc.grayaV(v), pc.grayaV(v), pc.grayaA(v)
+    elseif v == img.spec.transparentColor then
+        r, g, b, a = 0, 0, 0, 0
+    else
+        local c = pal:getColor(v)
+        r, g, b, a = c.red, c.green, c.blue, c.alpha
+    end
+    rgba[#rgba + 1] = string.char(r, g, b, a)
+end
+{open_data}
+data_file:write(table.concat(rgba))
+data_file:close()
+print(json.encode({{width = img.width, height = img.height, x = offX, y = offY}}))"##,
+        check_frame = lua_check_frame(frame),
+        image_source = lua_image_source(p.layer.as_deref(), frame),
+        open_data = pixel_file.lua_open()
+    );
+    let output = server.execute_script_on_locked_file(&file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let width = json["width"].as_u64().unwrap_or(0) as usize;
+    let height = json["height"].as_u64().unwrap_or(0) as usize;
+    let (off_x, off_y) = (json["x"].as_i64().unwrap_or(0), json["y"].as_i64().unwrap_or(0));
+    let pixels: Vec<[u8; 4]> = pixel_file
+        .read()
+        .await?
+        .chunks_exact(4)
+        .map(|px| [px[0], px[1], px[2], px[3]])
+        .collect();
+    if pixels.len() != width * height {
+        return Err(format!("Unexpected pixel data from the sprite: {}", output));
+    }
+
+    // Floaters are 8-connected opaque islands; holes are 4-connected transparent areas that
+    // don't reach the edge of the image
+    let opaque: Vec<bool> = pixels.iter().map(|px| px[3] > 0).collect();
+    let islands: Vec<Vec<usize>> = if holes {
+        let transparent: Vec<bool> = opaque.iter().map(|o| !o).collect();
+        let on_edge = |i: usize| {
+            let (x, y) = (i % width, i / width);
+            x == 0 || y == 0 || x + 1 == width || y + 1 == height
+        };
+        connected_components(&transparent, width, height, false)
+            .into_iter()
+            .filter(|c| !c.iter().any(|&i| on_edge(i)))
+            .collect()
+    } else {
+        connected_components(&opaque, width, height, true)
+    };
+
+    let mut found = Vec::new();
+    let mut edits = Vec::new();
+    for island in islands.into_iter().filter(|c| c.len() <= max_size) {
+        let fill = holes.then(|| surrounding_color(&island, &pixels, width, height));
+        let mut island_pixels = Vec::new();
+        for &i in &island {
+            let (x, y) = (off_x + (i % width) as i64, off_y + (i / width) as i64);
+            let [r, g, b, a] = fill.unwrap_or(pixels[i]);
+            island_pixels.push(serde_json::json!({
+                "x": x,
+                "y": y,
+                "color": format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+            }));
+            edits.push(match fill {
+                Some(_) => format!("{{{}, {}, Color({}, {}, {}, {})}}", x, y, r, g, b, a),
+                None => format!("{{{}, {}}}", x, y),
+            });
+        }
+        found.push(serde_json::json!({ "size": island.len(), "pixels": island_pixels }));
+    }
+    let mut result = serde_json::json!({
+        "mode": if holes { "holes" } else { "isolated" },
+        "frame": frame,
+        "max_island_size": max_size,
+        "count": found.len(),
+        "islands": found,
+    });
+    if !fix || edits.is_empty() {
+        return Ok(result.to_string());
+    }
+
+    let fix_script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+{color_to_pixel}
+local layer = find_layer(spr.layers, {name})
+local cel = layer and layer:cel({frame})
+if not cel then
+    print(json.encode({{error = "No cel at frame {frame} on layer " .. {name}}}))
+    return
+end
+local img = cel.image:clone()
+for _, e in ipairs({{ {edits} }}) do
+    local value = e[3] and color_to_pixel(spr, e[3]) or img.spec.transparentColor
+    img:drawPixel(e[1] - cel.position.x, e[2] - cel.position.y, value)
+end
+cel.image = img
+{save}
+print(json.encode({{status = "fixed"}}))"#,
+        find_layer = LUA_FIND_LAYER,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        name = lua_string(p.layer.as_deref().unwrap_or_default()),
+        frame = frame,
+        edits = edits.join(", "),
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_locked_file(&file_path, &fix_script).await?;
+    result["fixed_pixels"] = serde_json::json!(edits.len());
+    Ok(result.to_string())
+}
+
+/// Group the set cells of a `width` x `height` mask into connected components (8-connected
+/// when `diagonal`, otherwise 4-connected). Each component lists its cell indices.
+fn connected_components(mask: &[bool], width: usize, height: usize, diagonal: bool) -> Vec<Vec<usize>> {
+    let mut seen = vec![false; mask.len()];
+    let mut components = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut component = vec![start];
+        let mut next = 0;
+        while next < component.len() {
+            let (x, y) = ((component[next] % width) as i64, (component[next] / width) as i64);
+            next += 1;
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                if !diagonal && dx != 0 && dy != 0 {
+                    continue;
+                }
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                    continue;
+                }
+                let n = ny as usize * width + nx as usize;
+                if mask[n] && !seen[n] {
+                    seen[n] = true;
+                    component.push(n);
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+/// Most common opaque color among the 4-neighbors of a hole's pixels.
+fn surrounding_color(hole: &[usize], pixels: &[[u8; 4]], width: usize, height: usize) -> [u8; 4] {
+    let mut counts: Vec<([u8; 4], usize)> = Vec::new();
+    for &i in hole {
+        let (x, y) = (i % width, i / width);
+        let neighbors = [
+            (x > 0).then(|| i - 1),
+            (x + 1 < width).then(|| i + 1),
+            (y > 0).then(|| i - width),
+            (y + 1 < height).then(|| i + width),
+        ];
+        for n in neighbors.into_iter().flatten().filter(|&n| pixels[n][3] > 0) {
+            match counts.iter_mut().find(|(c, _)| *c == pixels[n]) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((pixels[n], 1)),
+            }
+        }
+    }
+    counts.into_iter().max_by_key(|(_, count)| *count).map_or([0, 0, 0, 255], |(c, _)| c)
+}
+
+pub async fn fill_region(server: &AsepriteServer, mut p: FillRegionParams) -> Result<String, String> {
+    if let Some(ref mut r) = p.op.region
+        && let Some(grid) = snap_grid(server, &p.file_path, p.snap_to_grid, p.grid.as_ref()).await?
+    {
+        (r.x, r.y, r.width, r.height) = grid.snap_rect(r.x, r.y, r.width, r.height);
+    }
+    let frame = resolve_optional_frame(server, &p.file_path, p.op.frame.as_ref()).await?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = lua_fragment_script(&fill_region_fragment(&p.op, frame)?, &save);
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Lua fragment filling or erasing the region (see `lua_fragment_script`).
+pub fn fill_region_fragment(p: &FillRegionOp, frame: Option<u32>) -> Result<String, String> {
+    let (r, g, b, a) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let use_selection = p.use_active_selection.or(p.selection.is_some().then_some(true));
+    let region_code = lua_region_or_selection(&p.region, use_selection)?;
+    let selection = lua_apply_selection(&p.selection)?;
+
+    let layer_select = if let Some(ref layer_name) = p.layer {
+        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name))
+    } else {
+        String::new()
+    };
+
+    Ok(format!(
+        r#"{selection}
+{check_frame}
+local frame = spr.frames[{frame}]
+app.frame = frame
+{layer_select}
+local layer = app.layer
+if not layer or not layer.isImage then
+    print(json.encode({{error = "Target layer must be an image layer"}}))
+    return
+end
+{color_to_pixel}
+{region_code}
+bounds = bounds:intersect(spr.bounds)
+if bounds.isEmpty then
+    print(json.encode({{error = "Region lies outside the canvas"}}))
+    return
+end
+
+local erase = {erase}
+local filled = 0
+app.transaction("Fill Region", function()
+    local cel = layer:cel(frame)
+    if not cel then
+        if erase then return end
+        cel = spr:newCel(layer, frame, Image(bounds.width, bounds.height, spr.colorMode), Point(bounds.x, bounds.y))
+    end
+    -- Grow the cel image when filling outside its current bounds
+    local target = erase and cel.bounds or cel.bounds:union(bounds)
+    local img = Image(target.width, target.height, spr.colorMode)
+    img:drawImage(cel.image, Point(cel.position.x - target.x, cel.position.y - target.y))
+    local value = erase and img.spec.transparentColor or color_to_pixel(spr, Color({r}, {g}, {b}, {a}))
+    local area = bounds:intersect(target)
+    for py = area.y, area.y + area.height - 1 do
+        for px = area.x, area.x + area.width - 1 do
+            if not mask or mask:contains(px, py) then
+                img:drawPixel(px - target.x, py - target.y, value)
+                filled = filled + 1
+            end
+        end
+    end
+    cel.image = img
+    cel.position = Point(target.x, target.y)
+end)
+result = {{}}
+result.status = erase and "erased" or "filled"
+result.layer = layer.name
+result.frame = frame.frameNumber
+result.bounds = {{
+    x = bounds.x,
+    y = bounds.y,
+    width = bounds.width,
+    height = bounds.height
+}}
+result.pixelCount = filled"#,
+        check_frame = lua_check_frame(frame.unwrap_or(1)),
+        selection = selection,
+        frame = frame.unwrap_or(1),
+        layer_select = layer_select,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        region_code = region_code,
+        erase = if a == 0 { "true" } else { "false" },
+        r = r,
+        g = g,
+        b = b,
+        a = a
+    ))
+}
+
+/// Largest shear accepted, in pixels per row/column and in degrees.
+const MAX_SHEAR_PIXELS: f64 = 16.0;
+const MAX_SHEAR_DEGREES: f64 = 80.0;
+
+/// Raw pixel values of a cel image and its position on the canvas.
+struct CelPixels {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+    data: Vec<u32>,
+}
+
+pub async fn skew_region(server: &AsepriteServer, p: SkewRegionParams) -> Result<String, String> {
+    let degrees = check_choice("unit", p.unit.as_deref().unwrap_or("pixels"), &[("pixels", false), ("degrees", true)], false)?;
+    let shear = |name: &str, value: Option<f64>| -> Result<f64, String> {
+        let value = value.unwrap_or(0.0);
+        if degrees {
+            check_range(name, value, -MAX_SHEAR_DEGREES..=MAX_SHEAR_DEGREES, false).map(|d| d.to_radians().tan())
+        } else {
+            check_range(name, value, -MAX_SHEAR_PIXELS..=MAX_SHEAR_PIXELS, false)
+        }
+    };
+    let (kx, ky) = (shear("shear_x", p.shear_x)?, shear("shear_y", p.shear_y)?);
+    if kx == 0.0 && ky == 0.0 {
+        return Err("Nothing to skew: provide a non-zero shear_x or shear_y".to_string());
+    }
+    if let Some(ref r) = p.region
+        && (r.width == 0 || r.height == 0)
+    {
+        return Err("region width and height must be at least 1".to_string());
+    }
+    let wrap = p.wrap.unwrap_or(false);
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?.unwrap_or(1);
+
+    let find_cel = format!(
+        r#"local spr = app.sprite
+{find_layer}
+{require_layer}
+if not layer.isImage or layer.isTilemap then
+    print(json.encode({{error = "Layer " .. {name} .. " is not a regular image layer"}}))
+    return
+end
+local cel = layer:cel({frame})
+if not cel then
+    print(json.encode({{error = "No cel at frame {frame} on layer " .. {name}}}))
+    return
+end"#,
+        require_layer = lua_require_layer("layer", &p.layer),
+        find_layer = LUA_FIND_LAYER,
+        name = lua_string(&p.layer),
+        frame = frame
+    );
+    // The cel's raw bytes travel through data files both ways, and the file stays locked from
+    // the read until the sheared cel is saved
+    let file_path = server.resolve_input_path(&p.file_path)?;
+    let _guards = server.lock_files([&file_path]).await;
+    let cel_file = DataFile::new(server);
+    let read_script = format!(
+        r#"{find_cel}
+local img = cel.image
+{open_data}
+data_file:write(img.bytes)
+data_file:close()
+local mode = img.colorMode == ColorMode.RGB and "rgb" or (img.colorMode == ColorMode.GRAYSCALE and "grayscale" or "indexed")
+print(json.encode({{x = cel.position.x, y = cel.position.y, width = img.width, height = img.height, mode = mode, transparent = img.spec.transparentColor}}))"#,
+        find_cel = find_cel,
+        open_data = cel_file.lua_open()
+    );
+    let output = server.execute_script_on_locked_file(&file_path, &read_script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let bytes_per_pixel = match json["mode"].as_str() {
+        Some("rgb") => 4,
+        Some("grayscale") => 2,
+        _ => 1,
+    };
+    let bytes = cel_file.read().await?;
+    let cel = CelPixels {
+        x: json["x"].as_i64().unwrap_or(0),
+        y: json["y"].as_i64().unwrap_or(0),
+        width: json["width"].as_i64().unwrap_or(0),
+        height: json["height"].as_i64().unwrap_or(0),
+        data: bytes
+            .chunks_exact(bytes_per_pixel)
+            .map(|px| px.iter().rev().fold(0, |v, &b| (v << 8) | b as u32))
+            .collect(),
+    };
+    if cel.data.len() as i64 != cel.width * cel.height {
+        return Err(format!("Unexpected pixel data from the sprite: {}", output));
+    }
+    let transparent = json["transparent"].as_u64().unwrap_or(0) as u32;
+    let is_clear = |v: u32| match json["mode"].as_str() {
+        Some("rgb") => v >> 24 == 0,
+        Some("grayscale") => (v >> 8) & 0xff == 0,
+        _ => v == transparent,
+    };
+
+    // Only the part of the region covered by the cel has pixels to move
+    let region = match p.region {
+        Some(ref r) => (r.x as i64, r.y as i64, r.width as i64, r.height as i64),
+        None => (cel.x, cel.y, cel.width, cel.height),
+    };
+    let (rx0, ry0) = (region.0.max(cel.x), region.1.max(cel.y));
+    let (rx1, ry1) = ((region.0 + region.2).min(cel.x + cel.width), (region.1 + region.3).min(cel.y + cel.height));
+    if rx0 >= rx1 || ry0 >= ry1 {
+        return Err(format!("region doesn't overlap the cel at frame {} on layer {}", frame, p.layer));
+    }
+    let (sheared, moved) = shear_cel(&cel, (rx0, ry0, rx1 - rx0, ry1 - ry0), kx, ky, wrap, transparent, is_clear);
+
+    let sheared_bytes: Vec<u8> = sheared
+        .data
+        .iter()
+        .flat_map(|v| v.to_le_bytes().into_iter().take(bytes_per_pixel))
+        .collect();
+    let sheared_file = DataFile::with_contents(server, &sheared_bytes).await?;
+    let (save, saved_to) = lua_save_target(server, &file_path, p.output_path.as_deref())?;
+    let write_script = format!(
+        r#"{find_cel}
+{read_data}
+local spec = cel.image.spec
+spec.width = {width}
+spec.height = {height}
+local img = Image(spec)
+img.bytes = data_bytes
+app.transaction("Skew Region", function()
+    cel.image = img
+    cel.position = Point({x}, {y})
+end)
+{save}
+print(json.encode({{status = "skewed"}}))"#,
+        find_cel = find_cel,
+        read_data = sheared_file.lua_read(),
+        width = sheared.width,
+        height = sheared.height,
+        x = sheared.x,
+        y = sheared.y,
+        save = save
+    );
+    server.execute_script_on_locked_file(&file_path, &write_script).await?;
+
+    let rect = |x: i64, y: i64, w: i64, h: i64| serde_json::json!({ "x": x, "y": y, "width": w, "height": h });
+    let mut content = None;
+    for (i, _) in sheared.data.iter().enumerate().filter(|&(_, &v)| !is_clear(v)) {
+        let (x, y) = (sheared.x + i as i64 % sheared.width, sheared.y + i as i64 / sheared.width);
+        let (x0, y0, x1, y1) = content.unwrap_or((x, y, x + 1, y + 1));
+        content = Some((x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1)));
+    }
+    let result = serde_json::json!({
+        "status": "skewed",
+        "layer": p.layer,
+        "frame": frame,
+        "region": rect(rx0, ry0, rx1 - rx0, ry1 - ry0),
+        "shear_x_per_row": kx,
+        "shear_y_per_column": ky,
+        "wrap": wrap,
+        "moved_pixels": moved,
+        "cel_bounds": rect(sheared.x, sheared.y, sheared.width, sheared.height),
+        "content_bounds": content.map(|(x0, y0, x1, y1)| rect(x0, y0, x1 - x0, y1 - y0)),
+    });
+    Ok(with_saved_to(result.to_string(), &saved_to))
+}
+
+/// Shear the opaque pixels inside `region` (x, y, width, height on the canvas, within the
+/// cel) and return the new cel with the number of pixels moved. Row offsets count from the
+/// region's bottom row and column offsets from its left column, each rounded half away from
+/// zero, with the horizontal shear applied first. Without `wrap` the cel grows to fit.
+fn shear_cel(
+    cel: &CelPixels,
+    region: (i64, i64, i64, i64),
+    kx: f64,
+    ky: f64,
+    wrap: bool,
+    transparent: u32,
+    is_clear: impl Fn(u32) -> bool,
+) -> (CelPixels, usize) {
+    let (rx, ry, rw, rh) = region;
+    let mut base = cel.data.clone();
+    let mut moved = Vec::new();
+    for y in ry..ry + rh {
+        for x in rx..rx + rw {
+            let i = ((y - cel.y) * cel.width + x - cel.x) as usize;
+            if is_clear(cel.data[i]) {
+                continue;
+            }
+            base[i] = transparent;
+            let mut nx = x + (kx * (ry + rh - 1 - y) as f64).round() as i64;
+            if wrap {
+                nx = rx + (nx - rx).rem_euclid(rw);
+            }
+            let mut ny = y + (ky * (nx - rx) as f64).round() as i64;
+            if wrap {
+                ny = ry + (ny - ry).rem_euclid(rh);
+            }
+            moved.push((nx, ny, cel.data[i]));
+        }
+    }
+
+    let (mut x0, mut y0, mut x1, mut y1) = (cel.x, cel.y, cel.x + cel.width, cel.y + cel.height);
+    for &(x, y, _) in &moved {
+        (x0, y0, x1, y1) = (x0.min(x), y0.min(y), x1.max(x + 1), y1.max(y + 1));
+    }
+    let width = x1 - x0;
+    let mut data = vec![transparent; (width * (y1 - y0)) as usize];
+    for row in 0..cel.height {
+        let src = (row * cel.width) as usize;
+        let dst = ((cel.y - y0 + row) * width + cel.x - x0) as usize;
+        data[dst..dst + cel.width as usize].copy_from_slice(&base[src..src + cel.width as usize]);
+    }
+    for &(x, y, v) in &moved {
+        data[((y - y0) * width + x - x0) as usize] = v;
+    }
+    let count = moved.len();
+    (CelPixels { x: x0, y: y0, width, height: y1 - y0, data }, count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fake Aseprite for in-place saves: reads the sprite, waits so that unserialized runs would
+    /// overlap, then saves it with the pixel coordinates its script draws appended.
+    #[cfg(unix)]
+    const SLOW_APPEND: &str = r#"file="$2"; save="${4#mcp_save_path=}"; script="$6"
+content=$(cat "$file")
+sleep 0.3
+printf '%s\n%s\n' "$content" "$(grep -o 'drawPixel([0-9]*, [0-9]*' "$script")" > "$save"
+echo '{"status":"drawn"}'"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn concurrent_draws_on_one_file_both_survive() {
+        let dir = crate::test_support::scratch_dir("concurrent_draws");
+        let server = crate::test_support::fake_server(&dir, SLOW_APPEND);
+        let sprite = dir.join("hero.aseprite");
+        std::fs::write(&sprite, "sprite").unwrap();
+        let draw = |x: i32| {
+            let params: DrawPixelsParams = serde_json::from_value(serde_json::json!({
+                "file_path": sprite.to_string_lossy(),
+                "pixels": [{ "x": x, "y": x, "color": "#ff0000" }],
+            }))
+            .unwrap();
+            draw_pixels(&server, params)
+        };
+        let (first, second) = tokio::join!(draw(1), draw(2));
+        first.unwrap();
+        second.unwrap();
+        let saved = std::fs::read_to_string(&sprite).unwrap();
+        assert!(saved.contains("drawPixel(1, 1"), "{}", saved);
+        assert!(saved.contains("drawPixel(2, 2"), "{}", saved);
+    }
+
+    fn draw_on(file_path: &str, layer: &str, frame: u32) -> DrawPixelsParams {
+        serde_json::from_value(serde_json::json!({
+            "file_path": file_path,
+            "pixels": [{ "x": 0, "y": 0, "color": "#ff0000" }],
+            "layer": layer,
+            "frame": frame,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn draw_script_checks_layer_and_frame_before_drawing() {
+        let server = crate::test_support::dry_server();
+        let script = crate::test_support::planned_script(draw_pixels(&server, draw_on("/art/in.png", "Nope", 3))).await;
+        let layer_check = script.find(&lua_require_layer("target_layer", "Nope")).expect("layer check");
+        let frame_check = script.find(&lua_check_frame(3)).expect("frame check");
+        let draw = script.find("drawPixel(").expect("draw call");
+        assert!(layer_check < draw && frame_check < draw, "{}", script);
+    }
+
+    /// Fake Aseprite answering every run with the error JSON a template prints.
+    #[cfg(unix)]
+    const MISSING_LAYER: &str = r#"echo '{"error":"Layer not found: Nope"}'"#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn missing_layer_is_reported_as_an_error() {
+        let dir = crate::test_support::scratch_dir("missing_layer_fake");
+        let server = crate::test_support::fake_server(&dir, MISSING_LAYER);
+        let sprite = dir.join("hero.aseprite");
+        std::fs::write(&sprite, "sprite").unwrap();
+        let err = draw_pixels(&server, draw_on(&sprite.to_string_lossy(), "Nope", 1)).await.unwrap_err();
+        assert_eq!(err, "Layer not found: Nope");
+        assert_eq!(std::fs::read_to_string(&sprite).unwrap(), "sprite");
+    }
+
+    #[tokio::test]
+    async fn missing_layer_and_frame_past_the_end_fail_in_aseprite() {
+        let Some(server) = crate::test_support::real_server() else { return };
+        let path = crate::test_support::scratch_dir("missing_layer").join("sprite.png").to_string_lossy().to_string();
+        let params = crate::tools::sprite::CreateSpriteParams {
+            width: 8,
+            height: 8,
+            output_path: path.clone(),
+            color_mode: None,
+        };
+        crate::tools::sprite::create_sprite(&server, params).await.unwrap();
+
+        let err = draw_pixels(&server, draw_on(&path, "Nope", 1)).await.unwrap_err();
+        assert!(err.contains("Layer not found: Nope"), "{}", err);
+        let err = draw_pixels(&server, draw_on(&path, "Layer 1", 5)).await.unwrap_err();
+        assert!(err.contains("Frame 5 out of range (sprite has 1 frames)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn region_at_negative_coordinates_reads_a_moved_cel() {
+        let Some(server) = crate::test_support::real_server() else { return };
+        let path = crate::test_support::scratch_dir("negative_region").join("sprite.aseprite").to_string_lossy().to_string();
+        let params = crate::tools::sprite::CreateSpriteParams {
+            width: 8,
+            height: 8,
+            output_path: path.clone(),
+            color_mode: None,
+        };
+        crate::tools::sprite::create_sprite(&server, params).await.unwrap();
+        draw_pixels(&server, draw_on(&path, "Layer 1", 1)).await.unwrap();
+        let moved: crate::tools::cel::MoveCelParams = serde_json::from_value(serde_json::json!({
+            "file_path": path, "layer": "Layer 1", "frame": 1, "x": -1, "y": -1,
+        }))
+        .unwrap();
+        crate::tools::cel::move_cel(&server, moved).await.unwrap();
+
+        // The pixel drawn at (0, 0) now sits at (-1, -1), the middle of this 3x3 region
+        let region = GetPixelDataParams {
+            file_path: path.clone(),
+            x: -2,
+            y: -2,
+            width: 3,
+            height: 3,
+            layer: Some("Layer 1".to_string()),
+            frame: None,
+        };
+        let read: serde_json::Value = serde_json::from_str(&get_pixel_data(&server, region).await.unwrap()).unwrap();
+        let colors: Vec<&str> = read["pixels"].as_array().unwrap().iter().map(|p| p["color"].as_str().unwrap()).collect();
+        let mut expected = vec!["#00000000"; 9];
+        expected[4] = "#ff0000ff";
+        assert_eq!(colors, expected);
+        assert_eq!((read["pixels"][4]["x"].as_i64(), read["pixels"][4]["y"].as_i64()), (Some(-1), Some(-1)));
+    }
+
+    #[tokio::test]
+    async fn out_of_range_island_size_is_rejected() {
+        let params: FindStrayPixelsParams = serde_json::from_value(serde_json::json!({
+            "file_path": "/art/in.png",
+            "max_island_size": 0,
+        }))
+        .unwrap();
+        let err = find_stray_pixels(&crate::test_support::dry_server(), params).await.unwrap_err();
+        assert_eq!(err, "max_island_size must be between 1 and 65536 (got 0)");
+    }
+
+    fn mask(rows: &[&str]) -> (Vec<bool>, usize, usize) {
+        let cells = rows.iter().flat_map(|r| r.chars().map(|c| c == '#')).collect();
+        (cells, rows[0].len(), rows.len())
+    }
+
+    fn sorted(mut components: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        for c in &mut components {
+            c.sort_unstable();
+        }
+        components.sort();
+        components
+    }
+
+    #[test]
+    fn connected_components_joins_diagonals_only_when_asked() {
+        let (cells, w, h) = mask(&["#..", ".#.", "..#"]);
+        assert_eq!(sorted(connected_components(&cells, w, h, true)), vec![vec![0, 4, 8]]);
+        assert_eq!(sorted(connected_components(&cells, w, h, false)), vec![vec![0], vec![4], vec![8]]);
+    }
+
+    #[test]
+    fn connected_components_separates_islands() {
+        let (cells, w, h) = mask(&["##..", "#...", "...#"]);
+        assert_eq!(sorted(connected_components(&cells, w, h, true)), vec![vec![0, 1, 4], vec![11]]);
+        let (empty, w, h) = mask(&["...", "..."]);
+        assert!(connected_components(&empty, w, h, true).is_empty());
+    }
+
+    #[test]
+    fn connected_components_does_not_wrap_rows() {
+        let (cells, w, h) = mask(&["..#", "#.."]);
+        assert_eq!(sorted(connected_components(&cells, w, h, true)), vec![vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn surrounding_color_picks_the_most_common_neighbor() {
+        const RED: [u8; 4] = [255, 0, 0, 255];
+        const BLUE: [u8; 4] = [0, 0, 255, 255];
+        const CLEAR: [u8; 4] = [0, 0, 0, 0];
+        #[rustfmt::skip]
+        let pixels = [
+            RED, RED,   BLUE,
+            RED, CLEAR, BLUE,
+            RED, RED,   RED,
+        ];
+        assert_eq!(surrounding_color(&[4], &pixels, 3, 3), RED);
+    }
+
+    #[test]
+    fn surrounding_color_ignores_transparent_neighbors() {
+        const GREEN: [u8; 4] = [0, 255, 0, 128];
+        const CLEAR: [u8; 4] = [0, 0, 0, 0];
+        let pixels = [CLEAR, CLEAR, GREEN, CLEAR];
+        assert_eq!(surrounding_color(&[0, 1], &pixels, 2, 2), GREEN);
+        assert_eq!(surrounding_color(&[0], &[CLEAR], 1, 1), [0, 0, 0, 255]);
+    }
+
+    fn cel_pixels(x: i64, y: i64, rows: &[&[u32]]) -> CelPixels {
+        CelPixels {
+            x,
+            y,
+            width: rows[0].len() as i64,
+            height: rows.len() as i64,
+            data: rows.concat(),
+        }
+    }
+
+    fn shear(cel: &CelPixels, kx: f64, ky: f64, wrap: bool) -> (CelPixels, usize) {
+        shear_cel(cel, (cel.x, cel.y, cel.width, cel.height), kx, ky, wrap, 0, |v| v == 0)
+    }
+
+    fn rows(cel: &CelPixels) -> Vec<Vec<u32>> {
+        cel.data.chunks(cel.width as usize).map(|r| r.to_vec()).collect()
+    }
+
+    #[test]
+    fn shear_cel_grows_to_fit_positive_shear() {
+        let (out, moved) = shear(&cel_pixels(4, 7, &[&[1, 2], &[3, 4]]), 1.0, 0.0, false);
+        assert_eq!(moved, 4);
+        assert_eq!((out.x, out.y, out.width, out.height), (4, 7, 3, 2));
+        assert_eq!(rows(&out), vec![vec![0, 1, 2], vec![3, 4, 0]]);
+    }
+
+    #[test]
+    fn shear_cel_grows_left_for_negative_shear() {
+        let (out, _) = shear(&cel_pixels(0, 0, &[&[1, 2], &[3, 4]]), -1.0, 0.0, false);
+        assert_eq!((out.x, out.width), (-1, 3));
+        assert_eq!(rows(&out), vec![vec![1, 2, 0], vec![0, 3, 4]]);
+    }
+
+    #[test]
+    fn shear_cel_rounds_offsets_half_away_from_zero() {
+        let column = cel_pixels(0, 0, &[&[1], &[2], &[3]]);
+        // Offsets from the bottom row: 2 * 0.5 = 1, 1 * 0.5 = 0.5 -> 1, 0
+        let (out, _) = shear(&column, 0.5, 0.0, false);
+        assert_eq!(out.x, 0);
+        assert_eq!(rows(&out), vec![vec![0, 1], vec![0, 2], vec![3, 0]]);
+        let (out, _) = shear(&column, -0.5, 0.0, false);
+        assert_eq!(out.x, -1);
+        assert_eq!(rows(&out), vec![vec![1, 0], vec![2, 0], vec![0, 3]]);
+    }
+
+    #[test]
+    fn shear_cel_shears_columns_downward() {
+        let (out, _) = shear(&cel_pixels(0, 0, &[&[1, 2]]), 0.0, 1.0, false);
+        assert_eq!((out.y, out.height), (0, 2));
+        assert_eq!(rows(&out), vec![vec![1, 0], vec![0, 2]]);
+    }
+
+    #[test]
+    fn shear_cel_wraps_inside_the_region() {
+        let (out, moved) = shear(&cel_pixels(0, 0, &[&[1, 2], &[3, 4]]), 1.0, 0.0, true);
+        assert_eq!(moved, 4);
+        assert_eq!((out.x, out.y, out.width, out.height), (0, 0, 2, 2));
+        assert_eq!(rows(&out), vec![vec![2, 1], vec![3, 4]]);
+
+        let (out, _) = shear(&cel_pixels(0, 0, &[&[1, 2]]), 0.0, -1.0, true);
+        assert_eq!(rows(&out), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn shear_cel_leaves_pixels_outside_the_region() {
+        let cel = cel_pixels(0, 0, &[&[1, 2, 5], &[3, 4, 6]]);
+        let (out, moved) = shear_cel(&cel, (0, 0, 2, 2), 1.0, 0.0, true, 0, |v| v == 0);
+        assert_eq!(moved, 4);
+        assert_eq!(rows(&out), vec![vec![2, 1, 5], vec![3, 4, 6]]);
+
+        let sparse = cel_pixels(0, 0, &[&[0, 2], &[3, 0]]);
+        let (_, moved) = shear(&sparse, 1.0, 0.0, false);
+        assert_eq!(moved, 2);
+    }
+}
@@ -1,37 +1,208 @@
+use std::collections::HashMap;
+
 use rmcp::schemars;
 use serde::Deserialize;
 
-use crate::aseprite::lua_string;
-use crate::lua_helpers::{LUA_FIND_LAYER, lua_select_layer};
+use crate::aseprite::{lua_path, lua_string};
+use crate::lua_helpers::{
+    DataFile, LUA_COLLECT_IMAGE_LAYERS, LUA_COLOR_TO_PIXEL, LUA_FIND_LAYER, LUA_PIXEL_ALPHA, LUA_SAVE_IN_PLACE,
+    lua_check_frame, lua_image_source, lua_require_layer, lua_require_tag, lua_save_target, with_saved_to,
+};
 use crate::server::AsepriteServer;
-use crate::utils::parse_hex_color;
+use crate::tools::frame::{FrameRef, lua_frame_range};
+use crate::tools::selection::{RectData, SelectionSpec, lua_apply_selection};
+use crate::tools::filter::{PixelTargets, pixel_filter_script};
+use crate::utils::{check_range, hsl_to_rgb, parse_color, rgb_to_hsl};
 
 // ============================================================================
 // Parameter Structs
 // ============================================================================
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ColorMapping {
+    /// Source color as hex string
+    pub from: String,
+    /// Target color as hex string
+    pub to: String,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ReplaceColorParams {
     /// Path to the sprite file
     pub file_path: String,
-    /// Source color as hex string
-    pub from_color: String,
-    /// Target color as hex string
-    pub to_color: String,
-    /// Tolerance (0-255, default: 0)
+    /// Source color as hex string (single replacement; combined with mappings if both given)
+    pub from_color: Option<String>,
+    /// Target color as hex string (required with from_color)
+    pub to_color: Option<String>,
+    /// Several {from, to} pairs applied together in one pass, so replacements never cascade
+    pub mappings: Option<Vec<ColorMapping>>,
+    /// Tolerance (0-255, default: 0). Per-channel distance for RGB sprites; other color modes match exactly
     pub tolerance: Option<u32>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Single target frame, 1-based (if omitted, uses from_frame/to_frame or all frames)
+    pub frame: Option<u32>,
+    /// First frame to process, 1-based (default: 1)
+    pub from_frame: Option<u32>,
+    /// Last frame to process, 1-based inclusive (default: last frame)
+    pub to_frame: Option<u32>,
+    /// Limit the effect to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Save the result to this path instead of overwriting the input file
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum PaletteSource {
+    /// Inline colors in palette order (hex, CSS names, or rgb()/rgba())
+    Colors(Vec<String>),
+    /// Palette file (.gpl, .pal, .act, .col, .png, etc.)
+    File(String),
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RemapColorsParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Colors to replace: a palette file path or an inline array of colors
+    pub from_palette: PaletteSource,
+    /// Replacement colors, same length as from_palette: color i replaces from_palette color i
+    pub to_palette: PaletteSource,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Single target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, uses from_frame/to_frame)
+    pub frame: Option<FrameRef>,
+    /// First frame to process (default: 1)
+    pub from_frame: Option<FrameRef>,
+    /// Last frame to process, inclusive (default: last frame)
+    pub to_frame: Option<FrameRef>,
+    /// Save the result to this path instead of overwriting the input file
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct OutlineParams {
     /// Path to the sprite file
     pub file_path: String,
-    /// Outline color as hex string (e.g. "#000000")
+    /// Outline color as hex, CSS name, or rgb() (e.g. "#000000", "black")
     pub color: String,
     /// Target layer name (if omitted, uses active layer)
     pub layer: Option<String>,
+    /// Outline every image layer separately instead of a single layer (default: false)
+    pub all_layers: Option<bool>,
     /// Target frame number, 1-based (if omitted, uses frame 1)
     pub frame: Option<u32>,
+    /// Outline every frame of this animation tag instead of a single frame
+    pub tag: Option<String>,
+    /// Outline thickness in pixels (default: 1)
+    pub thickness: Option<u32>,
+    /// Where the outline goes: "outside" (default) or "inside" the shape
+    pub place: Option<String>,
+    /// Corner shape: "circle" (default, no diagonal corners) or "square" (fills corners)
+    pub corners: Option<String>,
+    /// With all_layers, skip hidden layers so only visible edges get an outline (default: true)
+    pub only_visible_edges: Option<bool>,
+    /// Limit the effect to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Limit the effect to a rectangle: {x, y, width, height}
+    pub region: Option<RectData>,
+    /// Limit the effect to the bounds of this slice
+    pub slice: Option<String>,
+    /// Limit the effect to the selection stored in the sprite file (default: false)
+    pub use_active_selection: Option<bool>,
+    /// Save the result to this path instead of overwriting the input file
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DropShadowParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Horizontal shadow offset in pixels (positive = right)
+    pub offset_x: i32,
+    /// Vertical shadow offset in pixels (positive = down)
+    pub offset_y: i32,
+    /// Shadow color as hex, CSS name, or rgb()/rgba(), alpha optional (e.g. "#00000080", "rgba(0, 0, 0, 0.5)")
+    pub color: String,
+    /// Layer casting the shadow (if omitted, uses active layer)
+    pub layer: Option<String>,
+    /// First frame to process, 1-based (default: 1)
+    pub from_frame: Option<u32>,
+    /// Last frame to process, 1-based inclusive (default: last frame)
+    pub to_frame: Option<u32>,
+    /// Put the shadow on a new "Shadow" layer beneath the target instead of merging it into the same cel (default: false)
+    pub separate_layer: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum ShadeRamp {
+    /// Explicit ramp of colors (hex, CSS names, or rgb()) ordered from darkest to lightest
+    Colors(Vec<String>),
+    /// "derive": build a 5-step ramp (two shades darker, two lighter) around each base color
+    Derive(String),
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct AutoShadeParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer to shade (if omitted, uses active layer)
+    pub layer: Option<String>,
+    /// Target frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+    /// Where the light comes from: "top_left" (default), "top", "top_right", "left", "right",
+    /// "bottom_left", "bottom", "bottom_right"
+    pub light_direction: Option<String>,
+    /// Color ramp (dark to light) to shift edge pixels along, or "derive" (default)
+    pub ramp: Option<ShadeRamp>,
+    /// Number of ramp steps edge pixels are shifted (1-2, default: 1)
+    pub strength: Option<u32>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ScanlinesParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Darken every Nth row/column (default: 2)
+    pub spacing: Option<u32>,
+    /// How dark the lines are (0-100, default: 50)
+    pub darkness: Option<u32>,
+    /// Line direction: "horizontal" (default, darkens rows) or "vertical" (darkens columns)
+    pub direction: Option<String>,
+    /// Draw the lines on a new "Scanlines" layer set to multiply instead of darkening pixels in place (default: false)
+    pub separate_layer: Option<bool>,
+    /// Layer to darken in place (if omitted, all image layers; ignored with separate_layer)
+    pub layer: Option<String>,
+    /// First frame to process, 1-based (default: 1)
+    pub from_frame: Option<u32>,
+    /// Last frame to process, 1-based inclusive (default: last frame)
+    pub to_frame: Option<u32>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExtractSilhouetteParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Silhouette fill color as hex, CSS name, or rgb()/rgba() (default: "#000000")
+    pub color: Option<String>,
+    /// Source layer name (if omitted, uses the flattened sprite)
+    pub layer: Option<String>,
+    /// Source frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+    /// Minimum alpha (1-255) for a pixel to count as part of the silhouette (default: 1)
+    pub alpha_threshold: Option<u32>,
+    /// Write the silhouette to a new image file at this path
+    pub output_path: Option<String>,
+    /// Add the silhouette to the sprite as a new layer with this name
+    pub new_layer_name: Option<String>,
 }
 
 // ============================================================================
@@ -39,58 +210,1149 @@ pub struct OutlineParams {
 // ============================================================================
 
 pub async fn replace_color(server: &AsepriteServer, p: ReplaceColorParams) -> Result<String, String> {
-    let (fr, fg, fb) = parse_hex_color(&p.from_color);
-    let (tr, tg, tb) = parse_hex_color(&p.to_color);
-    let tolerance = p.tolerance.unwrap_or(0);
+    let mut pairs: Vec<(&str, &str)> = Vec::new();
+    match (&p.from_color, &p.to_color) {
+        (Some(from), Some(to)) => pairs.push((from, to)),
+        (None, None) => {}
+        _ => return Err("from_color and to_color must be given together".to_string()),
+    }
+    if let Some(ref mappings) = p.mappings {
+        pairs.extend(mappings.iter().map(|m| (m.from.as_str(), m.to.as_str())));
+    }
+    if pairs.is_empty() {
+        return Err("Provide from_color/to_color or a non-empty mappings array".to_string());
+    }
+    let mut parsed = Vec::with_capacity(pairs.len());
+    for (from, to) in &pairs {
+        let parse = |color: &str| parse_color(color).map_err(|e| format!("Invalid color '{}': {}", color, e));
+        parsed.push((parse(from)?, parse(to)?));
+    }
+    let tolerance = check_range("tolerance", p.tolerance.unwrap_or(0), 0..=255, false)?;
+
+    let (from_f, to_f) = match (p.frame, p.from_frame, p.to_frame) {
+        (Some(f), None, None) => (f.to_string(), f.to_string()),
+        (None, from, to) => (
+            from.unwrap_or(1).to_string(),
+            to.map(|t| t.to_string()).unwrap_or_else(|| "#spr.frames".to_string()),
+        ),
+        _ => return Err("Use either frame or from_frame/to_frame, not both".to_string()),
+    };
+    if p.frame == Some(0) || p.from_frame == Some(0) || p.to_frame == Some(0) {
+        return Err("Frame numbers are 1-based and must be >= 1".to_string());
+    }
+
+    let layers = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+local target_layer = find_layer(spr.layers, {name})
+if not target_layer or not target_layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+local layers = {{ target_layer }}"#,
+            find_layer = LUA_FIND_LAYER,
+            name = lua_string(layer_name)
+        )
+    } else {
+        format!("{}\nlocal layers = collect_image_layers(spr.layers, {{}}, false)", LUA_COLLECT_IMAGE_LAYERS)
+    };
+
+    let mut from_colors = Vec::new();
+    let mut to_colors = Vec::new();
+    for ((fr, fg, fb, fa), (tr, tg, tb, ta)) in parsed {
+        from_colors.push(format!("{{{}, {}, {}, {}}}", fr, fg, fb, fa));
+        to_colors.push(format!("{{{}, {}, {}, {}}}", tr, tg, tb, ta));
+    }
+    let mapping_json: Vec<String> = pairs
+        .iter()
+        .map(|(from, to)| format!("{{from = {}, to = {}}}", lua_string(from), lua_string(to)))
+        .collect();
+
+    let selection = lua_apply_selection(&p.selection)?;
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
 
     let script = format!(
         r#"local spr = app.sprite
-app.command.ReplaceColor {{
-    ui = false,
-    from = Color({fr}, {fg}, {fb}),
-    to = Color({tr}, {tg}, {tb}),
-    tolerance = {tol}
-}}
-spr:saveAs(spr.filename)
-print(json.encode({{status = "replaced", from = {from_s}, to = {to_s}}}))"#,
-        fr = fr,
-        fg = fg,
-        fb = fb,
-        tr = tr,
-        tg = tg,
-        tb = tb,
+{layers}
+local from_f, to_f = {from_f}, {to_f}
+if to_f > #spr.frames then to_f = #spr.frames end
+if from_f > to_f then
+    print(json.encode({{error = "Frame range is empty or out of range"}}))
+    return
+end
+{selection}
+{color_to_pixel}
+local pc = app.pixelColor
+local from_rgba = {{ {from_colors} }}
+local to_rgba = {{ {to_colors} }}
+local from_px, to_px = {{}}, {{}}
+for i = 1, #from_rgba do
+    local f, t = from_rgba[i], to_rgba[i]
+    from_px[i] = color_to_pixel(spr, Color(f[1], f[2], f[3], f[4]))
+    to_px[i] = color_to_pixel(spr, Color(t[1], t[2], t[3], t[4]))
+end
+local tol = {tol}
+local is_rgb = spr.colorMode == ColorMode.RGB
+-- Every pixel is looked up once against the full table (first match wins), so mappings never cascade
+local cache = {{}}
+local function lookup(px)
+    local hit = cache[px]
+    if hit ~= nil then return hit end
+    hit = false
+    for i = 1, #from_px do
+        if px == from_px[i] then hit = to_px[i] break end
+    end
+    if not hit and tol > 0 and is_rgb then
+        for i, f in ipairs(from_rgba) do
+            if math.abs(pc.rgbaR(px) - f[1]) <= tol and math.abs(pc.rgbaG(px) - f[2]) <= tol
+                and math.abs(pc.rgbaB(px) - f[3]) <= tol and math.abs(pc.rgbaA(px) - f[4]) <= tol then
+                hit = to_px[i]
+                break
+            end
+        end
+    end
+    cache[px] = hit
+    return hit
+end
+
+local sel = spr.selection
+local has_sel = not sel.isEmpty
+local replaced = 0
+-- Linked cels share one image: replace in it once, or later frames would chain the mappings
+local seen = {{}}
+app.transaction("Replace Colors", function()
+    for _, l in ipairs(layers) do
+        for f = from_f, to_f do
+            local cel = l:cel(f)
+            if cel and not seen[cel.image.id] then
+                seen[cel.image.id] = true
+                local img = cel.image:clone()
+                local ox, oy = cel.position.x, cel.position.y
+                local touched = false
+                for it in img:pixels() do
+                    if not has_sel or sel:contains(it.x + ox, it.y + oy) then
+                        local to = lookup(it())
+                        if to and to ~= it() then
+                            it(to)
+                            replaced = replaced + 1
+                            touched = true
+                        end
+                    end
+                end
+                if touched then
+                    cel.image = img
+                    seen[cel.image.id] = true
+                end
+            end
+        end
+    end
+end)
+{save}
+print(json.encode({{status = "replaced", mappings = {{ {mapping_json} }}, pixels_replaced = replaced}}))"#,
+        layers = layers,
+        from_f = from_f,
+        to_f = to_f,
+        selection = selection,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        from_colors = from_colors.join(", "),
+        to_colors = to_colors.join(", "),
         tol = tolerance,
-        from_s = lua_string(&p.from_color),
-        to_s = lua_string(&p.to_color)
+        save = save_code,
+        mapping_json = mapping_json.join(", ")
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Most frequent unmatched colors listed in the remap_colors result.
+const REMAP_UNMATCHED_LIMIT: usize = 10;
+
+/// Lua code defining `{name}` as a list of {r, g, b, a} colors from an inline list or a palette
+/// file. Prints an error JSON and returns if the file can't be loaded.
+fn lua_palette_source(server: &AsepriteServer, name: &str, source: &PaletteSource) -> Result<String, String> {
+    match source {
+        PaletteSource::Colors(colors) => {
+            if colors.is_empty() {
+                return Err(format!("{} is empty", name));
+            }
+            let entries = colors
+                .iter()
+                .map(|c| {
+                    let (r, g, b, a) = parse_color(c).map_err(|e| format!("Invalid {} color '{}': {}", name, c, e))?;
+                    Ok(format!("{{{}, {}, {}, {}}}", r, g, b, a))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(format!("local {} = {{ {} }}", name, entries.join(", ")))
+        }
+        PaletteSource::File(path) => Ok(format!(
+            r#"local {name} = {{}}
+do
+    local ok, pal = pcall(Palette, {{ fromFile = {path} }})
+    if not ok or not pal then
+        print(json.encode({{error = "Could not load {name} from " .. {path}}}))
+        return
+    end
+    for i = 0, #pal - 1 do
+        local c = pal:getColor(i)
+        {name}[#{name} + 1] = {{c.red, c.green, c.blue, c.alpha}}
+    end
+end"#,
+            name = name,
+            path = lua_path(&server.resolve_input_path(path)?)
+        )),
+    }
+}
+
+pub async fn remap_colors(server: &AsepriteServer, p: RemapColorsParams) -> Result<String, String> {
+    if let (PaletteSource::Colors(from), PaletteSource::Colors(to)) = (&p.from_palette, &p.to_palette)
+        && from.len() != to.len()
+    {
+        return Err(format!(
+            "from_palette has {} colors but to_palette has {}; they must be the same length",
+            from.len(),
+            to.len()
+        ));
+    }
+    let from_palette = lua_palette_source(server, "from_palette", &p.from_palette)?;
+    let to_palette = lua_palette_source(server, "to_palette", &p.to_palette)?;
+    let range = match p.frame {
+        Some(ref frame) => {
+            if p.from_frame.is_some() || p.to_frame.is_some() {
+                return Err("Use either frame or from_frame/to_frame, not both".to_string());
+            }
+            lua_frame_range(server, &p.file_path, Some(frame), Some(frame)).await?
+        }
+        None => lua_frame_range(server, &p.file_path, p.from_frame.as_ref(), p.to_frame.as_ref()).await?,
+    };
+
+    let layers = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+local target_layer = find_layer(spr.layers, {name})
+if not target_layer or not target_layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+local layers = {{ target_layer }}"#,
+            find_layer = LUA_FIND_LAYER,
+            name = lua_string(layer_name)
+        )
+    } else {
+        format!("{}\nlocal layers = collect_image_layers(spr.layers, {{}}, false)", LUA_COLLECT_IMAGE_LAYERS)
+    };
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+
+    // One lookup table built up front, so a pixel is mapped at most once (no cascading);
+    // when from_palette repeats a color, its first entry wins
+    let script = format!(
+        r##"local spr = app.sprite
+{layers}
+{range}
+if to_f > #spr.frames then
+    print(json.encode({{error = "Frame " .. to_f .. " is out of range (sprite has " .. #spr.frames .. " frames)"}}))
+    return
+end
+{from_palette}
+{to_palette}
+if #from_palette ~= #to_palette then
+    print(json.encode({{error = "from_palette has " .. #from_palette .. " colors but to_palette has " .. #to_palette .. "; they must be the same length"}}))
+    return
+end
+{color_to_pixel}
+local pc = app.pixelColor
+local map = {{}}
+for i, f in ipairs(from_palette) do
+    local from = color_to_pixel(spr, Color(f[1], f[2], f[3], f[4]))
+    if map[from] == nil then
+        local t = to_palette[i]
+        map[from] = color_to_pixel(spr, Color(t[1], t[2], t[3], t[4]))
+    end
+end
+local pal = spr.palettes[1]
+local function pixel_hex(v)
+    local c
+    if spr.colorMode == ColorMode.RGB then
+        c = Color(pc.rgbaR(v), pc.rgbaG(v), pc.rgbaB(v), pc.rgbaA(v))
+    elseif spr.colorMode == ColorMode.GRAYSCALE then
+        c = Color(pc.grayaV(v), pc.grayaV(v), pc.grayaV(v), pc.grayaA(v))
+    else
+        c = pal:getColor(v)
+    end
+    return string.format("#%02x%02x%02x%02x", c.red, c.green, c.blue, c.alpha)
+end
+local function is_clear(img, v)
+    if img.colorMode == ColorMode.RGB then return pc.rgbaA(v) == 0 end
+    if img.colorMode == ColorMode.GRAYSCALE then return pc.grayaA(v) == 0 end
+    return v == img.spec.transparentColor
+end
+
+local remapped, matched, unmatched = 0, 0, 0
+local unmatched_counts = {{}}
+-- Linked cels share one image: remap it once, or later frames would remap the result again
+local seen = {{}}
+app.transaction("Remap Colors", function()
+    for _, l in ipairs(layers) do
+        for f = from_f, to_f do
+            local cel = l:cel(f)
+            if cel and not seen[cel.image.id] then
+                seen[cel.image.id] = true
+                local img = cel.image:clone()
+                local touched = false
+                for it in img:pixels() do
+                    local v = it()
+                    local to = map[v]
+                    if to ~= nil then
+                        matched = matched + 1
+                        if to ~= v then
+                            it(to)
+                            remapped = remapped + 1
+                            touched = true
+                        end
+                    elseif not is_clear(img, v) then
+                        unmatched = unmatched + 1
+                        unmatched_counts[v] = (unmatched_counts[v] or 0) + 1
+                    end
+                end
+                if touched then
+                    cel.image = img
+                    seen[cel.image.id] = true
+                end
+            end
+        end
+    end
+end)
+{save}
+local unmatched_colors = {{}}
+for v, n in pairs(unmatched_counts) do unmatched_colors[#unmatched_colors + 1] = {{color = pixel_hex(v), count = n}} end
+table.sort(unmatched_colors, function(a, b) return a.count > b.count end)
+while #unmatched_colors > {limit} do table.remove(unmatched_colors) end
+print(json.encode({{
+    status = "remapped",
+    colors = #from_palette,
+    from_frame = from_f,
+    to_frame = to_f,
+    pixels_matched = matched,
+    pixels_remapped = remapped,
+    pixels_unmatched = unmatched,
+    unmatched_colors = unmatched_colors
+}}))"##,
+        layers = layers,
+        range = range,
+        from_palette = from_palette,
+        to_palette = to_palette,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        save = save_code,
+        limit = REMAP_UNMATCHED_LIMIT
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn outline(server: &AsepriteServer, p: OutlineParams) -> Result<String, String> {
+    let (r, g, b, _) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let thickness = p.thickness.unwrap_or(1);
+    if thickness == 0 {
+        return Err("thickness must be at least 1".to_string());
+    }
+    let place = p.place.as_deref().unwrap_or("outside");
+    if !matches!(place, "outside" | "inside") {
+        return Err(format!("Invalid place '{}'. Must be 'outside' or 'inside'", place));
+    }
+    let matrix = p.corners.as_deref().unwrap_or("circle");
+    if !matches!(matrix, "circle" | "square") {
+        return Err(format!("Invalid corners '{}'. Must be 'circle' or 'square'", matrix));
+    }
+    if p.tag.is_some() && p.frame.is_some() {
+        return Err("tag cannot be combined with frame".to_string());
+    }
+
+    let targets = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+{require_layer}
+local targets = {{ target_layer }}"#,
+            require_layer = lua_require_layer("target_layer", layer_name),
+            find_layer = LUA_FIND_LAYER,
+        )
+    } else if p.all_layers.unwrap_or(false) {
+        format!(
+            "{}\nlocal targets = collect_image_layers(spr.layers, {{}}, {})",
+            LUA_COLLECT_IMAGE_LAYERS,
+            p.only_visible_edges.unwrap_or(true)
+        )
+    } else {
+        "local targets = { app.layer }".to_string()
+    };
+
+    let range_code = match p.tag {
+        Some(ref tag) => format!(
+            r#"{require_tag}
+local from_frame, to_frame = tag.fromFrame.frameNumber, tag.toFrame.frameNumber"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        None => format!(
+            r#"local from_frame, to_frame = {frame}, {frame}
+{check_frame}"#,
+            check_frame = lua_check_frame(p.frame.unwrap_or(1)),
+            frame = p.frame.unwrap_or(1)
+        ),
+    };
+
+    let use_active_selection = p.use_active_selection.unwrap_or(false);
+    let masks = [p.selection.is_some(), p.region.is_some(), p.slice.is_some(), use_active_selection];
+    if masks.iter().filter(|&&m| m).count() > 1 {
+        return Err("Specify only one of selection, region, slice, or use_active_selection".to_string());
+    }
+    let selection = if let Some(region) = p.region {
+        lua_apply_selection(&Some(SelectionSpec::Rect(region)))?
+    } else if let Some(ref slice) = p.slice {
+        lua_apply_selection(&Some(SelectionSpec::Slice(format!("from_slice:{}", slice))))?
+    } else if use_active_selection {
+        r#"if spr.selection.isEmpty then
+    print(json.encode({error = "No active selection in sprite"}))
+    return
+end"#
+            .to_string()
+    } else {
+        lua_apply_selection(&p.selection)?
+    };
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+
+    // Linked cels share one image, so each image is outlined once however many frames show it
+    let script = format!(
+        r#"local spr = app.sprite
+{range_code}
+{targets}
+{selection}
+local outlined, outlined_seen, frames, seen = {{}}, {{}}, {{}}, {{}}
+for frame_num = from_frame, to_frame do
+    local frame = spr.frames[frame_num]
+    app.frame = frame
+    local touched = false
+    for li, l in ipairs(targets) do
+        local cel = l.isImage and l:cel(frame)
+        if cel and not seen[cel.image.id] then
+            seen[cel.image.id] = true
+            app.layer = l
+            for i = 1, {thickness} do
+                app.command.Outline {{
+                    ui = false,
+                    color = Color({r}, {g}, {b}),
+                    place = "{place}",
+                    matrix = "{matrix}"
+                }}
+            end
+            if not outlined_seen[li] then
+                outlined_seen[li] = true
+                outlined[#outlined + 1] = l.name
+            end
+            touched = true
+        end
+    end
+    if touched then frames[#frames + 1] = frame_num end
+end
+{save}
+local result = {{status = "outlined", layers = outlined, frames = frames, thickness = {thickness}, place = "{place}", corners = "{matrix}"}}
+if not spr.selection.isEmpty then
+    local b = spr.selection.bounds
+    result.region = {{x = b.x, y = b.y, width = b.width, height = b.height}}
+end
+print(json.encode(result))"#,
+        range_code = range_code,
+        targets = targets,
+        selection = selection,
+        thickness = thickness,
+        r = r,
+        g = g,
+        b = b,
+        place = place,
+        matrix = matrix,
+        save = save_code
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn drop_shadow(server: &AsepriteServer, p: DropShadowParams) -> Result<String, String> {
+    let (r, g, b, a) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let from = p.from_frame.unwrap_or(1);
+    if from == 0 || p.to_frame == Some(0) {
+        return Err("from_frame and to_frame are 1-based and must be >= 1".to_string());
+    }
+    let to = p.to_frame.map(|f| f.to_string()).unwrap_or_else(|| "#spr.frames".to_string());
+    let separate = p.separate_layer.unwrap_or(false);
+
+    let target = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+{require_layer}"#,
+            require_layer = lua_require_layer("target", layer_name),
+            find_layer = LUA_FIND_LAYER,
+        )
+    } else {
+        "local target = app.layer".to_string()
+    };
+
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+if spr.colorMode ~= ColorMode.RGB then
+    print(json.encode({{error = "drop_shadow requires an RGB sprite"}}))
+    return
+end
+{target}
+if not target or not target.isImage then
+    print(json.encode({{error = "Target layer must be an image layer"}}))
+    return
+end
+local from_f, to_f = {from}, {to}
+if to_f > #spr.frames then to_f = #spr.frames end
+if from_f > to_f then
+    print(json.encode({{error = "Frame range is empty or out of range"}}))
+    return
+end
+
+local function make_shadow(src)
+    local img = Image(src.width, src.height, ColorMode.RGB)
+    for it in src:pixels() do
+        local sa = app.pixelColor.rgbaA(it())
+        if sa > 0 then
+            img:drawPixel(it.x, it.y, app.pixelColor.rgba({r}, {g}, {b}, sa * {a} // 255))
+        end
+    end
+    return img
+end
+
+local shadow_layer = nil
+if {separate} then
+    app.layer = target
+    shadow_layer = spr:newLayer()
+    shadow_layer.name = "Shadow"
+    shadow_layer.parent = target.parent
+    shadow_layer.stackIndex = target.stackIndex
+end
+
+local count = 0
+-- Linked cels share one image and position: merge the shadow in once, or later frames would
+-- shadow the shadowed image again. They still count, since they show the shadow too.
+local seen = {{}}
+app.transaction("Drop Shadow", function()
+    for f = from_f, to_f do
+        local cel = target:cel(f)
+        if cel and not shadow_layer and seen[cel.image.id] then
+            count = count + 1
+        elseif cel then
+            seen[cel.image.id] = true
+            local src = cel.image
+            local pos = cel.position
+            local shadow = make_shadow(src)
+            local sx, sy = pos.x + ({ox}), pos.y + ({oy})
+            if shadow_layer then
+                spr:newCel(shadow_layer, f, shadow, Point(sx, sy))
+            else
+                local x0, y0 = math.min(pos.x, sx), math.min(pos.y, sy)
+                local x1 = math.max(pos.x + src.width, sx + src.width)
+                local y1 = math.max(pos.y + src.height, sy + src.height)
+                local merged = Image(x1 - x0, y1 - y0, ColorMode.RGB)
+                merged:drawImage(shadow, Point(sx - x0, sy - y0))
+                merged:drawImage(src, Point(pos.x - x0, pos.y - y0), 255, BlendMode.NORMAL)
+                cel.image = merged
+                cel.position = Point(x0, y0)
+                seen[cel.image.id] = true
+            end
+            count = count + 1
+        end
+    end
+end)
+{save}
+print(json.encode({{status = "shadowed", layer = target.name, frames = count, shadow_layer = shadow_layer and shadow_layer.name or nil}}))"#,
+        target = target,
+        from = from,
+        to = to,
+        r = r,
+        g = g,
+        b = b,
+        a = a,
+        separate = separate,
+        ox = p.offset_x,
+        oy = p.offset_y,
+        save = save_code
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn auto_shade(server: &AsepriteServer, p: AutoShadeParams) -> Result<String, String> {
     let frame_num = p.frame.unwrap_or(1);
-    let (r, g, b) = parse_hex_color(&p.color);
+    let direction = p.light_direction.as_deref().unwrap_or("top_left");
+    let light = light_vector(direction).ok_or_else(|| {
+        format!(
+            "Invalid light_direction '{}'. Must be one of: top_left, top, top_right, left, right, bottom_left, bottom, bottom_right",
+            direction
+        )
+    })?;
+    let strength = p.strength.unwrap_or(1);
+    if !(1..=2).contains(&strength) {
+        return Err(format!("strength must be 1 or 2 (got {})", strength));
+    }
+    let ramp = match p.ramp {
+        None => None,
+        Some(ShadeRamp::Derive(ref s)) if s == "derive" => None,
+        Some(ShadeRamp::Derive(ref s)) => {
+            return Err(format!("Invalid ramp '{}'. Use \"derive\" or an array of colors", s));
+        }
+        Some(ShadeRamp::Colors(ref colors)) => {
+            if colors.len() < 2 {
+                return Err("ramp needs at least 2 colors".to_string());
+            }
+            let ramp = colors
+                .iter()
+                .map(|c| {
+                    let (r, g, b, _) = parse_color(c).map_err(|e| format!("Invalid ramp color '{}': {}", c, e))?;
+                    Ok((r, g, b))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Some(ramp)
+        }
+    };
 
-    let layer_select = if let Some(ref layer_name) = p.layer {
-        format!("{}{}", LUA_FIND_LAYER, lua_select_layer(layer_name, false))
+    let target = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+{require_layer}"#,
+            require_layer = lua_require_layer("target", layer_name),
+            find_layer = LUA_FIND_LAYER,
+        )
     } else {
-        String::new()
+        "local target = app.layer".to_string()
+    };
+    let locate = format!(
+        r#"local spr = app.sprite
+if spr.colorMode ~= ColorMode.RGB then
+    print(json.encode({{error = "auto_shade requires an RGB sprite"}}))
+    return
+end
+{target}
+if not target or not target.isImage then
+    print(json.encode({{error = "Target layer must be an image layer"}}))
+    return
+end
+{check_frame}
+local frame = spr.frames[{frame}]
+local cel = target:cel(frame)
+if not cel then
+    print(json.encode({{error = "No cel on this layer at frame {frame}"}}))
+    return
+end
+local img = cel.image"#,
+        check_frame = lua_check_frame(frame_num),
+        target = target,
+        frame = frame_num
+    );
+
+    // Read the cel through a data file, compute the shading in Rust, then write the shaded image
+    // back; the file stays locked in between so no other call can edit it under us
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let file_path = server.resolve_input_path(&p.file_path)?;
+    let _guards = server.lock_files([&file_path]).await;
+    let cel_file = DataFile::new(server);
+    let read_script = format!(
+        r#"{locate}
+{open_data}
+data_file:write(img.bytes)
+data_file:close()
+print(json.encode({{width = img.width, height = img.height}}))"#,
+        locate = locate,
+        open_data = cel_file.lua_open()
+    );
+    let output = server.execute_script_on_locked_file(&file_path, &read_script).await?;
+    let data: serde_json::Value =
+        serde_json::from_str(&output).map_err(|e| format!("Unexpected pixel data from Aseprite: {}", e))?;
+    let width = data["width"].as_u64().unwrap_or(0) as usize;
+    let height = data["height"].as_u64().unwrap_or(0) as usize;
+    let bytes = cel_file.read().await?;
+    if bytes.len() != width * height * 4 {
+        return Err("Pixel data size does not match the cel size".to_string());
+    }
+    let mut pixels: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|px| u32::from_le_bytes([px[0], px[1], px[2], px[3]]))
+        .collect();
+
+    let changes = shade_edges(&pixels, width, height, light, ramp.as_deref(), strength as i32);
+    let (highlights, shadows) = changes.iter().fold((0, 0), |(h, s), c| if c.lighter { (h + 1, s) } else { (h, s + 1) });
+    for c in &changes {
+        pixels[c.y * width + c.x] = u32::from_le_bytes([c.rgba.0, c.rgba.1, c.rgba.2, c.rgba.3]);
+    }
+    let shaded: Vec<u8> = pixels.iter().flat_map(|v| v.to_le_bytes()).collect();
+    let shaded_file = DataFile::with_contents(server, &shaded).await?;
+
+    let write_script = format!(
+        r#"{locate}
+{read_data}
+app.transaction("Auto Shade", function()
+    img = img:clone()
+    img.bytes = data_bytes
+    cel.image = img
+end)
+{save}
+print(json.encode({{status = "shaded", light_direction = "{direction}", strength = {strength}, highlights = {highlights}, shadows = {shadows}}}))"#,
+        locate = locate,
+        read_data = shaded_file.lua_read(),
+        direction = direction,
+        strength = strength,
+        highlights = highlights,
+        shadows = shadows,
+        save = save_code
+    );
+    server
+        .execute_script_on_locked_file(&file_path, &write_script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn scanlines(server: &AsepriteServer, p: ScanlinesParams) -> Result<String, String> {
+    let spacing = p.spacing.unwrap_or(2);
+    if spacing < 2 {
+        return Err("spacing must be at least 2".to_string());
+    }
+    let darkness = p.darkness.unwrap_or(50);
+    if darkness > 100 {
+        return Err(format!("darkness must be between 0 and 100 (got {})", darkness));
+    }
+    let direction = p.direction.as_deref().unwrap_or("horizontal");
+    let coord = match direction {
+        "horizontal" => "y",
+        "vertical" => "x",
+        other => {
+            return Err(format!(
+                "Invalid direction '{}'. Must be 'horizontal' or 'vertical'",
+                other
+            ));
+        }
+    };
+    if p.from_frame == Some(0) || p.to_frame == Some(0) {
+        return Err("Frame numbers are 1-based and must be >= 1".to_string());
+    }
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let fields = format!(
+        r#"effect = "scanlines", spacing = {}, darkness = {}, direction = "{}""#,
+        spacing, darkness, direction
+    );
+
+    if !p.separate_layer.unwrap_or(false) {
+        let script = pixel_filter_script(
+            PixelTargets {
+                layer: p.layer.as_deref(),
+                from_frame: p.from_frame,
+                to_frame: p.to_frame,
+                selection: &None,
+            },
+            &format!("local k = {} / 100", 100 - darkness),
+            &format!(
+                "if a > 0 and {coord} % {spacing} == 0 then\n    r, g, b = math.floor(r * k + 0.5), math.floor(g * k + 0.5), math.floor(b * k + 0.5)\nend",
+                coord = coord,
+                spacing = spacing
+            ),
+            &fields,
+            &save_code,
+        )?;
+        return server
+            .execute_script_on_file(&p.file_path, &script)
+            .await
+            .map(|output| with_saved_to(output, &saved_to));
+    }
+
+    let gray = 255 * (100 - darkness) / 100;
+    let script = format!(
+        r#"local spr = app.sprite
+local from_f = {from}
+local to_f = {to}
+if to_f > #spr.frames then to_f = #spr.frames end
+if from_f > to_f then
+    print(json.encode({{error = "Frame range is empty or out of range"}}))
+    return
+end
+{color_to_pixel}
+local line = Image(spr.width, spr.height, spr.colorMode)
+local value = color_to_pixel(spr, Color({gray}, {gray}, {gray}, 255))
+for y = 0, spr.height - 1 do
+    for x = 0, spr.width - 1 do
+        if {coord} % {spacing} == 0 then line:drawPixel(x, y, value) end
+    end
+end
+local layer
+app.transaction("Scanlines", function()
+    app.layer = spr.layers[#spr.layers]
+    layer = spr:newLayer()
+    layer.name = "Scanlines"
+    layer.blendMode = BlendMode.MULTIPLY
+    for f = from_f, to_f do
+        spr:newCel(layer, f, line, Point(0, 0))
+    end
+end)
+{save}
+print(json.encode({{status = "applied", {fields}, layer = layer.name, frames = to_f - from_f + 1}}))"#,
+        from = p.from_frame.unwrap_or(1),
+        to = p.to_frame.map(|t| t.to_string()).unwrap_or_else(|| "#spr.frames".to_string()),
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        gray = gray,
+        coord = coord,
+        spacing = spacing,
+        fields = fields,
+        save = save_code
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn extract_silhouette(server: &AsepriteServer, p: ExtractSilhouetteParams) -> Result<String, String> {
+    let color = p.color.as_deref().unwrap_or("#000000");
+    let (r, g, b, a) = parse_color(color).map_err(|e| format!("Invalid color '{}': {}", color, e))?;
+    let threshold = p.alpha_threshold.unwrap_or(1);
+    if !(1..=255).contains(&threshold) {
+        return Err(format!("alpha_threshold must be between 1 and 255 (got {})", threshold));
+    }
+    let frame_num = p.frame.unwrap_or(1);
+
+    let (to_file, write_code) = match (&p.output_path, &p.new_layer_name) {
+        (Some(output), None) => (
+            true,
+            format!(
+                r#"local out_spr = Sprite(spr.width, spr.height, ColorMode.RGB)
+out_spr:newCel(out_spr.layers[1], 1, sil, Point(0, 0))
+out_spr:saveAs({out})
+out_spr:close()
+local written = {{path = {out}}}"#,
+                out = lua_path(&server.resolve_output_path(output)?)
+            ),
+        ),
+        (None, Some(name)) => (
+            false,
+            format!(
+                r#"app.transaction("Extract Silhouette", function()
+    app.layer = spr.layers[#spr.layers]
+    local layer = spr:newLayer()
+    layer.name = {name}
+    spr:newCel(layer, {frame}, sil, Point(0, 0))
+end)
+{save}
+local written = {{layer = {name}}}"#,
+                name = lua_string(name),
+                frame = frame_num,
+                save = LUA_SAVE_IN_PLACE
+            ),
+        ),
+        _ => return Err("Provide exactly one of output_path or new_layer_name".to_string()),
     };
 
     let script = format!(
         r#"local spr = app.sprite
-app.frame = spr.frames[{frame}]
-{layer_select}
-app.command.Outline {{
-    ui = false,
-    color = Color({r}, {g}, {b})
-}}
-spr:saveAs(spr.filename)
-print(json.encode({{status = "outlined"}}))"#,
-        frame = frame_num,
-        layer_select = layer_select,
+{check_frame}
+{image_source}
+{pixel_alpha}
+{color_to_pixel}
+local to_file = {to_file}
+local mode = to_file and ColorMode.RGB or spr.colorMode
+local value = to_file and app.pixelColor.rgba({r}, {g}, {b}, {a}) or color_to_pixel(spr, Color({r}, {g}, {b}, {a}))
+local sil = Image(spr.width, spr.height, mode)
+local min_x, min_y, max_x, max_y = spr.width, spr.height, -1, -1
+local count = 0
+for it in img:pixels() do
+    if pixel_alpha(spr, it()) >= {threshold} then
+        local x, y = it.x + offX, it.y + offY
+        if x >= 0 and y >= 0 and x < spr.width and y < spr.height then
+            sil:drawPixel(x, y, value)
+            count = count + 1
+            min_x, min_y = math.min(min_x, x), math.min(min_y, y)
+            max_x, max_y = math.max(max_x, x), math.max(max_y, y)
+        end
+    end
+end
+{write_code}
+local bounds = nil
+if count > 0 then
+    bounds = {{x = min_x, y = min_y, width = max_x - min_x + 1, height = max_y - min_y + 1}}
+end
+written.status = "extracted"
+written.pixels = count
+written.bounds = bounds
+print(json.encode(written))"#,
+        check_frame = lua_check_frame(frame_num),
+        image_source = lua_image_source(p.layer.as_deref(), frame_num),
+        pixel_alpha = LUA_PIXEL_ALPHA,
+        color_to_pixel = LUA_COLOR_TO_PIXEL,
+        to_file = to_file,
         r = r,
         g = g,
-        b = b
+        b = b,
+        a = a,
+        threshold = threshold,
+        write_code = write_code
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
+
+/// Unit step toward the light source for a light direction name.
+fn light_vector(direction: &str) -> Option<(i32, i32)> {
+    Some(match direction {
+        "top_left" => (-1, -1),
+        "top" => (0, -1),
+        "top_right" => (1, -1),
+        "left" => (-1, 0),
+        "right" => (1, 0),
+        "bottom_left" => (-1, 1),
+        "bottom" => (0, 1),
+        "bottom_right" => (1, 1),
+        _ => return None,
+    })
+}
+
+/// One pixel recolored by `shade_edges`.
+struct ShadeChange {
+    x: usize,
+    y: usize,
+    rgba: (u8, u8, u8, u8),
+    lighter: bool,
+}
+
+/// Find the edges of each same-color region facing toward (highlight) and away from (shadow) the
+/// light, and shift those pixels `steps` along the ramp. `pixels` are Aseprite RGBA pixel values
+/// (0xAABBGGRR) in row-major order. Without an explicit ramp, one is derived per base color.
+fn shade_edges(
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    light: (i32, i32),
+    ramp: Option<&[(u8, u8, u8)]>,
+    steps: i32,
+) -> Vec<ShadeChange> {
+    let channels = |v: u32| (v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8);
+    let at = |x: i32, y: i32| -> Option<u32> {
+        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+            return None;
+        }
+        let v = pixels[y as usize * width + x as usize];
+        (v >> 24 != 0).then_some(v)
+    };
+    let mut derived: HashMap<u32, Vec<(u8, u8, u8)>> = HashMap::new();
+
+    let mut changes = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let v = pixels[y * width + x];
+            let (r, g, b, a) = channels(v);
+            if a == 0 {
+                continue;
+            }
+            let (xi, yi) = (x as i32, y as i32);
+            // A pixel is on an edge when its neighbor in that direction is transparent or another color
+            let toward = at(xi + light.0, yi + light.1) != Some(v);
+            let away = at(xi - light.0, yi - light.1) != Some(v);
+            let shift = match (toward, away) {
+                (true, false) => steps,
+                (false, true) => -steps,
+                _ => continue,
+            };
+            let (ramp, index) = match ramp {
+                Some(ramp) => match ramp.iter().position(|&c| c == (r, g, b)) {
+                    Some(i) => (ramp, i as i32),
+                    None => continue,
+                },
+                None => (
+                    derived.entry(v & 0x00ff_ffff).or_insert_with(|| derive_ramp(r, g, b)).as_slice(),
+                    2,
+                ),
+            };
+            let target = (index + shift).clamp(0, ramp.len() as i32 - 1);
+            if target == index {
+                continue;
+            }
+            let (nr, ng, nb) = ramp[target as usize];
+            changes.push(ShadeChange {
+                x,
+                y,
+                rgba: (nr, ng, nb, a),
+                lighter: shift > 0,
+            });
+        }
+    }
+    changes
+}
+
+/// Build a 5-step ramp around a base color: two darker steps shifted toward cool hues, the base,
+/// and two lighter steps shifted toward warm hues (the usual pixel-art hue-shifting habit).
+fn derive_ramp(r: u8, g: u8, b: u8) -> Vec<(u8, u8, u8)> {
+    let (h, s, l) = rgb_to_hsl(r, g, b);
+    (-2..=2)
+        .map(|step| {
+            if step == 0 {
+                return (r, g, b);
+            }
+            let step = step as f64;
+            // Warm hues (yellow ~ 1/6) for highlights, cool hues (blue ~ 2/3) for shadows
+            let toward = if step > 0.0 { 1.0 / 6.0 } else { 2.0 / 3.0 };
+            let mut dh = toward - h;
+            if dh > 0.5 {
+                dh -= 1.0;
+            } else if dh < -0.5 {
+                dh += 1.0;
+            }
+            let hue = h + dh * 0.06 * step.abs();
+            let lightness = (l + 0.12 * step).clamp(0.0, 1.0);
+            hsl_to_rgb(hue, s, lightness)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lua_helpers::LUA_SAVE_IN_PLACE;
+    use crate::test_support::{dry_server, planned_script, real_server, save_linked_frames, scratch_dir};
+
+    #[tokio::test]
+    async fn linked_frames_get_one_merged_shadow() {
+        let Some(server) = real_server() else { return };
+        let path = scratch_dir("linked_shadow").join("linked.aseprite").to_string_lossy().to_string();
+        save_linked_frames(&server, &path, "#ff0000").await;
+        let script = "local c = app.sprite.layers[1]:cel(2)\nprint(json.encode({ w = c.bounds.width, h = c.bounds.height }))";
+        let bounds = async || -> serde_json::Value {
+            serde_json::from_str(&server.execute_script_on_file(&path, script).await.unwrap()).unwrap()
+        };
+        let before = bounds().await;
+
+        let params: DropShadowParams = serde_json::from_value(serde_json::json!({
+            "file_path": path, "offset_x": 1, "offset_y": 1, "color": "#000000",
+        }))
+        .unwrap();
+        let result: serde_json::Value = serde_json::from_str(&drop_shadow(&server, params).await.unwrap()).unwrap();
+        assert_eq!(result["frames"], 2);
+        // A one-pixel offset grows the cel by one pixel; shadowing it twice would grow it by two
+        let after = bounds().await;
+        assert_eq!(after["w"].as_u64(), before["w"].as_u64().map(|w| w + 1));
+        assert_eq!(after["h"].as_u64(), before["h"].as_u64().map(|h| h + 1));
+    }
+
+    #[tokio::test]
+    async fn linked_frames_never_chain_color_mappings() {
+        let Some(server) = real_server() else { return };
+        let path = scratch_dir("linked_replace").join("linked.aseprite").to_string_lossy().to_string();
+        save_linked_frames(&server, &path, "#ff0000").await;
+
+        let params: ReplaceColorParams = serde_json::from_value(serde_json::json!({
+            "file_path": path,
+            "mappings": [{ "from": "#ff0000", "to": "#00ff00" }, { "from": "#00ff00", "to": "#0000ff" }],
+        }))
+        .unwrap();
+        let result: serde_json::Value = serde_json::from_str(&replace_color(&server, params).await.unwrap()).unwrap();
+        assert_eq!(result["pixels_replaced"], 1);
+        let script = "local px = app.sprite.cels[1].image:getPixel(2, 2)\nprint(json.encode({ g = app.pixelColor.rgbaG(px) }))";
+        let pixel: serde_json::Value =
+            serde_json::from_str(&server.execute_script_on_file(&path, script).await.unwrap()).unwrap();
+        assert_eq!(pixel["g"], 255);
+    }
+
+    #[tokio::test]
+    async fn drop_shadow_and_scanlines_save_to_output_path() {
+        let server = dry_server();
+        let shadow = |output: Option<&str>| DropShadowParams {
+            file_path: "/art/in.png".to_string(),
+            offset_x: 1,
+            offset_y: 1,
+            color: "#00000080".to_string(),
+            layer: None,
+            from_frame: None,
+            to_frame: None,
+            separate_layer: None,
+            output_path: output.map(str::to_string),
+        };
+        let script = planned_script(drop_shadow(&server, shadow(None))).await;
+        assert!(script.contains(LUA_SAVE_IN_PLACE));
+        let script = planned_script(drop_shadow(&server, shadow(Some("/art/out.png")))).await;
+        assert!(script.contains("spr:saveCopyAs(\"/art/out.png\")"));
+        assert!(!script.contains(LUA_SAVE_IN_PLACE));
+
+        let lines: ScanlinesParams = serde_json::from_value(serde_json::json!({
+            "file_path": "/art/in.png",
+            "separate_layer": true,
+            "output_path": "/art/out.png",
+        }))
+        .unwrap();
+        let script = planned_script(scanlines(&server, lines)).await;
+        assert!(script.contains("spr:saveCopyAs(\"/art/out.png\")"));
+        assert!(!script.contains(LUA_SAVE_IN_PLACE));
+    }
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+        u32::from_le_bytes([r, g, b, a])
+    }
+
+    type Recolor = (usize, usize, (u8, u8, u8, u8));
+
+    fn sorted(changes: &[ShadeChange], lighter: bool) -> Vec<Recolor> {
+        let mut out: Vec<_> = changes
+            .iter()
+            .filter(|c| c.lighter == lighter)
+            .map(|c| (c.x, c.y, c.rgba))
+            .collect();
+        out.sort();
+        out
+    }
+
+    const DARK: (u8, u8, u8) = (20, 20, 20);
+    const BASE: (u8, u8, u8) = (100, 100, 100);
+    const LIGHT: (u8, u8, u8) = (200, 200, 200);
+
+    #[test]
+    fn shade_edges_lights_facing_edges_and_darkens_the_rest() {
+        let c = rgba(100, 100, 100, 200);
+        let pixels = vec![c; 9];
+        let ramp = [DARK, BASE, LIGHT];
+        let changes = shade_edges(&pixels, 3, 3, light_vector("top_left").unwrap(), Some(&ramp), 1);
+
+        let lit = (200, 200, 200, 200);
+        let shaded = (20, 20, 20, 200);
+        assert_eq!(sorted(&changes, true), vec![(0, 0, lit), (0, 1, lit), (1, 0, lit)]);
+        assert_eq!(sorted(&changes, false), vec![(1, 2, shaded), (2, 1, shaded), (2, 2, shaded)]);
+    }
+
+    #[test]
+    fn shade_edges_clamps_to_the_ramp_ends() {
+        let pixels = vec![rgba(100, 100, 100, 255); 4];
+        let ramp = [DARK, BASE, LIGHT];
+        let one = shade_edges(&pixels, 2, 2, light_vector("top").unwrap(), Some(&ramp), 1);
+        let two = shade_edges(&pixels, 2, 2, light_vector("top").unwrap(), Some(&ramp), 2);
+        assert_eq!(sorted(&one, true), sorted(&two, true));
+        assert_eq!(sorted(&one, false), sorted(&two, false));
+        assert_eq!(sorted(&one, true), vec![(0, 0, (200, 200, 200, 255)), (1, 0, (200, 200, 200, 255))]);
+    }
+
+    #[test]
+    fn shade_edges_skips_transparent_and_off_ramp_pixels() {
+        let pixels = vec![rgba(1, 2, 3, 255), 0, 0, rgba(1, 2, 3, 255)];
+        let ramp = [DARK, BASE, LIGHT];
+        assert!(shade_edges(&pixels, 2, 2, light_vector("left").unwrap(), Some(&ramp), 1).is_empty());
+    }
+
+    #[test]
+    fn shade_edges_derives_a_ramp_around_each_color() {
+        let pixels = vec![rgba(100, 100, 100, 255); 2];
+        let changes = shade_edges(&pixels, 2, 1, light_vector("left").unwrap(), None, 1);
+        let ramp = derive_ramp(100, 100, 100);
+        let (hr, hg, hb) = ramp[3];
+        let (sr, sg, sb) = ramp[1];
+        assert_eq!(sorted(&changes, true), vec![(0, 0, (hr, hg, hb, 255))]);
+        assert_eq!(sorted(&changes, false), vec![(1, 0, (sr, sg, sb, 255))]);
+    }
+}
@@ -1,7 +1,22 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use base64::Engine;
+use rmcp::model::{CallToolResult, Content};
 use rmcp::schemars;
 use serde::Deserialize;
+use tracing::warn;
 
+use crate::aseprite::{lua_path, lua_string};
+use crate::encode::{
+    RgbaFrame, decode_png_rgba, encode_apng, encode_png_rgba, encode_webp_animation, shelf_pack, upscale_nearest,
+};
+use crate::lua_helpers::{DataFile, LUA_FIND_LAYER, lua_check_frame, lua_matte_layer, lua_require_tag, lua_solo_layer};
 use crate::server::AsepriteServer;
+use crate::utils::{
+    MAX_INLINE_IMAGE_BYTES, check_range, create_output_dir, decode_hex, describe_output_file, expand_inputs, parse_color,
+    read_image_base64, sanitize_file_name, write_output,
+};
 
 // ============================================================================
 // Parameter Structs
@@ -15,10 +30,33 @@ pub struct ExportSpriteParams {
     pub output_path: String,
     /// Scale factor (e.g. 2 for 2x size)
     pub scale: Option<u32>,
-    /// Specific layer name to export (if omitted, exports all visible layers)
+    /// Specific layer name to export (if omitted, exports all visible layers). A layer with exactly
+    /// this name wins; otherwise "Group/Layer" selects a layer by its group path. A missing layer is an error
     pub layer: Option<String>,
-    /// Specific animation tag to export (if omitted, exports all frames)
+    /// Specific animation tag to export (if omitted, exports all frames); a missing tag is an error
     pub tag: Option<String>,
+    /// Also return the exported image inline as base64 (raster formats up to 2 MB, default: false, changeable with set_config)
+    pub return_base64: Option<bool>,
+    /// Export each layer to its own file (default: false). If neither output_path nor filename_format
+    /// contains "{layer}", "_{layer}" is inserted before the extension. Layer names containing "/"
+    /// are written into subdirectories of that name.
+    pub split_layers: Option<bool>,
+    /// Export each animation tag to its own file (default: false). If neither output_path nor
+    /// filename_format contains "{tag}", "_{tag}" is inserted before the extension. Tag names with
+    /// characters that are illegal in file names are sanitized and the mapping is reported.
+    pub split_tags: Option<bool>,
+    /// Filename format for multi-file exports, e.g. "{title}_{layer}.png" or "out/{layer}-{frame}.png".
+    /// Placeholders: {title}, {layer}, {tag}, {frame}, {extension}, {path}
+    pub filename_format: Option<String>,
+    /// First frame to export, 1-based inclusive (requires to_frame)
+    pub from_frame: Option<u32>,
+    /// Last frame to export, 1-based inclusive (requires from_frame)
+    pub to_frame: Option<u32>,
+    /// Matte color (e.g. "#ffffff" or "white") composited under the frames before saving, e.g. for jpg/bmp outputs
+    /// that have no alpha channel. Not supported with split_layers
+    pub background_color: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -35,54 +73,504 @@ pub struct ExportSpritesheetParams {
     pub columns: Option<u32>,
     /// Whether to trim empty space from each frame
     pub trim: Option<bool>,
+    /// Padding in pixels around the whole sheet (--border-padding)
+    pub border_padding: Option<u32>,
+    /// Padding in pixels between frames (--shape-padding)
+    pub shape_padding: Option<u32>,
+    /// Padding in pixels inside each frame (--inner-padding)
+    pub inner_padding: Option<u32>,
+    /// Duplicate each frame's edge pixels outward by one pixel to avoid texture bleeding (--extrude)
+    pub extrude: Option<bool>,
+    /// JSON data layout: "json-hash" (frames keyed by name, default) or "json-array"
+    pub data_format: Option<String>,
+    /// Include animation tags in the JSON data (meta.frameTags)
+    pub list_tags: Option<bool>,
+    /// Include layers in the JSON data (meta.layers)
+    pub list_layers: Option<bool>,
+    /// Include slices in the JSON data (meta.slices)
+    pub list_slices: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportUnityMetaParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Directory to write "<title>.png" and "<title>.json" into
+    pub output_dir: String,
+    /// Unity pixels per unit (default: 100)
+    pub pixels_per_unit: Option<f64>,
+    /// Default pivot when no slice provides one: "center" (default), "top-left", "top", "top-right",
+    /// "left", "right", "bottom-left", "bottom", "bottom-right", or normalized "x,y" with a bottom-left origin
+    pub pivot: Option<String>,
+    /// Name of the slice whose pivot to use (default: the first slice that has a pivot)
+    pub pivot_slice: Option<String>,
+    /// Whether to trim empty space from each frame (default: false)
+    pub trim: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportAtlasParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Output image path for the atlas page (e.g. "player.png")
+    pub output_image: String,
+    /// Output atlas path (e.g. "player.atlas" or "player.json")
+    pub output_atlas: String,
+    /// Atlas format: "libgdx" (default, .atlas text) or "texturepacker" (TexturePacker generic JSON hash)
+    pub atlas_format: Option<String>,
+    /// Sheet type: "horizontal", "vertical", "rows", "columns", "packed" (default: "packed")
+    pub sheet_type: Option<String>,
+    /// Number of columns (for "rows" type)
+    pub columns: Option<u32>,
+    /// Whether to trim empty space from each frame (offsets are written so trimmed frames don't jitter)
+    pub trim: Option<bool>,
+    /// Padding in pixels around the whole sheet (--border-padding)
+    pub border_padding: Option<u32>,
+    /// Padding in pixels between frames (--shape-padding)
+    pub shape_padding: Option<u32>,
+    /// Padding in pixels inside each frame (--inner-padding)
+    pub inner_padding: Option<u32>,
+    /// Duplicate each frame's edge pixels outward by one pixel to avoid texture bleeding (--extrude)
+    pub extrude: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportGifParams {
+    /// Path to the input sprite file (never modified)
+    pub file_path: String,
+    /// Output GIF path
+    pub output_path: String,
+    /// Number of times the animation plays; 0 = loop forever (default: 0). Finite counts are written
+    /// through a tag's repeat count (Aseprite 1.3+)
+    pub loop_count: Option<u32>,
+    /// Keep transparent pixels transparent (default: true). If false, frames are flattened onto an opaque background
+    pub transparent: Option<bool>,
+    /// Dithering used when reducing RGB/grayscale to 256 colors: "none" (default), "ordered", or "error-diffusion"
+    pub dithering: Option<String>,
+    /// Maximum palette size when reducing colors, 2-256 (default: 256)
+    pub max_colors: Option<u32>,
+    /// Matte color (e.g. "#ffffff" or "white") composited under the frames; makes the GIF fully opaque
+    pub background_color: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportFrameParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Output path. A single frame becomes one image; a range becomes an animation for gif/webp
+    /// outputs, otherwise numbered files ("{frame}" in the path, or "_001" inserted before the extension)
+    pub output_path: String,
+    /// Frame number to export (1-based)
+    pub frame: Option<u32>,
+    /// First frame of a range, 1-based inclusive (use with to_frame instead of frame)
+    pub from_frame: Option<u32>,
+    /// Last frame of a range, 1-based inclusive
+    pub to_frame: Option<u32>,
+    /// Integer scale factor (nearest neighbor, default: 1)
+    pub scale: Option<u32>,
+    /// Export only this layer (default: all visible layers)
+    pub layer: Option<String>,
+    /// Matte color (e.g. "#ffffff" or "white") composited under the frame, e.g. for jpg/bmp outputs without alpha
+    pub background_color: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportRawParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Output path: raw binary (e.g. "player.bin") or C source when as_c_array is set (e.g. "player.h")
+    pub output_path: String,
+    /// Pixel format: "rgba8888" (4 bytes/pixel), "rgb565" (2 bytes/pixel, little-endian), or "indexed8"
+    /// (1 byte/pixel, indexed sprites only; the palette is written alongside as RGBA bytes)
+    pub format: String,
+    /// Frame number to export (1-based, default: 1)
+    pub frame: Option<u32>,
+    /// Emit a C source file with `const uint8_t name[]` and width/height defines instead of raw binary
+    pub as_c_array: Option<bool>,
+    /// C identifier for the array (default: derived from the output file name)
+    pub name: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportIconParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Output .ico path
+    pub output_path: String,
+    /// Icon sizes in pixels, 1-256 (default: [16, 32, 48, 64, 128, 256]). Non-square sprites are centered
+    pub sizes: Option<Vec<u32>>,
+    /// Frame number to render (1-based, default: 1)
+    pub frame: Option<u32>,
+    /// Also write a macOS .icns file here (only sizes 16, 32, 64, 128, 256 are representable)
+    pub icns_output_path: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportAnimationParams {
+    /// Path to the input sprite file
+    pub file_path: String,
+    /// Output path: ".webp" for animated WebP, ".png" or ".apng" for APNG
+    pub output_path: String,
+    /// Export only this animation tag (its direction is honored)
+    pub tag: Option<String>,
+    /// Export only this layer (default: all visible layers)
+    pub layer: Option<String>,
+    /// Integer scale factor (nearest neighbor, default: 1)
+    pub scale: Option<u32>,
+    /// Number of times the animation plays; 0 = loop forever (default: 0)
+    pub loop_count: Option<u32>,
+    /// Matte color (e.g. "#ffffff" or "white") composited under the frames
+    pub background_color: Option<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct PackAtlasParams {
+    /// Input sprite/image paths (.aseprite, .png, ...); entries may be glob patterns like "sprites/*.aseprite"
+    pub inputs: Vec<String>,
+    /// Output image path for the packed atlas (e.g. "atlas.png")
+    pub output_image: String,
+    /// Output JSON path mapping names to rects (e.g. "atlas.json")
+    pub output_data: String,
+    /// Maximum atlas width in pixels (default: 4096)
+    pub max_width: Option<u32>,
+    /// Maximum atlas height in pixels (default: 4096)
+    pub max_height: Option<u32>,
+    /// Padding in pixels around and between packed frames (default: 0)
+    pub padding: Option<u32>,
+    /// Pack every frame of each input as "{name}_{frame}" instead of just the first frame as "{name}" (default: false)
+    pub all_frames: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportPreviewParams {
+    /// Path to the input sprite file (never modified)
+    pub file_path: String,
+    /// Only preview this animation tag's frames
+    pub tag: Option<String>,
+    /// Scale factor (default: 2)
+    pub scale: Option<f64>,
+    /// Cap on the preview's larger side in pixels; the scale is reduced to fit (default: 256)
+    pub max_dimension: Option<u32>,
+    /// Output GIF path (default: a file in the server's temp directory)
+    pub output_path: Option<String>,
+    /// Also return the GIF inline as base64 (default: false, changeable with set_config)
+    pub return_base64: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CompareToImageParams {
+    /// Path to the sprite file (never modified)
+    pub file_path: String,
+    /// Frame to compare, 1-based (default: 1)
+    pub frame: Option<u32>,
+    /// Reference PNG with the expected pixels (e.g. a golden image from an art test)
+    pub image_path: String,
+    /// Write a diff PNG here: matching pixels dimmed to gray, differing pixels in magenta
+    pub diff_output_path: Option<String>,
+    /// Also return the diff image inline as base64, with or without diff_output_path (default: false, changeable with set_config)
+    pub return_base64: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportContactSheetParams {
+    /// Path to the input sprite file (never modified)
+    pub file_path: String,
+    /// Output PNG path for the contact sheet
+    pub output_path: String,
+    /// Frames per row (default: a roughly square grid)
+    pub columns: Option<u32>,
+    /// Integer scale factor for each frame (nearest neighbor, default: 1)
+    pub scale: Option<u32>,
+    /// Burn each frame's number into a strip under it (default: true)
+    pub include_frame_numbers: Option<bool>,
+    /// Only include this animation tag's frames
+    pub tag: Option<String>,
+    /// Also return the sheet inline as base64 (default: false, changeable with set_config)
+    pub return_base64: Option<bool>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
 }
 
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
-pub async fn export_sprite(server: &AsepriteServer, p: ExportSpriteParams) -> Result<String, String> {
-    let mut args = vec![p.file_path.clone()];
-    if let Some(scale) = p.scale {
-        args.push("--scale".to_string());
-        args.push(scale.to_string());
+pub async fn export_sprite(server: &AsepriteServer, mut p: ExportSpriteParams) -> Result<CallToolResult, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let range = frame_range(&p)?;
+    let matte = parse_background_color(p.background_color.as_deref())?;
+    if matte.is_some() && p.split_layers.unwrap_or(false) {
+        return Err("background_color cannot be combined with split_layers".to_string());
     }
-    if let Some(ref layer) = p.layer {
-        args.push("--layer".to_string());
-        args.push(layer.clone());
+
+    let tag_range = if p.layer.is_some() || p.tag.is_some() || range.is_some() {
+        check_export_filters(server, &p.file_path, p.layer.as_deref(), p.tag.as_deref(), range).await?
+    } else {
+        None
+    };
+    // A tag the CLI can't be given by name is exported by its frame range instead
+    if let (Some(tag), Some(range)) = (p.tag.clone(), tag_range)
+        && cli_unsafe_name(&tag)
+    {
+        if p.from_frame.is_some() {
+            return Err(format!(
+                "Tag '{}' can't be passed to the Aseprite CLI by name, so it can't be combined with from_frame/to_frame",
+                tag
+            ));
+        }
+        (p.from_frame, p.to_frame) = (Some(range.0), Some(range.1));
+        let safe = sanitize_file_name(&tag);
+        p.output_path = p.output_path.replace("{tag}", &safe);
+        p.filename_format = p.filename_format.map(|f| f.replace("{tag}", &safe));
+        p.tag = None;
     }
-    if let Some(ref tag) = p.tag {
-        args.push("--tag".to_string());
-        args.push(tag.clone());
+
+    // Matting, and layers the CLI can't select by name, work on a temp copy (keeping the file
+    // name for {title}) with the matte layer added and any layer filter already applied, so the
+    // CLI export itself is unchanged.
+    let source = p.file_path.clone();
+    let mut copy_dir = None;
+    if matte.is_some() || p.layer.as_deref().is_some_and(cli_unsafe_name) {
+        let (dir, copy) = prepare_export_copy(server, &source, p.layer.as_deref(), matte).await?;
+        p.file_path = copy;
+        p.layer = None;
+        copy_dir = Some(dir);
     }
-    args.push("--save-as".to_string());
-    let resolved_output = server.resolve_output_path(&p.output_path);
-    args.push(resolved_output.clone());
 
+    let result = export_sprite_file(server, &p, &source).await;
+    if let Some(dir) = copy_dir {
+        tokio::fs::remove_dir_all(&dir).await.ok();
+    }
+    let note = matte_note(&p.output_path, p.background_color.as_deref());
+    result.map(|mut r| {
+        if let Some(note) = note {
+            r.content.push(Content::text(note));
+        }
+        r
+    })
+}
+
+/// Run the CLI export for `p`; `source` is the user-facing input path used in messages.
+async fn export_sprite_file(server: &AsepriteServer, p: &ExportSpriteParams, source: &str) -> Result<CallToolResult, String> {
+    if p.split_tags.unwrap_or(false) {
+        return export_split_tags(server, p, source).await;
+    }
+
+    let mut resolved_output = server.resolve_output_path(&p.output_path)?;
+    let filename_format = p.filename_format.as_ref().map(|f| server.resolve_output_path(f)).transpose()?;
+    if p.split_layers.unwrap_or(false) && filename_format.is_none() && !resolved_output.contains("{layer}") {
+        resolved_output = insert_before_extension(&resolved_output, "_{layer}");
+    }
+    let args = build_export_args(p, p.tag.as_deref(), None, false, filename_format.as_deref(), &resolved_output);
+
+    let started = SystemTime::now();
     match server.run_cli(&args).await {
         Ok(output) => {
-            if output.success {
-                Ok(format!(
-                    "Exported {} -> {}",
-                    p.file_path, resolved_output
-                ))
-            } else {
-                Err(output.result_text())
+            if !output.success {
+                return Err(output.result_text());
+            }
+            let output_pattern = filename_format.as_deref().unwrap_or(&resolved_output);
+            if has_placeholders(output_pattern) {
+                let files = list_written_files(output_pattern, started);
+                if files.is_empty() {
+                    return Err(format!(
+                        "Export of {} reported success but no files matching '{}' were written.\nAseprite output:\n{}\n{}",
+                        source,
+                        output_pattern,
+                        output.stdout.trim(),
+                        output.stderr.trim()
+                    ));
+                }
+                let outputs = verify_outputs(&files, &output.stderr).await?;
+                return Ok(CallToolResult::success(vec![Content::text(format!(
+                    "Exported {} -> {} file(s):\n{}",
+                    source,
+                    files.len(),
+                    json_lines(&outputs)
+                ))]));
+            }
+
+            let outputs = verify_outputs(std::slice::from_ref(&resolved_output), &output.stderr).await?;
+            let message = format!("Exported {} -> {}\nOutput: {}", source, resolved_output, outputs[0]);
+            if !p.return_base64.unwrap_or(server.config().return_base64) {
+                return Ok(CallToolResult::success(vec![Content::text(message)]));
+            }
+            match read_image_base64(&resolved_output).await {
+                Ok((data, mime)) => Ok(CallToolResult::success(vec![
+                    Content::text(message),
+                    Content::image(data, mime),
+                ])),
+                Err(reason) => {
+                    warn!("Not returning export inline: {}", reason);
+                    Ok(CallToolResult::success(vec![Content::text(format!(
+                        "{}\nWarning: image not returned inline: {}",
+                        message, reason
+                    ))]))
+                }
             }
         }
         Err(e) => Err(format!("Export failed: {}", e)),
     }
 }
 
-pub async fn export_spritesheet(server: &AsepriteServer, p: ExportSpritesheetParams) -> Result<String, String> {
-    let resolved_image = server.resolve_output_path(&p.output_image);
-    let resolved_data = p.output_data.as_ref().map(|d| server.resolve_output_path(d));
-    let mut args = vec![p.file_path.clone(), "--sheet".to_string(), resolved_image.clone()];
+/// A tag name and its 1-based inclusive frame range.
+type NamedRange<'a> = (&'a str, (u32, u32));
+
+/// Export each tag to its own file, reporting the files written and frame count per tag.
+/// Uses a single --split-tags run when every tag name is a valid file name, otherwise one
+/// --tag run per tag with the sanitized name substituted into the output format.
+async fn export_split_tags(
+    server: &AsepriteServer,
+    p: &ExportSpriteParams,
+    source: &str,
+) -> Result<CallToolResult, String> {
+    if p.tag.is_some() {
+        return Err("split_tags cannot be combined with tag".to_string());
+    }
+    let tags = sprite_tags(server, &p.file_path).await?;
+    if tags.is_empty() {
+        return Err(format!("{} has no tags to split", source));
+    }
+
+    let mut format = match p.filename_format {
+        Some(ref f) => server.resolve_output_path(f)?,
+        None => server.resolve_output_path(&p.output_path)?,
+    };
+    if !format.contains("{tag}") {
+        format = insert_before_extension(&format, "_{tag}");
+    }
+    if p.split_layers.unwrap_or(false) && !format.contains("{layer}") {
+        format = insert_before_extension(&format, "_{layer}");
+    }
+
+    let safe_names: Vec<String> = tags.iter().map(|(name, _, _)| sanitize_file_name(name)).collect();
+    let needs_sanitizing = tags.iter().zip(&safe_names).any(|((name, _, _), safe)| name != safe);
+
+    let started = SystemTime::now();
+    let runs: Vec<(Option<NamedRange>, String)> = if needs_sanitizing {
+        tags.iter()
+            .zip(&safe_names)
+            .map(|((name, frames, from), safe)| {
+                let range = (*from, from + (*frames as u32).max(1) - 1);
+                (Some((name.as_str(), range)), format.replace("{tag}", safe))
+            })
+            .collect()
+    } else {
+        vec![(None, format.clone())]
+    };
+    let mut stderr = String::new();
+    for (tag, output) in &runs {
+        let split = tag.is_none();
+        let filename_format = has_placeholders(output).then_some(output.as_str());
+        let args = build_export_args(p, tag.map(|t| t.0), tag.map(|t| t.1), split, filename_format, output);
+        let output = server
+            .run_cli(&args)
+            .await
+            .map_err(|e| format!("Export failed: {}", e))?;
+        if !output.success {
+            return Err(match tag {
+                Some((name, _)) => format!("Export of tag '{}' failed: {}", name, output.result_text()),
+                None => output.result_text(),
+            });
+        }
+        stderr.push_str(&output.stderr);
+    }
+
+    let mut lines = Vec::new();
+    let mut total = 0;
+    for ((name, frames, _), safe) in tags.iter().zip(&safe_names) {
+        let files = list_written_files(&format.replace("{tag}", safe), started);
+        total += files.len();
+        let renamed = if name != safe {
+            format!(" [sanitized as '{}']", safe)
+        } else {
+            String::new()
+        };
+        let written = if files.is_empty() {
+            "no files written".to_string()
+        } else {
+            let outputs = verify_outputs(&files, &stderr).await?;
+            serde_json::Value::from(outputs).to_string()
+        };
+        lines.push(format!("- '{}' ({} frames){} -> {}", name, frames, renamed, written));
+    }
+    if total == 0 {
+        return Err(format!(
+            "Export of {} reported success but no files matching '{}' were written.\nAseprite output:\n{}",
+            source,
+            format,
+            stderr.trim()
+        ));
+    }
+    Ok(CallToolResult::success(vec![Content::text(format!(
+        "Exported {} tag(s) of {} -> {} file(s):\n{}",
+        tags.len(),
+        source,
+        total,
+        lines.join("\n")
+    ))]))
+}
+
+pub async fn export_spritesheet(server: &AsepriteServer, mut p: ExportSpritesheetParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let resolved_image = server.resolve_output_path(&p.output_image)?;
+    let resolved_data = p.output_data.as_ref().map(|d| server.resolve_output_path(d)).transpose()?;
+
+    let list_flags: Vec<&str> = [
+        ("--list-tags", p.list_tags),
+        ("--list-layers", p.list_layers),
+        ("--list-slices", p.list_slices),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| enabled.unwrap_or(false))
+    .map(|(flag, _)| flag)
+    .collect();
+    if resolved_data.is_none() && (!list_flags.is_empty() || p.data_format.is_some()) {
+        return Err("output_data is required when using data_format or list_tags/list_layers/list_slices".to_string());
+    }
+    if let Some(ref format) = p.data_format
+        && !matches!(format.as_str(), "json-hash" | "json-array")
+    {
+        return Err(format!(
+            "Invalid data_format '{}'. Must be 'json-hash' or 'json-array'",
+            format
+        ));
+    }
+
+    // --list-* flags apply to the next sprite, so they go before the file
+    let mut args: Vec<String> = list_flags.iter().map(|f| f.to_string()).collect();
+    args.push(p.file_path.clone());
+    args.push("--sheet".to_string());
+    args.push(resolved_image.clone());
 
     if let Some(ref data_path) = resolved_data {
         args.push("--data".to_string());
         args.push(data_path.clone());
     }
+    if let Some(ref format) = p.data_format {
+        args.push("--format".to_string());
+        args.push(format.clone());
+    }
     if let Some(ref sheet_type) = p.sheet_type {
         args.push("--sheet-type".to_string());
         args.push(sheet_type.clone());
@@ -95,15 +583,49 @@ pub async fn export_spritesheet(server: &AsepriteServer, p: ExportSpritesheetPar
         args.push("--trim".to_string());
     }
 
+    let mut packing = Vec::new();
+    for (flag, value) in [
+        ("border-padding", p.border_padding),
+        ("shape-padding", p.shape_padding),
+        ("inner-padding", p.inner_padding),
+    ] {
+        if let Some(value) = value {
+            args.push(format!("--{}", flag));
+            args.push(value.to_string());
+            packing.push(format!("{}={}", flag, value));
+        }
+    }
+    if p.extrude.unwrap_or(false) {
+        args.push("--extrude".to_string());
+        packing.push("extrude".to_string());
+    }
+
     match server.run_cli(&args).await {
         Ok(output) => {
             if output.success {
+                let mut expected = vec![resolved_image.clone()];
+                expected.extend(resolved_data.clone());
+                let outputs = verify_outputs(&expected, &output.stderr).await?;
+                let summary = match resolved_data {
+                    Some(ref data_path) => match tokio::fs::read_to_string(data_path).await {
+                        Ok(data) => format!("\n{}", summarize_sheet_data(&data)),
+                        Err(e) => format!("\nWarning: could not read data file: {}", e),
+                    },
+                    None => String::new(),
+                };
                 Ok(format!(
-                    "Spritesheet exported: {}{}",
+                    "Spritesheet exported: {}{}{}{}\nOutputs:\n{}",
                     resolved_image,
                     resolved_data
                         .map(|d| format!(", data: {}", d))
-                        .unwrap_or_default()
+                        .unwrap_or_default(),
+                    if packing.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" (packing: {})", packing.join(", "))
+                    },
+                    summary,
+                    json_lines(&outputs)
                 ))
             } else {
                 Err(output.result_text())
@@ -112,3 +634,2272 @@ pub async fn export_spritesheet(server: &AsepriteServer, p: ExportSpritesheetPar
         Err(e) => Err(format!("Export failed: {}", e)),
     }
 }
+
+pub async fn export_unity_meta(server: &AsepriteServer, mut p: ExportUnityMetaParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let ppu = p.pixels_per_unit.unwrap_or(100.0);
+    if ppu <= 0.0 {
+        return Err("pixels_per_unit must be greater than 0".to_string());
+    }
+    let default_pivot = parse_pivot(p.pivot.as_deref().unwrap_or("center"))?;
+
+    let output_dir = PathBuf::from(server.resolve_output_path(&p.output_dir)?);
+    create_output_dir(&output_dir)
+        .await
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+    let title = sprite_title(&p.file_path);
+    let image_path = output_dir.join(format!("{}.png", title));
+    let meta_path = output_dir.join(format!("{}.json", title));
+
+    let mut sheet_args = vec!["--sheet-type".to_string(), "packed".to_string()];
+    if p.trim.unwrap_or(false) {
+        sheet_args.push("--trim".to_string());
+    }
+    let sheet = export_sheet_data(
+        server,
+        &p.file_path,
+        &image_path.to_string_lossy(),
+        &["--list-slices"],
+        &sheet_args,
+    )
+    .await?;
+    let frames = parse_sheet_frames(&sheet)?;
+    let sheet_height = sheet["meta"]["size"]["h"].as_i64().unwrap_or(0);
+    let slice = find_pivot_slice(&sheet, p.pivot_slice.as_deref())?;
+
+    let sprites: Vec<serde_json::Value> = frames
+        .iter()
+        .enumerate()
+        .map(|(i, f)| {
+            let pivot_px = slice
+                .as_ref()
+                .and_then(|s| slice_pivot_at(s, i))
+                .unwrap_or((
+                    default_pivot.0 * f.source_width as f64,
+                    (1.0 - default_pivot.1) * f.source_height as f64,
+                ));
+            let rect = unity_rect(&f.frame, sheet_height);
+            let pivot = unity_pivot(pivot_px, &f.source);
+            serde_json::json!({
+                "name": format!("{}_{}", title, i),
+                "rect": { "x": rect.x, "y": rect.y, "width": rect.w, "height": rect.h },
+                "pivot": { "x": pivot.0, "y": pivot.1 },
+                "duration": f.duration,
+            })
+        })
+        .collect();
+
+    let meta = serde_json::json!({
+        "texture": image_path.file_name().map(|n| n.to_string_lossy().to_string()),
+        "width": sheet["meta"]["size"]["w"],
+        "height": sheet_height,
+        "pixelsPerUnit": ppu,
+        "pivotSource": slice.as_ref().map(|s| s["name"].clone()).unwrap_or_else(|| "default".into()),
+        "sprites": sprites,
+    });
+    let text = serde_json::to_string_pretty(&meta).map_err(|e| e.to_string())?;
+    write_output(&meta_path, text)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", meta_path.display(), e))?;
+
+    Ok(format!(
+        "Exported {} Unity sprite(s): {}, {}",
+        frames.len(),
+        image_path.display(),
+        meta_path.display()
+    ))
+}
+
+pub async fn export_atlas(server: &AsepriteServer, mut p: ExportAtlasParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let atlas_format = p.atlas_format.as_deref().unwrap_or("libgdx");
+    if !matches!(atlas_format, "libgdx" | "texturepacker") {
+        return Err(format!(
+            "Invalid atlas_format '{}'. Must be 'libgdx' or 'texturepacker'",
+            atlas_format
+        ));
+    }
+    let image_path = server.resolve_output_path(&p.output_image)?;
+    let atlas_path = server.resolve_output_path(&p.output_atlas)?;
+
+    let mut sheet_args = vec![
+        "--sheet-type".to_string(),
+        p.sheet_type.clone().unwrap_or_else(|| "packed".to_string()),
+    ];
+    if let Some(columns) = p.columns {
+        sheet_args.push("--sheet-columns".to_string());
+        sheet_args.push(columns.to_string());
+    }
+    if p.trim.unwrap_or(false) {
+        sheet_args.push("--trim".to_string());
+    }
+    for (flag, value) in [
+        ("--border-padding", p.border_padding),
+        ("--shape-padding", p.shape_padding),
+        ("--inner-padding", p.inner_padding),
+    ] {
+        if let Some(value) = value {
+            sheet_args.push(flag.to_string());
+            sheet_args.push(value.to_string());
+        }
+    }
+    if p.extrude.unwrap_or(false) {
+        sheet_args.push("--extrude".to_string());
+    }
+
+    let sheet = export_sheet_data(server, &p.file_path, &image_path, &["--list-tags"], &sheet_args).await?;
+    let frames = parse_sheet_frames(&sheet)?;
+    let regions = atlas_regions(&sprite_title(&p.file_path), &sheet, frames.len());
+    let page_name = Path::new(&image_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| image_path.clone());
+    let width = sheet["meta"]["size"]["w"].as_i64().unwrap_or(0);
+    let height = sheet["meta"]["size"]["h"].as_i64().unwrap_or(0);
+
+    let text = if atlas_format == "libgdx" {
+        libgdx_atlas(&page_name, width, height, &regions, &frames)
+    } else {
+        let mut entries = serde_json::Map::new();
+        for (name, index, frame_index) in &regions {
+            let f = &frames[*frame_index];
+            let trimmed = f.source.x != 0
+                || f.source.y != 0
+                || f.source.w != f.source_width
+                || f.source.h != f.source_height;
+            entries.insert(
+                format!("{}_{}", name, index),
+                serde_json::json!({
+                    "frame": { "x": f.frame.x, "y": f.frame.y, "w": f.frame.w, "h": f.frame.h },
+                    "rotated": false,
+                    "trimmed": trimmed,
+                    "spriteSourceSize": { "x": f.source.x, "y": f.source.y, "w": f.source.w, "h": f.source.h },
+                    "sourceSize": { "w": f.source_width, "h": f.source_height },
+                    "duration": f.duration,
+                }),
+            );
+        }
+        let json = serde_json::json!({
+            "frames": entries,
+            "meta": {
+                "app": "aseprite_mcp",
+                "image": page_name,
+                "format": "RGBA8888",
+                "size": { "w": width, "h": height },
+                "scale": "1",
+            },
+        });
+        serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?
+    };
+    write_output(&atlas_path, text)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", atlas_path, e))?;
+
+    Ok(format!(
+        "Atlas exported ({}): {}, image: {} ({} region(s) from {} frame(s))",
+        atlas_format,
+        atlas_path,
+        image_path,
+        regions.len(),
+        frames.len()
+    ))
+}
+
+pub async fn export_gif(server: &AsepriteServer, mut p: ExportGifParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let dithering = p.dithering.as_deref().unwrap_or("none");
+    if !matches!(dithering, "none" | "ordered" | "error-diffusion") {
+        return Err(format!(
+            "Invalid dithering '{}'. Must be 'none', 'ordered', or 'error-diffusion'",
+            dithering
+        ));
+    }
+    let matte = parse_background_color(p.background_color.as_deref())?;
+    let loop_count = p.loop_count.unwrap_or(0);
+    let transparent = p.transparent.unwrap_or(true) && matte.is_none();
+    let max_colors = check_range("max_colors", p.max_colors.unwrap_or(256), 2..=256, false)?;
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    let out = lua_path(&resolved_output);
+
+    // Work on an in-memory copy so the source file is never touched
+    let script = format!(
+        r#"local src = app.sprite
+local spr = Sprite(src)
+app.activeSprite = spr
+local loopCount = {loop_count}
+if loopCount > 0 then
+  for i = #spr.tags, 1, -1 do spr:deleteTag(spr.tags[i]) end
+  local t = spr:newTag(1, #spr.frames)
+  t.repeats = loopCount
+end
+{matte}
+if not {transparent} then
+  app.command.FlattenLayers {{ visibleOnly = true }}
+  app.command.BackgroundFromLayer()
+end
+if spr.colorMode ~= ColorMode.INDEXED then
+  app.command.ColorQuantization {{ ui = false, withAlpha = {transparent}, maxColors = {max_colors} }}
+  app.command.ChangePixelFormat {{ ui = false, format = "indexed", dithering = "{dithering}" }}
+end
+spr:saveCopyAs({out})
+local result = {{
+  status = "exported",
+  filename = {out},
+  frames = #spr.frames,
+  paletteSize = #spr.palettes[1],
+  loopCount = loopCount,
+  transparent = {transparent},
+  dithering = "{dithering}",
+  matteApplied = {matte_applied}
+}}
+spr:close()
+print(json.encode(result))"#,
+        loop_count = loop_count,
+        matte = matte.map(|(r, g, b)| lua_matte_layer(r, g, b)).unwrap_or_default(),
+        matte_applied = matte.is_some(),
+        transparent = transparent,
+        max_colors = max_colors,
+        dithering = dithering,
+        out = out
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let mut json: serde_json::Value =
+        serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let outputs = verify_outputs(std::slice::from_ref(&resolved_output), "").await?;
+    json["fileSize"] = outputs[0]["bytes"].clone();
+    json["output"] = outputs[0].clone();
+    Ok(json.to_string())
+}
+
+pub async fn export_frame(server: &AsepriteServer, mut p: ExportFrameParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let (from, to) = match (p.frame, p.from_frame, p.to_frame) {
+        (Some(frame), None, None) => (frame, frame),
+        (None, Some(from), Some(to)) => (from, to),
+        _ => return Err("Provide either frame, or both from_frame and to_frame".to_string()),
+    };
+    if from == 0 || from > to {
+        return Err(format!("Invalid frame range {}-{} (frames are 1-based)", from, to));
+    }
+    let scale = p.scale.unwrap_or(1);
+    if scale == 0 {
+        return Err("scale must be at least 1".to_string());
+    }
+    let matte = parse_background_color(p.background_color.as_deref())?;
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    let animated = Path::new(&resolved_output)
+        .extension()
+        .map(|e| matches!(e.to_string_lossy().to_lowercase().as_str(), "gif" | "webp"))
+        .unwrap_or(false);
+
+    if animated && from != to {
+        if matte.is_some() {
+            return Err("background_color is only supported for still image exports; use export_gif for animations".to_string());
+        }
+        let mut args = Vec::new();
+        let mut input = p.file_path.clone();
+        let mut copy_dir = None;
+        if let Some(ref layer) = p.layer {
+            check_export_filters(server, &p.file_path, Some(layer), None, None).await?;
+            if cli_unsafe_name(layer) {
+                let (dir, copy) = prepare_export_copy(server, &p.file_path, Some(layer), None).await?;
+                input = copy;
+                copy_dir = Some(dir);
+            } else {
+                args.push("--layer".to_string());
+                args.push(layer.clone());
+            }
+        }
+        args.push("--frame-range".to_string());
+        args.push(format!("{},{}", from - 1, to - 1));
+        args.push(input);
+        if scale != 1 {
+            args.push("--scale".to_string());
+            args.push(scale.to_string());
+        }
+        args.push("--save-as".to_string());
+        args.push(resolved_output.clone());
+        let result = match server.run_cli(&args).await {
+            Ok(output) if output.success => verify_outputs(std::slice::from_ref(&resolved_output), &output.stderr)
+                .await
+                .map(|outputs| {
+                    format!(
+                        "Exported frames {}-{} of {} -> {}\nOutput: {}",
+                        from, to, p.file_path, resolved_output, outputs[0]
+                    )
+                }),
+            Ok(output) => Err(output.result_text()),
+            Err(e) => Err(format!("Export failed: {}", e)),
+        };
+        if let Some(dir) = copy_dir {
+            tokio::fs::remove_dir_all(&dir).await.ok();
+        }
+        return result;
+    }
+
+    // Still images are rendered in Lua so layer filtering and scaling are reliable headless
+    let name_pattern = if from == to {
+        resolved_output.replace('%', "%%").replace("{frame}", &format!("{:03}", from))
+    } else if resolved_output.contains("{frame}") {
+        resolved_output.replace('%', "%%").replace("{frame}", "%03d")
+    } else {
+        insert_before_extension(&resolved_output.replace('%', "%%"), "_%03d")
+    };
+    let solo = p.layer.as_deref().map(lua_solo_layer).unwrap_or_default();
+    let script = format!(
+        r#"local spr = app.sprite
+local fromFrame, toFrame = {from}, {to}
+if toFrame > #spr.frames then
+    print(json.encode({{error = "Frame " .. toFrame .. " out of range (sprite has " .. #spr.frames .. " frames)"}}))
+    return
+end
+{solo}
+{matte}
+local files = {{}}
+for f = fromFrame, toFrame do
+    local img = Image(spr.spec)
+    img:drawSprite(spr, f)
+    if {scale} ~= 1 then img:resize(img.width * {scale}, img.height * {scale}) end
+    local name = string.format({pattern}, f)
+    img:saveAs{{ filename = name, palette = spr.palettes[1] }}
+    files[#files + 1] = name
+end
+print(json.encode({{status = "exported", files = files, width = spr.width * {scale}, height = spr.height * {scale}, matteApplied = {matte_applied}}}))"#,
+        from = from,
+        to = to,
+        solo = solo,
+        matte = matte.map(|(r, g, b)| lua_matte_layer(r, g, b)).unwrap_or_default(),
+        matte_applied = matte.is_some(),
+        scale = scale,
+        pattern = lua_path(&name_pattern)
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn export_raw(server: &AsepriteServer, mut p: ExportRawParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let format = p.format.to_lowercase();
+    if !matches!(format.as_str(), "rgba8888" | "rgb565" | "indexed8") {
+        return Err(format!(
+            "Invalid format '{}'. Must be 'rgba8888', 'rgb565', or 'indexed8'",
+            p.format
+        ));
+    }
+    let frame = p.frame.unwrap_or(1);
+    let indexed = format == "indexed8";
+
+    // Render the frame into the data file: RGBA for rgba8888/rgb565, palette indices for indexed8
+    let pixel_file = DataFile::new(server);
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
+local indexed = {indexed}
+if indexed and spr.colorMode ~= ColorMode.INDEXED then
+    print(json.encode({{error = "indexed8 requires an indexed sprite (use change_color_mode first)"}}))
+    return
+end
+local img
+if indexed then
+    img = Image(spr.spec)
+else
+    img = Image(spr.width, spr.height, ColorMode.RGB)
+end
+img:drawSprite(spr, {frame})
+{open_data}
+data_file:write(img.bytes)
+data_file:close()
+local palette = {{}}
+if indexed then
+    local pal = spr.palettes[1]
+    for i = 0, #pal - 1 do
+        local c = pal:getColor(i)
+        palette[#palette + 1] = string.format("%02x%02x%02x%02x", c.red, c.green, c.blue, c.alpha)
+    end
+end
+print(json.encode({{width = img.width, height = img.height, palette = palette, transparentIndex = spr.transparentColor}}))"#,
+        check_frame = lua_check_frame(frame),
+        frame = frame,
+        indexed = indexed,
+        open_data = pixel_file.lua_open()
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let width = json["width"].as_u64().unwrap_or(0);
+    let height = json["height"].as_u64().unwrap_or(0);
+    let pixels = pixel_file.read().await?;
+    let bytes_per_pixel = if indexed { 1 } else { 4 };
+    if pixels.len() as u64 != width * height * bytes_per_pixel {
+        return Err("The script returned incomplete pixel data".to_string());
+    }
+    let data = match format.as_str() {
+        "rgb565" => rgba_to_rgb565(&pixels),
+        _ => pixels,
+    };
+    let palette = if indexed {
+        let mut bytes = Vec::new();
+        for color in json["palette"].as_array().into_iter().flatten() {
+            bytes.extend(decode_hex(color.as_str().unwrap_or_default())?);
+        }
+        Some(bytes)
+    } else {
+        None
+    };
+
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    let mut written = vec![resolved_output.clone()];
+    if p.as_c_array.unwrap_or(false) {
+        let name = c_identifier(p.name.as_deref().unwrap_or_else(|| {
+            Path::new(&resolved_output)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("sprite")
+        }));
+        let source = c_array_source(&name, &format, width, height, &data, palette.as_deref());
+        write_output(&resolved_output, source)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", resolved_output, e))?;
+    } else {
+        write_output(&resolved_output, &data)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", resolved_output, e))?;
+        if let Some(ref palette) = palette {
+            let palette_path = insert_before_extension(&resolved_output, "_palette");
+            write_output(&palette_path, palette)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", palette_path, e))?;
+            written.push(palette_path);
+        }
+    }
+
+    Ok(serde_json::json!({
+        "status": "exported",
+        "format": format,
+        "width": width,
+        "height": height,
+        "bytes": data.len(),
+        "paletteColors": palette.as_ref().map(|p| p.len() / 4),
+        "transparentIndex": if indexed { json["transparentIndex"].clone() } else { serde_json::Value::Null },
+        "files": written,
+    })
+    .to_string())
+}
+
+pub async fn export_icon(server: &AsepriteServer, mut p: ExportIconParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let mut sizes = p.sizes.clone().unwrap_or_else(|| vec![16, 32, 48, 64, 128, 256]);
+    sizes.sort_unstable();
+    sizes.dedup();
+    if sizes.is_empty() || sizes.iter().any(|&s| s == 0 || s > 256) {
+        return Err("sizes must be a non-empty list of values between 1 and 256".to_string());
+    }
+    let frame = p.frame.unwrap_or(1);
+
+    // Aseprite renders one PNG per size (nearest neighbor); the containers are assembled here
+    let dir = server.temp_file_path("icon");
+    create_output_dir(&dir)
+        .await
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let size_list = sizes.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", ");
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
+local src = Image(spr.width, spr.height, ColorMode.RGB)
+src:drawSprite(spr, {frame})
+for _, size in ipairs({{ {sizes} }}) do
+    local scale = math.min(size / spr.width, size / spr.height)
+    local w = math.max(1, math.floor(spr.width * scale))
+    local h = math.max(1, math.floor(spr.height * scale))
+    local scaled = src:clone()
+    scaled:resize(w, h)
+    local icon = Image(size, size, ColorMode.RGB)
+    icon:drawImage(scaled, Point((size - w) // 2, (size - h) // 2))
+    icon:saveAs({dir} .. "/" .. size .. ".png")
+end
+print(json.encode({{status = "ok"}}))"#,
+        check_frame = lua_check_frame(frame),
+        frame = frame,
+        sizes = size_list,
+        dir = lua_path(&dir.to_string_lossy())
+    );
+    let result = async {
+        server.execute_script_on_file(&p.file_path, &script).await?;
+        let mut images = Vec::new();
+        for &size in &sizes {
+            let path = dir.join(format!("{}.png", size));
+            let png = tokio::fs::read(&path)
+                .await
+                .map_err(|e| format!("Missing rendered {}px icon: {}", size, e))?;
+            images.push((size, png));
+        }
+        Ok::<_, String>(images)
+    }
+    .await;
+    tokio::fs::remove_dir_all(&dir).await.ok();
+    let images = result?;
+
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    let ico = build_ico(&images);
+    write_output(&resolved_output, &ico)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", resolved_output, e))?;
+    let mut report = serde_json::json!({
+        "status": "exported",
+        "ico": { "path": resolved_output, "sizes": sizes, "bytes": ico.len() },
+    });
+
+    if let Some(ref icns_path) = p.icns_output_path {
+        let icns_path = server.resolve_output_path(icns_path)?;
+        let (icns, included) = build_icns(&images);
+        if included.is_empty() {
+            return Err("None of the requested sizes can be stored in .icns (use 16, 32, 64, 128, or 256)".to_string());
+        }
+        write_output(&icns_path, &icns)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", icns_path, e))?;
+        report["icns"] = serde_json::json!({ "path": icns_path, "sizes": included, "bytes": icns.len() });
+    }
+    Ok(report.to_string())
+}
+
+pub async fn export_animation(server: &AsepriteServer, mut p: ExportAnimationParams) -> Result<String, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    let ext = Path::new(&resolved_output)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !matches!(ext.as_str(), "webp" | "png" | "apng") {
+        return Err("output_path must end in .webp, .png, or .apng".to_string());
+    }
+    let scale = p.scale.unwrap_or(1);
+    if scale == 0 {
+        return Err("scale must be at least 1".to_string());
+    }
+    let loop_count = p.loop_count.unwrap_or(0);
+    let matte = parse_background_color(p.background_color.as_deref())?;
+
+    let tag_code = match p.tag {
+        Some(ref tag) => format!(
+            r#"{require_tag}
+fromFrame = tag.fromFrame.frameNumber
+toFrame = tag.toFrame.frameNumber
+if tag.aniDir == AniDir.REVERSE then direction = "reverse" end
+if tag.aniDir == AniDir.PING_PONG then direction = "pingpong" end"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        None => String::new(),
+    };
+
+    // Render each frame in playback order, writing the RGBA bytes to the data file and returning
+    // the durations; encoding happens in Rust
+    let data = DataFile::new(server);
+    let script = format!(
+        r#"local spr = app.sprite
+{solo}
+{matte}
+local fromFrame, toFrame, direction = 1, #spr.frames, "forward"
+{tag_code}
+local order = {{}}
+if direction == "reverse" then
+    for i = toFrame, fromFrame, -1 do order[#order + 1] = i end
+else
+    for i = fromFrame, toFrame do order[#order + 1] = i end
+    if direction == "pingpong" then
+        for i = toFrame - 1, fromFrame + 1, -1 do order[#order + 1] = i end
+    end
+end
+local durations = {{}}
+local w, h = spr.width * {scale}, spr.height * {scale}
+{open_data}
+for _, f in ipairs(order) do
+    local img = Image(spr.width, spr.height, ColorMode.RGB)
+    img:drawSprite(spr, f)
+    if {scale} ~= 1 then img:resize(w, h) end
+    data_file:write(img.bytes)
+    durations[#durations + 1] = math.floor(spr.frames[f].duration * 1000 + 0.5)
+end
+data_file:close()
+print(json.encode({{width = w, height = h, durations = durations}}))"#,
+        open_data = data.lua_open(),
+        solo = p.layer.as_deref().map(lua_solo_layer).unwrap_or_default(),
+        matte = matte.map(|(r, g, b)| lua_matte_layer(r, g, b)).unwrap_or_default(),
+        tag_code = tag_code,
+        scale = scale
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let width = json["width"].as_u64().unwrap_or(0) as u32;
+    let height = json["height"].as_u64().unwrap_or(0) as u32;
+    let pixels = data.read().await?;
+    let frame_len = (width * height * 4) as usize;
+    let durations: Vec<u32> = json["durations"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|d| d.as_u64().unwrap_or(100) as u32)
+        .collect();
+    if durations.is_empty() || frame_len == 0 {
+        return Err("No frames to export".to_string());
+    }
+    if pixels.len() != frame_len * durations.len() {
+        return Err("The script returned incomplete frame data".to_string());
+    }
+    let frames: Vec<RgbaFrame> = pixels
+        .chunks_exact(frame_len)
+        .zip(&durations)
+        .map(|(chunk, &duration_ms)| RgbaFrame { pixels: chunk.to_vec(), duration_ms })
+        .collect();
+    let frame_count = frames.len();
+
+    let webp = ext == "webp";
+    let bytes = tokio::task::spawn_blocking(move || {
+        if webp {
+            encode_webp_animation(&frames, width, height, loop_count)
+        } else {
+            encode_apng(&frames, width, height, loop_count)
+        }
+    })
+    .await
+    .map_err(|e| format!("Animation encoding failed: {}", e))??;
+    let encoder = if webp { "webp (lossless)" } else { "apng" };
+    write_output(&resolved_output, &bytes)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", resolved_output, e))?;
+
+    Ok(serde_json::json!({
+        "status": "exported",
+        "filename": resolved_output,
+        "encoder": encoder,
+        "frames": frame_count,
+        "width": width,
+        "height": height,
+        "loopCount": loop_count,
+        "fileSize": bytes.len(),
+    })
+    .to_string())
+}
+
+pub async fn pack_atlas(server: &AsepriteServer, p: PackAtlasParams) -> Result<String, String> {
+    let max_width = p.max_width.unwrap_or(4096);
+    let max_height = p.max_height.unwrap_or(4096);
+    let padding = p.padding.unwrap_or(0);
+    let all_frames = p.all_frames.unwrap_or(false);
+
+    let inputs = expand_inputs(server, &p.inputs)?;
+    let mut names: Vec<(String, String)> = Vec::new();
+    for input in &inputs {
+        let name = sprite_title(input);
+        if let Some((_, other)) = names.iter().find(|(n, _)| *n == name) {
+            return Err(format!("Inputs {} and {} would both be named '{}'", other, input, name));
+        }
+        names.push((name, input.clone()));
+    }
+    let source_of = |name: &str| -> String {
+        let base = if all_frames {
+            name.rsplit_once('_').map(|(base, _)| base).unwrap_or(name)
+        } else {
+            name
+        };
+        names
+            .iter()
+            .find(|(n, _)| n == base)
+            .map(|(_, path)| path.clone())
+            .unwrap_or_default()
+    };
+
+    let image_path = server.resolve_output_path(&p.output_image)?;
+    let data_path = server.resolve_output_path(&p.output_data)?;
+
+    // Prefer Aseprite's own packer; fall back to shelf packing when it fails or exceeds the limits
+    let (packer, width, height, rects) =
+        match pack_with_aseprite(server, &inputs, &image_path, padding, all_frames).await {
+            Ok((width, height, rects)) if width <= max_width && height <= max_height => {
+                ("aseprite", width, height, rects)
+            }
+            attempt => {
+                if let Err(e) = attempt {
+                    warn!("Aseprite packing failed, falling back to shelf packing: {}", e);
+                }
+                let (width, height, rects) =
+                    pack_with_shelves(server, &inputs, &image_path, max_width, max_height, padding, all_frames).await?;
+                ("shelf", width, height, rects)
+            }
+        };
+
+    let mut frames = serde_json::Map::new();
+    for (name, x, y, w, h) in &rects {
+        frames.insert(
+            name.clone(),
+            serde_json::json!({ "x": x, "y": y, "w": w, "h": h, "source": source_of(name) }),
+        );
+    }
+    let data = serde_json::json!({
+        "image": image_path,
+        "size": { "w": width, "h": height },
+        "packer": packer,
+        "frames": frames,
+    });
+    let text = serde_json::to_string_pretty(&data).map_err(|e| e.to_string())?;
+    write_output(&data_path, text)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", data_path, e))?;
+    let outputs = verify_outputs(&[image_path.clone(), data_path.clone()], "").await?;
+
+    Ok(serde_json::json!({
+        "status": "packed",
+        "packer": packer,
+        "width": width,
+        "height": height,
+        "inputs": inputs.len(),
+        "placements": frames,
+        "outputs": outputs,
+    })
+    .to_string())
+}
+
+/// Preview GIFs above this size get a warning, since they're meant for chat contexts.
+const PREVIEW_WARN_BYTES: u64 = 500 * 1024;
+
+pub async fn export_preview(server: &AsepriteServer, mut p: ExportPreviewParams) -> Result<CallToolResult, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let scale = p.scale.unwrap_or(2.0);
+    if scale <= 0.0 {
+        return Err("scale must be greater than 0".to_string());
+    }
+    let max_dimension = p.max_dimension.unwrap_or(256).max(1);
+    let resolved_output = match p.output_path {
+        Some(ref path) => server.resolve_output_path(path)?,
+        None => server.temp_file_path("gif").to_string_lossy().to_string(),
+    };
+    let tag_code = match p.tag {
+        Some(ref tag) => format!(
+            r#"{require_tag}
+local fromFrame, toFrame = tag.fromFrame.frameNumber, tag.toFrame.frameNumber
+for i = #spr.frames, 1, -1 do
+    if i < fromFrame or i > toFrame then spr:deleteFrame(i) end
+end"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        None => String::new(),
+    };
+
+    // Work on an in-memory copy; without tags the GIF loops forever
+    let script = format!(
+        r#"local spr = Sprite(app.sprite)
+app.activeSprite = spr
+{tag_code}
+for i = #spr.tags, 1, -1 do spr:deleteTag(spr.tags[i]) end
+local scale = math.min({scale}, {max_dim} / math.max(spr.width, spr.height))
+local w = math.max(1, math.floor(spr.width * scale + 0.5))
+local h = math.max(1, math.floor(spr.height * scale + 0.5))
+if w ~= spr.width or h ~= spr.height then spr:resize(w, h) end
+spr:saveCopyAs({out})
+local result = {{status = "exported", filename = {out}, frames = #spr.frames, width = w, height = h, scale = scale}}
+spr:close()
+print(json.encode(result))"#,
+        tag_code = tag_code,
+        scale = scale,
+        max_dim = max_dimension,
+        out = lua_path(&resolved_output)
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let outputs = verify_outputs(std::slice::from_ref(&resolved_output), "").await?;
+    let bytes = outputs[0]["bytes"].as_u64().unwrap_or(0);
+
+    let mut message = format!("Preview: {}", json);
+    if bytes > PREVIEW_WARN_BYTES {
+        message.push_str(&format!(
+            "\nWarning: preview is {} KB; lower scale or max_dimension for chat use",
+            bytes / 1024
+        ));
+    }
+    let mut content = vec![Content::text(message)];
+    if p.return_base64.unwrap_or(server.config().return_base64) {
+        match read_image_base64(&resolved_output).await {
+            Ok((data, mime)) => content.push(Content::image(data, mime)),
+            Err(reason) => content.push(Content::text(format!("Warning: image not returned inline: {}", reason))),
+        }
+    }
+    Ok(CallToolResult::success(content))
+}
+
+pub async fn compare_to_image(server: &AsepriteServer, mut p: CompareToImageParams) -> Result<CallToolResult, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let reference_path = server.resolve_input_path(&p.image_path)?;
+    if !reference_path.to_ascii_lowercase().ends_with(".png") {
+        return Err(format!("image_path must be a PNG: {}", reference_path));
+    }
+    let diff_output = p.diff_output_path.as_deref().map(|path| server.resolve_output_path(path)).transpose()?;
+    let reference_bytes = tokio::fs::read(&reference_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", reference_path, e))?;
+    let (ref_width, ref_height, expected) = decode_png_rgba(&reference_bytes)?;
+
+    let frame = p.frame.unwrap_or(1);
+    let render = DataFile::new(server);
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
+local img = Image(spr.width, spr.height, ColorMode.RGB)
+img:drawSprite(spr, {frame})
+{open_data}
+data_file:write(img.bytes)
+data_file:close()
+print(json.encode({{width = img.width, height = img.height}}))"#,
+        check_frame = lua_check_frame(frame),
+        frame = frame,
+        open_data = render.lua_open()
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let width = json["width"].as_u64().unwrap_or(0) as u32;
+    let height = json["height"].as_u64().unwrap_or(0) as u32;
+    if (width, height) != (ref_width, ref_height) {
+        let result = serde_json::json!({
+            "status": "size_mismatch",
+            "frame": frame,
+            "sprite_size": { "width": width, "height": height },
+            "reference_size": { "width": ref_width, "height": ref_height },
+        });
+        return Ok(CallToolResult::success(vec![Content::text(result.to_string())]));
+    }
+    let actual = render.read().await?;
+    if actual.len() != expected.len() {
+        return Err("The script returned incomplete pixel data".to_string());
+    }
+
+    // Fully transparent pixels match whatever color they carry
+    let mut differing = 0u64;
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    let mut diff = Vec::with_capacity(actual.len());
+    for (i, (a, e)) in actual.chunks_exact(4).zip(expected.chunks_exact(4)).enumerate() {
+        if a == e || (a[3] == 0 && e[3] == 0) {
+            let gray = ((a[0] as u32 * 299 + a[1] as u32 * 587 + a[2] as u32 * 114) / 1000) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, a[3] / 4]);
+            continue;
+        }
+        differing += 1;
+        diff.extend_from_slice(&[255, 0, 255, 255]);
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    }
+    let total = width as u64 * height as u64;
+    let match_percent = if total == 0 {
+        100.0
+    } else {
+        ((total - differing) as f64 * 10000.0 / total as f64).floor() / 100.0
+    };
+    let mut result = serde_json::json!({
+        "status": if differing == 0 { "match" } else { "different" },
+        "frame": frame,
+        "width": width,
+        "height": height,
+        "match_percent": match_percent,
+        "differing_pixels": differing,
+        "diff_bounds": bounds.map(|(x0, y0, x1, y1)| serde_json::json!({
+            "x": x0, "y": y0, "width": x1 - x0 + 1, "height": y1 - y0 + 1
+        })),
+    });
+
+    // The diff is rendered in memory, so it can be returned inline without being written anywhere
+    let return_base64 = p.return_base64.unwrap_or(server.config().return_base64);
+    let png = if diff_output.is_some() || return_base64 {
+        Some(encode_png_rgba(&diff, width, height)?)
+    } else {
+        None
+    };
+    if let (Some(diff_output), Some(png)) = (&diff_output, &png) {
+        write_output(diff_output, png)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", diff_output, e))?;
+        result["diff_output"] = serde_json::json!(diff_output);
+    }
+    let mut content = vec![Content::text(result.to_string())];
+    if let Some(png) = png.filter(|_| return_base64) {
+        if png.len() as u64 > MAX_INLINE_IMAGE_BYTES {
+            content.push(Content::text(format!(
+                "Warning: image not returned inline: the diff is {} bytes, over the {} byte inline limit",
+                png.len(),
+                MAX_INLINE_IMAGE_BYTES
+            )));
+        } else {
+            content.push(Content::image(base64::engine::general_purpose::STANDARD.encode(png), "image/png"));
+        }
+    }
+    Ok(CallToolResult::success(content))
+}
+
+pub async fn export_contact_sheet(server: &AsepriteServer, mut p: ExportContactSheetParams) -> Result<CallToolResult, String> {
+    p.file_path = server.resolve_input_path(&p.file_path)?;
+    let resolved_output = server.resolve_output_path(&p.output_path)?;
+    if !resolved_output.to_ascii_lowercase().ends_with(".png") {
+        return Err("output_path must end in .png".to_string());
+    }
+    let scale = p.scale.unwrap_or(1);
+    if scale == 0 {
+        return Err("scale must be at least 1".to_string());
+    }
+    if p.columns == Some(0) {
+        return Err("columns must be at least 1".to_string());
+    }
+    let tag_code = match p.tag {
+        Some(ref tag) => format!(
+            r#"{require_tag}
+fromFrame = tag.fromFrame.frameNumber
+toFrame = tag.toFrame.frameNumber"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        None => String::new(),
+    };
+
+    // Aseprite only renders the frames at 1x into the data file; scaling, the grid, labels, and
+    // tag markers are done here
+    let render = DataFile::new(server);
+    let script = format!(
+        r##"local spr = app.sprite
+local fromFrame, toFrame = 1, #spr.frames
+{tag_code}
+{open_data}
+local frames = {{}}
+for f = fromFrame, toFrame do
+    local img = Image(spr.width, spr.height, ColorMode.RGB)
+    img:drawSprite(spr, f)
+    data_file:write(img.bytes)
+    frames[#frames + 1] = f
+end
+data_file:close()
+local tags = {{}}
+for _, t in ipairs(spr.tags) do
+    local a, b = t.fromFrame.frameNumber, t.toFrame.frameNumber
+    if a <= toFrame and b >= fromFrame then
+        tags[#tags + 1] = {{ name = t.name, from = a, to = b, color = string.format("#%02x%02x%02x", t.color.red, t.color.green, t.color.blue) }}
+    end
+end
+print(json.encode({{width = spr.width, height = spr.height, frames = frames, tags = tags}}))"##,
+        tag_code = tag_code,
+        open_data = render.lua_open()
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let (sprite_w, sprite_h) = (json["width"].as_u64().unwrap_or(0) as u32, json["height"].as_u64().unwrap_or(0) as u32);
+    let frame_numbers: Vec<u32> = json["frames"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|f| f.as_u64().unwrap_or(0) as u32)
+        .collect();
+    if frame_numbers.is_empty() {
+        return Err("No frames to export".to_string());
+    }
+    let pixels = render.read().await?;
+    let frame_len = (sprite_w * sprite_h * 4) as usize;
+    if frame_len == 0 || pixels.len() != frame_len * frame_numbers.len() {
+        return Err("The script returned incomplete frame data".to_string());
+    }
+    let (frame_w, frame_h) = (sprite_w.saturating_mul(scale), sprite_h.saturating_mul(scale));
+    if frame_w > CONTACT_SHEET_MAX_SIDE || frame_h > CONTACT_SHEET_MAX_SIDE {
+        return Err(format!(
+            "Frames would be {}x{} at scale {} (max {} per side); lower scale",
+            frame_w, frame_h, scale, CONTACT_SHEET_MAX_SIDE
+        ));
+    }
+    let mut tags = Vec::new();
+    for tag in json["tags"].as_array().into_iter().flatten() {
+        let (r, g, b, _) = parse_color(tag["color"].as_str().unwrap_or("#808080")).unwrap_or((128, 128, 128, 255));
+        tags.push((tag["from"].as_u64().unwrap_or(0) as u32, tag["to"].as_u64().unwrap_or(0) as u32, [r, g, b, 255]));
+    }
+
+    let numbers = p.include_frame_numbers.unwrap_or(true);
+    let text_scale = scale.min(3);
+    let last_frame = frame_numbers.last().copied().unwrap_or(1);
+    let (label_w, label_h) = if numbers {
+        (digit_text_width(last_frame, text_scale) + 2 * text_scale, (DIGIT_HEIGHT + 2) * text_scale)
+    } else {
+        (0, 0)
+    };
+    let layout = contact_sheet_layout(frame_numbers.len() as u32, frame_w.max(label_w), frame_h + label_h, p.columns);
+    if layout.width > CONTACT_SHEET_MAX_SIDE || layout.height > CONTACT_SHEET_MAX_SIDE {
+        return Err(format!(
+            "Contact sheet would be {}x{} (max {} per side); lower scale or change columns",
+            layout.width, layout.height, CONTACT_SHEET_MAX_SIDE
+        ));
+    }
+    let frames: Vec<(u32, Vec<u8>)> = frame_numbers
+        .iter()
+        .zip(pixels.chunks_exact(frame_len))
+        .map(|(&frame, rgba)| (frame, upscale_nearest(rgba, sprite_w, sprite_h, scale)))
+        .collect();
+
+    let mut sheet = vec![0u8; (layout.width * layout.height * 4) as usize];
+    let mut cells = Vec::new();
+    for (i, (frame, pixels)) in frames.iter().enumerate() {
+        let (cx, cy) = layout.cell_origin(i as u32);
+        let fx = cx + (layout.cell_width - frame_w) / 2;
+        for row in 0..frame_h {
+            let src = (row * frame_w * 4) as usize;
+            let dst = (((cy + row) * layout.width + fx) * 4) as usize;
+            let len = (frame_w * 4) as usize;
+            if let Some(line) = pixels.get(src..src + len) {
+                sheet[dst..dst + len].copy_from_slice(line);
+            }
+        }
+        if numbers {
+            let label_y = cy + frame_h;
+            fill_rect(&mut sheet, layout.width, (cx, label_y, layout.cell_width, label_h), CONTACT_SHEET_LABEL_BG);
+            draw_digits(&mut sheet, layout.width, *frame, cx + text_scale, label_y + text_scale, text_scale);
+        }
+        // Tag boundaries: a bar in the gutter before a tag's first frame and after its last,
+        // and a line over every frame inside the tag
+        let half = CONTACT_SHEET_GUTTER / 2;
+        for &(from, to, color) in &tags {
+            if !(from..=to).contains(frame) {
+                continue;
+            }
+            fill_rect(&mut sheet, layout.width, (cx, cy - half, layout.cell_width, half), color);
+            if *frame == from {
+                fill_rect(&mut sheet, layout.width, (cx - half, cy - half, half, layout.cell_height + half), color);
+            }
+            if *frame == to {
+                fill_rect(&mut sheet, layout.width, (cx + layout.cell_width, cy - half, half, layout.cell_height + half), color);
+            }
+        }
+        cells.push(serde_json::json!({ "frame": frame, "x": cx, "y": cy }));
+    }
+
+    let png = encode_png_rgba(&sheet, layout.width, layout.height)?;
+    write_output(&resolved_output, &png)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", resolved_output, e))?;
+    let result = serde_json::json!({
+        "status": "exported",
+        "filename": resolved_output,
+        "frames": frames.len(),
+        "columns": layout.columns,
+        "rows": layout.rows,
+        "width": layout.width,
+        "height": layout.height,
+        "cell": { "width": layout.cell_width, "height": layout.cell_height },
+        "cells": cells,
+        "tags": json["tags"].as_array().cloned().unwrap_or_default(),
+        "fileSize": png.len(),
+    });
+    let mut content = vec![Content::text(result.to_string())];
+    if p.return_base64.unwrap_or(server.config().return_base64) {
+        match read_image_base64(&resolved_output).await {
+            Ok((data, mime)) => content.push(Content::image(data, mime)),
+            Err(reason) => content.push(Content::text(format!("Warning: image not returned inline: {}", reason))),
+        }
+    }
+    Ok(CallToolResult::success(content))
+}
+
+// ============================================================================
+// Helpers
+// ============================================================================
+
+/// Whether a layer or tag name can't be handed to the CLI as-is: "/" and "\\" turn `--layer`
+/// into a group path, quotes don't survive argument parsing on Windows, a leading "-" reads as
+/// a flag, and surrounding spaces get lost. Exports select such layers and tags in Lua instead.
+fn cli_unsafe_name(name: &str) -> bool {
+    name.contains(['/', '\\', '"', '\''])
+        || name.starts_with('-')
+        || name.trim() != name
+        || name.chars().any(char::is_control)
+}
+
+/// Check in one short run that the layer and tag an export filters by exist, and that a frame
+/// range ends inside the sprite, so a typo fails instead of Aseprite quietly exporting every layer
+/// or frame. The layer is found like every other
+/// tool finds one (`find_layer`: exact name, else group path). Returns the tag's 1-based frame range.
+async fn check_export_filters(
+    server: &AsepriteServer,
+    file_path: &str,
+    layer: Option<&str>,
+    tag: Option<&str>,
+    frames: Option<(u32, u32)>,
+) -> Result<Option<(u32, u32)>, String> {
+    let script = format!(
+        r#"local spr = app.sprite
+{frame_check}
+{find_layer}
+local layer_name, tag_name = {layer}, {tag}
+if layer_name and not find_layer(spr.layers, layer_name) then
+    local names = {{}}
+    local function walk(lyrs)
+        for _, l in ipairs(lyrs) do
+            names[#names + 1] = l.name
+            if l.isGroup then walk(l.layers) end
+        end
+    end
+    walk(spr.layers)
+    print(json.encode({{error = "Layer not found: " .. layer_name .. ". Available layers: " .. table.concat(names, ", ")}}))
+    return
+end
+local result = {{status = "ok"}}
+if tag_name then
+    local names = {{}}
+    for _, t in ipairs(spr.tags) do
+        if t.name == tag_name then
+            result.from, result.to = t.fromFrame.frameNumber, t.toFrame.frameNumber
+        end
+        names[#names + 1] = t.name
+    end
+    if not result.from then
+        print(json.encode({{error = "Tag not found: " .. tag_name .. ". Available tags: " .. (#names > 0 and table.concat(names, ", ") or "(none)")}}))
+        return
+    end
+end
+print(json.encode(result))"#,
+        frame_check = frames.map(|(_, to)| lua_check_frame(to)).unwrap_or_default(),
+        find_layer = LUA_FIND_LAYER,
+        layer = layer.map_or("nil".to_string(), lua_string),
+        tag = tag.map_or("nil".to_string(), lua_string)
+    );
+    let output = server.execute_script_on_file(file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    Ok(json["from"].as_u64().zip(json["to"].as_u64()).map(|(from, to)| (from as u32, to as u32)))
+}
+
+/// Save a temp copy of `source` (same file name, so {title} is unchanged) with only `layer`
+/// visible and/or a matte layer added, for CLI exports that can't express either themselves.
+/// Returns the temp directory, which the caller deletes afterwards, and the copy's path.
+async fn prepare_export_copy(
+    server: &AsepriteServer,
+    source: &str,
+    layer: Option<&str>,
+    matte: Option<(u8, u8, u8)>,
+) -> Result<(PathBuf, String), String> {
+    let dir = server.temp_file_path("export_copy");
+    let copy = dir.join(Path::new(source).file_name().unwrap_or_default());
+    create_output_dir(&dir)
+        .await
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let script = format!(
+        r#"local spr = app.sprite
+{solo}
+{matte}
+spr:saveCopyAs({copy})
+print(json.encode({{status = "ok"}}))"#,
+        solo = layer.map(lua_solo_layer).unwrap_or_default(),
+        matte = matte.map(|(r, g, b)| lua_matte_layer(r, g, b)).unwrap_or_default(),
+        copy = lua_path(&copy.to_string_lossy())
+    );
+    if let Err(e) = server.execute_script_on_file(source, &script).await {
+        tokio::fs::remove_dir_all(&dir).await.ok();
+        return Err(e);
+    }
+    Ok((dir, copy.to_string_lossy().to_string()))
+}
+
+/// Build CLI arguments for a --save-as export. Layer/tag filters and the split flags apply
+/// to the next sprite, so they go before the file. A tag whose name the CLI would misread is
+/// exported by its `tag_range` (1-based, inclusive) instead.
+fn build_export_args(
+    p: &ExportSpriteParams,
+    tag: Option<&str>,
+    tag_range: Option<(u32, u32)>,
+    split_tags: bool,
+    filename_format: Option<&str>,
+    save_as: &str,
+) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(ref layer) = p.layer {
+        args.push("--layer".to_string());
+        args.push(layer.clone());
+    }
+    let mut range = frame_range(p).ok().flatten();
+    if let Some(tag) = tag {
+        match tag_range {
+            Some(tag_range) if cli_unsafe_name(tag) => range = Some(tag_range),
+            _ => {
+                args.push("--tag".to_string());
+                args.push(tag.to_string());
+            }
+        }
+    }
+    if let Some((from, to)) = range {
+        args.push("--frame-range".to_string());
+        args.push(format!("{},{}", from - 1, to - 1));
+    }
+    if p.split_layers.unwrap_or(false) {
+        args.push("--split-layers".to_string());
+    }
+    if split_tags {
+        args.push("--split-tags".to_string());
+    }
+    args.push(p.file_path.clone());
+    if let Some(scale) = p.scale {
+        args.push("--scale".to_string());
+        args.push(scale.to_string());
+    }
+    if let Some(format) = filename_format {
+        args.push("--filename-format".to_string());
+        args.push(format.to_string());
+    }
+    args.push("--save-as".to_string());
+    args.push(save_as.to_string());
+    args
+}
+
+/// Summarize Aseprite spritesheet JSON data (either json-hash or json-array layout).
+fn summarize_sheet_data(data: &str) -> String {
+    let json: serde_json::Value = match serde_json::from_str(data) {
+        Ok(json) => json,
+        Err(e) => return format!("Warning: data file is not valid JSON: {}", e),
+    };
+    let frames = match &json["frames"] {
+        serde_json::Value::Array(frames) => frames.len(),
+        serde_json::Value::Object(frames) => frames.len(),
+        _ => 0,
+    };
+    let meta = &json["meta"];
+    let mut parts = vec![format!(
+        "Sheet: {}x{}, {} frame(s)",
+        meta["size"]["w"].as_u64().unwrap_or(0),
+        meta["size"]["h"].as_u64().unwrap_or(0),
+        frames
+    )];
+    let names = |key: &str| -> Option<Vec<String>> {
+        meta[key].as_array().map(|items| {
+            items
+                .iter()
+                .map(|item| item["name"].as_str().unwrap_or_default().to_string())
+                .collect()
+        })
+    };
+    for (key, label) in [("frameTags", "tags"), ("layers", "layers"), ("slices", "slices")] {
+        if let Some(items) = names(key) {
+            parts.push(format!("{}: [{}]", label, items.join(", ")));
+        }
+    }
+    parts.join(", ")
+}
+
+/// Stat the expected output files of an export. Aseprite exits 0 even when e.g. a typoed layer
+/// or tag name means nothing was written, so a missing file is an error carrying its stderr.
+async fn verify_outputs(paths: &[String], stderr: &str) -> Result<Vec<serde_json::Value>, String> {
+    let mut outputs = Vec::new();
+    for path in paths {
+        let info = describe_output_file(path).await;
+        if info["exists"] != true {
+            let detail = if stderr.trim().is_empty() {
+                String::new()
+            } else {
+                format!("\nAseprite output:\n{}", stderr.trim())
+            };
+            return Err(format!(
+                "Export reported success but {} was not written (check layer/tag names){}",
+                path, detail
+            ));
+        }
+        outputs.push(info);
+    }
+    Ok(outputs)
+}
+
+/// Render JSON values one per line.
+fn json_lines(values: &[serde_json::Value]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+/// Convert RGBA8888 bytes to little-endian RGB565 (alpha is dropped).
+fn rgba_to_rgb565(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| {
+            let value = ((px[0] as u16 >> 3) << 11) | ((px[1] as u16 >> 2) << 5) | (px[2] as u16 >> 3);
+            value.to_le_bytes()
+        })
+        .collect()
+}
+
+/// Turn an arbitrary name into a valid C identifier.
+fn c_identifier(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// Render pixel data (and an optional RGBA palette) as C source with width/height defines.
+fn c_array_source(name: &str, format: &str, width: u64, height: u64, data: &[u8], palette: Option<&[u8]>) -> String {
+    fn byte_array(out: &mut String, name: &str, bytes: &[u8]) {
+        out.push_str(&format!("const uint8_t {}[{}] = {{\n", name, bytes.len()));
+        for line in bytes.chunks(16) {
+            let values: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+            out.push_str(&format!("    {},\n", values.join(", ")));
+        }
+        out.push_str("};\n");
+    }
+
+    let upper = name.to_uppercase();
+    let mut out = format!(
+        "// Generated by aseprite_mcp export_raw ({})\n#include <stdint.h>\n\n#define {}_WIDTH {}\n#define {}_HEIGHT {}\n\n",
+        format, upper, width, upper, height
+    );
+    byte_array(&mut out, name, data);
+    if let Some(palette) = palette {
+        out.push_str(&format!("\n#define {}_PALETTE_COLORS {}\n", upper, palette.len() / 4));
+        byte_array(&mut out, &format!("{}_palette", name), palette);
+    }
+    out
+}
+
+/// Assemble a Windows .ico from PNG images (PNG-compressed entries, supported since Vista).
+fn build_ico(images: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(0u16.to_le_bytes()); // reserved
+    out.extend(1u16.to_le_bytes()); // type: icon
+    out.extend((images.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * images.len() as u32;
+    for (size, png) in images {
+        // A dimension byte of 0 means 256
+        let dim = if *size >= 256 { 0 } else { *size as u8 };
+        out.extend([dim, dim, 0, 0]); // width, height, palette colors, reserved
+        out.extend(1u16.to_le_bytes()); // color planes
+        out.extend(32u16.to_le_bytes()); // bits per pixel
+        out.extend((png.len() as u32).to_le_bytes());
+        out.extend(offset.to_le_bytes());
+        offset += png.len() as u32;
+    }
+    for (_, png) in images {
+        out.extend(png);
+    }
+    out
+}
+
+/// Assemble a macOS .icns from PNG images, skipping sizes that have no PNG icon type.
+/// Returns the file bytes and the sizes included.
+fn build_icns(images: &[(u32, Vec<u8>)]) -> (Vec<u8>, Vec<u32>) {
+    let mut body = Vec::new();
+    let mut included = Vec::new();
+    for (size, png) in images {
+        let kind: &[u8; 4] = match size {
+            16 => b"icp4",
+            32 => b"icp5",
+            64 => b"icp6",
+            128 => b"ic07",
+            256 => b"ic08",
+            _ => continue,
+        };
+        body.extend(kind);
+        body.extend((png.len() as u32 + 8).to_be_bytes());
+        body.extend(png);
+        included.push(*size);
+    }
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend(b"icns");
+    out.extend((body.len() as u32 + 8).to_be_bytes());
+    out.extend(body);
+    (out, included)
+}
+
+/// Validate an optional matte color and return its RGB components.
+fn parse_background_color(color: Option<&str>) -> Result<Option<(u8, u8, u8)>, String> {
+    match color {
+        Some(color) => {
+            let (r, g, b, _) =
+                parse_color(color).map_err(|e| format!("Invalid background_color '{}': {}", color, e))?;
+            Ok(Some((r, g, b)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Describe whether matting was applied, warning when the output format has no alpha channel.
+fn matte_note(output_path: &str, background_color: Option<&str>) -> Option<String> {
+    let no_alpha = Path::new(output_path)
+        .extension()
+        .map(|e| matches!(e.to_string_lossy().to_lowercase().as_str(), "jpg" | "jpeg" | "bmp"))
+        .unwrap_or(false);
+    match background_color {
+        Some(color) => Some(format!("Matte: composited over {}", color)),
+        None if no_alpha => Some(
+            "Matte: not applied; this format has no alpha, so transparent areas may come out black (set background_color)"
+                .to_string(),
+        ),
+        None => None,
+    }
+}
+
+/// Validate from_frame/to_frame and return the 1-based inclusive range, if any.
+fn frame_range(p: &ExportSpriteParams) -> Result<Option<(u32, u32)>, String> {
+    let (from, to) = match (p.from_frame, p.to_frame) {
+        (None, None) => return Ok(None),
+        (Some(from), Some(to)) => (from, to),
+        _ => return Err("from_frame and to_frame must be given together".to_string()),
+    };
+    if from == 0 || to == 0 {
+        return Err("from_frame and to_frame are 1-based and must be >= 1".to_string());
+    }
+    if from > to {
+        return Err(format!("from_frame ({}) must not be after to_frame ({})", from, to));
+    }
+    Ok(Some((from, to)))
+}
+
+/// A packed atlas entry: name and x, y, width, height in the atlas.
+type PackedRect = (String, u32, u32, u32, u32);
+
+/// Pack inputs with Aseprite's multi-file --sheet-pack. Returns sheet size and (name, x, y, w, h) rects.
+async fn pack_with_aseprite(
+    server: &AsepriteServer,
+    inputs: &[String],
+    image_path: &str,
+    padding: u32,
+    all_frames: bool,
+) -> Result<(u32, u32, Vec<PackedRect>), String> {
+    let mut args = Vec::new();
+    for input in inputs {
+        // --frame-range applies to the next sprite
+        if !all_frames {
+            args.push("--frame-range".to_string());
+            args.push("0,0".to_string());
+        }
+        args.push(input.clone());
+    }
+    let data_path = server.temp_file_path("json");
+    args.extend([
+        "--sheet".to_string(),
+        image_path.to_string(),
+        "--sheet-pack".to_string(),
+        "--data".to_string(),
+        data_path.to_string_lossy().to_string(),
+        "--format".to_string(),
+        "json-array".to_string(),
+        "--filename-format".to_string(),
+        if all_frames { "{title}_{frame}" } else { "{title}" }.to_string(),
+    ]);
+    if padding > 0 {
+        args.extend([
+            "--shape-padding".to_string(),
+            padding.to_string(),
+            "--border-padding".to_string(),
+            padding.to_string(),
+        ]);
+    }
+
+    let output = server.run_cli(&args).await.map_err(|e| e.to_string())?;
+    let data = tokio::fs::read_to_string(&data_path).await;
+    tokio::fs::remove_file(&data_path).await.ok();
+    if !output.success {
+        return Err(output.result_text());
+    }
+    let sheet: serde_json::Value = serde_json::from_str(&data.map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Invalid sheet data: {}", e))?;
+    let rects = sheet["frames"]
+        .as_array()
+        .ok_or("Sheet data has no frames")?
+        .iter()
+        .map(|f| {
+            let r = PixelRect::from_json(&f["frame"]);
+            (
+                f["filename"].as_str().unwrap_or_default().to_string(),
+                r.x as u32,
+                r.y as u32,
+                r.w as u32,
+                r.h as u32,
+            )
+        })
+        .collect();
+    Ok((
+        sheet["meta"]["size"]["w"].as_u64().unwrap_or(0) as u32,
+        sheet["meta"]["size"]["h"].as_u64().unwrap_or(0) as u32,
+        rects,
+    ))
+}
+
+/// Export each input's frames to PNGs, shelf-pack them in Rust, and write the atlas image.
+async fn pack_with_shelves(
+    server: &AsepriteServer,
+    inputs: &[String],
+    image_path: &str,
+    max_width: u32,
+    max_height: u32,
+    padding: u32,
+    all_frames: bool,
+) -> Result<(u32, u32, Vec<PackedRect>), String> {
+    let dir = server.temp_file_path("pack");
+    create_output_dir(&dir)
+        .await
+        .map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let result = async {
+        let mut images = Vec::new();
+        for (i, input) in inputs.iter().enumerate() {
+            let mut args = Vec::new();
+            if !all_frames {
+                args.push("--frame-range".to_string());
+                args.push("0,0".to_string());
+            }
+            args.push(input.clone());
+            args.push("--save-as".to_string());
+            args.push(dir.join(format!("{}_{{frame}}.png", i)).to_string_lossy().to_string());
+            let output = server.run_cli(&args).await.map_err(|e| e.to_string())?;
+            if !output.success {
+                return Err(format!("Failed to export {}: {}", input, output.result_text()));
+            }
+
+            let mut frame_files: Vec<(u32, PathBuf)> = glob::glob(&format!(
+                "{}/{}_*.png",
+                glob::Pattern::escape(&dir.to_string_lossy()),
+                i
+            ))
+            .map_err(|e| e.to_string())?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|path| {
+                let stem = path.file_stem()?.to_string_lossy().to_string();
+                let frame = stem.rsplit_once('_')?.1.parse().ok()?;
+                Some((frame, path))
+            })
+            .collect();
+            frame_files.sort();
+            if frame_files.is_empty() {
+                return Err(format!("Aseprite wrote no frames for {}", input));
+            }
+            let title = sprite_title(input);
+            for (k, (_, path)) in frame_files.iter().enumerate() {
+                let bytes = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+                let (w, h, pixels) = decode_png_rgba(&bytes)?;
+                let name = if all_frames { format!("{}_{}", title, k) } else { title.clone() };
+                images.push((name, w, h, pixels));
+            }
+        }
+        Ok(images)
+    }
+    .await;
+    tokio::fs::remove_dir_all(&dir).await.ok();
+    let images = result?;
+
+    let sizes: Vec<(u32, u32)> = images.iter().map(|(_, w, h, _)| (*w, *h)).collect();
+    let layout = shelf_pack(&sizes, max_width, max_height, padding).ok_or_else(|| {
+        format!(
+            "{} frame(s) don't fit in {}x{} with padding {}",
+            images.len(),
+            max_width,
+            max_height,
+            padding
+        )
+    })?;
+
+    let (width, height) = (layout.width, layout.height);
+    let mut canvas = vec![0u8; (width * height * 4) as usize];
+    let mut rects = Vec::new();
+    for ((name, w, h, pixels), (x, y)) in images.iter().zip(&layout.positions) {
+        for row in 0..*h {
+            let src = (row * w * 4) as usize;
+            let dst = (((y + row) * width + x) * 4) as usize;
+            canvas[dst..dst + (*w * 4) as usize].copy_from_slice(&pixels[src..src + (*w * 4) as usize]);
+        }
+        rects.push((name.clone(), *x, *y, *w, *h));
+    }
+    let png = encode_png_rgba(&canvas, width, height)?;
+    write_output(image_path, png)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", image_path, e))?;
+    Ok((width, height, rects))
+}
+
+/// Sprite title used for generated names: the input file name without extension.
+fn sprite_title(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "sprite".to_string())
+}
+
+/// Export a spritesheet plus json-array data to a temp file, returning the parsed data.
+/// `list_flags` (e.g. "--list-tags") are placed before the file since they apply to the next sprite;
+/// `sheet_args` (sheet type, trim, padding, ...) follow the --sheet output.
+async fn export_sheet_data(
+    server: &AsepriteServer,
+    file_path: &str,
+    image_path: &str,
+    list_flags: &[&str],
+    sheet_args: &[String],
+) -> Result<serde_json::Value, String> {
+    let data_path = server.temp_file_path("json");
+    let mut args: Vec<String> = list_flags.iter().map(|f| f.to_string()).collect();
+    args.extend([
+        file_path.to_string(),
+        "--sheet".to_string(),
+        image_path.to_string(),
+        "--data".to_string(),
+        data_path.to_string_lossy().to_string(),
+        "--format".to_string(),
+        "json-array".to_string(),
+    ]);
+    args.extend(sheet_args.iter().cloned());
+
+    let output = server
+        .run_cli(&args)
+        .await
+        .map_err(|e| format!("Export failed: {}", e))?;
+    let data = tokio::fs::read_to_string(&data_path).await;
+    tokio::fs::remove_file(&data_path).await.ok();
+    if !output.success {
+        return Err(output.result_text());
+    }
+    verify_outputs(&[image_path.to_string()], &output.stderr).await?;
+    let data = data.map_err(|e| format!("Failed to read sheet data: {}", e))?;
+    serde_json::from_str(&data).map_err(|e| format!("Invalid sheet data: {}", e))
+}
+
+/// Integer pixel rectangle (top-left origin unless stated otherwise).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PixelRect {
+    x: i64,
+    y: i64,
+    w: i64,
+    h: i64,
+}
+
+impl PixelRect {
+    fn from_json(v: &serde_json::Value) -> Self {
+        Self {
+            x: v["x"].as_i64().unwrap_or(0),
+            y: v["y"].as_i64().unwrap_or(0),
+            w: v["w"].as_i64().unwrap_or(0),
+            h: v["h"].as_i64().unwrap_or(0),
+        }
+    }
+}
+
+/// One frame of a json-array spritesheet export.
+#[derive(Debug)]
+struct SheetFrame {
+    /// Region in the sheet image
+    frame: PixelRect,
+    /// Region of the original (untrimmed) sprite the frame covers (spriteSourceSize)
+    source: PixelRect,
+    /// Original sprite size (sourceSize)
+    source_width: i64,
+    source_height: i64,
+    duration: i64,
+}
+
+/// Parse the frames of a json-array spritesheet export.
+fn parse_sheet_frames(sheet: &serde_json::Value) -> Result<Vec<SheetFrame>, String> {
+    let frames = sheet["frames"]
+        .as_array()
+        .ok_or("Sheet data has no frames array (expected json-array format)")?;
+    Ok(frames
+        .iter()
+        .map(|f| SheetFrame {
+            frame: PixelRect::from_json(&f["frame"]),
+            source: PixelRect::from_json(&f["spriteSourceSize"]),
+            source_width: f["sourceSize"]["w"].as_i64().unwrap_or(0),
+            source_height: f["sourceSize"]["h"].as_i64().unwrap_or(0),
+            duration: f["duration"].as_i64().unwrap_or(100),
+        })
+        .collect())
+}
+
+/// Parse a pivot name or "x,y" into normalized coordinates with a bottom-left origin.
+fn parse_pivot(pivot: &str) -> Result<(f64, f64), String> {
+    let named = match pivot.trim().to_lowercase().as_str() {
+        "center" => Some((0.5, 0.5)),
+        "top-left" => Some((0.0, 1.0)),
+        "top" => Some((0.5, 1.0)),
+        "top-right" => Some((1.0, 1.0)),
+        "left" => Some((0.0, 0.5)),
+        "right" => Some((1.0, 0.5)),
+        "bottom-left" => Some((0.0, 0.0)),
+        "bottom" => Some((0.5, 0.0)),
+        "bottom-right" => Some((1.0, 0.0)),
+        _ => None,
+    };
+    if let Some(named) = named {
+        return Ok(named);
+    }
+    let parts: Vec<f64> = pivot
+        .split(',')
+        .map(|v| v.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid pivot '{}'", pivot))?;
+    match parts[..] {
+        [x, y] => Ok((x, y)),
+        _ => Err(format!("Invalid pivot '{}'. Use a name like 'center' or 'x,y'", pivot)),
+    }
+}
+
+/// Pick the slice providing pivots: the named slice, or the first slice whose keys have a pivot.
+fn find_pivot_slice(sheet: &serde_json::Value, name: Option<&str>) -> Result<Option<serde_json::Value>, String> {
+    let empty = Vec::new();
+    let slices = sheet["meta"]["slices"].as_array().unwrap_or(&empty);
+    match name {
+        Some(name) => slices
+            .iter()
+            .find(|s| s["name"] == name)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| format!("Slice not found: {}", name)),
+        None => Ok(slices
+            .iter()
+            .find(|s| {
+                s["keys"]
+                    .as_array()
+                    .is_some_and(|keys| keys.iter().any(|k| k.get("pivot").is_some()))
+            })
+            .cloned()),
+    }
+}
+
+/// Pivot of a slice at a 0-based frame in sprite pixel coordinates (top-left origin).
+/// Uses the last key starting at or before the frame.
+fn slice_pivot_at(slice: &serde_json::Value, frame: usize) -> Option<(f64, f64)> {
+    let key = slice["keys"]
+        .as_array()?
+        .iter()
+        .rfind(|k| k["frame"].as_u64().unwrap_or(0) as usize <= frame)?;
+    let pivot = key.get("pivot")?;
+    let bounds = PixelRect::from_json(&key["bounds"]);
+    Some((
+        (bounds.x + pivot["x"].as_i64().unwrap_or(0)) as f64,
+        (bounds.y + pivot["y"].as_i64().unwrap_or(0)) as f64,
+    ))
+}
+
+/// Convert a sheet rectangle from Aseprite's top-left origin to Unity's bottom-left origin.
+fn unity_rect(frame: &PixelRect, sheet_height: i64) -> PixelRect {
+    PixelRect {
+        y: sheet_height - (frame.y + frame.h),
+        ..*frame
+    }
+}
+
+/// Convert a pivot in sprite pixel coordinates (top-left origin) into Unity's normalized
+/// pivot for a frame, relative to the frame's (possibly trimmed) source region.
+fn unity_pivot(pivot_px: (f64, f64), source: &PixelRect) -> (f64, f64) {
+    if source.w == 0 || source.h == 0 {
+        return (0.5, 0.5);
+    }
+    (
+        (pivot_px.0 - source.x as f64) / source.w as f64,
+        1.0 - (pivot_px.1 - source.y as f64) / source.h as f64,
+    )
+}
+
+/// Name atlas regions "{title}_{tag}" with an index per frame within the tag; frames outside
+/// every tag are named "{title}" indexed by frame number. Returns (name, index, frame index).
+fn atlas_regions(title: &str, sheet: &serde_json::Value, frame_count: usize) -> Vec<(String, usize, usize)> {
+    let mut regions = Vec::new();
+    let mut tagged = vec![false; frame_count];
+    for tag in sheet["meta"]["frameTags"].as_array().into_iter().flatten() {
+        let from = tag["from"].as_u64().unwrap_or(0) as usize;
+        let to = tag["to"].as_u64().unwrap_or(0) as usize;
+        let name = format!("{}_{}", title, tag["name"].as_str().unwrap_or_default());
+        for (index, frame) in (from..=to).filter(|f| *f < frame_count).enumerate() {
+            tagged[frame] = true;
+            regions.push((name.clone(), index, frame));
+        }
+    }
+    for (frame, _) in tagged.iter().enumerate().filter(|(_, t)| !**t) {
+        regions.push((title.to_string(), frame, frame));
+    }
+    regions
+}
+
+/// Render a libGDX .atlas page. libGDX offsets are measured from the bottom-left of the
+/// original frame, so trimmed frames use sourceSize.h - (spriteSourceSize.y + h) for offset y.
+fn libgdx_atlas(
+    page_name: &str,
+    width: i64,
+    height: i64,
+    regions: &[(String, usize, usize)],
+    frames: &[SheetFrame],
+) -> String {
+    let mut out = format!(
+        "\n{}\nsize: {}, {}\nformat: RGBA8888\nfilter: Nearest, Nearest\nrepeat: none\n",
+        page_name, width, height
+    );
+    for (name, index, frame_index) in regions {
+        let f = &frames[*frame_index];
+        let offset_x = f.source.x;
+        let offset_y = f.source_height - (f.source.y + f.source.h);
+        out.push_str(&format!(
+            "{}\n  rotate: false\n  xy: {}, {}\n  size: {}, {}\n  orig: {}, {}\n  offset: {}, {}\n  index: {}\n",
+            name,
+            f.frame.x,
+            f.frame.y,
+            f.frame.w,
+            f.frame.h,
+            f.source_width,
+            f.source_height,
+            offset_x,
+            offset_y,
+            index
+        ));
+    }
+    out
+}
+
+/// Read the sprite's tags as (name, frame count, 1-based first frame) tuples.
+async fn sprite_tags(server: &AsepriteServer, file_path: &str) -> Result<Vec<(String, u64, u32)>, String> {
+    let script = r#"
+local spr = app.sprite
+local tags = {}
+for i, t in ipairs(spr.tags) do
+  tags[i] = { name = t.name, frames = t.frames, from = t.fromFrame.frameNumber }
+end
+print(json.encode({ tags = tags }))
+"#;
+    let out = server.execute_script_on_file(file_path, script).await?;
+    let json: serde_json::Value =
+        serde_json::from_str(&out).map_err(|e| format!("Failed to read tags: {}", e))?;
+    Ok(json["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .map(|t| {
+                    (
+                        t["name"].as_str().unwrap_or_default().to_string(),
+                        t["frames"].as_u64().unwrap_or(0),
+                        t["from"].as_u64().unwrap_or(1) as u32,
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Whether a filename contains Aseprite filename-format placeholders like "{layer}".
+fn has_placeholders(path: &str) -> bool {
+    ["{title}", "{layer}", "{tag}", "{frame", "{extension}", "{path}", "{name}", "{group}", "{slice}"]
+        .iter()
+        .any(|ph| path.contains(ph))
+}
+
+/// Insert a suffix before the file extension ("out/a.png" + "_{layer}" -> "out/a_{layer}.png").
+fn insert_before_extension(path: &str, suffix: &str) -> String {
+    let file_start = path.rfind(['/', '\\']).map(|i| i + 1).unwrap_or(0);
+    match path[file_start..].rfind('.') {
+        Some(dot) => format!("{}{}{}", &path[..file_start + dot], suffix, &path[file_start + dot..]),
+        None => format!("{}{}", path, suffix),
+    }
+}
+
+/// Convert an Aseprite filename format into a glob pattern, replacing each placeholder with "*".
+/// Placeholders may expand to names containing "/", so those also match nested directories.
+fn format_to_glob(format: &str) -> String {
+    let mut pattern = String::new();
+    let mut rest = format;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else { break };
+        pattern.push_str(&glob::Pattern::escape(&rest[..open]));
+        pattern.push('*');
+        rest = &rest[open + close + 1..];
+    }
+    pattern.push_str(&glob::Pattern::escape(rest));
+    pattern
+}
+
+/// List files matching an Aseprite filename format that were modified at or after `since`.
+/// Placeholders can expand to nested paths (a layer "a/b" in a group), so everything below the
+/// format's last literal directory is searched.
+fn list_written_files(format: &str, since: SystemTime) -> Vec<String> {
+    let Ok(pattern) = glob::Pattern::new(&format_to_glob(format)) else {
+        return Vec::new();
+    };
+    let options = glob::MatchOptions {
+        require_literal_separator: false,
+        ..Default::default()
+    };
+    let literal = &format[..format.find('{').unwrap_or(format.len())];
+    let base = literal.rfind(['/', '\\']).map_or("", |i| &literal[..=i]);
+    let mut found = Vec::new();
+    collect_files(Path::new(if base.is_empty() { "." } else { base }), &mut found);
+    // Allow for coarse filesystem timestamp resolution
+    let since = since - std::time::Duration::from_secs(2);
+    let mut files: Vec<String> = found
+        .into_iter()
+        .map(|path| match base {
+            "" => path.strip_prefix(".").map(Path::to_path_buf).unwrap_or(path),
+            _ => path,
+        })
+        .filter(|path| pattern.matches_path_with(path, options))
+        .filter(|path| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .map(|t| t >= since)
+                .unwrap_or(false)
+        })
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Add every file under `dir` (recursively, without following symlinked directories) to `out`.
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => collect_files(&entry.path(), out),
+            Ok(_) => out.push(entry.path()),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Space around and between contact sheet cells; tag markers are drawn in it.
+const CONTACT_SHEET_GUTTER: u32 = 4;
+const CONTACT_SHEET_MAX_SIDE: u32 = 16384;
+const CONTACT_SHEET_LABEL_BG: [u8; 4] = [32, 32, 32, 255];
+
+/// 3x5 bitmap digits for burned-in frame numbers: one byte per row, bit 2 is the left column.
+const DIGIT_GLYPHS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const DIGIT_WIDTH: u32 = 3;
+const DIGIT_HEIGHT: u32 = 5;
+
+struct ContactSheetLayout {
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+    cell_height: u32,
+    width: u32,
+    height: u32,
+}
+
+impl ContactSheetLayout {
+    fn cell_origin(&self, index: u32) -> (u32, u32) {
+        let (col, row) = (index % self.columns, index / self.columns);
+        (
+            CONTACT_SHEET_GUTTER + col * (self.cell_width + CONTACT_SHEET_GUTTER),
+            CONTACT_SHEET_GUTTER + row * (self.cell_height + CONTACT_SHEET_GUTTER),
+        )
+    }
+}
+
+/// Grid for `count` cells: `columns` wide (default: the smallest square that fits), with a
+/// gutter around and between cells.
+fn contact_sheet_layout(count: u32, cell_width: u32, cell_height: u32, columns: Option<u32>) -> ContactSheetLayout {
+    let columns = columns
+        .unwrap_or_else(|| (count as f64).sqrt().ceil() as u32)
+        .clamp(1, count.max(1));
+    let rows = count.div_ceil(columns);
+    ContactSheetLayout {
+        columns,
+        rows,
+        cell_width,
+        cell_height,
+        width: CONTACT_SHEET_GUTTER + columns * (cell_width + CONTACT_SHEET_GUTTER),
+        height: CONTACT_SHEET_GUTTER + rows * (cell_height + CONTACT_SHEET_GUTTER),
+    }
+}
+
+/// Width of a number drawn with `draw_digits`.
+fn digit_text_width(n: u32, scale: u32) -> u32 {
+    let digits = n.to_string().len() as u32;
+    (digits * (DIGIT_WIDTH + 1) - 1) * scale
+}
+
+fn fill_rect(rgba: &mut [u8], width: u32, (x, y, w, h): (u32, u32, u32, u32), color: [u8; 4]) {
+    for row in y..y + h {
+        for col in x..(x + w).min(width) {
+            if let Some(px) = rgba.get_mut(((row * width + col) * 4) as usize..((row * width + col) * 4 + 4) as usize) {
+                px.copy_from_slice(&color);
+            }
+        }
+    }
+}
+
+/// Draw `n` in white with the top-left corner at (x, y), each font pixel `scale` wide.
+fn draw_digits(rgba: &mut [u8], width: u32, n: u32, x: u32, y: u32, scale: u32) {
+    for (i, digit) in n.to_string().bytes().enumerate() {
+        let glyph = DIGIT_GLYPHS[(digit - b'0') as usize];
+        let gx = x + i as u32 * (DIGIT_WIDTH + 1) * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..DIGIT_WIDTH {
+                if bits & (1 << (DIGIT_WIDTH - 1 - col)) != 0 {
+                    let rect = (gx + col * scale, y + row as u32 * scale, scale, scale);
+                    fill_rect(rgba, width, rect, [255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aseprite::dry_run;
+    use crate::test_support::{dry_server, scratch_dir};
+
+    #[tokio::test]
+    async fn export_layer_filter_accepts_group_paths() {
+        let Some(server) = crate::test_support::real_server() else { return };
+        let path = scratch_dir("group_path_filter").join("body.aseprite").to_string_lossy().to_string();
+        let script = format!(
+            r#"local spr = Sprite(8, 8)
+local body = spr:newGroup()
+body.name = "Body"
+local arm = spr:newLayer()
+arm.name = "Arm"
+arm.parent = body
+spr.layers[1].name = "a/b"
+spr:saveAs({})"#,
+            lua_path(&path)
+        );
+        server.execute_script(&script).await.unwrap();
+
+        assert!(check_export_filters(&server, &path, Some("Body/Arm"), None, None).await.is_ok());
+        assert!(check_export_filters(&server, &path, Some("a/b"), None, None).await.is_ok());
+        let err = check_export_filters(&server, &path, Some("Body/Leg"), None, None).await.unwrap_err();
+        assert!(err.starts_with("Layer not found: Body/Leg"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn export_frame_range_must_end_inside_the_sprite() {
+        let Some(server) = crate::test_support::real_server() else { return };
+        let path = scratch_dir("frame_range_check").join("anim.aseprite").to_string_lossy().to_string();
+        let script = format!("local spr = Sprite(8, 8)\nspr:newFrame()\nspr:newFrame()\nspr:saveAs({})", lua_path(&path));
+        server.execute_script(&script).await.unwrap();
+
+        assert!(check_export_filters(&server, &path, None, None, Some((2, 3))).await.is_ok());
+        let err = check_export_filters(&server, &path, None, None, Some((2, 6))).await.unwrap_err();
+        assert!(err.contains("Frame 6 out of range (sprite has 3 frames)"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn export_sprite_checks_the_frame_range_before_exporting() {
+        let server = dry_server();
+        let params: ExportSpriteParams = serde_json::from_value(serde_json::json!({
+            "file_path": "/art/anim.aseprite",
+            "output_path": "/art/attack_{frame}.png",
+            "from_frame": 3,
+            "to_frame": 6,
+        }))
+        .unwrap();
+        let script = crate::test_support::planned_script(export_sprite(&server, params)).await;
+        assert!(script.contains(&lua_check_frame(6)), "{}", script);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failed_export_copy_reports_the_script_error() {
+        let dir = scratch_dir("export_copy_error");
+        let server = crate::test_support::fake_server(&dir, r#"echo '{"error":"Layer not found: Nope"}'"#);
+        let sprite = dir.join("hero.aseprite");
+        std::fs::write(&sprite, "sprite").unwrap();
+        let err = prepare_export_copy(&server, &sprite.to_string_lossy(), Some("Nope"), None).await.unwrap_err();
+        assert_eq!(err, "Layer not found: Nope");
+    }
+
+    #[tokio::test]
+    async fn export_gif_rejects_palettes_outside_2_to_256_colors() {
+        let gif = |max_colors: u32| -> ExportGifParams {
+            serde_json::from_value(serde_json::json!({
+                "file_path": "/art/in.aseprite",
+                "output_path": "/art/out.gif",
+                "max_colors": max_colors,
+            }))
+            .unwrap()
+        };
+        let server = dry_server();
+        let err = export_gif(&server, gif(1)).await.unwrap_err();
+        assert_eq!(err, "max_colors must be between 2 and 256 (got 1)");
+        assert!(export_gif(&server, gif(300)).await.is_err());
+        let (_, planned) = dry_run(export_gif(&server, gif(16))).await;
+        assert!(planned[0]["script"].as_str().unwrap().contains("maxColors = 16"));
+    }
+
+    #[test]
+    fn parse_pivot_accepts_names_and_coordinates() {
+        assert_eq!(parse_pivot("center").unwrap(), (0.5, 0.5));
+        assert_eq!(parse_pivot(" Top-Left ").unwrap(), (0.0, 1.0));
+        assert_eq!(parse_pivot("bottom").unwrap(), (0.5, 0.0));
+        assert_eq!(parse_pivot("0.25, 0.75").unwrap(), (0.25, 0.75));
+        assert!(parse_pivot("middle").is_err());
+        assert!(parse_pivot("0.5").is_err());
+        assert!(parse_pivot("0.1,0.2,0.3").is_err());
+    }
+
+    #[test]
+    fn unity_rect_flips_to_a_bottom_left_origin() {
+        let frame = PixelRect { x: 16, y: 0, w: 16, h: 8 };
+        assert_eq!(unity_rect(&frame, 32), PixelRect { x: 16, y: 24, w: 16, h: 8 });
+        let bottom = PixelRect { x: 0, y: 24, w: 16, h: 8 };
+        assert_eq!(unity_rect(&bottom, 32).y, 0);
+    }
+
+    #[test]
+    fn unity_pivot_is_relative_to_the_trimmed_source() {
+        let full = PixelRect { x: 0, y: 0, w: 16, h: 16 };
+        assert_eq!(unity_pivot((8.0, 16.0), &full), (0.5, 0.0));
+        assert_eq!(unity_pivot((0.0, 0.0), &full), (0.0, 1.0));
+        // Trimmed to the 8x8 region at (4, 8): the feet at (8, 16) are bottom-center of it
+        let trimmed = PixelRect { x: 4, y: 8, w: 8, h: 8 };
+        assert_eq!(unity_pivot((8.0, 16.0), &trimmed), (0.5, 0.0));
+        assert_eq!(unity_pivot((8.0, 16.0), &PixelRect { x: 0, y: 0, w: 0, h: 0 }), (0.5, 0.5));
+    }
+
+    #[test]
+    fn slice_pivot_uses_the_latest_key_at_the_frame() {
+        let slice = serde_json::json!({
+            "name": "feet",
+            "keys": [
+                { "frame": 0, "bounds": { "x": 2, "y": 4, "w": 8, "h": 8 }, "pivot": { "x": 4, "y": 8 } },
+                { "frame": 3, "bounds": { "x": 6, "y": 4, "w": 8, "h": 8 }, "pivot": { "x": 1, "y": 2 } },
+            ],
+        });
+        assert_eq!(slice_pivot_at(&slice, 0), Some((6.0, 12.0)));
+        assert_eq!(slice_pivot_at(&slice, 2), Some((6.0, 12.0)));
+        assert_eq!(slice_pivot_at(&slice, 5), Some((7.0, 6.0)));
+    }
+
+    /// A trimmed json-array sheet of a 32x32 sprite: frame 0 is trimmed to a 10x12 region at
+    /// (4, 16), frame 1 is untrimmed, and a "run" tag covers both.
+    fn trimmed_sheet() -> serde_json::Value {
+        serde_json::json!({
+            "frames": [
+                {
+                    "frame": { "x": 1, "y": 1, "w": 10, "h": 12 },
+                    "spriteSourceSize": { "x": 4, "y": 16, "w": 10, "h": 12 },
+                    "sourceSize": { "w": 32, "h": 32 },
+                    "duration": 80,
+                },
+                {
+                    "frame": { "x": 12, "y": 1, "w": 32, "h": 32 },
+                    "spriteSourceSize": { "x": 0, "y": 0, "w": 32, "h": 32 },
+                    "sourceSize": { "w": 32, "h": 32 },
+                    "duration": 120,
+                },
+            ],
+            "meta": {
+                "size": { "w": 45, "h": 34 },
+                "frameTags": [{ "name": "run", "from": 0, "to": 1 }],
+            },
+        })
+    }
+
+    #[test]
+    fn libgdx_atlas_offsets_trimmed_frames_from_the_bottom_left() {
+        let sheet = trimmed_sheet();
+        let frames = parse_sheet_frames(&sheet).unwrap();
+        let regions = atlas_regions("hero", &sheet, frames.len());
+        assert_eq!(regions, [("hero_run".to_string(), 0, 0), ("hero_run".to_string(), 1, 1)]);
+
+        let atlas = libgdx_atlas("hero.png", 45, 34, &regions, &frames);
+        assert!(atlas.starts_with("\nhero.png\nsize: 45, 34\n"));
+        // 32 - (16 + 12) = 4 pixels of trimmed space below the frame
+        assert!(atlas.contains(
+            "hero_run\n  rotate: false\n  xy: 1, 1\n  size: 10, 12\n  orig: 32, 32\n  offset: 4, 4\n  index: 0\n"
+        ));
+        assert!(atlas.contains(
+            "hero_run\n  rotate: false\n  xy: 12, 1\n  size: 32, 32\n  orig: 32, 32\n  offset: 0, 0\n  index: 1\n"
+        ));
+    }
+
+    #[test]
+    fn untagged_frames_are_named_after_the_sprite() {
+        let mut sheet = trimmed_sheet();
+        sheet["meta"]["frameTags"] = serde_json::json!([{ "name": "idle", "from": 1, "to": 1 }]);
+        let regions = atlas_regions("hero", &sheet, 2);
+        assert_eq!(regions, [("hero_idle".to_string(), 0, 1), ("hero".to_string(), 0, 0)]);
+    }
+
+    #[test]
+    fn written_files_include_nested_layer_folders() {
+        let dir = scratch_dir("written_files");
+        std::fs::create_dir_all(dir.join("out/group")).unwrap();
+        for name in ["out/body.png", "out/group/arm.png", "out/notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let format = format!("{}/out/{{layer}}.png", dir.to_string_lossy().replace('\\', "/"));
+        let files = list_written_files(&format, SystemTime::now());
+        let names: Vec<&str> = files.iter().map(|f| &f[f.find("/out/").unwrap() + 5..]).collect();
+        assert_eq!(names, ["body.png", "group/arm.png"]);
+    }
+
+    #[tokio::test]
+    async fn dry_run_unity_meta_plans_the_sheet_export_without_writing() {
+        let dir = scratch_dir("dry_unity_meta");
+        let output_dir = dir.join("unity");
+        let params = ExportUnityMetaParams {
+            file_path: dir.join("hero.aseprite").to_string_lossy().to_string(),
+            output_dir: output_dir.to_string_lossy().to_string(),
+            pixels_per_unit: None,
+            pivot: None,
+            pivot_slice: None,
+            trim: None,
+            timeout_secs: None,
+        };
+        let server = dry_server();
+        let (result, planned) = dry_run(export_unity_meta(&server, params)).await;
+        assert!(result.is_err());
+        assert_eq!(planned.len(), 1);
+        let args = planned[0]["args"].to_string();
+        assert!(args.contains("--sheet") && args.contains("hero.png"), "{}", args);
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    fn rgb565_packs_little_endian_and_drops_alpha() {
+        let rgba = [255, 255, 255, 0, 255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 8, 4, 8, 255];
+        assert_eq!(
+            rgba_to_rgb565(&rgba),
+            vec![0xff, 0xff, 0x00, 0xf8, 0xe0, 0x07, 0x1f, 0x00, 0x21, 0x08]
+        );
+    }
+
+    #[test]
+    fn c_array_source_emits_defines_and_wrapped_rows() {
+        let data: Vec<u8> = (0..18).collect();
+        let source = c_array_source("hero", "rgba8888", 3, 2, &data, None);
+        assert!(source.contains("// Generated by aseprite_mcp export_raw (rgba8888)"));
+        assert!(source.contains("#include <stdint.h>"));
+        assert!(source.contains("#define HERO_WIDTH 3\n#define HERO_HEIGHT 2\n"));
+        assert!(source.contains("const uint8_t hero[18] = {\n"));
+        assert!(source.contains("    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,\n    0x10, 0x11,\n};\n"));
+        assert!(!source.contains("PALETTE"));
+    }
+
+    #[test]
+    fn c_array_source_includes_palette() {
+        let palette = [0, 0, 0, 0, 255, 0, 0, 255];
+        let source = c_array_source("tiles", "indexed8", 1, 1, &[1], Some(&palette));
+        assert!(source.contains("const uint8_t tiles[1] = {\n    0x01,\n};\n"));
+        assert!(source.contains("#define TILES_PALETTE_COLORS 2\n"));
+        assert!(source.contains("const uint8_t tiles_palette[8] = {\n    0x00, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00, 0xff,\n};\n"));
+    }
+
+    #[test]
+    fn contact_sheet_layout_defaults_to_a_square_grid() {
+        let layout = contact_sheet_layout(5, 10, 20, None);
+        assert_eq!((layout.columns, layout.rows), (3, 2));
+        let g = CONTACT_SHEET_GUTTER;
+        assert_eq!(layout.width, g + 3 * (10 + g));
+        assert_eq!(layout.height, g + 2 * (20 + g));
+        assert_eq!(layout.cell_origin(0), (g, g));
+        assert_eq!(layout.cell_origin(4), (g + 10 + g, g + 20 + g));
+    }
+
+    #[test]
+    fn contact_sheet_layout_honors_and_clamps_columns() {
+        let layout = contact_sheet_layout(4, 8, 8, Some(1));
+        assert_eq!((layout.columns, layout.rows), (1, 4));
+        let layout = contact_sheet_layout(3, 8, 8, Some(10));
+        assert_eq!((layout.columns, layout.rows), (3, 1));
+        let layout = contact_sheet_layout(1, 8, 8, None);
+        assert_eq!((layout.columns, layout.rows), (1, 1));
+        assert_eq!(layout.width, 2 * CONTACT_SHEET_GUTTER + 8);
+    }
+}
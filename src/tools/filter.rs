@@ -1,7 +1,12 @@
 use rmcp::schemars;
 use serde::Deserialize;
 
+use crate::aseprite::lua_string;
+use crate::lua_helpers::{LUA_COLLECT_IMAGE_LAYERS, LUA_FIND_LAYER, LUA_HSL, lua_save_target, with_saved_to};
 use crate::server::AsepriteServer;
+use crate::tools::frame::{FrameRef, resolve_optional_frame};
+use crate::tools::selection::{SelectionSpec, lua_apply_selection};
+use crate::utils::{check_choice, check_range, parse_color, resolve_file_list, rgb_to_hsl};
 
 // ============================================================================
 // Parameter Structs
@@ -15,6 +20,12 @@ pub struct BrightnessContrastParams {
     pub brightness: i32,
     /// Contrast adjustment (-100 to 100)
     pub contrast: i32,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -27,22 +38,186 @@ pub struct HueSaturationParams {
     pub saturation: i32,
     /// Lightness adjustment (-100 to 100)
     pub lightness: Option<i32>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct InvertColorParams {
     /// Path to the sprite file
     pub file_path: String,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct DespeckleParams {
-    /// Path to the sprite file
-    pub file_path: String,
-    /// Width of the median filter matrix (default: 3)
+    /// Path to the sprite file (or use file_paths / glob to process several)
+    pub file_path: Option<String>,
+    /// Several sprite files to process in turn, each saved in place; entries may be glob patterns
+    pub file_paths: Option<Vec<String>>,
+    /// Glob pattern selecting the sprite files to process, e.g. "sprites/*.aseprite"
+    pub glob: Option<String>,
+    /// Width of the median filter matrix (1-100, default: 3)
     pub width: Option<u32>,
-    /// Height of the median filter matrix (default: 3)
+    /// Height of the median filter matrix (1-100, default: 3)
     pub height: Option<u32>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CurvePoint {
+    /// Input value (0-255)
+    pub input: u8,
+    /// Output value (0-255)
+    pub output: u8,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ColorCurveParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Channel to adjust: "rgb" (default), "r", "g", "b", or "alpha"
+    pub channel: Option<String>,
+    /// Curve control points (input -> output). Values between points are interpolated linearly;
+    /// (0,0) and (255,255) are added when no point is given for those inputs
+    pub points: Option<Vec<CurvePoint>>,
+    /// Levels form: input value mapped to 0 (default: 0)
+    pub black_point: Option<u8>,
+    /// Levels form: input value mapped to 255 (default: 255)
+    pub white_point: Option<u8>,
+    /// Levels form: gamma correction, > 1 brightens midtones (default: 1.0)
+    pub gamma: Option<f64>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct PosterizeParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Number of evenly spaced values kept per RGB channel (2-64)
+    pub levels: u32,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct AddNoiseParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Noise strength (0-100)
+    pub amount: u32,
+    /// Apply the same offset to R, G, and B (grain without color speckles, default: false)
+    pub monochrome: Option<bool>,
+    /// Random seed; the same seed always produces the same noise (default: 0)
+    pub seed: Option<u64>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TintParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Tint color as hex, CSS name, or rgb() (e.g. "#704214" for sepia)
+    pub color: String,
+    /// Tint strength (0-100, default: 100)
+    pub strength: Option<u32>,
+    /// Blend mode: "multiply", "overlay", or "colorize" (default; takes the tint's hue and
+    /// saturation while keeping each pixel's lightness)
+    pub mode: Option<String>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DesaturateParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Gray value formula: "luminance" (default, perceptual weights), "average", or "lightness"
+    pub method: Option<String>,
+    /// How far to desaturate (0-100, default: 100 for full grayscale)
+    pub amount: Option<u32>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ShiftHueRangeParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Center of the hue range to affect, in degrees (0-360, e.g. 120 for greens)
+    pub target_hue: f64,
+    /// Tolerance in degrees on either side of target_hue (0-180; 180 matches every hue)
+    pub hue_range: f64,
+    /// Hue shift for matching pixels in degrees (-180 to 180, default: 0)
+    pub hue_shift: Option<f64>,
+    /// Saturation adjustment for matching pixels (-100 to 100, default: 0)
+    pub saturation_shift: Option<f64>,
+    /// Lightness adjustment for matching pixels (-100 to 100, default: 0)
+    pub lightness_shift: Option<f64>,
+    /// Target layer name (if omitted, all image layers)
+    pub layer: Option<String>,
+    /// Target frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (if omitted, all frames)
+    pub frame: Option<FrameRef>,
+    /// Limit the filter to a selection: a rectangle, a polygon point list, or "from_slice:<name>"
+    pub selection: Option<SelectionSpec>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
@@ -53,32 +228,45 @@ pub async fn brightness_contrast(
     server: &AsepriteServer,
     p: BrightnessContrastParams,
 ) -> Result<String, String> {
-    let brightness = p.brightness.clamp(-100, 100);
-    let contrast = p.contrast.clamp(-100, 100);
+    let lenient = p.lenient.unwrap_or(false);
+    let brightness = check_range("brightness", p.brightness, -100..=100, lenient)?;
+    let contrast = check_range("contrast", p.contrast, -100..=100, lenient)?;
+    let selection = lua_apply_selection(&p.selection)?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
+{selection}
 app.command.BrightnessContrast {{
     ui = false,
     brightness = {brightness},
     contrast = {contrast}
 }}
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "applied", filter = "brightness_contrast", brightness = {brightness}, contrast = {contrast}}}))"#,
+        selection = selection,
         brightness = brightness,
-        contrast = contrast
+        contrast = contrast,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn hue_saturation(
     server: &AsepriteServer,
     p: HueSaturationParams,
 ) -> Result<String, String> {
-    let hue = p.hue.clamp(-180, 180);
-    let saturation = p.saturation.clamp(-100, 100);
-    let lightness = p.lightness.unwrap_or(0).clamp(-100, 100);
+    let lenient = p.lenient.unwrap_or(false);
+    let hue = check_range("hue", p.hue, -180..=180, lenient)?;
+    let saturation = check_range("saturation", p.saturation, -100..=100, lenient)?;
+    let lightness = check_range("lightness", p.lightness.unwrap_or(0), -100..=100, lenient)?;
+    let selection = lua_apply_selection(&p.selection)?;
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
+{selection}
 app.command.HueSaturation {{
     ui = false,
     hue = {hue},
@@ -86,42 +274,730 @@ app.command.HueSaturation {{
     lightness = {lightness},
     mode = "hsl"
 }}
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "applied", filter = "hue_saturation", hue = {hue}, saturation = {saturation}, lightness = {lightness}}}))"#,
+        selection = selection,
         hue = hue,
         saturation = saturation,
-        lightness = lightness
+        lightness = lightness,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn invert_color(
     server: &AsepriteServer,
     p: InvertColorParams,
 ) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
-app.command.InvertColor {
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+{selection}
+app.command.InvertColor {{
     ui = false
-}
-spr:saveAs(spr.filename)
-print(json.encode({status = "applied", filter = "invert_color"}))"#;
-    server.execute_script_on_file(&p.file_path, script).await
+}}
+{save}
+print(json.encode({{status = "applied", filter = "invert_color"}}))"#,
+        selection = lua_apply_selection(&p.selection)?,
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
+/// Largest median filter matrix Aseprite's Despeckle accepts.
+const MAX_DESPECKLE_SIZE: u32 = 100;
+
 pub async fn despeckle(server: &AsepriteServer, p: DespeckleParams) -> Result<String, String> {
-    let width = p.width.unwrap_or(3).max(1);
-    let height = p.height.unwrap_or(3).max(1);
-    let script = format!(
-        r#"local spr = app.sprite
+    let lenient = p.lenient.unwrap_or(false);
+    let width = check_range("width", p.width.unwrap_or(3), 1..=MAX_DESPECKLE_SIZE, lenient)?;
+    let height = check_range("height", p.height.unwrap_or(3), 1..=MAX_DESPECKLE_SIZE, lenient)?;
+    let selection = &lua_apply_selection(&p.selection)?;
+    let targets = resolve_file_list(
+        server,
+        p.file_path.as_deref(),
+        p.file_paths.as_deref(),
+        p.glob.as_deref(),
+        p.output_path.as_deref(),
+    )?;
+    let output_path = p.output_path.as_deref();
+    targets
+        .run(|file| async move {
+            let (save, saved_to) = lua_save_target(server, &file, output_path)?;
+            let script = format!(
+                r#"local spr = app.sprite
+{selection}
 app.command.Despeckle {{
     ui = false,
     width = {width},
     height = {height}
 }}
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "applied", filter = "despeckle", width = {width}, height = {height}}}))"#,
-        width = width,
-        height = height
+                selection = selection,
+                width = width,
+                height = height,
+                save = save
+            );
+            server
+                .execute_script_on_file(&file, &script)
+                .await
+                .map(|output| with_saved_to(output, &saved_to))
+        })
+        .await
+}
+
+pub async fn color_curve(server: &AsepriteServer, p: ColorCurveParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    // Each channel maps to the range of LUTs (r, g, b, a) it replaces
+    let (channel, slots) = check_choice(
+        "channel",
+        p.channel.as_deref().unwrap_or("rgb"),
+        &[
+            ("rgb", ("rgb", (0, 3))),
+            ("r", ("r", (0, 1))),
+            ("g", ("g", (1, 2))),
+            ("b", ("b", (2, 3))),
+            ("alpha", ("alpha", (3, 4))),
+        ],
+        p.lenient.unwrap_or(false),
+    )?;
+    let uses_levels = p.black_point.is_some() || p.white_point.is_some() || p.gamma.is_some();
+    let lut = match (&p.points, uses_levels) {
+        (Some(points), false) => curve_lut(points)?,
+        (None, true) => levels_lut(
+            p.black_point.unwrap_or(0),
+            p.white_point.unwrap_or(255),
+            p.gamma.unwrap_or(1.0),
+        )?,
+        (Some(_), true) => return Err("Use either points or black_point/white_point/gamma, not both".to_string()),
+        (None, false) => return Err("Provide curve points or black_point/white_point/gamma".to_string()),
+    };
+    let mut luts = [identity_lut(); 4];
+    luts[slots.0..slots.1].fill(lut);
+
+    let setup = format!(
+        "local lut_r = {}\nlocal lut_g = {}\nlocal lut_b = {}\nlocal lut_a = {}",
+        lua_lut(&luts[0]),
+        lua_lut(&luts[1]),
+        lua_lut(&luts[2]),
+        lua_lut(&luts[3])
+    );
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        "r, g, b, a = lut_r[r + 1], lut_g[g + 1], lut_b[b + 1], lut_a[a + 1]",
+        &format!(r#"filter = "color_curve", channel = {}"#, lua_string(channel)),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn posterize(server: &AsepriteServer, p: PosterizeParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    let levels = check_range("levels", p.levels, 2..=64, p.lenient.unwrap_or(false))?;
+    let steps = (levels - 1) as f64;
+    let lut: Lut = std::array::from_fn(|i| {
+        let level = (i as f64 / 255.0 * steps).round();
+        (level / steps * 255.0).round() as u8
+    });
+    let setup = format!("local lut = {}", lua_lut(&lut));
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        "r, g, b = lut[r + 1], lut[g + 1], lut[b + 1]",
+        &format!(r#"filter = "posterize", levels = {}"#, levels),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn tint(server: &AsepriteServer, p: TintParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    let (tr, tg, tb, _) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let lenient = p.lenient.unwrap_or(false);
+    let strength = check_range("strength", p.strength.unwrap_or(100), 0..=100, lenient)?;
+    let mode = check_choice(
+        "mode",
+        p.mode.as_deref().unwrap_or("colorize"),
+        &[("colorize", "colorize"), ("multiply", "multiply"), ("overlay", "overlay")],
+        lenient,
+    )?;
+    let tint_pixel = match mode {
+        "multiply" => format!(
+            "local function tint_pixel(r, g, b)\n    return r * {tr} / 255, g * {tg} / 255, b * {tb} / 255\nend",
+            tr = tr,
+            tg = tg,
+            tb = tb
+        ),
+        "overlay" => format!(
+            r#"local function overlay(base, t)
+    if base < 128 then return 2 * base * t / 255 end
+    return 255 - 2 * (255 - base) * (255 - t) / 255
+end
+local function tint_pixel(r, g, b)
+    return overlay(r, {tr}), overlay(g, {tg}), overlay(b, {tb})
+end"#,
+            tr = tr,
+            tg = tg,
+            tb = tb
+        ),
+        _ => {
+            let (h, s, _) = rgb_to_hsl(tr, tg, tb);
+            format!(
+                r#"{hsl}
+local function tint_pixel(r, g, b)
+    local l = (math.max(r, g, b) + math.min(r, g, b)) / 510
+    return hsl_to_rgb({h}, {s}, l)
+end"#,
+                hsl = LUA_HSL,
+                h = h,
+                s = s
+            )
+        }
+    };
+    let setup = format!(
+        r#"{tint_pixel}
+local k = {strength} / 100
+local function mix(v, t)
+    return math.max(0, math.min(255, math.floor(v + (t - v) * k + 0.5)))
+end"#,
+        tint_pixel = tint_pixel,
+        strength = strength
+    );
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        "if a > 0 then\n    local nr, ng, nb = tint_pixel(r, g, b)\n    r, g, b = mix(r, nr), mix(g, ng), mix(b, nb)\nend",
+        &format!(
+            r#"filter = "tint", mode = "{}", color = {}, strength = {}"#,
+            mode,
+            lua_string(&p.color),
+            strength
+        ),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn desaturate(server: &AsepriteServer, p: DesaturateParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    let lenient = p.lenient.unwrap_or(false);
+    let (method, gray) = check_choice(
+        "method",
+        p.method.as_deref().unwrap_or("luminance"),
+        &[
+            ("luminance", ("luminance", "0.299 * r + 0.587 * g + 0.114 * b")),
+            ("average", ("average", "(r + g + b) / 3")),
+            ("lightness", ("lightness", "(math.max(r, g, b) + math.min(r, g, b)) / 2")),
+        ],
+        lenient,
+    )?;
+    let amount = check_range("amount", p.amount.unwrap_or(100), 0..=100, lenient)?;
+    let setup = format!(
+        r#"local k = {amount} / 100
+local function mix(v, t)
+    return math.max(0, math.min(255, math.floor(v + (t - v) * k + 0.5)))
+end"#,
+        amount = amount
+    );
+    let map_pixel = format!(
+        "local v = {}\nr, g, b = mix(r, v), mix(g, v), mix(b, v)",
+        gray
+    );
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        &map_pixel,
+        &format!(r#"filter = "desaturate", method = "{}", amount = {}"#, method, amount),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn shift_hue_range(server: &AsepriteServer, p: ShiftHueRangeParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    let lenient = p.lenient.unwrap_or(false);
+    let window = hue_window(
+        check_range("target_hue", p.target_hue, 0.0..=360.0, lenient)?,
+        check_range("hue_range", p.hue_range, 0.0..=180.0, lenient)?,
+    );
+    let hue_shift = check_range("hue_shift", p.hue_shift.unwrap_or(0.0), -180.0..=180.0, lenient)?;
+    let saturation_shift = check_range("saturation_shift", p.saturation_shift.unwrap_or(0.0), -100.0..=100.0, lenient)?;
+    let lightness_shift = check_range("lightness_shift", p.lightness_shift.unwrap_or(0.0), -100.0..=100.0, lenient)?;
+
+    let setup = format!(
+        r#"{hsl}
+local hue_lo, hue_hi, hue_wraps = {lo}, {hi}, {wraps}
+local function hue_matches(deg)
+    if hue_wraps then return deg >= hue_lo or deg <= hue_hi end
+    return deg >= hue_lo and deg <= hue_hi
+end
+local function clamp01(v) return math.max(0, math.min(1, v)) end
+local function round8(v) return math.max(0, math.min(255, math.floor(v + 0.5))) end
+local matched = 0"#,
+        hsl = LUA_HSL,
+        lo = window.start,
+        hi = window.end,
+        wraps = window.wraps
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    // Gray pixels have no hue, so they never match
+    let map_pixel = format!(
+        r#"if a > 0 then
+    local h, s, l = rgb_to_hsl(r, g, b)
+    if s > 0 and hue_matches(h * 360) then
+        matched = matched + 1
+        h = (h + {dh} / 360) % 1
+        s = clamp01(s + {ds} / 100)
+        l = clamp01(l + {dl} / 100)
+        local nr, ng, nb = hsl_to_rgb(h, s, l)
+        r, g, b = round8(nr), round8(ng), round8(nb)
+    end
+end"#,
+        dh = hue_shift,
+        ds = saturation_shift,
+        dl = lightness_shift
+    );
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        &map_pixel,
+        &format!(
+            r#"filter = "shift_hue_range", target_hue = {}, hue_range = {}, hue_shift = {}, saturation_shift = {}, lightness_shift = {}, pixels_matched = matched"#,
+            p.target_hue, p.hue_range, hue_shift, saturation_shift, lightness_shift
+        ),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Inclusive hue window in degrees; when `wraps` is set it spans 360/0 (e.g. 350 to 10).
+struct HueWindow {
+    start: f64,
+    end: f64,
+    wraps: bool,
+}
+
+/// Turn a target hue (0-360) and tolerance (0-180) into a normalized window, handling
+/// wraparound at 0/360.
+fn hue_window(target: f64, range: f64) -> HueWindow {
+    if range >= 180.0 {
+        return HueWindow {
+            start: 0.0,
+            end: 360.0,
+            wraps: false,
+        };
+    }
+    let start = (target - range).rem_euclid(360.0);
+    let end = (target + range).rem_euclid(360.0);
+    HueWindow {
+        start,
+        end,
+        wraps: start > end,
+    }
+}
+
+/// Size of each noise table; prime so the coordinate hash spreads evenly.
+const NOISE_TABLE_SIZE: usize = 8191;
+
+pub async fn add_noise(server: &AsepriteServer, p: AddNoiseParams) -> Result<String, String> {
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?;
+    let amount = check_range("amount", p.amount, 0..=100, p.lenient.unwrap_or(false))?;
+    let monochrome = p.monochrome.unwrap_or(false);
+    let seed = p.seed.unwrap_or(0);
+    let max_offset = (amount * 128 / 100) as i64;
+
+    // Offsets come from a seeded generator here rather than Lua's math.random, so a seed gives
+    // identical output everywhere. Pixels pick an entry by hashing their sprite coordinates,
+    // which keeps the grain stable across frames.
+    let mut rng = SplitMix64(seed);
+    let mut table = || {
+        let values: Vec<String> = (0..NOISE_TABLE_SIZE)
+            .map(|_| {
+                let offset = (rng.next() % (2 * max_offset as u64 + 1)) as i64 - max_offset;
+                offset.to_string()
+            })
+            .collect();
+        format!("{{{}}}", values.join(","))
+    };
+    let (setup, map_pixel) = if monochrome {
+        (
+            format!("local noise = {}", table()),
+            "if a > 0 then\n    local d = noise[noise_index(x, y)]\n    r, g, b = clamp8(r + d), clamp8(g + d), clamp8(b + d)\nend",
+        )
+    } else {
+        (
+            format!(
+                "local noise_r = {}\nlocal noise_g = {}\nlocal noise_b = {}",
+                table(),
+                table(),
+                table()
+            ),
+            "if a > 0 then\n    local i = noise_index(x, y)\n    r, g, b = clamp8(r + noise_r[i]), clamp8(g + noise_g[i]), clamp8(b + noise_b[i])\nend",
+        )
+    };
+    let setup = format!(
+        r#"{setup}
+local function noise_index(x, y)
+    return ((x * 73856093) ~ (y * 19349663)) % {size} + 1
+end
+local function clamp8(v)
+    if v < 0 then return 0 elseif v > 255 then return 255 end
+    return v
+end"#,
+        setup = setup,
+        size = NOISE_TABLE_SIZE
+    );
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = pixel_filter_script(
+        PixelTargets {
+            layer: p.layer.as_deref(),
+            from_frame: frame,
+            to_frame: frame,
+            selection: &p.selection,
+        },
+        &setup,
+        map_pixel,
+        &format!(
+            r#"filter = "add_noise", amount = {}, monochrome = {}, seed = {}"#,
+            amount, monochrome, seed
+        ),
+        &save,
+    )?;
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Small, fast seeded generator (SplitMix64) for reproducible noise.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+// ============================================================================
+// Per-pixel filter helpers
+// ============================================================================
+
+/// A 256-entry lookup table mapping one 8-bit channel value to another.
+pub(crate) type Lut = [u8; 256];
+
+/// Which cels a per-pixel filter touches.
+pub(crate) struct PixelTargets<'a> {
+    /// Layer name; all image layers when `None`
+    pub layer: Option<&'a str>,
+    /// First 1-based frame; the sprite's first frame when `None`
+    pub from_frame: Option<u32>,
+    /// Last 1-based frame (inclusive); the sprite's last frame when `None`
+    pub to_frame: Option<u32>,
+    /// Optional selection restricting the pixels that change
+    pub selection: &'a Option<SelectionSpec>,
+}
+
+pub(crate) fn identity_lut() -> Lut {
+    std::array::from_fn(|i| i as u8)
+}
+
+/// Build a LUT from curve control points, interpolating linearly between them.
+fn curve_lut(points: &[CurvePoint]) -> Result<Lut, String> {
+    if points.is_empty() {
+        return Err("points must contain at least one control point".to_string());
+    }
+    let mut pts: Vec<(f64, f64)> = points.iter().map(|pt| (pt.input as f64, pt.output as f64)).collect();
+    pts.sort_by(|a, b| a.0.total_cmp(&b.0));
+    pts.dedup_by(|a, b| a.0 == b.0);
+    if pts[0].0 > 0.0 {
+        pts.insert(0, (0.0, 0.0));
+    }
+    if pts[pts.len() - 1].0 < 255.0 {
+        pts.push((255.0, 255.0));
+    }
+    Ok(std::array::from_fn(|i| {
+        let x = i as f64;
+        let seg = pts.windows(2).find(|w| x <= w[1].0).unwrap_or(&pts[pts.len() - 2..]);
+        let (x0, y0) = seg[0];
+        let (x1, y1) = seg[1];
+        let t = if x1 > x0 { (x - x0) / (x1 - x0) } else { 0.0 };
+        (y0 + (y1 - y0) * t).round().clamp(0.0, 255.0) as u8
+    }))
+}
+
+/// Build a LUT for a levels adjustment (input black/white points plus gamma).
+fn levels_lut(black: u8, white: u8, gamma: f64) -> Result<Lut, String> {
+    if black >= white {
+        return Err(format!("black_point ({}) must be lower than white_point ({})", black, white));
+    }
+    if !(gamma > 0.0 && gamma.is_finite()) {
+        return Err("gamma must be a positive number".to_string());
+    }
+    let (black, white) = (black as f64, white as f64);
+    Ok(std::array::from_fn(|i| {
+        let t = ((i as f64 - black) / (white - black)).clamp(0.0, 1.0);
+        (t.powf(1.0 / gamma) * 255.0).round() as u8
+    }))
+}
+
+/// Render a LUT as a Lua table literal (index with `value + 1`).
+pub(crate) fn lua_lut(lut: &Lut) -> String {
+    let values: Vec<String> = lut.iter().map(|v| v.to_string()).collect();
+    format!("{{{}}}", values.join(","))
+}
+
+/// Build a script that rewrites every pixel of the targeted cels in one transaction and runs `save`.
+/// `setup` runs once; `map_pixel` runs per pixel with `r, g, b, a` locals (plus `x, y` in sprite
+/// coordinates) and must leave the new channel values in them. Only RGB sprites are supported.
+/// The result JSON contains `fields` plus the number of cels and pixels changed and the unique
+/// colors among the affected pixels before and after.
+pub(crate) fn pixel_filter_script(
+    targets: PixelTargets,
+    setup: &str,
+    map_pixel: &str,
+    fields: &str,
+    save: &str,
+) -> Result<String, String> {
+    let selection = lua_apply_selection(targets.selection)?;
+    let layers = match targets.layer {
+        Some(name) => format!(
+            r#"{find_layer}
+local target_layer = find_layer(spr.layers, {name})
+if not target_layer or not target_layer.isImage then
+    print(json.encode({{error = "Image layer not found: " .. {name}}}))
+    return
+end
+local layers = {{ target_layer }}"#,
+            find_layer = LUA_FIND_LAYER,
+            name = lua_string(name)
+        ),
+        None => format!("{}\nlocal layers = collect_image_layers(spr.layers, {{}}, false)", LUA_COLLECT_IMAGE_LAYERS),
+    };
+    if targets.from_frame == Some(0) || targets.to_frame == Some(0) {
+        return Err("Frame numbers are 1-based and must be >= 1".to_string());
+    }
+    if let (Some(from), Some(to)) = (targets.from_frame, targets.to_frame)
+        && from > to
+    {
+        return Err(format!("from_frame ({}) must not be after to_frame ({})", from, to));
+    }
+    let frames = format!(
+        r#"local from_f, to_f = {from}, {to}
+if from_f > to_f or to_f > #spr.frames then
+    print(json.encode({{error = "Frames " .. from_f .. "-" .. to_f .. " out of range (sprite has " .. #spr.frames .. " frames)"}}))
+    return
+end
+local frames = {{}}
+for i = from_f, to_f do frames[#frames + 1] = i end"#,
+        from = targets.from_frame.unwrap_or(1),
+        to = targets.to_frame.map(|t| t.to_string()).unwrap_or_else(|| "#spr.frames".to_string())
+    );
+
+    Ok(format!(
+        r#"local spr = app.sprite
+if spr.colorMode ~= ColorMode.RGB then
+    print(json.encode({{error = "This filter requires an RGB sprite"}}))
+    return
+end
+{layers}
+{frames}
+{selection}
+local sel = spr.selection
+local has_sel = not sel.isEmpty
+local pc = app.pixelColor
+{setup}
+local before, after = {{}}, {{}}
+local n_before, n_after, cels, changed = 0, 0, 0, 0
+-- Linked cels share one image: filter it once, or later frames would filter the result again
+local seen = {{}}
+app.transaction("Pixel Filter", function()
+    for _, l in ipairs(layers) do
+        for _, f in ipairs(frames) do
+            local cel = l:cel(f)
+            if cel and not seen[cel.image.id] then
+                seen[cel.image.id] = true
+                local img = cel.image:clone()
+                local ox, oy = cel.position.x, cel.position.y
+                for it in img:pixels() do
+                    local x, y = it.x + ox, it.y + oy
+                    if not has_sel or sel:contains(x, y) then
+                        local px = it()
+                        local r, g, b, a = pc.rgbaR(px), pc.rgbaG(px), pc.rgbaB(px), pc.rgbaA(px)
+                        {map_pixel}
+                        local np = pc.rgba(r, g, b, a)
+                        if not before[px] then before[px] = true; n_before = n_before + 1 end
+                        if not after[np] then after[np] = true; n_after = n_after + 1 end
+                        if np ~= px then
+                            it(np)
+                            changed = changed + 1
+                        end
+                    end
+                end
+                cel.image = img
+                seen[cel.image.id] = true
+                cels = cels + 1
+            end
+        end
+    end
+end)
+{save}
+print(json.encode({{status = "applied", {fields}, cels = cels, pixels_changed = changed, colors_before = n_before, colors_after = n_after}}))"#,
+        layers = layers,
+        frames = frames,
+        selection = selection,
+        setup = setup,
+        map_pixel = map_pixel,
+        fields = fields,
+        save = save
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, planned_script};
+
+    #[test]
+    fn split_mix_64_matches_the_reference_sequence() {
+        let mut rng = SplitMix64(0);
+        assert_eq!(rng.next(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next(), 0x6E78_9E6A_A1B9_65F4);
+    }
+
+    fn window(target: f64, range: f64) -> (f64, f64, bool) {
+        let w = hue_window(target, range);
+        (w.start, w.end, w.wraps)
+    }
+
+    #[test]
+    fn hue_window_wraps_around_zero() {
+        assert_eq!(window(120.0, 30.0), (90.0, 150.0, false));
+        assert_eq!(window(0.0, 10.0), (350.0, 10.0, true));
+        assert_eq!(window(355.0, 15.0), (340.0, 10.0, true));
+        assert_eq!(window(10.0, 10.0), (0.0, 20.0, false));
+        assert_eq!(window(200.0, 180.0), (0.0, 360.0, false));
+    }
+
+    fn noise_params(seed: u64) -> AddNoiseParams {
+        serde_json::from_value(serde_json::json!({
+            "file_path": "/art/grass.aseprite",
+            "amount": 40,
+            "seed": seed,
+        }))
+        .unwrap()
+    }
+
+    fn multiply_tint(file_path: &str) -> TintParams {
+        serde_json::from_value(serde_json::json!({
+            "file_path": file_path,
+            "color": "#808080",
+            "mode": "multiply",
+        }))
+        .unwrap()
+    }
+
+    fn curve(channel: &str, lenient: bool) -> ColorCurveParams {
+        serde_json::from_value(serde_json::json!({
+            "file_path": "/art/in.aseprite",
+            "channel": channel,
+            "gamma": 2.0,
+            "lenient": lenient,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn color_curve_channel_uses_the_shared_choice_check() {
+        let server = dry_server();
+        let err = color_curve(&server, curve("hue", false)).await.unwrap_err();
+        assert_eq!(err, "Invalid channel 'hue'. Must be one of: rgb, r, g, b, alpha");
+        let script = planned_script(color_curve(&server, curve("hue", true))).await;
+        assert!(script.contains(r#"channel = "rgb""#), "{}", script);
+        let script = planned_script(color_curve(&server, curve("G", false))).await;
+        assert!(script.contains(r#"channel = "g""#), "{}", script);
+    }
+
+    #[tokio::test]
+    async fn pixel_filters_skip_cels_linked_to_one_already_filtered() {
+        let script = planned_script(tint(&dry_server(), multiply_tint("/art/in.aseprite"))).await;
+        let skip = script.find("if cel and not seen[cel.image.id] then").expect("linked cel check");
+        let mark = script.find("cel.image = img\n                seen[cel.image.id] = true").expect("new image marked");
+        assert!(skip < mark);
+    }
+
+    #[tokio::test]
+    async fn linked_frames_are_tinted_once() {
+        let Some(server) = crate::test_support::real_server() else { return };
+        let path = crate::test_support::scratch_dir("linked_tint").join("linked.aseprite").to_string_lossy().to_string();
+        crate::test_support::save_linked_frames(&server, &path, "#ffffff").await;
+
+        let result: serde_json::Value = serde_json::from_str(&tint(&server, multiply_tint(&path)).await.unwrap()).unwrap();
+        assert_eq!(result["cels"], 1);
+        assert_eq!(result["pixels_changed"], 1);
+    }
+
+    #[tokio::test]
+    async fn add_noise_is_reproducible_for_a_seed() {
+        let server = dry_server();
+        let first = planned_script(add_noise(&server, noise_params(7))).await;
+        let again = planned_script(add_noise(&server, noise_params(7))).await;
+        let other = planned_script(add_noise(&server, noise_params(8))).await;
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+    }
 }
@@ -1,12 +1,28 @@
 use rmcp::schemars;
 use serde::Deserialize;
+use serde_json::json;
 
+use crate::lua_helpers::{DataFile, LUA_SAVE_IN_PLACE, lua_check_frame, lua_fragment_script, lua_pager, lua_require_tag};
 use crate::server::AsepriteServer;
 
 // ============================================================================
 // Parameter Structs
 // ============================================================================
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListFramesParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// First frame to include: 1-based number or "tag:n" / "tag:start" / "tag:end" (default: 1)
+    pub from_frame: Option<FrameRef>,
+    /// Last frame to include, same format (default: last frame)
+    pub to_frame: Option<FrameRef>,
+    /// Maximum number of entries to return (default: all)
+    pub limit: Option<u32>,
+    /// Number of matching entries to skip before returning (default: 0)
+    pub offset: Option<u32>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct AddFrameParams {
     /// Path to the sprite file
@@ -29,27 +45,72 @@ pub struct RemoveFrameParams {
 pub struct SetFrameDurationParams {
     /// Path to the sprite file
     pub file_path: String,
+    #[serde(flatten)]
+    pub op: SetFrameDurationOp,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetFrameDurationOp {
     /// Frame number (1-based)
     pub frame_number: u32,
     /// Duration in milliseconds
     pub duration_ms: u32,
 }
 
+/// A frame given by number or relative to an animation tag, which stays valid when frames
+/// are inserted or removed elsewhere.
+#[derive(Debug, Clone, Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum FrameRef {
+    /// 1-based frame number
+    Number(u32),
+    /// "walk:3" (third frame of tag "walk"), "walk:start", "walk:end", or a number as a string
+    Tag(String),
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FindDuplicateFramesParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Only check the frames of this animation tag
+    pub tag: Option<String>,
+    /// First frame to check, 1-based (default: 1; not combinable with tag)
+    pub from_frame: Option<u32>,
+    /// Last frame to check, 1-based inclusive (default: last frame; not combinable with tag)
+    pub to_frame: Option<u32>,
+    /// Max per-channel difference for pixels to count as equal (default: 0, exact match).
+    /// Above 0, frames are compared on a downsampled copy (at most 64 px on the larger side).
+    pub tolerance: Option<u8>,
+    /// "merge_durations": delete each duplicate that directly follows an equal frame and add its
+    /// duration to the frame it repeats. Non-adjacent duplicates are only reported.
+    pub apply: Option<String>,
+}
+
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
-pub async fn list_frames(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
-local frames = {}
-for i, frame in ipairs(spr.frames) do
-    local f = {}
-    f.frameNumber = frame.frameNumber
-    f.duration = frame.duration
-    table.insert(frames, f)
+pub async fn list_frames(server: &AsepriteServer, p: ListFramesParams) -> Result<String, String> {
+    let range = lua_frame_range(server, &p.file_path, p.from_frame.as_ref(), p.to_frame.as_ref()).await?;
+    let script = format!(
+        r#"local spr = app.sprite
+{range}
+{pager}
+local frames = {{}}
+for i = from_f, math.min(to_f, #spr.frames) do
+    if in_page() then
+        local frame = spr.frames[i]
+        local f = {{}}
+        f.frameNumber = frame.frameNumber
+        f.duration = frame.duration
+        table.insert(frames, f)
+    end
 end
-print(json.encode({frames = frames, total = #frames}))"#;
-    server.execute_script_on_file(file_path, script).await
+print(json.encode({{frames = frames, total = matched, returned = #frames}}))"#,
+        range = range,
+        pager = lua_pager(p.limit, p.offset)
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn add_frame(server: &AsepriteServer, p: AddFrameParams) -> Result<String, String> {
@@ -62,44 +123,301 @@ pub async fn add_frame(server: &AsepriteServer, p: AddFrameParams) -> Result<Str
 for i = 1, {count} do
     spr:{frame_fn}(#spr.frames + 1)
 end
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "added", count = {count}, totalFrames = #spr.frames}}))"#,
         count = count,
-        frame_fn = frame_fn
+        frame_fn = frame_fn,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
-pub async fn remove_frame(server: &AsepriteServer, p: RemoveFrameParams) -> Result<String, String> {
-    let frame_num = p.frame_number;
+/// `resolve_frame` for an optional frame parameter.
+pub async fn resolve_optional_frame(
+    server: &AsepriteServer,
+    file_path: &str,
+    frame: Option<&FrameRef>,
+) -> Result<Option<u32>, String> {
+    match frame {
+        Some(frame) => resolve_frame(server, file_path, frame).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Resolve optional `from_frame`/`to_frame` parameters into the Lua locals `from_f` and `to_f`,
+/// defaulting to the whole sprite. `to_f` isn't clamped to the sprite's frame count.
+pub async fn lua_frame_range(
+    server: &AsepriteServer,
+    file_path: &str,
+    from_frame: Option<&FrameRef>,
+    to_frame: Option<&FrameRef>,
+) -> Result<String, String> {
+    let from = resolve_optional_frame(server, file_path, from_frame).await?;
+    let to = resolve_optional_frame(server, file_path, to_frame).await?;
+    if from == Some(0) || to == Some(0) {
+        return Err("Frame numbers are 1-based and must be >= 1".to_string());
+    }
+    if let (Some(from), Some(to)) = (from, to)
+        && from > to
+    {
+        return Err(format!("from_frame ({}) must not be after to_frame ({})", from, to));
+    }
+    Ok(format!(
+        "local from_f, to_f = {}, {}",
+        from.unwrap_or(1),
+        to.map_or("#spr.frames".to_string(), |t| t.to_string())
+    ))
+}
+
+/// Resolve a frame reference to a 1-based frame number. Plain numbers are returned as-is; tag
+/// references are looked up in `file_path` with a short Aseprite run.
+pub async fn resolve_frame(server: &AsepriteServer, file_path: &str, frame: &FrameRef) -> Result<u32, String> {
+    let text = match frame {
+        FrameRef::Number(n) => return Ok(*n),
+        FrameRef::Tag(text) => text.trim(),
+    };
+    if let Ok(n) = text.parse::<u32>() {
+        return Ok(n);
+    }
+    let Some((tag, offset)) = text.rsplit_once(':') else {
+        return Err(format!(
+            "Invalid frame '{}': use a frame number or \"tag:offset\" (e.g. \"walk:3\", \"walk:start\", \"walk:end\")",
+            text
+        ));
+    };
+    let offset_code = match offset.trim() {
+        "start" => "1".to_string(),
+        "end" => "tag.frames".to_string(),
+        n => match n.parse::<u32>() {
+            Ok(n) if n >= 1 => n.to_string(),
+            _ => {
+                return Err(format!(
+                    "Invalid frame offset '{}' in '{}': use a 1-based number, \"start\", or \"end\"",
+                    n, text
+                ));
+            }
+        },
+    };
     let script = format!(
         r#"local spr = app.sprite
-if {fnum} > #spr.frames then
-    print(json.encode({{error = "Frame number out of range"}}))
+{require_tag}
+local offset = {offset}
+if offset > tag.frames then
+    print(json.encode({{error = "Tag " .. tag.name .. " has " .. tag.frames .. " frames (" .. tag.fromFrame.frameNumber .. "-" .. tag.toFrame.frameNumber .. "); offset " .. offset .. " is past its end"}}))
     return
 end
+print(json.encode({{frame = tag.fromFrame.frameNumber + offset - 1}}))"#,
+        require_tag = lua_require_tag("tag", tag),
+        offset = offset_code
+    );
+    let output = server.execute_script_on_file(file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    json["frame"]
+        .as_u64()
+        .map(|f| f as u32)
+        .ok_or_else(|| format!("Could not resolve frame '{}': {}", text, output))
+}
+
+pub async fn remove_frame(server: &AsepriteServer, p: RemoveFrameParams) -> Result<String, String> {
+    let frame_num = p.frame_number;
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
 spr:deleteFrame({fnum})
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "deleted", frameNumber = {fnum}, totalFrames = #spr.frames}}))"#,
-        fnum = frame_num
+        check_frame = lua_check_frame(frame_num),
+        fnum = frame_num,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn set_frame_duration(server: &AsepriteServer, p: SetFrameDurationParams) -> Result<String, String> {
+    let script = lua_fragment_script(&set_frame_duration_fragment(&p.op), LUA_SAVE_IN_PLACE);
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+/// Lua fragment setting the frame duration (see `lua_fragment_script`).
+pub fn set_frame_duration_fragment(p: &SetFrameDurationOp) -> String {
     let duration_sec = p.duration_ms as f64 / 1000.0;
-    let script = format!(
-        r#"local spr = app.sprite
+    format!(
+        r#"{check_frame}
 local frame = spr.frames[{frame}]
-if not frame then
-    print(json.encode({{error = "Frame not found"}}))
-    return
-end
 frame.duration = {dur}
-spr:saveAs(spr.filename)
-print(json.encode({{status = "updated", frameNumber = {frame}, duration = {dur}}}))"#,
+result = {{status = "updated", frameNumber = {frame}, duration = {dur}}}"#,
+        check_frame = lua_check_frame(p.frame_number),
         frame = p.frame_number,
         dur = duration_sec
+    )
+}
+
+/// Largest side of the downsampled copy used for tolerant frame comparison.
+const DUPLICATE_SAMPLE_SIZE: u32 = 64;
+
+pub async fn find_duplicate_frames(server: &AsepriteServer, p: FindDuplicateFramesParams) -> Result<String, String> {
+    let merge = match p.apply.as_deref() {
+        None => false,
+        Some("merge_durations") => true,
+        Some(other) => return Err(format!("Unknown apply '{}'. Use \"merge_durations\"", other)),
+    };
+    if p.tag.is_some() && (p.from_frame.is_some() || p.to_frame.is_some()) {
+        return Err("tag cannot be combined with from_frame/to_frame".to_string());
+    }
+    let tolerance = p.tolerance.unwrap_or(0);
+
+    let range_code = match p.tag {
+        Some(ref tag) => format!(
+            r#"{require_tag}
+local from_frame, to_frame = tag.fromFrame.frameNumber, tag.toFrame.frameNumber"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        None => format!(
+            r#"local from_frame, to_frame = {from}, {to}
+if from_frame < 1 or to_frame > #spr.frames or from_frame > to_frame then
+    print(json.encode({{error = "Frame range " .. from_frame .. "-" .. to_frame .. " is outside 1-" .. #spr.frames}}))
+    return
+end"#,
+            from = p.from_frame.unwrap_or(1),
+            to = p.to_frame.map_or("#spr.frames".to_string(), |t| t.to_string())
+        ),
+    };
+    // Exact matching groups by the full flattened bytes in Lua; tolerant matching writes a
+    // downsampled RGBA copy of each frame to the data file, in frame order, to compare here
+    let file_path = server.resolve_input_path(&p.file_path)?;
+    let _guards = server.lock_files([&file_path]).await;
+    let sample_file = DataFile::new(server);
+    let script = format!(
+        r#"local spr = app.sprite
+{range_code}
+local tolerant = {tolerant}
+local step = math.max(1, math.ceil(math.max(spr.width, spr.height) / {sample}))
+{open_data}
+local first_with = {{}}
+local frames = {{}}
+for f = from_frame, to_frame do
+    local img = Image(spr.width, spr.height, ColorMode.RGB)
+    img:drawSprite(spr, f)
+    local entry = {{frame = f}}
+    if tolerant then
+        local sample = {{}}
+        for y = 0, spr.height - 1, step do
+            for x = 0, spr.width - 1, step do
+                sample[#sample + 1] = string.pack("<I4", img:getPixel(x, y))
+            end
+        end
+        data_file:write(table.concat(sample))
+    else
+        local key = img.bytes
+        if not first_with[key] then first_with[key] = f end
+        entry.same_as = first_with[key]
+    end
+    frames[#frames + 1] = entry
+end
+data_file:close()
+print(json.encode({{frames = frames}}))"#,
+        range_code = range_code,
+        tolerant = tolerance > 0,
+        sample = DUPLICATE_SAMPLE_SIZE,
+        open_data = sample_file.lua_open()
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    let output = server.execute_script_on_locked_file(&file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let frames = json["frames"].as_array().cloned().unwrap_or_default();
+    let sample_data = if tolerance > 0 { sample_file.read().await? } else { Vec::new() };
+    let sample_len = sample_data.len() / frames.len().max(1);
+    if tolerance > 0 && (sample_len == 0 || sample_len * frames.len() != sample_data.len()) {
+        return Err("The script returned incomplete frame samples".to_string());
+    }
+
+    // Each group starts with its first frame; later frames join the first group they match
+    let mut groups: Vec<Vec<u32>> = Vec::new();
+    let mut samples: Vec<&[u8]> = Vec::new();
+    for (i, entry) in frames.iter().enumerate() {
+        let frame = entry["frame"].as_u64().unwrap_or(0) as u32;
+        let group = if tolerance > 0 {
+            let data = &sample_data[i * sample_len..(i + 1) * sample_len];
+            let found = samples.iter().position(|s| pixels_within(s, data, tolerance));
+            if found.is_none() {
+                samples.push(data);
+            }
+            found
+        } else {
+            let first = entry["same_as"].as_u64().unwrap_or(0) as u32;
+            groups.iter().position(|g| g[0] == first)
+        };
+        match group {
+            Some(i) => groups[i].push(frame),
+            None => groups.push(vec![frame]),
+        }
+    }
+    groups.retain(|g| g.len() > 1);
+    let duplicate_frames: usize = groups.iter().map(|g| g.len() - 1).sum();
+
+    let mut result = json!({
+        "frames_checked": frames.len(),
+        "tolerance": tolerance,
+        "groups": groups,
+        "duplicate_frames": duplicate_frames,
+    });
+    if !merge {
+        return Ok(result.to_string());
+    }
+
+    // Runs of consecutive frames from the same group collapse into their first frame
+    let mut merges: Vec<(u32, Vec<u32>)> = Vec::new();
+    for group in &groups {
+        for pair in group.windows(2) {
+            if pair[1] != pair[0] + 1 {
+                continue;
+            }
+            match merges.last_mut() {
+                Some((_, removed)) if removed.last() == Some(&pair[0]) => removed.push(pair[1]),
+                _ => merges.push((pair[0], vec![pair[1]])),
+            }
+        }
+    }
+    if merges.is_empty() {
+        result["merged"] = json!([]);
+        return Ok(result.to_string());
+    }
+    merges.sort_by_key(|m| std::cmp::Reverse(m.0));
+    let merge_list: Vec<String> = merges
+        .iter()
+        .map(|(keep, removed)| {
+            let removed: Vec<String> = removed.iter().map(|f| f.to_string()).collect();
+            format!("{{keep = {}, remove = {{{}}}}}", keep, removed.join(", "))
+        })
+        .collect();
+    let script = format!(
+        r#"local spr = app.sprite
+local merges = {{{merges}}}
+local merged = {{}}
+for _, m in ipairs(merges) do
+    local extra = 0
+    for _, f in ipairs(m.remove) do extra = extra + spr.frames[f].duration end
+    for i = #m.remove, 1, -1 do spr:deleteFrame(m.remove[i]) end
+    local kept = spr.frames[m.keep]
+    kept.duration = kept.duration + extra
+    merged[#merged + 1] = {{kept = m.keep, removed = m.remove, duration_ms = math.floor(kept.duration * 1000 + 0.5)}}
+end
+{save}
+print(json.encode({{merged = merged, total_frames = #spr.frames}}))"#,
+        merges = merge_list.join(", "),
+        save = LUA_SAVE_IN_PLACE
+    );
+    let output = server.execute_script_on_locked_file(&file_path, &script).await?;
+    let applied: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    result["merged"] = applied["merged"].clone();
+    result["total_frames"] = applied["total_frames"].clone();
+    Ok(result.to_string())
+}
+
+/// Whether two RGBA buffers match within `tolerance` per channel (fully transparent pixels
+/// match regardless of their color).
+fn pixels_within(a: &[u8], b: &[u8], tolerance: u8) -> bool {
+    a.len() == b.len()
+        && a.chunks_exact(4).zip(b.chunks_exact(4)).all(|(pa, pb)| {
+            (pa[3] == 0 && pb[3] == 0) || pa.iter().zip(pb).all(|(x, y)| x.abs_diff(*y) <= tolerance)
+        })
 }
@@ -1,14 +1,30 @@
 use rmcp::schemars;
 use serde::Deserialize;
 
-use crate::aseprite::{lua_path, lua_string};
-use crate::lua_helpers::LUA_FIND_LAYER;
+use crate::aseprite::lua_string;
+use crate::lua_helpers::{
+    LUA_FIND_LAYER, LUA_SAVE_IN_PLACE, lua_fragment_script, lua_name_filter, lua_pager, lua_require_layer,
+    lua_save_target, with_saved_to,
+};
 use crate::server::AsepriteServer;
+use crate::utils::{check_choice, check_range};
 
 // ============================================================================
 // Parameter Structs
 // ============================================================================
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListLayersParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Only include layers whose name contains this text (case-insensitive)
+    pub name_contains: Option<String>,
+    /// Maximum number of entries to return (default: all)
+    pub limit: Option<u32>,
+    /// Number of matching entries to skip before returning (default: 0)
+    pub offset: Option<u32>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct DuplicateLayerParams {
     /// Path to the sprite file
@@ -39,6 +55,12 @@ pub struct FlattenLayersParams {
 pub struct AddLayerParams {
     /// Path to the sprite file
     pub file_path: String,
+    #[serde(flatten)]
+    pub op: AddLayerOp,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct AddLayerOp {
     /// Name for the new layer
     pub name: String,
     /// Create a group layer instead of a normal layer (default: false)
@@ -69,42 +91,58 @@ pub struct SetLayerPropertyParams {
     pub opacity: Option<u32>,
     /// Set blend mode ("normal", "multiply", "screen", "overlay", "darken", "lighten", etc.)
     pub blend_mode: Option<String>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
 }
 
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
-pub async fn list_layers(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
-local layers = {}
+pub async fn list_layers(server: &AsepriteServer, p: ListLayersParams) -> Result<String, String> {
+    let script = format!(
+        r#"local spr = app.sprite
+{name_filter}
+{pager}
+local layers = {{}}
 local function collect(lyrs, depth, parent_name)
     for i, layer in ipairs(lyrs) do
-        local l = {}
-        l.name = layer.name
-        l.isVisible = layer.isVisible
-        l.isEditable = layer.isEditable
-        l.isGroup = layer.isGroup
-        l.stackIndex = layer.stackIndex
-        l.depth = depth
-        l.parent = parent_name
-        if layer.opacity then l.opacity = layer.opacity end
-        if layer.blendMode then l.blendMode = tostring(layer.blendMode) end
-        l.isBackground = layer.isBackground or false
-        l.isTilemap = layer.isTilemap or false
-        l.numCels = #layer.cels
-        table.insert(layers, l)
+        if name_matches(layer.name) and in_page() then
+            local l = {{}}
+            l.name = layer.name
+            l.isVisible = layer.isVisible
+            l.isEditable = layer.isEditable
+            l.isGroup = layer.isGroup
+            l.stackIndex = layer.stackIndex
+            l.depth = depth
+            l.parent = parent_name
+            if layer.opacity then l.opacity = layer.opacity end
+            if layer.blendMode then l.blendMode = tostring(layer.blendMode) end
+            l.isBackground = layer.isBackground or false
+            l.isTilemap = layer.isTilemap or false
+            l.numCels = #layer.cels
+            table.insert(layers, l)
+        end
         if layer.isGroup and layer.layers then
             collect(layer.layers, depth + 1, layer.name)
         end
     end
 end
 collect(spr.layers, 0, nil)
-print(json.encode({layers = layers, total = #layers}))"#;
-    server.execute_script_on_file(file_path, script).await
+print(json.encode({{layers = layers, total = matched, returned = #layers}}))"#,
+        name_filter = lua_name_filter(p.name_contains.as_deref()),
+        pager = lua_pager(p.limit, p.offset)
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn add_layer(server: &AsepriteServer, p: AddLayerParams) -> Result<String, String> {
+    let script = lua_fragment_script(&add_layer_fragment(&p.op), LUA_SAVE_IN_PLACE);
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+/// Lua fragment creating the layer (see `lua_fragment_script`).
+pub fn add_layer_fragment(p: &AddLayerOp) -> String {
     let is_group = p.is_group.unwrap_or(false);
     let create_fn = if is_group { "newGroup" } else { "newLayer" };
     let after_code = if let Some(ref after) = p.after_layer {
@@ -123,37 +161,54 @@ end"#,
         String::new()
     };
 
-    let script = format!(
-        r#"local spr = app.sprite
-local new_layer = spr:{create_fn}()
+    format!(
+        r#"local new_layer = spr:{create_fn}()
 new_layer.name = {name}
 {after_code}
-spr:saveAs(spr.filename)
-local result = {{}}
+result = {{}}
 result.name = new_layer.name
 result.isGroup = new_layer.isGroup
 result.stackIndex = new_layer.stackIndex
-result.status = "created"
-print(json.encode(result))"#,
+result.status = "created""#,
         create_fn = create_fn,
         name = lua_string(&p.name),
         after_code = after_code
-    );
-    server.execute_script_on_file(&p.file_path, &script).await
+    )
 }
 
 pub async fn remove_layer(server: &AsepriteServer, p: RemoveLayerParams) -> Result<String, String> {
     let script = format!(
         r#"local spr = app.sprite
 spr:deleteLayer({name})
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "deleted", layer = {name}}}))"#,
-        name = lua_string(&p.name)
+        name = lua_string(&p.name),
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
+/// Blend mode names accepted by `set_layer_property`, the default first.
+const BLEND_MODES: &[(&str, &str)] = &[
+    ("normal", "BlendMode.NORMAL"),
+    ("multiply", "BlendMode.MULTIPLY"),
+    ("screen", "BlendMode.SCREEN"),
+    ("overlay", "BlendMode.OVERLAY"),
+    ("darken", "BlendMode.DARKEN"),
+    ("lighten", "BlendMode.LIGHTEN"),
+    ("color_dodge", "BlendMode.COLOR_DODGE"),
+    ("color_burn", "BlendMode.COLOR_BURN"),
+    ("hard_light", "BlendMode.HARD_LIGHT"),
+    ("soft_light", "BlendMode.SOFT_LIGHT"),
+    ("difference", "BlendMode.DIFFERENCE"),
+    ("exclusion", "BlendMode.EXCLUSION"),
+    ("addition", "BlendMode.ADDITION"),
+    ("subtract", "BlendMode.SUBTRACT"),
+    ("divide", "BlendMode.DIVIDE"),
+];
+
 pub async fn set_layer_property(server: &AsepriteServer, p: SetLayerPropertyParams) -> Result<String, String> {
+    let lenient = p.lenient.unwrap_or(false);
     let mut property_code = String::new();
 
     if let Some(ref new_name) = p.new_name {
@@ -166,27 +221,11 @@ pub async fn set_layer_property(server: &AsepriteServer, p: SetLayerPropertyPara
         ));
     }
     if let Some(opacity) = p.opacity {
-        property_code.push_str(&format!("    layer.opacity = {}\n", opacity.min(255)));
+        let opacity = check_range("opacity", opacity, 0..=255, lenient)?;
+        property_code.push_str(&format!("    layer.opacity = {}\n", opacity));
     }
     if let Some(ref blend_mode) = p.blend_mode {
-        let bm = match blend_mode.to_lowercase().as_str() {
-            "normal" => "BlendMode.NORMAL",
-            "multiply" => "BlendMode.MULTIPLY",
-            "screen" => "BlendMode.SCREEN",
-            "overlay" => "BlendMode.OVERLAY",
-            "darken" => "BlendMode.DARKEN",
-            "lighten" => "BlendMode.LIGHTEN",
-            "color_dodge" => "BlendMode.COLOR_DODGE",
-            "color_burn" => "BlendMode.COLOR_BURN",
-            "hard_light" => "BlendMode.HARD_LIGHT",
-            "soft_light" => "BlendMode.SOFT_LIGHT",
-            "difference" => "BlendMode.DIFFERENCE",
-            "exclusion" => "BlendMode.EXCLUSION",
-            "addition" => "BlendMode.ADDITION",
-            "subtract" => "BlendMode.SUBTRACT",
-            "divide" => "BlendMode.DIVIDE",
-            _ => "BlendMode.NORMAL",
-        };
+        let bm = check_choice("blend_mode", blend_mode, BLEND_MODES, lenient)?;
         property_code.push_str(&format!("    layer.blendMode = {}\n", bm));
     }
 
@@ -200,7 +239,7 @@ pub async fn set_layer_property(server: &AsepriteServer, p: SetLayerPropertyPara
 local layer = find_layer(spr.layers, {name})
 if layer then
 {props}
-    spr:saveAs(spr.filename)
+    {save}
     local result = {{}}
     result.name = layer.name
     result.isVisible = layer.isVisible
@@ -213,7 +252,8 @@ else
 end"#,
         find_layer = LUA_FIND_LAYER,
         name = lua_string(&p.name),
-        props = property_code
+        props = property_code,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
@@ -228,24 +268,21 @@ pub async fn duplicate_layer(server: &AsepriteServer, p: DuplicateLayerParams) -
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end
+{require_layer}
 app.layer = layer
 app.command.DuplicateLayer()
 {rename}
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.name = app.layer.name
 result.isGroup = app.layer.isGroup
 result.stackIndex = app.layer.stackIndex
 result.status = "duplicated"
 print(json.encode(result))"#,
+        require_layer = lua_require_layer("layer", &p.name),
         find_layer = LUA_FIND_LAYER,
-        name = lua_string(&p.name),
-        rename = rename_code
+        rename = rename_code,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
@@ -254,31 +291,23 @@ pub async fn merge_down_layer(server: &AsepriteServer, p: MergeDownLayerParams)
     let script = format!(
         r#"local spr = app.sprite
 {find_layer}
-local layer = find_layer(spr.layers, {name})
-if not layer then
-    print(json.encode({{error = "Layer not found: " .. {name}}}))
-    return
-end
+{require_layer}
 app.layer = layer
 app.command.MergeDownLayer()
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.name = app.layer.name
 result.status = "merged"
 print(json.encode(result))"#,
+        require_layer = lua_require_layer("layer", &p.name),
         find_layer = LUA_FIND_LAYER,
-        name = lua_string(&p.name)
+        save = LUA_SAVE_IN_PLACE,
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn flatten_layers(server: &AsepriteServer, p: FlattenLayersParams) -> Result<String, String> {
-    let save_code = if let Some(ref output) = p.output_path {
-        let out = lua_path(&server.resolve_output_path(output));
-        format!("spr:saveCopyAs({})", out)
-    } else {
-        "spr:saveAs(spr.filename)".to_string()
-    };
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
 
     let script = format!(
         r#"local spr = app.sprite
@@ -290,5 +319,8 @@ result.status = "flattened"
 print(json.encode(result))"#,
         save = save_code
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
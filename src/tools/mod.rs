@@ -1,3 +1,4 @@
+pub mod batch;
 pub mod cel;
 pub mod drawing;
 pub mod effects;
@@ -10,4 +11,8 @@ pub mod scripting;
 pub mod selection;
 pub mod slice;
 pub mod sprite;
+pub mod system;
 pub mod tag;
+pub mod tileset;
+pub mod user_data;
+pub mod workspace;
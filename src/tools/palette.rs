@@ -1,9 +1,11 @@
 use rmcp::schemars;
 use serde::{Deserialize, Serialize};
 
-use crate::aseprite::lua_path;
+use crate::aseprite::{lua_path, lua_string};
+use crate::lua_helpers::{LUA_NEAREST_PALETTE_INDEX, LUA_SAVE_IN_PLACE, lua_check_frame, lua_save_target, with_saved_to};
 use crate::server::AsepriteServer;
-use crate::utils::parse_hex_color_with_alpha;
+use crate::tools::frame::{FrameRef, resolve_optional_frame};
+use crate::utils::{check_choice, check_range, parse_color, resolve_file_list};
 
 // ============================================================================
 // Parameter Structs
@@ -23,13 +25,15 @@ pub struct SetPaletteColorParams {
     pub file_path: String,
     /// Array of palette entries: [{"index": 0, "color": "#ff0000"}, ...]
     pub colors: Vec<PaletteEntry>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct PaletteEntry {
     /// Palette index
     pub index: u32,
-    /// Color as hex string (e.g. "#ff0000")
+    /// Color as hex, CSS name, or rgb()/rgba() (e.g. "#ff0000", "red")
     pub color: String,
 }
 
@@ -39,6 +43,8 @@ pub struct ResizePaletteParams {
     pub file_path: String,
     /// New palette size (number of colors)
     pub size: u32,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -47,6 +53,8 @@ pub struct LoadPaletteParams {
     pub file_path: String,
     /// Path to the palette file to load (.gpl, .pal, .act, .col, .png, etc.)
     pub palette_path: String,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -59,12 +67,46 @@ pub struct SavePaletteParams {
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ColorQuantizationParams {
-    /// Path to the sprite file
-    pub file_path: String,
+    /// Path to the sprite file (or use file_paths / glob to process several)
+    pub file_path: Option<String>,
+    /// Several sprite files to process in turn, each saved in place; entries may be glob patterns
+    pub file_paths: Option<Vec<String>>,
+    /// Glob pattern selecting the sprite files to process, e.g. "sprites/*.aseprite"
+    pub glob: Option<String>,
     /// Maximum number of colors in the quantized palette (2-256, default: 256)
     pub max_colors: Option<u32>,
     /// Use alpha channel in quantization (default: false)
     pub with_alpha: Option<bool>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CheckIndexedComplianceParams {
+    /// Path to the sprite file (RGB)
+    pub file_path: String,
+    /// Snap every off-palette pixel to its nearest palette color and save (default: false)
+    pub fix: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GeneratePaletteCycleParams {
+    /// Path to the sprite file (indexed)
+    pub file_path: String,
+    /// First palette index of the cycling span
+    pub start_index: u32,
+    /// Last palette index of the cycling span (inclusive, after start_index)
+    pub end_index: u32,
+    /// Frames in the baked animation, including the source frame (default: one full cycle,
+    /// i.e. the span length)
+    pub frames: Option<u32>,
+    /// "forward" (default; colors move to higher indices each frame) or "backward"
+    pub direction: Option<String>,
+    /// Frame to cycle: 1-based number or "tag:n" / "tag:start" / "tag:end" (default: 1).
+    /// The new frames are inserted right after it.
+    pub frame: Option<FrameRef>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
@@ -102,33 +144,49 @@ print(json.encode({{colors = colors, total = #pal}}))"##,
 }
 
 pub async fn set_palette_color(server: &AsepriteServer, p: SetPaletteColorParams) -> Result<String, String> {
+    let max_entries = server.config().max_palette_entries;
+    if p.colors.len() as u64 > max_entries {
+        return Err(format!(
+            "Got {} palette entries, more than the {} allowed per call (max_palette_entries); split them into calls of at most {} entries, or load a whole palette file with load_palette",
+            p.colors.len(),
+            max_entries,
+            max_entries
+        ));
+    }
     let mut set_code = String::new();
     for entry in &p.colors {
-        let (r, g, b, a) = parse_hex_color_with_alpha(&entry.color);
+        let (r, g, b, a) =
+            parse_color(&entry.color).map_err(|e| format!("Invalid color '{}': {}", entry.color, e))?;
         set_code.push_str(&format!(
             "    pal:setColor({}, Color({}, {}, {}, {}))\n",
             entry.index, r, g, b, a
         ));
     }
 
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 local pal = spr.palettes[1]
 app.transaction("Set Palette Colors", function()
 {set_code}
 end)
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "updated", colorsSet = {count}}}))"#,
         set_code = set_code,
-        count = p.colors.len()
+        count = p.colors.len(),
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn resize_palette(server: &AsepriteServer, p: ResizePaletteParams) -> Result<String, String> {
     if p.size == 0 {
         return Err("Palette size must be greater than 0".to_string());
     }
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 local pal = spr.palettes[1]
@@ -137,29 +195,38 @@ app.command.PaletteSize {{
     ui = false,
     size = {size}
 }}
-spr:saveAs(spr.filename)
+{save}
 pal = spr.palettes[1]
 print(json.encode({{status = "resized", oldSize = oldSize, newSize = #pal}}))"#,
-        size = p.size
+        size = p.size,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn load_palette(server: &AsepriteServer, p: LoadPaletteParams) -> Result<String, String> {
-    let pal_path = lua_path(&p.palette_path);
+    let pal_path = lua_path(&server.resolve_input_path(&p.palette_path)?);
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 spr:loadPalette({path})
-spr:saveAs(spr.filename)
+{save}
 local pal = spr.palettes[1]
 print(json.encode({{status = "loaded", paletteSize = #pal}}))"#,
-        path = pal_path
+        path = pal_path,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn save_palette(server: &AsepriteServer, p: SavePaletteParams) -> Result<String, String> {
-    let out = lua_path(&server.resolve_output_path(&p.output_path));
+    let out = lua_path(&server.resolve_output_path(&p.output_path)?);
     let script = format!(
         r#"local spr = app.sprite
 local pal = spr.palettes[1]
@@ -171,20 +238,200 @@ print(json.encode({{status = "saved", paletteSize = #pal, filename = {out}}}))"#
 }
 
 pub async fn color_quantization(server: &AsepriteServer, p: ColorQuantizationParams) -> Result<String, String> {
-    let max_colors = p.max_colors.unwrap_or(256).clamp(2, 256);
+    let max_colors = check_range("max_colors", p.max_colors.unwrap_or(256), 2..=256, false)?;
     let with_alpha = p.with_alpha.unwrap_or(false);
-    let script = format!(
-        r#"local spr = app.sprite
+    let targets = resolve_file_list(
+        server,
+        p.file_path.as_deref(),
+        p.file_paths.as_deref(),
+        p.glob.as_deref(),
+        p.output_path.as_deref(),
+    )?;
+    let output_path = p.output_path.as_deref();
+    targets
+        .run(|file| async move {
+            let (save, saved_to) = lua_save_target(server, &file, output_path)?;
+            let script = format!(
+                r#"local spr = app.sprite
 app.command.ColorQuantization {{
     ui = false,
     withAlpha = {alpha},
     maxColors = {max_colors}
 }}
-spr:saveAs(spr.filename)
+{save}
 local pal = spr.palettes[1]
 print(json.encode({{status = "quantized", paletteSize = #pal, maxColors = {max_colors}}}))"#,
-        alpha = if with_alpha { "true" } else { "false" },
-        max_colors = max_colors
+                alpha = if with_alpha { "true" } else { "false" },
+                max_colors = max_colors,
+                save = save
+            );
+            server
+                .execute_script_on_file(&file, &script)
+                .await
+                .map(|output| with_saved_to(output, &saved_to))
+        })
+        .await
+}
+
+/// How many off-palette colors `check_indexed_compliance` lists individually.
+const MAX_REPORTED_COLORS: usize = 32;
+
+pub async fn check_indexed_compliance(
+    server: &AsepriteServer,
+    p: CheckIndexedComplianceParams,
+) -> Result<String, String> {
+    let fix = p.fix.unwrap_or(false);
+    let script = format!(
+        r##"local spr = app.sprite
+local pc = app.pixelColor
+if spr.colorMode ~= ColorMode.RGB then
+    print(json.encode({{error = "check_indexed_compliance needs an RGB sprite (this one is " .. tostring(spr.colorMode) .. ")"}}))
+    return
+end
+{nearest}
+local pal = spr.palettes[1]
+local in_palette = {{}}
+for i = 0, #pal - 1 do in_palette[pal:getColor(i).rgbaPixel] = true end
+
+local unique, unique_count = {{}}, 0
+local missing, missing_count = {{}}, 0
+local fixed = 0
+local function scan(lyrs)
+    for _, layer in ipairs(lyrs) do
+        if layer.isGroup then
+            scan(layer.layers)
+        elseif layer.isImage then
+            for _, cel in ipairs(layer.cels) do
+                local img = cel.image
+                local snapped = nil
+                for it in img:pixels() do
+                    local v = it()
+                    if pc.rgbaA(v) > 0 then
+                        if not unique[v] then
+                            unique[v] = true
+                            unique_count = unique_count + 1
+                        end
+                        if not in_palette[v] then
+                            local m = missing[v]
+                            if not m then
+                                m = {{count = 0, layer = layer.name, frame = cel.frameNumber,
+                                      x = cel.position.x + it.x, y = cel.position.y + it.y}}
+                                missing[v] = m
+                                missing_count = missing_count + 1
+                            end
+                            m.count = m.count + 1
+                            if {fix} then
+                                snapped = snapped or img:clone()
+                                snapped:drawPixel(it.x, it.y, pal:getColor(nearest_palette_index(pal, v)).rgbaPixel)
+                                fixed = fixed + 1
+                            end
+                        end
+                    end
+                end
+                if snapped then cel.image = snapped end
+            end
+        end
+    end
+end
+scan(spr.layers)
+
+local list = {{}}
+for v, m in pairs(missing) do
+    m.color = string.format("#%02x%02x%02x%02x", pc.rgbaR(v), pc.rgbaG(v), pc.rgbaB(v), pc.rgbaA(v))
+    list[#list + 1] = m
+end
+table.sort(list, function(a, b) return a.count > b.count end)
+local shown = {{}}
+for i = 1, math.min(#list, {max_colors}) do shown[i] = list[i] end
+
+local result = {{}}
+result.unique_colors = unique_count
+result.palette_size = #pal
+result.off_palette_colors = missing_count
+result.missing = shown
+result.lossless_indexed = missing_count == 0 and unique_count <= 256 and #pal <= 256
+if {fix} and fixed > 0 then
+    {save}
+    result.fixed_pixels = fixed
+end
+print(json.encode(result))"##,
+        nearest = LUA_NEAREST_PALETTE_INDEX,
+        fix = fix,
+        max_colors = MAX_REPORTED_COLORS,
+        // Only a fixing run saves, so a plain check never backs up or rewrites the file
+        save = if fix { LUA_SAVE_IN_PLACE } else { "" }
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
+
+pub async fn generate_palette_cycle(server: &AsepriteServer, p: GeneratePaletteCycleParams) -> Result<String, String> {
+    let start = check_range("start_index", p.start_index, 0..=254, false)?;
+    let end = check_range("end_index", p.end_index, start + 1..=255, false)?;
+    let span = end - start + 1;
+    let frames = check_range("frames", p.frames.unwrap_or(span), 2..=256, false)?;
+    let step = check_choice(
+        "direction",
+        p.direction.as_deref().unwrap_or("forward"),
+        &[("forward", -1), ("backward", 1)],
+        false,
+    )?;
+    let frame = resolve_optional_frame(server, &p.file_path, p.frame.as_ref()).await?.unwrap_or(1);
+
+    // Cycling is baked into each new frame's pixel indices rather than per-frame palettes: a
+    // pixel showing index i at step k shows the color that was k entries behind (or ahead of) it,
+    // which looks identical and survives exports that only keep one palette. `newFrame` copies the
+    // previous frame, which is already cycled k - 1 steps, so each new frame shifts one more step.
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = format!(
+        r#"local spr = app.sprite
+if spr.colorMode ~= ColorMode.INDEXED then
+    print(json.encode({{error = "Palette cycling needs an indexed sprite (this one is " .. (spr.colorMode == ColorMode.RGB and "RGB" or "grayscale") .. "); convert it with set_color_mode first"}}))
+    return
+end
+if {end} >= #spr.palettes[1] then
+    print(json.encode({{error = "end_index {end} is outside the palette, which has " .. #spr.palettes[1] .. " colors"}}))
+    return
+end
+{check_frame}
+local first, span = {start}, {span}
+local created = 0
+app.transaction("Palette Cycle", function()
+    for k = 1, {frames} - 1 do
+        spr:newFrame({frame} + k)
+        created = created + 1
+        local shift = {step}
+        for _, cel in ipairs(spr.cels) do
+            if cel.frameNumber == {frame} + k and cel.layer.isImage and not cel.layer.isTilemap then
+                local img = cel.image:clone()
+                for it in img:pixels() do
+                    local v = it()
+                    if v >= first and v < first + span then
+                        it(first + (v - first + shift) % span)
+                    end
+                end
+                -- Replace rather than edit the cel so cels linked to the source frame keep their colors
+                local layer, pos, opacity = cel.layer, cel.position, cel.opacity
+                spr:deleteCel(cel)
+                local copy = spr:newCel(layer, {frame} + k, img, pos)
+                copy.opacity = opacity
+            end
+        end
+    end
+end)
+{save}
+print(json.encode({{status = "cycled", frames_created = created, source_frame = {frame}, start_index = first, end_index = first + span - 1, direction = {direction}}}))"#,
+        check_frame = lua_check_frame(frame),
+        start = start,
+        end = end,
+        span = span,
+        frames = frames,
+        frame = frame,
+        step = step,
+        direction = lua_string(&p.direction.as_deref().unwrap_or("forward").to_ascii_lowercase()),
+        save = save
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
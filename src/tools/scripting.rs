@@ -1,6 +1,9 @@
 use rmcp::schemars;
 use serde::Deserialize;
+use serde_json::json;
+use std::path::{Component, Path, PathBuf};
 
+use crate::aseprite::lua_path;
 use crate::server::AsepriteServer;
 
 // ============================================================================
@@ -9,10 +12,30 @@ use crate::server::AsepriteServer;
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct RunLuaScriptParams {
-    /// The Lua script code to execute in Aseprite
-    pub script: String,
+    /// The Lua script code to execute in Aseprite (provide this or script_path)
+    pub script: Option<String>,
+    /// Path to a Lua script file to execute (provide this or script)
+    pub script_path: Option<String>,
     /// Optional sprite file to open before running the script
     pub file_path: Option<String>,
+    /// Parameters passed as `--script-param key=value`; the script reads them from `app.params`.
+    /// Non-string values are passed as their JSON text
+    pub params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RunNamedScriptParams {
+    /// Script name from list_scripts (file name in the scripts directory, ".lua" optional)
+    pub name: String,
+    /// Optional sprite file to open before running the script
+    pub file_path: Option<String>,
+    /// Parameters passed as `--script-param key=value`; the script reads them from `app.params`.
+    /// Non-string values are passed as their JSON text
+    pub params: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -20,22 +43,217 @@ pub struct ExecuteCliParams {
     /// CLI arguments to pass to Aseprite (batch mode is always enabled).
     /// Example: ["sprite.ase", "--save-as", "output.png"]
     pub args: Vec<String>,
+    /// Timeout for this call in seconds, overriding the default (1-1800)
+    pub timeout_secs: Option<u64>,
+    /// Directory relative paths in args resolve against (default: the workspace root, see list_files)
+    pub working_dir: Option<String>,
 }
 
+/// Flags execute_cli rejects by default, with the reason given in the error.
+/// ASEPRITE_CLI_DENYLIST (comma-separated flags) replaces this list.
+const DEFAULT_CLI_DENYLIST: &[(&str, &str)] = &[
+    ("--shell", "it starts an interactive Lua shell that waits for input until the timeout"),
+    ("--preview", "it only prints what would be done, without producing output"),
+    ("-p", "it is --preview, which only prints what would be done"),
+];
+
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
+/// Script param carrying the path `mcp_return` writes to.
+const RESULT_FILE_PARAM: &str = "mcp_result_file";
+
+/// Defines `mcp_return(value)` for run_lua_script, which JSON-encodes `value` into the result file;
+/// the tool returns that JSON instead of stdout. Kept on one line so the user's line numbers hold.
+const LUA_RESULT_PRELUDE: &str = "function mcp_return(value) \
+local f = assert(io.open(app.params.mcp_result_file, \"w\")) f:write(json.encode(value)) f:close() end";
+
+/// Arbitrary scripts and CLI arguments can touch any file, so they bypass the path sandbox.
+const SANDBOX_DISABLED: &str = "This tool is disabled because ASEPRITE_ALLOWED_DIRS restricts file access; \
+set ASEPRITE_ALLOW_ARBITRARY_SCRIPTS=1 to enable it";
+
 pub async fn run_lua_script(server: &AsepriteServer, p: RunLuaScriptParams) -> Result<String, String> {
-    if let Some(ref file_path) = p.file_path {
-        server.execute_script_on_file(file_path, &p.script).await
-    } else {
-        server.execute_script(&p.script).await
+    if !server.arbitrary_scripts_allowed() {
+        return Err(SANDBOX_DISABLED.to_string());
+    }
+    let params = script_params(p.params)?;
+
+    // The user's code runs after the prelude: inline code on the prelude's line (so reported
+    // line numbers match), a script file via dofile
+    let body = match (p.script, p.script_path) {
+        (Some(script), None) => script,
+        (None, Some(script_path)) => {
+            let script_path = server.resolve_input_path(&script_path)?;
+            if !Path::new(&script_path).is_file() {
+                return Err(format!("Script file not found: {}", script_path));
+            }
+            format!("dofile({})", lua_path(&script_path))
+        }
+        _ => return Err("Provide exactly one of script or script_path".to_string()),
+    };
+    run_with_result_file(server, &body, p.file_path.as_deref(), params).await
+}
+
+/// Scripts in ASEPRITE_SCRIPTS_DIR with the description from their leading comment.
+pub async fn list_scripts(server: &AsepriteServer) -> Result<String, String> {
+    let dir = scripts_dir(server)?;
+    let scripts: Vec<serde_json::Value> = available_scripts(&dir)
+        .iter()
+        .map(|name| {
+            let code = std::fs::read_to_string(dir.join(format!("{}.lua", name))).unwrap_or_default();
+            json!({ "name": name, "description": header_comment(&code) })
+        })
+        .collect();
+    Ok(json!({ "dir": dir.to_string_lossy(), "count": scripts.len(), "scripts": scripts }).to_string())
+}
+
+/// Run a script from ASEPRITE_SCRIPTS_DIR. The directory is set up by whoever runs the server,
+/// so these scripts stay available when sandboxing disables run_lua_script.
+pub async fn run_named_script(server: &AsepriteServer, p: RunNamedScriptParams) -> Result<String, String> {
+    let dir = scripts_dir(server)?;
+    let name = p.name.trim().trim_end_matches(".lua");
+    let name_path = Path::new(name);
+    let plain_name = !name.is_empty()
+        && !name.contains(['/', '\\'])
+        && name_path.components().all(|c| matches!(c, Component::Normal(_)));
+    if !plain_name {
+        return Err(format!("Invalid script name '{}': use a name from list_scripts", p.name));
+    }
+    let script_path = dir.join(format!("{}.lua", name));
+    // A symlink in the scripts directory must not lead outside it
+    let inside = script_path
+        .canonicalize()
+        .ok()
+        .zip(dir.canonicalize().ok())
+        .is_some_and(|(script, dir)| script.starts_with(dir) && script.is_file());
+    if !inside {
+        let available = available_scripts(&dir);
+        return Err(format!(
+            "Script '{}' not found in {}. Available: {}",
+            name,
+            dir.display(),
+            if available.is_empty() { "none".to_string() } else { available.join(", ") }
+        ));
+    }
+    let params = script_params(p.params)?;
+    let body = format!("dofile({})", lua_path(&script_path.to_string_lossy()));
+    run_with_result_file(server, &body, p.file_path.as_deref(), params).await
+}
+
+fn scripts_dir(server: &AsepriteServer) -> Result<PathBuf, String> {
+    let dir = server
+        .scripts_dir()
+        .ok_or("No script library configured: set ASEPRITE_SCRIPTS_DIR to a directory of .lua files")?;
+    if !dir.is_dir() {
+        return Err(format!("ASEPRITE_SCRIPTS_DIR is not a directory: {}", dir.display()));
+    }
+    Ok(dir.to_path_buf())
+}
+
+/// Names (without ".lua") of the scripts directly in `dir`, sorted.
+fn available_scripts(dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "lua"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// The comment at the top of a script: leading `--` lines or a `--[[ ... ]]` block.
+fn header_comment(code: &str) -> Option<String> {
+    let code = code.trim_start();
+    if let Some(block) = code.strip_prefix("--[[") {
+        let text = block.split("]]").next().unwrap_or_default().trim();
+        return (!text.is_empty()).then(|| text.to_string());
+    }
+    let lines: Vec<&str> = code
+        .lines()
+        .map_while(|line| line.trim().strip_prefix("--"))
+        .map(|line| line.trim_start_matches('-').trim())
+        .collect();
+    let text = lines.join("\n").trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Convert tool `params` to `--script-param` pairs; non-string values become their JSON text.
+fn script_params(params: Option<serde_json::Map<String, serde_json::Value>>) -> Result<Vec<(String, String)>, String> {
+    let params: Vec<(String, String)> = params
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (key, value)
+        })
+        .collect();
+    if let Some((key, _)) = params.iter().find(|(key, _)| key.is_empty() || key.contains('=')) {
+        return Err(format!("Invalid script param name '{}'", key));
+    }
+
+    if params.iter().any(|(key, _)| key == RESULT_FILE_PARAM) {
+        return Err(format!("Script param name '{}' is reserved", RESULT_FILE_PARAM));
+    }
+    Ok(params)
+}
+
+/// Run `body` after the `mcp_return` prelude, returning the JSON it passed to `mcp_return`, or
+/// the printed output when it didn't call it.
+async fn run_with_result_file(
+    server: &AsepriteServer,
+    body: &str,
+    file_path: Option<&str>,
+    mut params: Vec<(String, String)>,
+) -> Result<String, String> {
+    let result_file = server.temp_file_path("json");
+    params.push((RESULT_FILE_PARAM.to_string(), result_file.to_string_lossy().to_string()));
+
+    let script_path = server.temp_file_path("lua");
+    tokio::fs::write(&script_path, format!("{} {}", LUA_RESULT_PRELUDE, body))
+        .await
+        .map_err(|e| format!("Failed to write temporary Lua script: {}", e))?;
+    let output = server
+        .execute_script_file(&script_path.to_string_lossy(), file_path, &params)
+        .await;
+    // A failed run's error points at the script when ASEPRITE_DEBUG_SCRIPTS keeps it
+    if output.is_ok() || !server.keeps_failed_scripts() {
+        tokio::fs::remove_file(&script_path).await.ok();
+    }
+    let returned = tokio::fs::read_to_string(&result_file).await.ok();
+    tokio::fs::remove_file(&result_file).await.ok();
+
+    let output = output?;
+    match returned {
+        Some(text) => {
+            let value: serde_json::Value = serde_json::from_str(&text)
+                .map_err(|e| format!("mcp_return wrote invalid JSON: {}", e))?;
+            Ok(value.to_string())
+        }
+        None => Ok(output),
     }
 }
 
 pub async fn execute_cli(server: &AsepriteServer, p: ExecuteCliParams) -> Result<String, String> {
-    match server.run_cli(&p.args).await {
+    if !server.arbitrary_scripts_allowed() {
+        return Err(SANDBOX_DISABLED.to_string());
+    }
+    let working_dir = match p.working_dir {
+        Some(ref dir) => PathBuf::from(server.resolve_input_path(dir)?),
+        None => server.workspace_root(),
+    };
+    if !working_dir.is_dir() {
+        return Err(format!("working_dir is not a directory: {}", working_dir.display()));
+    }
+    validate_cli_args(server, &p.args, &working_dir)?;
+    match server.run_cli_in(&p.args, Some(&working_dir)).await {
         Ok(output) => {
             if output.success {
                 Ok(output.result_text())
@@ -46,3 +264,45 @@ pub async fn execute_cli(server: &AsepriteServer, p: ExecuteCliParams) -> Result
         Err(e) => Err(format!("CLI execution failed: {}", e)),
     }
 }
+
+/// Reject denylisted flags and, when sandboxing is enabled, path-like arguments (resolved against
+/// `working_dir`) outside the allowed directories.
+fn validate_cli_args(server: &AsepriteServer, args: &[String], working_dir: &Path) -> Result<(), String> {
+    let custom: Option<Vec<String>> = std::env::var("ASEPRITE_CLI_DENYLIST")
+        .ok()
+        .map(|list| list.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect());
+    for arg in args {
+        let flag = arg.split('=').next().unwrap_or(arg);
+        if !flag.starts_with('-') {
+            continue;
+        }
+        let reason = match custom {
+            Some(ref list) => list
+                .iter()
+                .any(|f| f == flag)
+                .then_some("it is listed in ASEPRITE_CLI_DENYLIST"),
+            None => DEFAULT_CLI_DENYLIST.iter().find(|(f, _)| *f == flag).map(|(_, reason)| *reason),
+        };
+        if let Some(reason) = reason {
+            return Err(format!("Rejected argument '{}': {}", arg, reason));
+        }
+    }
+
+    // Flag values (`--flag=value`) and plain arguments that look like paths are checked
+    for arg in args {
+        let value = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with('-') => value,
+            _ if arg.starts_with('-') => continue,
+            _ => arg.as_str(),
+        };
+        let looks_like_path = value.contains(['/', '\\'])
+            || Path::new(value).extension().is_some()
+            || working_dir.join(value).exists();
+        if looks_like_path {
+            server
+                .check_path_allowed(&working_dir.join(value))
+                .map_err(|e| format!("Rejected argument '{}': {}", arg, e))?;
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,15 @@
 use rmcp::schemars;
 use serde::Deserialize;
 
+use crate::aseprite::lua_string;
+use crate::lua_helpers::{
+    LUA_FIND_LAYER, LUA_PIXEL_ALPHA, LUA_POLYGON_SELECTION, LUA_SAVE_IN_PLACE, lua_check_frame, lua_find_slice,
+    lua_image_source, lua_require_layer,
+};
 use crate::server::AsepriteServer;
-use crate::utils::parse_hex_color;
+use crate::tools::drawing::PointData;
+use crate::tools::sprite::snap_grid;
+use crate::utils::{check_choice, check_range, parse_color};
 
 // ============================================================================
 // Parameter Structs
@@ -22,34 +29,261 @@ pub struct SelectRegionParams {
     pub height: u32,
     /// Selection mode: "replace", "add", "subtract", "intersect" (default: "replace")
     pub mode: Option<String>,
+    /// Snap the rectangle's edges to the nearest grid lines (default: false)
+    pub snap_to_grid: Option<bool>,
+    /// Grid for snap_to_grid as {x, y, width, height}: origin and cell size (if omitted, uses the sprite's grid)
+    pub grid: Option<RectData>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct SelectByColorParams {
     /// Path to the sprite file
     pub file_path: String,
-    /// Color to select in hex format (e.g. "#ff0000")
+    /// Color to select as hex, CSS name, or rgb() (e.g. "#ff0000", "red")
     pub color: String,
     /// Tolerance for color matching (0-255, default: 0)
     pub tolerance: Option<u32>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RectData {
+    /// X coordinate of the top-left corner
+    pub x: i32,
+    /// Y coordinate of the top-left corner
+    pub y: i32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
+/// A selection to re-establish inside a script before a selection-aware command runs.
+/// Each tool call runs in a fresh Aseprite process, so selections must be passed explicitly.
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum SelectionSpec {
+    /// Rectangle: {"x": 0, "y": 0, "width": 16, "height": 16}
+    Rect(RectData),
+    /// Polygon as a list of points: [{"x": 0, "y": 0}, {"x": 8, "y": 0}, {"x": 4, "y": 8}]
+    Polygon(Vec<PointData>),
+    /// Slice bounds: "from_slice:<name>"
+    Slice(String),
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetSelectionInfoParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Point to test for containment: {"x": 0, "y": 0}
+    pub point: Option<PointData>,
+    /// Count the selected pixels (O(area) scan of the selection bounds, default: false)
+    pub count_pixels: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SelectFromSliceParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Name of the slice whose bounds become the selection
+    pub slice: String,
+    /// Selection mode: "replace", "add", "subtract", "intersect" (default: "replace")
+    pub mode: Option<String>,
+    /// Frame number, 1-based. When given, uses the slice key active at that frame instead of the first key.
+    pub frame: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SelectOpaqueParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Layer to scan (if omitted, uses the flattened sprite)
+    pub layer: Option<String>,
+    /// Frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+    /// Minimum alpha (1-255) for a pixel to be selected (default: 1)
+    pub alpha_threshold: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SelectionToLayerParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Name for the new layer that receives the pixels
+    pub new_layer_name: String,
+    /// Explicit region to copy: {x, y, width, height}. Required unless use_active_selection is true.
+    pub region: Option<RectData>,
+    /// Use the selection stored in the sprite file instead of an explicit region (default: false)
+    pub use_active_selection: Option<bool>,
+    /// Cut instead of copy: clear the pixels from the source layer (default: false)
+    pub cut: Option<bool>,
+    /// Source layer name (if omitted, uses active layer)
+    pub layer: Option<String>,
+    /// Frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+}
+
 // ============================================================================
-// Tool Implementations
+// Shared Helpers
 // ============================================================================
 
-pub async fn select_region(server: &AsepriteServer, p: SelectRegionParams) -> Result<String, String> {
-    let mode_fn = match p.mode.as_deref() {
-        Some("add") => "add",
-        Some("subtract") => "subtract",
-        Some("intersect") => "intersect",
-        _ => "select",
+/// Map a selection mode name to the Selection method that applies it ("replace" by default).
+pub fn selection_mode_fn(mode: Option<&str>) -> Result<&'static str, String> {
+    check_choice(
+        "mode",
+        mode.unwrap_or("replace"),
+        &[("replace", "select"), ("add", "add"), ("subtract", "subtract"), ("intersect", "intersect")],
+        false,
+    )
+}
+
+/// Look up a slice's bounds at a specific frame (1-based).
+///
+/// The Lua API only exposes a slice's first key, so this exports sheet data with
+/// `--list-slices` and picks the key in effect at `frame`. Returns `Ok(None)` when the
+/// slice doesn't exist, leaving the error (with available names) to the Lua side.
+async fn slice_bounds_at_frame(
+    server: &AsepriteServer,
+    file_path: &str,
+    slice: &str,
+    frame: u32,
+) -> Result<Option<RectData>, String> {
+    let file_path = server.resolve_input_path(file_path)?;
+    let sheet_path = server.temp_file_path("png");
+    let data_path = server.temp_file_path("json");
+    let args = vec![
+        "--list-slices".to_string(),
+        file_path,
+        "--sheet".to_string(),
+        sheet_path.to_string_lossy().to_string(),
+        "--data".to_string(),
+        data_path.to_string_lossy().to_string(),
+    ];
+    let output = server
+        .run_cli(&args)
+        .await
+        .map_err(|e| format!("Failed to read slice keys: {}", e))?;
+    let data = tokio::fs::read_to_string(&data_path).await;
+    tokio::fs::remove_file(&sheet_path).await.ok();
+    tokio::fs::remove_file(&data_path).await.ok();
+    if !output.success {
+        return Err(output.result_text());
+    }
+    let data = data.map_err(|e| format!("Failed to read slice keys: {}", e))?;
+    let json: serde_json::Value =
+        serde_json::from_str(&data).map_err(|e| format!("Invalid sheet data: {}", e))?;
+
+    let Some(entry) = json["meta"]["slices"]
+        .as_array()
+        .and_then(|slices| slices.iter().find(|s| s["name"] == slice))
+    else {
+        return Ok(None);
     };
+    // Keys use 0-based frame numbers; the active key is the last one starting at or before `frame`
+    let key = entry["keys"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|k| k["frame"].as_u64().is_some_and(|f| f < u64::from(frame)))
+        .max_by_key(|k| k["frame"].as_u64());
+    let Some(key) = key else {
+        return Err(format!("Slice '{}' has no key at or before frame {}", slice, frame));
+    };
+    let b = &key["bounds"];
+    Ok(Some(RectData {
+        x: b["x"].as_i64().unwrap_or(0) as i32,
+        y: b["y"].as_i64().unwrap_or(0) as i32,
+        width: b["w"].as_u64().unwrap_or(0) as u32,
+        height: b["h"].as_u64().unwrap_or(0) as u32,
+    }))
+}
+
+/// Build the Lua code that replaces `spr.selection` with the given selection.
+/// Returns an empty string when no selection is given, leaving the sprite's selection as-is.
+pub fn lua_apply_selection(selection: &Option<SelectionSpec>) -> Result<String, String> {
+    let Some(selection) = selection else {
+        return Ok(String::new());
+    };
+    match selection {
+        SelectionSpec::Rect(r) => {
+            if r.width == 0 || r.height == 0 {
+                return Err("Selection width and height must be greater than 0".to_string());
+            }
+            Ok(format!(
+                "spr.selection = Selection(Rectangle({}, {}, {}, {}))",
+                r.x, r.y, r.width, r.height
+            ))
+        }
+        SelectionSpec::Polygon(points) => {
+            if points.len() < 3 {
+                return Err(format!(
+                    "Selection polygon needs at least 3 points (got {})",
+                    points.len()
+                ));
+            }
+            let pts: Vec<String> = points
+                .iter()
+                .map(|pt| format!("Point({}, {})", pt.x, pt.y))
+                .collect();
+            Ok(format!(
+                "{}\nspr.selection = polygon_selection({{ {} }})",
+                LUA_POLYGON_SELECTION,
+                pts.join(", ")
+            ))
+        }
+        SelectionSpec::Slice(spec) => {
+            let Some(name) = spec.strip_prefix("from_slice:") else {
+                return Err(format!(
+                    "Invalid selection '{}': expected a rectangle, a point list, or \"from_slice:<name>\"",
+                    spec
+                ));
+            };
+            Ok(format!(
+                "{}\nspr.selection = Selection(target_slice.bounds)",
+                lua_find_slice(name)
+            ))
+        }
+    }
+}
+
+/// Build the Lua preamble defining `mask` (a Selection or nil) and `bounds` (a Rectangle)
+/// from either an explicit region or the selection stored in the sprite.
+pub fn lua_region_or_selection(region: &Option<RectData>, use_active_selection: Option<bool>) -> Result<String, String> {
+    match (region, use_active_selection.unwrap_or(false)) {
+        (Some(_), true) => Err("Specify either region or use_active_selection, not both".to_string()),
+        (Some(r), false) => {
+            if r.width == 0 || r.height == 0 {
+                return Err("Region width and height must be greater than 0".to_string());
+            }
+            Ok(format!(
+                "local mask = nil\nlocal bounds = Rectangle({}, {}, {}, {})",
+                r.x, r.y, r.width, r.height
+            ))
+        }
+        (None, true) => Ok(r#"if spr.selection.isEmpty then
+    print(json.encode({error = "No active selection in sprite"}))
+    return
+end
+local mask = spr.selection
+local bounds = mask.bounds"#
+            .to_string()),
+        (None, false) => Err("Either region or use_active_selection must be provided".to_string()),
+    }
+}
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+pub async fn select_region(server: &AsepriteServer, mut p: SelectRegionParams) -> Result<String, String> {
+    let mode_fn = selection_mode_fn(p.mode.as_deref())?;
+    if let Some(grid) = snap_grid(server, &p.file_path, p.snap_to_grid, p.grid.as_ref()).await? {
+        (p.x, p.y, p.width, p.height) = grid.snap_rect(p.x, p.y, p.width, p.height);
+    }
     let script = format!(
         r#"local spr = app.sprite
 local sel = spr.selection
 sel:{mode}(Rectangle({x}, {y}, {w}, {h}))
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.status = "selected"
 result.bounds = {{
@@ -64,59 +298,69 @@ print(json.encode(result))"#,
         x = p.x,
         y = p.y,
         w = p.width,
-        h = p.height
+        h = p.height,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
 pub async fn deselect(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
+    let script = format!(
+        r#"local spr = app.sprite
 spr.selection:deselect()
-spr:saveAs(spr.filename)
-print(json.encode({status = "deselected"}))"#;
-    server.execute_script_on_file(file_path, script).await
+{save}
+print(json.encode({{status = "deselected"}}))"#,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(file_path, &script).await
 }
 
 pub async fn select_all(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
+    let script = format!(
+        r#"local spr = app.sprite
 app.command.MaskAll()
-spr:saveAs(spr.filename)
+{save}
 local sel = spr.selection
-local result = {}
+local result = {{}}
 result.status = "selected_all"
-result.bounds = {
+result.bounds = {{
     x = sel.bounds.x,
     y = sel.bounds.y,
     width = sel.bounds.width,
     height = sel.bounds.height
-}
-print(json.encode(result))"#;
-    server.execute_script_on_file(file_path, script).await
+}}
+print(json.encode(result))"#,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(file_path, &script).await
 }
 
 pub async fn invert_selection(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r#"local spr = app.sprite
+    let script = format!(
+        r#"local spr = app.sprite
 app.command.InvertMask()
-spr:saveAs(spr.filename)
+{save}
 local sel = spr.selection
-local result = {}
+local result = {{}}
 result.status = "inverted"
 result.isEmpty = sel.isEmpty
 if not sel.isEmpty then
-    result.bounds = {
+    result.bounds = {{
         x = sel.bounds.x,
         y = sel.bounds.y,
         width = sel.bounds.width,
         height = sel.bounds.height
-    }
+    }}
 end
-print(json.encode(result))"#;
-    server.execute_script_on_file(file_path, script).await
+print(json.encode(result))"#,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(file_path, &script).await
 }
 
 pub async fn select_by_color(server: &AsepriteServer, p: SelectByColorParams) -> Result<String, String> {
-    let (r, g, b) = parse_hex_color(&p.color);
-    let tolerance = p.tolerance.unwrap_or(0).min(255);
+    let (r, g, b, _) = parse_color(&p.color).map_err(|e| format!("Invalid color '{}': {}", p.color, e))?;
+    let tolerance = check_range("tolerance", p.tolerance.unwrap_or(0), 0..=255, false)?;
     let color_hex = format!("#{:02x}{:02x}{:02x}", r, g, b);
     let script = format!(
         r#"local spr = app.sprite
@@ -125,7 +369,7 @@ app.command.MaskByColor {{
     ui = false,
     tolerance = {tolerance}
 }}
-spr:saveAs(spr.filename)
+{save}
 local sel = spr.selection
 local result = {{}}
 result.status = "selected_by_color"
@@ -145,7 +389,313 @@ print(json.encode(result))"#,
         g = g,
         b = b,
         tolerance = tolerance,
-        color_hex = color_hex
+        color_hex = color_hex,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn selection_to_layer(server: &AsepriteServer, p: SelectionToLayerParams) -> Result<String, String> {
+    if p.new_layer_name.trim().is_empty() {
+        return Err("new_layer_name cannot be empty".to_string());
+    }
+    let region_code = lua_region_or_selection(&p.region, p.use_active_selection)?;
+
+    let source_code = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+{require_layer}"#,
+            require_layer = lua_require_layer("src_layer", layer_name),
+            find_layer = LUA_FIND_LAYER,
+        )
+    } else {
+        "local src_layer = app.layer".to_string()
+    };
+
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
+local frame = spr.frames[{frame}]
+{source_code}
+if not src_layer or not src_layer.isImage then
+    print(json.encode({{error = "Source layer must be an image layer"}}))
+    return
+end
+{region_code}
+local src_cel = src_layer:cel(frame)
+if not src_cel then
+    print(json.encode({{error = "No cel at frame {frame} on layer " .. src_layer.name}}))
+    return
+end
+
+local copied = 0
+local new_layer
+app.transaction("Selection To Layer", function()
+    local src_img = src_cel.image:clone()
+    local pos = src_cel.position
+    local clear_px = src_img.spec.transparentColor
+    local out = Image(bounds.width, bounds.height, spr.colorMode)
+    out:clear()
+    for py = bounds.y, bounds.y + bounds.height - 1 do
+        for px = bounds.x, bounds.x + bounds.width - 1 do
+            local ix = px - pos.x
+            local iy = py - pos.y
+            if (not mask or mask:contains(px, py))
+                and ix >= 0 and ix < src_img.width and iy >= 0 and iy < src_img.height then
+                out:drawPixel(px - bounds.x, py - bounds.y, src_img:getPixel(ix, iy))
+                if {cut} then src_img:drawPixel(ix, iy, clear_px) end
+                copied = copied + 1
+            end
+        end
+    end
+    if {cut} then src_cel.image = src_img end
+    new_layer = spr:newLayer()
+    new_layer.name = {new_name}
+    if new_layer.parent == src_layer.parent then
+        new_layer.stackIndex = src_layer.stackIndex + 1
+    end
+    spr:newCel(new_layer, frame, out, Point(bounds.x, bounds.y))
+end)
+{save}
+local result = {{}}
+result.layer = new_layer.name
+result.sourceLayer = src_layer.name
+result.frame = frame.frameNumber
+result.bounds = {{
+    x = bounds.x,
+    y = bounds.y,
+    width = bounds.width,
+    height = bounds.height
+}}
+result.pixelsCopied = copied
+result.status = {status}
+print(json.encode(result))"#,
+        check_frame = lua_check_frame(p.frame.unwrap_or(1)),
+        frame = p.frame.unwrap_or(1),
+        source_code = source_code,
+        region_code = region_code,
+        cut = if p.cut.unwrap_or(false) { "true" } else { "false" },
+        new_name = lua_string(&p.new_layer_name),
+        status = lua_string(if p.cut.unwrap_or(false) { "cut" } else { "copied" }),
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn select_from_slice(server: &AsepriteServer, p: SelectFromSliceParams) -> Result<String, String> {
+    if p.frame == Some(0) {
+        return Err("frame must be 1 or greater".to_string());
+    }
+    let mode_fn = selection_mode_fn(p.mode.as_deref())?;
+    let key_bounds = match p.frame {
+        Some(frame) => slice_bounds_at_frame(server, &p.file_path, &p.slice, frame).await?,
+        None => None,
+    };
+    let bounds_code = match key_bounds {
+        Some(r) => format!("local bounds = Rectangle({}, {}, {}, {})", r.x, r.y, r.width, r.height),
+        None => "local bounds = target_slice.bounds".to_string(),
+    };
+
+    let script = format!(
+        r#"local spr = app.sprite
+{find_slice}
+{bounds_code}
+local sel = spr.selection
+sel:{mode}(bounds)
+{save}
+local result = {{}}
+result.status = "selected"
+result.slice = target_slice.name
+result.sliceBounds = {{
+    x = bounds.x,
+    y = bounds.y,
+    width = bounds.width,
+    height = bounds.height
+}}
+result.isEmpty = sel.isEmpty
+if not sel.isEmpty then
+    result.bounds = {{
+        x = sel.bounds.x,
+        y = sel.bounds.y,
+        width = sel.bounds.width,
+        height = sel.bounds.height
+    }}
+end
+print(json.encode(result))"#,
+        find_slice = lua_find_slice(&p.slice),
+        bounds_code = bounds_code,
+        mode = mode_fn,
+        save = LUA_SAVE_IN_PLACE
     );
     server.execute_script_on_file(&p.file_path, &script).await
 }
+
+pub async fn select_opaque(server: &AsepriteServer, p: SelectOpaqueParams) -> Result<String, String> {
+    let threshold = p.alpha_threshold.unwrap_or(1);
+    if !(1..=255).contains(&threshold) {
+        return Err(format!("alpha_threshold must be between 1 and 255 (got {})", threshold));
+    }
+    let frame_num = p.frame.unwrap_or(1);
+
+    let script = format!(
+        r#"local spr = app.sprite
+{check_frame}
+{image_source}
+{pixel_alpha}
+
+-- Build the selection from horizontal runs of opaque pixels, one rectangle per run
+local sel = Selection()
+local count = 0
+for y = 0, img.height - 1 do
+    local x = 0
+    while x < img.width do
+        if pixel_alpha(spr, img:getPixel(x, y)) >= {threshold} then
+            local start = x
+            while x < img.width and pixel_alpha(spr, img:getPixel(x, y)) >= {threshold} do
+                x = x + 1
+            end
+            sel:add(Rectangle(offX + start, offY + y, x - start, 1))
+            -- Count only what survives the intersect with the canvas below
+            local cy, x0, x1 = offY + y, math.max(offX + start, 0), math.min(offX + x, spr.width)
+            if cy >= 0 and cy < spr.height and x1 > x0 then count = count + (x1 - x0) end
+        else
+            x = x + 1
+        end
+    end
+end
+sel:intersect(spr.bounds)
+spr.selection = sel
+{save}
+local result = {{}}
+result.status = "selected_opaque"
+result.alphaThreshold = {threshold}
+result.pixelCount = count
+result.isEmpty = sel.isEmpty
+if not sel.isEmpty then
+    result.bounds = {{
+        x = sel.bounds.x,
+        y = sel.bounds.y,
+        width = sel.bounds.width,
+        height = sel.bounds.height
+    }}
+end
+print(json.encode(result))"#,
+        check_frame = lua_check_frame(frame_num),
+        image_source = lua_image_source(p.layer.as_deref(), frame_num),
+        pixel_alpha = LUA_PIXEL_ALPHA,
+        threshold = threshold,
+        save = LUA_SAVE_IN_PLACE
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn get_selection_info(server: &AsepriteServer, p: GetSelectionInfoParams) -> Result<String, String> {
+    let point_code = if let Some(ref pt) = p.point {
+        format!(
+            r#"result.point = {{x = {x}, y = {y}}}
+result.containsPoint = (not sel.isEmpty) and sel:contains({x}, {y})"#,
+            x = pt.x,
+            y = pt.y
+        )
+    } else {
+        String::new()
+    };
+    let count_code = if p.count_pixels.unwrap_or(false) {
+        r#"local count = 0
+if not sel.isEmpty then
+    local b = sel.bounds
+    for py = b.y, b.y + b.height - 1 do
+        for px = b.x, b.x + b.width - 1 do
+            if sel:contains(px, py) then count = count + 1 end
+        end
+    end
+end
+result.pixelCount = count"#
+    } else {
+        ""
+    };
+
+    let script = format!(
+        r#"local spr = app.sprite
+local sel = spr.selection
+local result = {{}}
+result.hasSelection = not sel.isEmpty
+if not sel.isEmpty then
+    result.bounds = {{
+        x = sel.bounds.x,
+        y = sel.bounds.y,
+        width = sel.bounds.width,
+        height = sel.bounds.height
+    }}
+end
+{count_code}
+{point_code}
+print(json.encode(result))"#,
+        count_code = count_code,
+        point_code = point_code
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, real_server, scratch_dir};
+
+    #[tokio::test]
+    async fn select_by_color_rejects_tolerance_above_255() {
+        let params = SelectByColorParams {
+            file_path: "/art/in.aseprite".to_string(),
+            color: "#ff0000".to_string(),
+            tolerance: Some(300),
+        };
+        let err = select_by_color(&dry_server(), params).await.unwrap_err();
+        assert_eq!(err, "tolerance must be between 0 and 255 (got 300)");
+    }
+
+    #[tokio::test]
+    async fn opaque_pixel_count_leaves_out_pixels_off_the_canvas() {
+        let Some(server) = real_server() else { return };
+        let path = scratch_dir("opaque_count").join("sprite.aseprite").to_string_lossy().to_string();
+        let params = crate::tools::sprite::CreateSpriteParams {
+            width: 8,
+            height: 8,
+            output_path: path.clone(),
+            color_mode: None,
+        };
+        crate::tools::sprite::create_sprite(&server, params).await.unwrap();
+        let draw: crate::tools::drawing::DrawPixelsParams = serde_json::from_value(serde_json::json!({
+            "file_path": path,
+            "pixels": [{ "x": 0, "y": 0, "color": "#ff0000" }, { "x": 1, "y": 1, "color": "#ff0000" }],
+            "layer": "Layer 1",
+        }))
+        .unwrap();
+        crate::tools::drawing::draw_pixels(&server, draw).await.unwrap();
+        let moved: crate::tools::cel::MoveCelParams = serde_json::from_value(serde_json::json!({
+            "file_path": path, "layer": "Layer 1", "frame": 1, "x": -1, "y": -1,
+        }))
+        .unwrap();
+        crate::tools::cel::move_cel(&server, moved).await.unwrap();
+
+        // The pixel drawn at (0, 0) now sits off the canvas, so only one is selected
+        let params = SelectOpaqueParams {
+            file_path: path.clone(),
+            layer: Some("Layer 1".to_string()),
+            frame: None,
+            alpha_threshold: None,
+        };
+        let result: serde_json::Value = serde_json::from_str(&select_opaque(&server, params).await.unwrap()).unwrap();
+        assert_eq!(result["pixelCount"], 1);
+    }
+
+    #[test]
+    fn selection_modes_map_to_selection_methods() {
+        assert_eq!(selection_mode_fn(None), Ok("select"));
+        assert_eq!(selection_mode_fn(Some("replace")), Ok("select"));
+        assert_eq!(selection_mode_fn(Some("intersect")), Ok("intersect"));
+        assert_eq!(
+            selection_mode_fn(Some("xor")),
+            Err("Invalid mode 'xor'. Must be one of: replace, add, subtract, intersect".to_string())
+        );
+    }
+}
@@ -2,12 +2,28 @@ use rmcp::schemars;
 use serde::Deserialize;
 
 use crate::aseprite::lua_string;
+use crate::lua_helpers::{lua_name_filter, lua_pager, lua_save_target, with_saved_to};
 use crate::server::AsepriteServer;
+use crate::tools::selection::RectData;
+use crate::tools::sprite::snap_grid;
+use crate::utils::parse_color;
 
 // ============================================================================
 // Parameter Structs
 // ============================================================================
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListSlicesParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Only include slices whose name contains this text (case-insensitive)
+    pub name_contains: Option<String>,
+    /// Maximum number of entries to return (default: all)
+    pub limit: Option<u32>,
+    /// Number of matching entries to skip before returning (default: 0)
+    pub offset: Option<u32>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct CreateSliceParams {
     /// Path to the sprite file
@@ -26,10 +42,16 @@ pub struct CreateSliceParams {
     pub center: Option<SliceRect>,
     /// Pivot point for the slice (anchor point for game engines). Format: {x, y} relative to slice bounds.
     pub pivot: Option<SlicePoint>,
-    /// User-defined color for the slice in hex (e.g. "#ff0000")
+    /// User-defined color for the slice as hex, CSS name, or rgb()/rgba() (e.g. "#ff0000")
     pub color: Option<String>,
     /// User-defined data string (can store JSON metadata for game engines)
     pub data: Option<String>,
+    /// Snap the slice bounds' edges to the nearest grid lines (default: false)
+    pub snap_to_grid: Option<bool>,
+    /// Grid for snap_to_grid as {x, y, width, height}: origin and cell size (if omitted, uses the sprite's grid)
+    pub grid: Option<RectData>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -58,53 +80,66 @@ pub struct DeleteSliceParams {
     pub file_path: String,
     /// Name of the slice to delete
     pub name: String,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
 // Tool Implementations
 // ============================================================================
 
-pub async fn list_slices(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
-    let script = r##"local spr = app.sprite
-local slices = {}
+pub async fn list_slices(server: &AsepriteServer, p: ListSlicesParams) -> Result<String, String> {
+    let script = format!(
+        r##"local spr = app.sprite
+{name_filter}
+{pager}
+local slices = {{}}
 for i, slice in ipairs(spr.slices) do
-    local s = {}
-    s.name = slice.name
-    if slice.bounds then
-        s.bounds = {
-            x = slice.bounds.x,
-            y = slice.bounds.y,
-            width = slice.bounds.width,
-            height = slice.bounds.height
-        }
-    end
-    if slice.center then
-        s.center = {
-            x = slice.center.x,
-            y = slice.center.y,
-            width = slice.center.width,
-            height = slice.center.height
-        }
-    end
-    if slice.pivot then
-        s.pivot = {
-            x = slice.pivot.x,
-            y = slice.pivot.y
-        }
-    end
-    if slice.color then
-        s.color = string.format("#%02x%02x%02x%02x", slice.color.red, slice.color.green, slice.color.blue, slice.color.alpha)
+    if name_matches(slice.name) and in_page() then
+        local s = {{}}
+        s.name = slice.name
+        if slice.bounds then
+            s.bounds = {{
+                x = slice.bounds.x,
+                y = slice.bounds.y,
+                width = slice.bounds.width,
+                height = slice.bounds.height
+            }}
+        end
+        if slice.center then
+            s.center = {{
+                x = slice.center.x,
+                y = slice.center.y,
+                width = slice.center.width,
+                height = slice.center.height
+            }}
+        end
+        if slice.pivot then
+            s.pivot = {{
+                x = slice.pivot.x,
+                y = slice.pivot.y
+            }}
+        end
+        if slice.color then
+            s.color = string.format("#%02x%02x%02x%02x", slice.color.red, slice.color.green, slice.color.blue, slice.color.alpha)
+        end
+        if slice.data and slice.data ~= "" then
+            s.data = slice.data
+        end
+        table.insert(slices, s)
     end
-    if slice.data and slice.data ~= "" then
-        s.data = slice.data
-    end
-    table.insert(slices, s)
 end
-print(json.encode({slices = slices, total = #slices}))"##;
-    server.execute_script_on_file(file_path, script).await
+print(json.encode({{slices = slices, total = matched, returned = #slices}}))"##,
+        name_filter = lua_name_filter(p.name_contains.as_deref()),
+        pager = lua_pager(p.limit, p.offset)
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
 }
 
-pub async fn create_slice(server: &AsepriteServer, p: CreateSliceParams) -> Result<String, String> {
+pub async fn create_slice(server: &AsepriteServer, mut p: CreateSliceParams) -> Result<String, String> {
+    if let Some(grid) = snap_grid(server, &p.file_path, p.snap_to_grid, p.grid.as_ref()).await? {
+        (p.x, p.y, p.width, p.height) = grid.snap_rect(p.x, p.y, p.width, p.height);
+    }
     let mut extra_code = String::new();
 
     if let Some(ref center) = p.center {
@@ -120,32 +155,23 @@ pub async fn create_slice(server: &AsepriteServer, p: CreateSliceParams) -> Resu
         ));
     }
     if let Some(ref color) = p.color {
-        let color_clean = color.trim_start_matches('#');
-        if color_clean.len() >= 6 {
-            let r = u8::from_str_radix(&color_clean[0..2], 16).unwrap_or(0);
-            let g = u8::from_str_radix(&color_clean[2..4], 16).unwrap_or(0);
-            let b = u8::from_str_radix(&color_clean[4..6], 16).unwrap_or(0);
-            let a = if color_clean.len() >= 8 {
-                u8::from_str_radix(&color_clean[6..8], 16).unwrap_or(255)
-            } else {
-                255
-            };
-            extra_code.push_str(&format!(
-                "slice.color = Color({}, {}, {}, {})\n",
-                r, g, b, a
-            ));
-        }
+        let (r, g, b, a) = parse_color(color).map_err(|e| format!("Invalid color '{}': {}", color, e))?;
+        extra_code.push_str(&format!(
+            "slice.color = Color({}, {}, {}, {})\n",
+            r, g, b, a
+        ));
     }
     if let Some(ref data) = p.data {
         extra_code.push_str(&format!("slice.data = {}\n", lua_string(data)));
     }
 
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 local slice = spr:newSlice(Rectangle({x}, {y}, {w}, {h}))
 slice.name = {name}
 {extra}
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.name = slice.name
 result.bounds = {{
@@ -175,18 +201,27 @@ print(json.encode(result))"#,
         w = p.width,
         h = p.height,
         name = lua_string(&p.name),
-        extra = extra_code
+        extra = extra_code,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn delete_slice(server: &AsepriteServer, p: DeleteSliceParams) -> Result<String, String> {
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 spr:deleteSlice({name})
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "deleted", slice = {name}}}))"#,
-        name = lua_string(&p.name)
+        name = lua_string(&p.name),
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
@@ -1,8 +1,22 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use base64::Engine;
+use rmcp::model::{CallToolResult, Content};
 use rmcp::schemars;
 use serde::Deserialize;
+use serde_json::json;
 
 use crate::aseprite::{lua_path, lua_string};
+use crate::encode::encode_png_rgba;
+use crate::lua_helpers::{
+    DataFile, LUA_FIND_LAYER, lua_find_slice, lua_require_tag, lua_save_as_target, lua_save_target, same_file,
+    with_saved_to,
+};
 use crate::server::AsepriteServer;
+use crate::tools::selection::RectData;
+use crate::tools::frame::{FrameRef, lua_frame_range};
+use crate::utils::{check_choice, check_range, resolve_file_list};
 
 // ============================================================================
 // Parameter Structs
@@ -16,7 +30,7 @@ pub struct CreateSpriteParams {
     pub height: u32,
     /// Output file path (e.g. "my_sprite.aseprite", "art/player.png")
     pub output_path: String,
-    /// Color mode: "rgb", "grayscale", or "indexed" (default: "rgb")
+    /// Color mode: "rgb", "grayscale", or "indexed" (default: "rgb", changeable with set_config)
     pub color_mode: Option<String>,
 }
 
@@ -86,6 +100,22 @@ pub struct CanvasSizeParams {
     pub right: i32,
     /// Bottom padding
     pub bottom: i32,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct PadCanvasToParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// "pot" to pad each side up to the next power of two, or "multiple" to pad up to the next multiple of `multiple`
+    pub mode: String,
+    /// Size step for mode "multiple", e.g. 4 or 16 (1-4096)
+    pub multiple: Option<u32>,
+    /// Where the existing content sits on the larger canvas: "top_left" (default) or "center"
+    pub anchor: Option<String>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -96,10 +126,42 @@ pub struct DuplicateSpriteParams {
     pub output_path: String,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExtractRegionParams {
+    /// Path to the source sprite file
+    pub file_path: String,
+    /// Path to save the new sprite (e.g. "props/barrel.aseprite")
+    pub output_path: String,
+    /// Region to extract: {x, y, width, height}. Required unless slice is given.
+    pub region: Option<RectData>,
+    /// Extract the bounds of this slice instead of an explicit region
+    pub slice: Option<String>,
+    /// Only keep this layer (if omitted, keeps all layers)
+    pub layer: Option<String>,
+    /// Only keep this frame, 1-based (if omitted, keeps all frames)
+    pub frame: Option<u32>,
+    /// Keep the layer structure instead of flattening into a single layer (default: false)
+    pub keep_layers: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct AutoCropParams {
+    /// Path to the sprite file (or use file_paths / glob to process several)
+    pub file_path: Option<String>,
+    /// Several sprite files to process in turn, each saved in place; entries may be glob patterns
+    pub file_paths: Option<Vec<String>>,
+    /// Glob pattern selecting the sprite files to process, e.g. "sprites/*.aseprite"
+    pub glob: Option<String>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CropToSelectionParams {
     /// Path to the sprite file
     pub file_path: String,
+    /// Region to crop to: {x, y, width, height}. If omitted, uses the selection stored in the sprite.
+    pub region: Option<RectData>,
     /// Save to a different path (if omitted, overwrites the original)
     pub output_path: Option<String>,
 }
@@ -122,6 +184,55 @@ pub struct ReverseFramesParams {
     pub from_frame: Option<u32>,
     /// Last frame number (1-based) of the range to reverse. Defaults to last frame.
     pub to_frame: Option<u32>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ValidateSpriteParams {
+    /// Path to the sprite file to check
+    pub file_path: String,
+    /// If the file opens but with problems, save a recovered copy to output_path (default: false)
+    pub repair: Option<bool>,
+    /// Where to save the recovered copy (required with repair; the original is never overwritten)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DescribeSpriteParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Largest side of the inline thumbnail of frame 1, in pixels; 0 leaves it out (default: 64)
+    pub thumbnail_size: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetFrameThumbnailsParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Only show this tag's frames
+    pub tag: Option<String>,
+    /// First frame: 1-based number or "tag:n" / "tag:start" / "tag:end" (default: 1)
+    pub from_frame: Option<FrameRef>,
+    /// Last frame, inclusive (default: last frame)
+    pub to_frame: Option<FrameRef>,
+    /// Largest side of each thumbnail in pixels, 1-256; smaller sprites aren't upscaled (default: 64)
+    pub max_size: Option<u32>,
+    /// "frames" for one image per frame (default) or "strip" for a single image with the frames side by side
+    pub layout: Option<String>,
+    /// Most frames to return, at most 32 (default: 32)
+    pub limit: Option<u32>,
+    /// Frames of the range to skip, for paging through long animations (default: 0)
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CheckGridAlignmentParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Grid to check against as {x, y, width, height}: origin and cell size (if omitted, uses the
+    /// sprite's grid). Tilemap cels are always checked against their tileset's grid.
+    pub grid: Option<RectData>,
 }
 
 // ============================================================================
@@ -135,12 +246,12 @@ pub async fn create_sprite(server: &AsepriteServer, p: CreateSpriteParams) -> Re
     if p.output_path.trim().is_empty() {
         return Err("Output path cannot be empty".to_string());
     }
-    let color_mode = match p.color_mode.as_deref() {
-        Some("grayscale") => "ColorMode.GRAYSCALE",
-        Some("indexed") => "ColorMode.INDEXED",
+    let color_mode = match p.color_mode.unwrap_or_else(|| server.config().default_color_mode).as_str() {
+        "grayscale" => "ColorMode.GRAYSCALE",
+        "indexed" => "ColorMode.INDEXED",
         _ => "ColorMode.RGB",
     };
-    let output = lua_path(&server.resolve_output_path(&p.output_path));
+    let output = lua_path(&server.resolve_output_path(&p.output_path)?);
 
     let script = format!(
         r#"local spr = Sprite({w}, {h}, {cm})
@@ -259,12 +370,133 @@ print(json.encode(result))"#;
     server.execute_script_on_file(&p.file_path, script).await
 }
 
+pub async fn get_sprite_stats(server: &AsepriteServer, p: SpriteFileParams) -> Result<String, String> {
+    let file_path = server.resolve_input_path(&p.file_path)?;
+    let file_size = tokio::fs::metadata(&file_path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))?
+        .len();
+    let script = r#"local spr = app.sprite
+local pc = app.pixelColor
+local mode = spr.colorMode
+local pal = spr.palettes[1]
+local transparent = spr.transparentColor
+
+-- Palette entry lookup for RGB sprites (exact color match); indexed sprites use the index itself
+local pal_index = {}
+if mode == ColorMode.RGB then
+    for i = 0, #pal - 1 do
+        local c = pal:getColor(i)
+        if pal_index[c.rgbaPixel] == nil then pal_index[c.rgbaPixel] = i end
+    end
+end
+local used = {}
+
+local function visible(v, layer)
+    if mode == ColorMode.RGB then return pc.rgbaA(v) > 0 end
+    if mode == ColorMode.GRAYSCALE then return pc.grayaA(v) > 0 end
+    return layer.isBackground or v ~= transparent
+end
+
+local min_x, min_y, max_x, max_y = nil, nil, nil, nil
+local seen_images, seen_content = {}, {}
+local layer_stats = {}
+local cels, image_bytes, empty, duplicates, linked = 0, 0, 0, 0, 0
+
+local function scan(lyrs)
+    for _, layer in ipairs(lyrs) do
+        if layer.isGroup then
+            scan(layer.layers)
+        elseif layer.isImage then
+            local ls = {name = layer.name, cels = 0, image_bytes = 0}
+            for _, cel in ipairs(layer.cels) do
+                local img = cel.image
+                ls.cels = ls.cels + 1
+                cels = cels + 1
+                local ok, id = pcall(function() return img.id end)
+                if ok and id and seen_images[id] then
+                    linked = linked + 1
+                else
+                    if ok and id then seen_images[id] = true end
+                    local bytes = img.bytes
+                    ls.image_bytes = ls.image_bytes + #bytes
+                    image_bytes = image_bytes + #bytes
+                    local any = false
+                    for it in img:pixels() do
+                        local v = it()
+                        if visible(v, layer) then
+                            any = true
+                            local x, y = cel.position.x + it.x, cel.position.y + it.y
+                            if x >= 0 and y >= 0 and x < spr.width and y < spr.height then
+                                if not min_x or x < min_x then min_x = x end
+                                if not min_y or y < min_y then min_y = y end
+                                if not max_x or x > max_x then max_x = x end
+                                if not max_y or y > max_y then max_y = y end
+                            end
+                            if mode == ColorMode.INDEXED then
+                                used[v] = true
+                            elseif mode == ColorMode.RGB and pal_index[v] then
+                                used[pal_index[v]] = true
+                            end
+                        end
+                    end
+                    if not any then
+                        empty = empty + 1
+                    else
+                        local key = img.width .. "x" .. img.height .. ":" .. bytes
+                        if seen_content[key] then
+                            duplicates = duplicates + 1
+                        else
+                            seen_content[key] = true
+                        end
+                    end
+                end
+            end
+            table.insert(layer_stats, ls)
+        end
+    end
+end
+scan(spr.layers)
+
+local modes = {[ColorMode.RGB] = "RGB", [ColorMode.GRAYSCALE] = "grayscale", [ColorMode.INDEXED] = "indexed"}
+local result = {}
+result.width = spr.width
+result.height = spr.height
+result.color_mode = modes[mode] or "unknown"
+result.frames = #spr.frames
+result.cels = cels
+result.image_bytes = image_bytes
+result.empty_cels = empty
+result.duplicate_cels = duplicates
+result.linked_cels = linked
+result.palette_size = #pal
+if mode ~= ColorMode.GRAYSCALE then
+    local n = 0
+    for _ in pairs(used) do n = n + 1 end
+    result.unused_palette_entries = #pal - n
+end
+if min_x then
+    local w, h = max_x - min_x + 1, max_y - min_y + 1
+    result.content_bounds = {x = min_x, y = min_y, width = w, height = h}
+    result.canvas_utilization = math.floor(w * h * 1000 / (spr.width * spr.height)) / 1000
+else
+    result.canvas_utilization = 0
+end
+result.layers = layer_stats
+print(json.encode(result))"#;
+    let output = server.execute_script_on_file(&file_path, script).await?;
+    let mut stats: serde_json::Value =
+        serde_json::from_str(&output).map_err(|e| format!("Unexpected output from the stats script: {}", e))?;
+    stats["file_size"] = json!(file_size);
+    Ok(stats.to_string())
+}
+
 pub async fn resize_sprite(server: &AsepriteServer, p: ResizeSpriteParams) -> Result<String, String> {
-    let output = server.resolve_output_path(p.output_path.as_deref().unwrap_or(&p.file_path));
+    let (save, saved_to) = lua_save_as_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 spr:resize({w}, {h})
-spr:saveCopyAs({out})
+{save}
 local result = {{}}
 result.width = spr.width
 result.height = spr.height
@@ -273,17 +505,21 @@ result.status = "resized"
 print(json.encode(result))"#,
         w = p.width,
         h = p.height,
-        out = lua_path(&output)
+        save = save,
+        out = lua_path(&saved_to)
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn crop_sprite(server: &AsepriteServer, p: CropSpriteParams) -> Result<String, String> {
-    let output = server.resolve_output_path(p.output_path.as_deref().unwrap_or(&p.file_path));
+    let (save, saved_to) = lua_save_as_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 spr:crop({x}, {y}, {w}, {h})
-spr:saveCopyAs({out})
+{save}
 local result = {{}}
 result.width = spr.width
 result.height = spr.height
@@ -293,13 +529,16 @@ print(json.encode(result))"#,
         y = p.y,
         w = p.width,
         h = p.height,
-        out = lua_path(&output)
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn flip_sprite(server: &AsepriteServer, p: FlipSpriteParams) -> Result<String, String> {
-    let output = server.resolve_output_path(p.output_path.as_deref().unwrap_or(&p.file_path));
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     match p.direction.to_lowercase().as_str() {
         "horizontal" | "vertical" => {}
         _ => return Err("direction must be 'horizontal' or 'vertical'".to_string()),
@@ -311,20 +550,23 @@ app.command.Flip {{
     target = "canvas",
     orientation = {orient}
 }}
-spr:saveCopyAs({out})
+{save}
 print(json.encode({{status = "flipped", direction = {dir}}}))"#,
         orient = lua_string(match p.direction.to_lowercase().as_str() {
             "horizontal" => "horizontal",
             _ => "vertical",
         }),
-        out = lua_path(&output),
+        save = save,
         dir = lua_string(&p.direction)
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn rotate_sprite(server: &AsepriteServer, p: RotateSpriteParams) -> Result<String, String> {
-    let output = server.resolve_output_path(p.output_path.as_deref().unwrap_or(&p.file_path));
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     if p.angle != 90 && p.angle != 180 && p.angle != 270 {
         return Err("angle must be 90, 180, or 270".to_string());
     }
@@ -335,15 +577,19 @@ app.command.Rotate {{
     angle = {angle},
     rotsprite = false
 }}
-spr:saveCopyAs({out})
+{save}
 print(json.encode({{status = "rotated", angle = {angle}, width = spr.width, height = spr.height}}))"#,
         angle = p.angle,
-        out = lua_path(&output)
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn canvas_size(server: &AsepriteServer, p: CanvasSizeParams) -> Result<String, String> {
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 local newW = spr.width + {left} + {right}
@@ -355,7 +601,7 @@ app.command.CanvasSize {{
     right = {right},
     bottom = {bottom}
 }}
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.width = spr.width
 result.height = spr.height
@@ -364,13 +610,99 @@ print(json.encode(result))"#,
         left = p.left,
         top = p.top,
         right = p.right,
-        bottom = p.bottom
+        bottom = p.bottom,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Largest canvas side `pad_canvas_to` will produce (Aseprite's own limit).
+const MAX_CANVAS_SIDE: u32 = 65535;
+
+/// The side length `side` is padded up to: the next power of two, or the next multiple of `step`.
+fn padded_side(side: u32, step: Option<u32>) -> u32 {
+    match step {
+        None => side.next_power_of_two(),
+        Some(multiple) => side.div_ceil(multiple) * multiple,
+    }
+}
+
+pub async fn pad_canvas_to(server: &AsepriteServer, p: PadCanvasToParams) -> Result<String, String> {
+    let by_multiple = check_choice("mode", &p.mode, &[("pot", false), ("multiple", true)], false)?;
+    let step = if by_multiple {
+        let multiple = p.multiple.ok_or("multiple is required for mode \"multiple\"")?;
+        Some(check_range("multiple", multiple, 1..=4096, false)?)
+    } else {
+        if p.multiple.is_some() {
+            return Err("multiple only applies to mode \"multiple\"".to_string());
+        }
+        None
+    };
+    let centered = check_choice(
+        "anchor",
+        p.anchor.as_deref().unwrap_or("top_left"),
+        &[("top_left", false), ("center", true)],
+        false,
+    )?;
+
+    let output = server
+        .execute_script_on_file(
+            &p.file_path,
+            "local spr = app.sprite\nprint(json.encode({width = spr.width, height = spr.height}))",
+        )
+        .await?;
+    let size: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let (width, height) = (size["width"].as_u64().unwrap_or(0) as u32, size["height"].as_u64().unwrap_or(0) as u32);
+    let (new_width, new_height) = (padded_side(width, step), padded_side(height, step));
+    if new_width > MAX_CANVAS_SIDE || new_height > MAX_CANVAS_SIDE {
+        return Err(format!(
+            "Padding {}x{} would give {}x{}, larger than the {} pixel canvas limit",
+            width, height, new_width, new_height, MAX_CANVAS_SIDE
+        ));
+    }
+
+    let mut result = json!({
+        "mode": if by_multiple { "multiple" } else { "pot" },
+        "old_width": width,
+        "old_height": height,
+        "width": new_width,
+        "height": new_height,
+    });
+    if let Some(multiple) = step {
+        result["multiple"] = json!(multiple);
+    }
+    let unchanged = (new_width, new_height) == (width, height);
+    if unchanged && p.output_path.is_none() {
+        result["status"] = json!("unchanged");
+        return Ok(result.to_string());
+    }
+    // A sprite that needs no padding is still written to output_path when one is given
+    let (extra_w, extra_h) = ((new_width - width) as i32, (new_height - height) as i32);
+    let (left, top) = if centered { (extra_w / 2, extra_h / 2) } else { (0, 0) };
+    let resized = canvas_size(
+        server,
+        CanvasSizeParams {
+            file_path: p.file_path,
+            left,
+            top,
+            right: extra_w - left,
+            bottom: extra_h - top,
+            output_path: p.output_path,
+        },
+    )
+    .await?;
+    let resized: serde_json::Value = serde_json::from_str(&resized).map_err(|_| resized.clone())?;
+    result["padding"] = json!({ "left": left, "top": top, "right": extra_w - left, "bottom": extra_h - top });
+    result["saved_to"] = resized["saved_to"].clone();
+    result["status"] = json!(if unchanged { "unchanged" } else { "padded" });
+    Ok(result.to_string())
 }
 
 pub async fn duplicate_sprite(server: &AsepriteServer, p: DuplicateSpriteParams) -> Result<String, String> {
-    let output = lua_path(&server.resolve_output_path(&p.output_path));
+    let output = lua_path(&server.resolve_output_path(&p.output_path)?);
     let script = format!(
         r#"local spr = app.sprite
 local copy = Sprite(spr)
@@ -388,16 +720,122 @@ print(json.encode(result))"#,
     server.execute_script_on_file(&p.file_path, &script).await
 }
 
-pub async fn auto_crop_sprite(server: &AsepriteServer, p: AutoCropParams) -> Result<String, String> {
-    let save_code = if let Some(ref output) = p.output_path {
-        let out = lua_path(&server.resolve_output_path(output));
-        format!("spr:saveCopyAs({})", out)
+pub async fn extract_region_to_sprite(server: &AsepriteServer, p: ExtractRegionParams) -> Result<String, String> {
+    if p.output_path.trim().is_empty() {
+        return Err("Output path cannot be empty".to_string());
+    }
+    let bounds_code = match (&p.region, &p.slice) {
+        (Some(_), Some(_)) => return Err("Specify either region or slice, not both".to_string()),
+        (Some(r), None) => {
+            if r.width == 0 || r.height == 0 {
+                return Err("Region width and height must be greater than 0".to_string());
+            }
+            format!("local bounds = Rectangle({}, {}, {}, {})", r.x, r.y, r.width, r.height)
+        }
+        (None, Some(slice)) => format!("{}\nlocal bounds = target_slice.bounds", lua_find_slice(slice)),
+        (None, None) => return Err("Either region or slice must be provided".to_string()),
+    };
+    let layer_code = if let Some(ref layer_name) = p.layer {
+        format!(
+            r#"{find_layer}
+local keep = find_layer(copy.layers, {name})
+if not keep then
+    copy:close()
+    print(json.encode({{error = "Layer not found: " .. {name}}}))
+    return
+end
+-- Delete every layer that is neither the kept layer nor one of its parent groups
+local function prune(lyrs)
+    for i = #lyrs, 1, -1 do
+        local l = lyrs[i]
+        local ancestor = false
+        local p = keep.parent
+        while p and p ~= copy do
+            if p == l then ancestor = true break end
+            p = p.parent
+        end
+        if ancestor then
+            prune(l.layers)
+        elseif l ~= keep then
+            copy:deleteLayer(l)
+        end
+    end
+end
+prune(copy.layers)"#,
+            find_layer = LUA_FIND_LAYER,
+            name = lua_string(layer_name)
+        )
+    } else {
+        String::new()
+    };
+    let frame_code = if let Some(frame) = p.frame {
+        format!(
+            r#"if not copy.frames[{frame}] then
+    copy:close()
+    print(json.encode({{error = "Frame {frame} does not exist"}}))
+    return
+end
+for i = #copy.frames, 1, -1 do
+    if i ~= {frame} then copy:deleteFrame(i) end
+end"#,
+            frame = frame
+        )
     } else {
-        "spr:saveAs(spr.filename)".to_string()
+        String::new()
     };
+    let flatten_code = if p.keep_layers.unwrap_or(false) { "" } else { "copy:flatten()" };
 
     let script = format!(
         r#"local spr = app.sprite
+{bounds_code}
+bounds = bounds:intersect(spr.bounds)
+if bounds.isEmpty then
+    print(json.encode({{error = "Region lies outside the canvas"}}))
+    return
+end
+local copy = Sprite(spr)
+{layer_code}
+{frame_code}
+copy:crop(bounds)
+{flatten_code}
+copy:saveAs({out})
+local result = {{}}
+result.filename = copy.filename
+result.bounds = {{
+    x = bounds.x,
+    y = bounds.y,
+    width = bounds.width,
+    height = bounds.height
+}}
+result.width = copy.width
+result.height = copy.height
+result.numLayers = #copy.layers
+result.numFrames = #copy.frames
+result.status = "extracted"
+print(json.encode(result))"#,
+        bounds_code = bounds_code,
+        layer_code = layer_code,
+        frame_code = frame_code,
+        flatten_code = flatten_code,
+        out = lua_path(&server.resolve_output_path(&p.output_path)?)
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn auto_crop_sprite(server: &AsepriteServer, p: AutoCropParams) -> Result<String, String> {
+    let targets = resolve_file_list(
+        server,
+        p.file_path.as_deref(),
+        p.file_paths.as_deref(),
+        p.glob.as_deref(),
+        p.output_path.as_deref(),
+    )?;
+    let output_path = p.output_path.as_deref();
+    targets
+        .run(|file| async move {
+            let (save_code, saved_to) = lua_save_target(server, &file, output_path)?;
+            let script = format!(
+                r#"local spr = app.sprite
 local oldW, oldH = spr.width, spr.height
 app.command.AutocropSprite()
 {save}
@@ -408,9 +846,59 @@ result.width = spr.width
 result.height = spr.height
 result.status = "auto_cropped"
 print(json.encode(result))"#,
+                save = save_code
+            );
+            server
+                .execute_script_on_file(&file, &script)
+                .await
+                .map(|output| with_saved_to(output, &saved_to))
+        })
+        .await
+}
+
+pub async fn crop_to_selection(server: &AsepriteServer, p: CropToSelectionParams) -> Result<String, String> {
+    let bounds_code = if let Some(ref r) = p.region {
+        if r.width == 0 || r.height == 0 {
+            return Err("Region width and height must be greater than 0".to_string());
+        }
+        format!("local bounds = Rectangle({}, {}, {}, {})", r.x, r.y, r.width, r.height)
+    } else {
+        r#"if spr.selection.isEmpty then
+    print(json.encode({error = "No selection stored in the sprite and no region provided"}))
+    return
+end
+local bounds = spr.selection.bounds"#
+            .to_string()
+    };
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+
+    let script = format!(
+        r#"local spr = app.sprite
+{bounds_code}
+local oldW, oldH = spr.width, spr.height
+spr:crop(bounds)
+spr.selection:deselect()
+{save}
+local result = {{}}
+result.oldWidth = oldW
+result.oldHeight = oldH
+result.bounds = {{
+    x = bounds.x,
+    y = bounds.y,
+    width = bounds.width,
+    height = bounds.height
+}}
+result.width = spr.width
+result.height = spr.height
+result.status = "cropped"
+print(json.encode(result))"#,
+        bounds_code = bounds_code,
         save = save_code
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn change_color_mode(server: &AsepriteServer, p: ChangeColorModeParams) -> Result<String, String> {
@@ -421,12 +909,7 @@ pub async fn change_color_mode(server: &AsepriteServer, p: ChangeColorModeParams
         _ => return Err("color_mode must be 'rgb', 'grayscale', or 'indexed'".to_string()),
     };
 
-    let save_code = if let Some(ref output) = p.output_path {
-        let out = lua_path(&server.resolve_output_path(output));
-        format!("spr:saveCopyAs({})", out)
-    } else {
-        "spr:saveAs(spr.filename)".to_string()
-    };
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
 
     let script = format!(
         r#"local spr = app.sprite
@@ -444,7 +927,10 @@ print(json.encode(result))"#,
         format = format_str,
         save = save_code
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
 
 pub async fn reverse_frames(server: &AsepriteServer, p: ReverseFramesParams) -> Result<String, String> {
@@ -455,6 +941,7 @@ pub async fn reverse_frames(server: &AsepriteServer, p: ReverseFramesParams) ->
         "local toFrame = #spr.frames".to_string()
     };
 
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 local fromFrame = {from}
@@ -467,7 +954,7 @@ for i = fromFrame, toFrame do
     range:contains(spr.frames[i])
 end
 app.command.ReverseFrames()
-spr:saveAs(spr.filename)
+{save}
 local result = {{}}
 result.fromFrame = fromFrame
 result.toFrame = toFrame
@@ -475,7 +962,786 @@ result.numFrames = #spr.frames
 result.status = "reversed"
 print(json.encode(result))"#,
         from = from,
-        to_code = to_code
+        to_code = to_code,
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+pub async fn validate_sprite(server: &AsepriteServer, p: ValidateSpriteParams) -> Result<String, String> {
+    let repair = p.repair.unwrap_or(false);
+    let output = match (repair, p.output_path.as_deref()) {
+        (true, Some(output)) => Some(server.resolve_output_path(output)?),
+        (true, None) => return Err("repair requires output_path".to_string()),
+        (false, _) => None,
+    };
+    let file_path = match server.resolve_input_path(&p.file_path) {
+        Ok(path) if Path::new(&path).is_file() => path,
+        Ok(_) => return Ok(json!({ "status": "missing", "file": p.file_path }).to_string()),
+        Err(e) if e.starts_with("File not found") => {
+            return Ok(json!({ "status": "missing", "file": p.file_path, "error": e }).to_string());
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(ref output) = output
+        && same_file(output, &file_path)
+    {
+        return Err("output_path must differ from file_path: the repaired copy never replaces the original".to_string());
+    }
+
+    let mut warnings: Vec<String> = Vec::new();
+    let header = aseprite_header_check(&file_path).await;
+    if let Some(ref header) = header
+        && header["truncated"] == true
+    {
+        warnings.push(format!(
+            "File is {} bytes but its header declares {} bytes (truncated save?)",
+            header["actual_size"], header["declared_size"]
+        ));
+    }
+
+    let (opened, stdout_noise, stderr) = open_sprite_summary(server, &file_path, None).await?;
+    let Some(summary) = opened else {
+        let mut error = stderr.trim().to_string();
+        if error.is_empty() {
+            error = stdout_noise.join("\n");
+        }
+        return Ok(json!({
+            "status": "unreadable",
+            "file": file_path,
+            "error": if error.is_empty() { "Aseprite could not open the file".to_string() } else { error },
+            "header": header,
+        })
+        .to_string());
+    };
+    if !stderr.trim().is_empty() {
+        warnings.push(stderr.trim().to_string());
+    }
+    warnings.extend(stdout_noise);
+    let missing = summary["missingImages"].as_u64().unwrap_or(0);
+    if missing > 0 {
+        warnings.push(format!("{} cel(s) have no image", missing));
+    }
+
+    let status = if warnings.is_empty() { "ok" } else { "opened_with_warnings" };
+    let mut result = json!({
+        "status": status,
+        "file": file_path,
+        "summary": summary,
+        "warnings": warnings,
+        "header": header,
+    });
+    if let Some(output) = output {
+        if status == "ok" {
+            result["repair"] = json!("not needed");
+        } else {
+            let (saved, _, stderr) = open_sprite_summary(server, &file_path, Some(&output)).await?;
+            if saved.is_none() {
+                return Err(format!("Failed to save a recovered copy: {}", stderr.trim()));
+            }
+            result["repaired_path"] = json!(output);
+        }
+    }
+    Ok(result.to_string())
+}
+
+/// Compare an .aseprite file's declared size (first header field) with its actual size.
+/// Returns `None` for other formats.
+async fn aseprite_header_check(path: &str) -> Option<serde_json::Value> {
+    let bytes = tokio::fs::read(path).await.ok()?;
+    if bytes.get(4..6) != Some(&[0xe0, 0xa5]) {
+        return None;
+    }
+    let declared = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?) as usize;
+    Some(json!({
+        "declared_size": declared,
+        "actual_size": bytes.len(),
+        "truncated": bytes.len() < declared,
+    }))
+}
+
+/// Open `path` in a fresh Aseprite run and summarize it, optionally saving a copy to `save_to`.
+/// Returns the summary (`None` if the file couldn't be opened), any extra stdout lines, and stderr.
+async fn open_sprite_summary(
+    server: &AsepriteServer,
+    path: &str,
+    save_to: Option<&str>,
+) -> Result<(Option<serde_json::Value>, Vec<String>, String), String> {
+    let script = format!(
+        r#"local spr = app.open({path})
+if not spr then
+    print(json.encode({{opened = false}}))
+    return
+end
+local function count_layers(lyrs)
+    local n = 0
+    for _, l in ipairs(lyrs) do
+        n = n + 1
+        if l.isGroup then n = n + count_layers(l.layers) end
+    end
+    return n
+end
+local cels, missing = 0, 0
+for _, cel in ipairs(spr.cels) do
+    cels = cels + 1
+    if not cel.image then missing = missing + 1 end
+end
+{save}
+print(json.encode({{
+    opened = true,
+    width = spr.width,
+    height = spr.height,
+    colorMode = spr.colorMode == ColorMode.RGB and "rgb" or spr.colorMode == ColorMode.GRAYSCALE and "grayscale" or "indexed",
+    layers = count_layers(spr.layers),
+    frames = #spr.frames,
+    cels = cels,
+    missingImages = missing,
+    tags = #spr.tags,
+    slices = #spr.slices
+}}))"#,
+        path = lua_path(path),
+        save = save_to
+            .map(|out| format!("spr:saveCopyAs({})", lua_path(out)))
+            .unwrap_or_default()
+    );
+    let script_path = server.temp_file_path("lua");
+    tokio::fs::write(&script_path, script)
+        .await
+        .map_err(|e| format!("Failed to write temporary Lua script: {}", e))?;
+    let args = vec!["--script".to_string(), script_path.to_string_lossy().to_string()];
+    let output = server.run_cli(&args).await;
+    tokio::fs::remove_file(&script_path).await.ok();
+    let output = output.map_err(|e| format!("Failed to run Aseprite: {}", e))?;
+
+    // The summary is the last JSON line; anything else Aseprite printed is a warning
+    let mut summary = None;
+    let mut noise = Vec::new();
+    for line in output.stdout.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(value) if value.get("opened").is_some() => summary = Some(value),
+            _ => noise.push(line.to_string()),
+        }
+    }
+    let summary = summary.filter(|s| s["opened"] == true).map(|mut s| {
+        if let Some(obj) = s.as_object_mut() {
+            obj.remove("opened");
+        }
+        s
+    });
+    Ok((summary, noise, output.stderr))
+}
+
+const DESCRIBE_TOP_COLORS: usize = 5;
+
+pub async fn describe_sprite(server: &AsepriteServer, p: DescribeSpriteParams) -> Result<CallToolResult, String> {
+    let thumbnail_size = p.thumbnail_size.unwrap_or(64);
+    // Frame 1 is rendered at full size into the data file, so color shares count real pixels
+    let render = DataFile::new(server);
+    let script = format!(
+        r##"local spr = app.sprite
+local layers = {{}}
+local function walk(lyrs, depth)
+    for i = #lyrs, 1, -1 do
+        local l = lyrs[i]
+        local kind = l.isGroup and "group" or (l.isTilemap and "tilemap" or (l.isReference and "reference" or "image"))
+        layers[#layers + 1] = {{name = l.name, depth = depth, kind = kind, visible = l.isVisible}}
+        if l.isGroup then walk(l.layers, depth + 1) end
+    end
+end
+walk(spr.layers, 0)
+local function duration_ms(from, to)
+    local total = 0
+    for f = from, to do total = total + spr.frames[f].duration end
+    return math.floor(total * 1000 + 0.5)
+end
+local dirs = {{[AniDir.FORWARD] = "forward", [AniDir.REVERSE] = "reverse", [AniDir.PING_PONG] = "pingpong"}}
+local tags = {{}}
+for _, t in ipairs(spr.tags) do
+    local a, b = t.fromFrame.frameNumber, t.toFrame.frameNumber
+    tags[#tags + 1] = {{name = t.name, from = a, to = b, duration = duration_ms(a, b), direction = dirs[t.aniDir] or "other"}}
+end
+local bounds = nil
+for _, cel in ipairs(spr.cels) do
+    if cel.layer.isVisible and not cel.image:isEmpty() then
+        bounds = bounds and bounds:union(cel.bounds) or cel.bounds
+    end
+end
+local content = nil
+if bounds then
+    bounds = bounds:intersect(spr.bounds)
+    if not bounds.isEmpty then
+        content = {{x = bounds.x, y = bounds.y, width = bounds.width, height = bounds.height}}
+    end
+end
+local img = Image(spr.width, spr.height, ColorMode.RGB)
+img:drawSprite(spr, 1)
+{open_data}
+data_file:write(img.bytes)
+data_file:close()
+local modes = {{[ColorMode.RGB] = "RGB", [ColorMode.GRAYSCALE] = "grayscale", [ColorMode.INDEXED] = "indexed"}}
+print(json.encode({{
+    name = app.fs.fileName(spr.filename),
+    width = spr.width,
+    height = spr.height,
+    color_mode = modes[spr.colorMode] or "unknown",
+    palette_size = #spr.palettes[1],
+    frames = #spr.frames,
+    duration = duration_ms(1, #spr.frames),
+    layers = layers,
+    tags = tags,
+    content = content
+}}))"##,
+        open_data = render.lua_open()
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let info: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let sample_w = info["width"].as_u64().unwrap_or(0) as u32;
+    let sample_h = info["height"].as_u64().unwrap_or(0) as u32;
+    let pixels = render.read().await?;
+    if pixels.len() != (sample_w as usize) * (sample_h as usize) * 4 {
+        return Err(format!("Unexpected pixel data from the sprite: {}", output));
+    }
+
+    let mut text = format!(
+        "{}: {}x{} {}, {} frame{} ({} ms total)",
+        info["name"].as_str().unwrap_or_default(),
+        info["width"],
+        info["height"],
+        info["color_mode"].as_str().unwrap_or_default(),
+        info["frames"],
+        if info["frames"] == 1 { "" } else { "s" },
+        info["duration"]
+    );
+    if info["color_mode"] == "indexed" {
+        text.push_str(&format!(", {} palette colors", info["palette_size"]));
+    }
+    text.push('\n');
+    match info["content"].as_object() {
+        Some(c) => text.push_str(&format!(
+            "Content bounds: x={} y={} {}x{}\n",
+            c["x"], c["y"], c["width"], c["height"]
+        )),
+        None => text.push_str("Content bounds: empty (no visible pixels)\n"),
+    }
+
+    let colors = dominant_colors(&pixels, DESCRIBE_TOP_COLORS);
+    if colors.is_empty() {
+        text.push_str("Dominant colors (frame 1): none, fully transparent\n");
+    } else {
+        let list: Vec<String> = colors.iter().map(|(hex, pct)| format!("{} {:.1}%", hex, pct)).collect();
+        text.push_str(&format!("Dominant colors (frame 1, share of opaque pixels): {}\n", list.join(", ")));
+    }
+
+    text.push_str("Layers (top first):\n");
+    for layer in info["layers"].as_array().into_iter().flatten() {
+        let depth = layer["depth"].as_u64().unwrap_or(0) as usize;
+        let mut notes = Vec::new();
+        if layer["kind"] != "image" {
+            notes.push(layer["kind"].as_str().unwrap_or_default());
+        }
+        if layer["visible"] == false {
+            notes.push("hidden");
+        }
+        let notes = if notes.is_empty() { String::new() } else { format!(" [{}]", notes.join(", ")) };
+        text.push_str(&format!("{}{}{}\n", "  ".repeat(depth + 1), layer["name"].as_str().unwrap_or_default(), notes));
+    }
+
+    let tags = info["tags"].as_array().cloned().unwrap_or_default();
+    if tags.is_empty() {
+        text.push_str("Tags: none");
+    } else {
+        text.push_str("Tags:");
+        for tag in &tags {
+            let (from, to) = (tag["from"].as_u64().unwrap_or(0), tag["to"].as_u64().unwrap_or(0));
+            text.push_str(&format!(
+                "\n  {}: frames {}-{} ({} frames, {} ms, {})",
+                tag["name"].as_str().unwrap_or_default(),
+                from,
+                to,
+                to.saturating_sub(from) + 1,
+                tag["duration"],
+                tag["direction"].as_str().unwrap_or_default()
+            ));
+        }
+    }
+
+    let mut content = vec![Content::text(text)];
+    if thumbnail_size > 0 && sample_w > 0 && sample_h > 0 {
+        let (thumb, w, h) = downscale_nearest(&pixels, sample_w, sample_h, thumbnail_size);
+        let png = encode_png_rgba(&thumb, w, h)?;
+        content.push(Content::image(base64::engine::general_purpose::STANDARD.encode(png), "image/png"));
+    }
+    Ok(CallToolResult::success(content))
+}
+
+/// Most frames `get_frame_thumbnails` returns per call.
+const MAX_THUMBNAIL_FRAMES: u32 = 32;
+/// Transparent gap between frames in a thumbnail strip.
+const THUMBNAIL_STRIP_GAP: u32 = 2;
+
+pub async fn get_frame_thumbnails(
+    server: &AsepriteServer,
+    p: GetFrameThumbnailsParams,
+) -> Result<CallToolResult, String> {
+    let max_size = check_range("max_size", p.max_size.unwrap_or(64), 1..=256, false)?;
+    let strip = check_choice("layout", p.layout.as_deref().unwrap_or("frames"), &[("frames", false), ("strip", true)], false)?;
+    let limit = check_range("limit", p.limit.unwrap_or(MAX_THUMBNAIL_FRAMES), 1..=MAX_THUMBNAIL_FRAMES, false)?;
+    let offset = p.offset.unwrap_or(0);
+    let range = match p.tag {
+        Some(ref tag) if p.from_frame.is_none() && p.to_frame.is_none() => format!(
+            r#"{require_tag}
+local from_f, to_f = tag.fromFrame.frameNumber, tag.toFrame.frameNumber"#,
+            require_tag = lua_require_tag("tag", tag)
+        ),
+        Some(_) => return Err("Use either tag or from_frame/to_frame, not both".to_string()),
+        None => lua_frame_range(server, &p.file_path, p.from_frame.as_ref(), p.to_frame.as_ref()).await?,
+    };
+
+    // Full-size frames go through the data file and are downscaled here with nearest-neighbor
+    // sampling so pixel art stays crisp
+    let render = DataFile::new(server);
+    let script = format!(
+        r##"local spr = app.sprite
+{range}
+to_f = math.min(to_f, #spr.frames)
+if from_f > to_f then
+    print(json.encode({{error = "Frame " .. from_f .. " out of range (sprite has " .. #spr.frames .. " frames)"}}))
+    return
+end
+{open_data}
+local full = Image(spr.width, spr.height, ColorMode.RGB)
+local frames = {{}}
+local first = from_f + {offset}
+local last = math.min(to_f, first + {limit} - 1)
+for f = first, last do
+    full:clear()
+    full:drawSprite(spr, f)
+    data_file:write(full.bytes)
+    frames[#frames + 1] = {{frame = f, duration = math.floor(spr.frames[f].duration * 1000 + 0.5)}}
+end
+data_file:close()
+print(json.encode({{from = from_f, to = to_f, sprite_width = spr.width, sprite_height = spr.height, frames = frames}}))"##,
+        range = range,
+        open_data = render.lua_open(),
+        offset = offset,
+        limit = limit
+    );
+    let output = server.execute_script_on_file(&p.file_path, &script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let (from, to) = (json["from"].as_u64().unwrap_or(1), json["to"].as_u64().unwrap_or(0));
+    let sprite_w = json["sprite_width"].as_u64().unwrap_or(0) as u32;
+    let sprite_h = json["sprite_height"].as_u64().unwrap_or(0) as u32;
+    let listed: Vec<serde_json::Value> = json["frames"].as_array().cloned().unwrap_or_default();
+    let pixels = render.read().await?;
+    let frame_len = sprite_w as usize * sprite_h as usize * 4;
+    if pixels.len() != frame_len * listed.len() {
+        return Err("The script returned incomplete frame data".to_string());
+    }
+    let (w, h) = fit_within(sprite_w, sprite_h, max_size);
+    let images: Vec<Vec<u8>> = pixels
+        .chunks_exact(frame_len.max(1))
+        .map(|full| downscale_nearest(full, sprite_w, sprite_h, max_size).0)
+        .collect();
+
+    let total = to.saturating_sub(from) + 1;
+    let shown_to = offset as u64 + listed.len() as u64;
+    let mut summary = json!({
+        "from_frame": from,
+        "to_frame": to,
+        "total_frames": total,
+        "offset": offset,
+        "frames": listed,
+        "layout": if strip { "strip" } else { "frames" },
+        "thumbnail_width": w,
+        "thumbnail_height": h,
+        "sprite_width": json["sprite_width"],
+        "sprite_height": json["sprite_height"],
+    });
+    if shown_to < total {
+        summary["next_offset"] = json!(shown_to);
+    }
+
+    let mut content = vec![Content::text(summary.to_string())];
+    let encode = |png: Vec<u8>| Content::image(base64::engine::general_purpose::STANDARD.encode(png), "image/png");
+    if strip && !images.is_empty() {
+        let n = images.len() as u32;
+        let strip_w = n * w + (n - 1) * THUMBNAIL_STRIP_GAP;
+        let mut rgba = vec![0u8; (strip_w * h * 4) as usize];
+        for (i, pixels) in images.iter().enumerate() {
+            let left = i as u32 * (w + THUMBNAIL_STRIP_GAP);
+            for y in 0..h {
+                let src = (y * w * 4) as usize;
+                let dst = ((y * strip_w + left) * 4) as usize;
+                rgba[dst..dst + (w * 4) as usize].copy_from_slice(&pixels[src..src + (w * 4) as usize]);
+            }
+        }
+        content.push(encode(encode_png_rgba(&rgba, strip_w, h)?));
+    } else {
+        for pixels in &images {
+            content.push(encode(encode_png_rgba(pixels, w, h)?));
+        }
+    }
+    Ok(CallToolResult::success(content))
+}
+
+/// The `top` most common opaque colors as hex with their share of opaque pixels in percent.
+fn dominant_colors(rgba: &[u8], top: usize) -> Vec<(String, f64)> {
+    let mut counts: HashMap<[u8; 4], usize> = HashMap::new();
+    let mut opaque = 0;
+    for px in rgba.chunks_exact(4).filter(|px| px[3] > 0) {
+        *counts.entry([px[0], px[1], px[2], px[3]]).or_default() += 1;
+        opaque += 1;
+    }
+    let mut counts: Vec<([u8; 4], usize)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+        .into_iter()
+        .take(top)
+        .map(|([r, g, b, a], n)| {
+            let hex = if a == 255 {
+                format!("#{:02x}{:02x}{:02x}", r, g, b)
+            } else {
+                format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+            };
+            (hex, n as f64 * 100.0 / opaque as f64)
+        })
+        .collect()
+}
+
+/// Size of a `width` x `height` image shrunk so the larger side is at most `max_side`
+/// (never enlarged).
+fn fit_within(width: u32, height: u32, max_side: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_side {
+        return (width, height);
+    }
+    ((width * max_side / longest).max(1), (height * max_side / longest).max(1))
+}
+
+/// Nearest-neighbor downscale so the larger side is at most `max_side` (never upscales).
+fn downscale_nearest(rgba: &[u8], width: u32, height: u32, max_side: u32) -> (Vec<u8>, u32, u32) {
+    let (w, h) = fit_within(width, height, max_side);
+    if (w, h) == (width, height) {
+        return (rgba.to_vec(), width, height);
+    }
+    let mut out = Vec::with_capacity((w * h * 4) as usize);
+    for y in 0..h {
+        let sy = y * height / h;
+        for x in 0..w {
+            let i = ((sy * width + x * width / w) * 4) as usize;
+            out.extend_from_slice(&rgba[i..i + 4]);
+        }
+    }
+    (out, w, h)
+}
+
+/// A grid origin and cell size, used to snap coordinates to the sprite's grid.
+#[derive(Debug, Clone, Copy)]
+pub struct Grid {
+    pub x: i64,
+    pub y: i64,
+    pub width: i64,
+    pub height: i64,
+}
+
+impl Grid {
+    fn from_rect(r: &RectData) -> Result<Self, String> {
+        if r.width == 0 || r.height == 0 {
+            return Err("grid width and height must be at least 1".to_string());
+        }
+        Ok(Grid { x: r.x as i64, y: r.y as i64, width: r.width as i64, height: r.height as i64 })
+    }
+
+    fn from_json(v: &serde_json::Value) -> Option<Self> {
+        let grid = Grid {
+            x: v["x"].as_i64()?,
+            y: v["y"].as_i64()?,
+            width: v["width"].as_i64()?,
+            height: v["height"].as_i64()?,
+        };
+        (grid.width > 0 && grid.height > 0).then_some(grid)
+    }
+
+    fn to_json(self) -> serde_json::Value {
+        json!({ "x": self.x, "y": self.y, "width": self.width, "height": self.height })
+    }
+
+    /// Distance of (x, y) past the nearest grid lines at or before it (0 when on the grid).
+    fn misalignment(&self, x: i64, y: i64) -> (i64, i64) {
+        ((x - self.x).rem_euclid(self.width), (y - self.y).rem_euclid(self.height))
+    }
+
+    /// Nearest grid intersection to a point. Points halfway between lines snap right/down.
+    pub fn snap_point(&self, x: i32, y: i32) -> (i32, i32) {
+        (snap_axis(x as i64, self.x, self.width) as i32, snap_axis(y as i64, self.y, self.height) as i32)
+    }
+
+    /// Rectangle with each edge moved to the nearest grid line. A rectangle that would
+    /// collapse becomes the grid cell containing its top-left corner on that axis.
+    pub fn snap_rect(&self, x: i32, y: i32, width: u32, height: u32) -> (i32, i32, u32, u32) {
+        let (x0, w) = snap_span(x as i64, width as i64, self.x, self.width);
+        let (y0, h) = snap_span(y as i64, height as i64, self.y, self.height);
+        (x0 as i32, y0 as i32, w as u32, h as u32)
+    }
+}
+
+fn snap_axis(v: i64, origin: i64, cell: i64) -> i64 {
+    origin + (v - origin + cell / 2).div_euclid(cell) * cell
+}
+
+fn snap_span(start: i64, len: i64, origin: i64, cell: i64) -> (i64, i64) {
+    let (a, b) = (snap_axis(start, origin, cell), snap_axis(start + len, origin, cell));
+    if b > a {
+        (a, b - a)
+    } else {
+        (origin + (start - origin).div_euclid(cell) * cell, cell)
+    }
+}
+
+/// The grid to snap to when `snap` is set: the caller's `grid` if given (saving a run of
+/// Aseprite), otherwise the sprite's grid bounds.
+pub async fn snap_grid(
+    server: &AsepriteServer,
+    file_path: &str,
+    snap: Option<bool>,
+    grid: Option<&RectData>,
+) -> Result<Option<Grid>, String> {
+    if !snap.unwrap_or(false) {
+        return Ok(None);
+    }
+    if let Some(r) = grid {
+        return Grid::from_rect(r).map(Some);
+    }
+    let script = r#"local g = app.sprite.gridBounds
+print(json.encode({x = g.x, y = g.y, width = g.width, height = g.height}))"#;
+    let output = server.execute_script_on_file(file_path, script).await?;
+    let json: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    Grid::from_json(&json)
+        .map(Some)
+        .ok_or_else(|| format!("Unexpected grid from the sprite: {}", output))
+}
+
+pub async fn check_grid_alignment(server: &AsepriteServer, p: CheckGridAlignmentParams) -> Result<String, String> {
+    let given = p.grid.as_ref().map(Grid::from_rect).transpose()?;
+    let script = r#"local spr = app.sprite
+local g = spr.gridBounds
+local cels = {}
+for _, cel in ipairs(spr.cels) do
+    local entry = {layer = cel.layer.name, frame = cel.frameNumber, x = cel.position.x, y = cel.position.y}
+    if cel.layer.isTilemap and cel.layer.tileset then
+        local tg = cel.layer.tileset.grid
+        entry.grid = {x = tg.origin.x, y = tg.origin.y, width = tg.tileSize.width, height = tg.tileSize.height}
+    end
+    cels[#cels + 1] = entry
+end
+local slices = {}
+for _, slice in ipairs(spr.slices) do
+    slices[#slices + 1] = {name = slice.name, x = slice.bounds.x, y = slice.bounds.y}
+end
+print(json.encode({grid = {x = g.x, y = g.y, width = g.width, height = g.height}, cels = cels, slices = slices}))"#;
+    let output = server.execute_script_on_file(&p.file_path, script).await?;
+    let data: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+    let grid = match given {
+        Some(grid) => grid,
+        None => Grid::from_json(&data["grid"]).ok_or_else(|| format!("Unexpected grid from the sprite: {}", output))?,
+    };
+
+    let mut misaligned = Vec::new();
+    let mut check = |kind: &str, entry: &serde_json::Value, grid: Grid| {
+        let (x, y) = (entry["x"].as_i64().unwrap_or(0), entry["y"].as_i64().unwrap_or(0));
+        let (dx, dy) = grid.misalignment(x, y);
+        if dx == 0 && dy == 0 {
+            return;
+        }
+        let mut item = json!({ "kind": kind, "x": x, "y": y, "offset_x": dx, "offset_y": dy });
+        for key in ["layer", "frame", "name"] {
+            if !entry[key].is_null() {
+                item[key] = entry[key].clone();
+            }
+        }
+        if kind == "tilemap" {
+            item["grid"] = grid.to_json();
+        }
+        misaligned.push(item);
+    };
+    let empty = Vec::new();
+    let cels = data["cels"].as_array().unwrap_or(&empty);
+    let slices = data["slices"].as_array().unwrap_or(&empty);
+    let mut tilemap_cels = 0;
+    for cel in cels {
+        match Grid::from_json(&cel["grid"]) {
+            Some(tile_grid) => {
+                tilemap_cels += 1;
+                check("tilemap", cel, tile_grid);
+            }
+            None => check("cel", cel, grid),
+        }
+    }
+    for slice in slices {
+        check("slice", slice, grid);
+    }
+
+    Ok(json!({
+        "grid": grid.to_json(),
+        "checked": {
+            "cels": cels.len() - tilemap_cels,
+            "tilemap_cels": tilemap_cels,
+            "slices": slices.len(),
+        },
+        "aligned": misaligned.is_empty(),
+        "misaligned_count": misaligned.len(),
+        "misaligned": misaligned,
+    })
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lua_helpers::LUA_SAVE_AS_ORIGINAL;
+    use crate::test_support::{dry_server, planned_script, real_server, scratch_dir};
+
+    fn resize_params(file_path: &str, output_path: Option<&str>) -> ResizeSpriteParams {
+        ResizeSpriteParams {
+            file_path: file_path.to_string(),
+            width: 24,
+            height: 12,
+            output_path: output_path.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn padded_side_rounds_up_to_a_power_of_two_or_a_multiple() {
+        assert_eq!(padded_side(1, None), 1);
+        assert_eq!(padded_side(16, None), 16);
+        assert_eq!(padded_side(17, None), 32);
+        assert_eq!(padded_side(100, None), 128);
+        assert_eq!(padded_side(16, Some(16)), 16);
+        assert_eq!(padded_side(17, Some(16)), 32);
+        assert_eq!(padded_side(30, Some(4)), 32);
+        assert_eq!(padded_side(7, Some(1)), 7);
+    }
+
+    #[tokio::test]
+    async fn pad_canvas_to_rejects_unknown_modes() {
+        let params = PadCanvasToParams {
+            file_path: "/art/in.png".to_string(),
+            mode: "square".to_string(),
+            multiple: None,
+            anchor: None,
+            output_path: None,
+        };
+        let err = pad_canvas_to(&dry_server(), params).await.unwrap_err();
+        assert_eq!(err, "Invalid mode 'square'. Must be one of: pot, multiple");
+    }
+
+    #[test]
+    fn snap_axis_rounds_to_the_nearest_line_from_the_origin() {
+        assert_eq!(snap_axis(3, 0, 8), 0);
+        assert_eq!(snap_axis(4, 0, 8), 8);
+        assert_eq!(snap_axis(5, 0, 8), 8);
+        assert_eq!(snap_axis(16, 0, 8), 16);
+        assert_eq!(snap_axis(-4, 0, 8), 0);
+        assert_eq!(snap_axis(-5, 0, 8), -8);
+        assert_eq!(snap_axis(7, 2, 8), 10);
+        assert_eq!(snap_axis(5, 2, 8), 2);
+    }
+
+    #[test]
+    fn snap_span_moves_both_edges_and_never_collapses() {
+        assert_eq!(snap_span(3, 10, 0, 8), (0, 16));
+        assert_eq!(snap_span(9, 6, 0, 8), (8, 8));
+        assert_eq!(snap_span(4, 14, 2, 8), (2, 16));
+        // Both edges round to the same line: the cell holding the start
+        assert_eq!(snap_span(1, 2, 0, 8), (0, 8));
+        assert_eq!(snap_span(-3, 2, 0, 8), (-8, 8));
+        let grid = Grid { x: 0, y: 4, width: 8, height: 8 };
+        assert_eq!(grid.snap_rect(3, 6, 10, 1), (0, 4, 16, 8));
+    }
+
+    #[tokio::test]
+    async fn resize_saves_in_place_with_save_as_and_copies_with_save_copy_as() {
+        let server = dry_server();
+        let script = planned_script(resize_sprite(&server, resize_params("/art/in.png", None))).await;
+        assert!(script.contains(LUA_SAVE_AS_ORIGINAL));
+        assert!(!script.contains("saveCopyAs"));
+
+        let copy = resize_params("/art/in.png", Some("/art/out.png"));
+        let script = planned_script(resize_sprite(&server, copy)).await;
+        assert!(script.contains("spr:saveCopyAs(\"/art/out.png\")"));
+        assert!(!script.contains(LUA_SAVE_AS_ORIGINAL));
+    }
+
+    async fn create_indexed(server: &AsepriteServer, path: &str) -> serde_json::Value {
+        let params = CreateSpriteParams {
+            width: 16,
+            height: 16,
+            output_path: path.to_string(),
+            color_mode: Some("indexed".to_string()),
+        };
+        create_sprite(server, params).await.unwrap();
+        sprite_info(server, path).await
+    }
+
+    async fn sprite_info(server: &AsepriteServer, path: &str) -> serde_json::Value {
+        let info = get_sprite_info(server, SpriteFileParams { file_path: path.to_string() }).await.unwrap();
+        serde_json::from_str(&info).unwrap()
+    }
+
+    #[tokio::test]
+    async fn repair_refuses_to_overwrite_the_original() {
+        let dir = scratch_dir("repair_in_place");
+        let path = dir.join("hero.aseprite");
+        std::fs::write(&path, "sprite").unwrap();
+        // Spelled differently, but the same file
+        let output = dir.join(".").join("hero.aseprite");
+        let params = ValidateSpriteParams {
+            file_path: path.to_string_lossy().to_string(),
+            repair: Some(true),
+            output_path: Some(output.to_string_lossy().to_string()),
+        };
+        let err = validate_sprite(&dry_server(), params).await.unwrap_err();
+        assert!(err.starts_with("output_path must differ from file_path"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn stats_name_the_color_mode() {
+        let Some(server) = real_server() else { return };
+        let path = scratch_dir("stats_color_mode").join("sprite.aseprite").to_string_lossy().to_string();
+        create_indexed(&server, &path).await;
+        let stats = get_sprite_stats(&server, SpriteFileParams { file_path: path }).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&stats).unwrap();
+        assert_eq!(stats["color_mode"], "indexed");
+    }
+
+    #[tokio::test]
+    async fn resize_in_place_updates_the_file() {
+        let Some(server) = real_server() else { return };
+        let path = scratch_dir("resize_in_place").join("sprite.png").to_string_lossy().to_string();
+        let before = create_indexed(&server, &path).await;
+
+        resize_sprite(&server, resize_params(&path, None)).await.unwrap();
+        let after = sprite_info(&server, &path).await;
+        assert_eq!((after["width"].as_u64(), after["height"].as_u64()), (Some(24), Some(12)));
+        assert_eq!(after["colorMode"], before["colorMode"]);
+    }
+
+    #[tokio::test]
+    async fn resize_to_output_path_leaves_the_original() {
+        let Some(server) = real_server() else { return };
+        let dir = scratch_dir("resize_copy");
+        let path = dir.join("sprite.png").to_string_lossy().to_string();
+        let copy = dir.join("resized.png").to_string_lossy().to_string();
+        let before = create_indexed(&server, &path).await;
+
+        resize_sprite(&server, resize_params(&path, Some(&copy))).await.unwrap();
+        let resized = sprite_info(&server, &copy).await;
+        assert_eq!((resized["width"].as_u64(), resized["height"].as_u64()), (Some(24), Some(12)));
+        assert_eq!(resized["colorMode"], before["colorMode"]);
+        let original = sprite_info(&server, &path).await;
+        assert_eq!((original["width"].as_u64(), original["height"].as_u64()), (Some(16), Some(16)));
+    }
 }
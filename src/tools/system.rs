@@ -0,0 +1,409 @@
+use std::path::{Path, PathBuf};
+
+use rmcp::schemars;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::aseprite::{MAX_TIMEOUT_SECS, is_dry_run, lua_path};
+use crate::server::{AsepriteServer, ServerConfig};
+
+/// CLI options worth reporting, since they only exist in some Aseprite versions.
+const PROBED_CLI_FLAGS: &[&str] = &[
+    "--extrude",
+    "--split-grid",
+    "--split-slices",
+    "--tileset",
+    "--ignore-empty",
+    "--merge-duplicates",
+    "--play-subtags",
+];
+
+// ============================================================================
+// Parameter Structs
+// ============================================================================
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetConfigParams {
+    /// Default Aseprite process timeout in seconds (1-1800)
+    pub timeout_secs: Option<u64>,
+    /// Back up files before tools save over them (the default for each call's backup flag)
+    pub auto_backup: Option<bool>,
+    /// Color mode create_sprite uses when none is given: "rgb", "grayscale", or "indexed"
+    pub default_color_mode: Option<String>,
+    /// Whether exports return images inline when return_base64 isn't passed
+    pub return_base64: Option<bool>,
+    /// Largest region get_pixel_data reads in one call, in pixels (width * height)
+    pub max_pixel_read_area: Option<u64>,
+    /// Most pixels draw_pixels (or points use_tool) takes in one call, and in total per batch_edit
+    pub max_draw_pixels: Option<u64>,
+    /// Most entries set_palette_color takes in one call
+    pub max_palette_entries: Option<u64>,
+    /// Any other setting; locked settings are rejected with an explanation
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Settings reported by `get_config` that can't be changed at runtime.
+const LOCKED_SETTINGS: &[&str] = &[
+    "allowed_dirs",
+    "allow_arbitrary_scripts",
+    "output_dir",
+    "workspace_dir",
+    "scripts_dir",
+    "temp_dir",
+    "dry_run",
+    "backup_keep",
+];
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListFontsParams {
+    /// Only list likely pixel fonts: bitmap formats and well-known pixel font names (default: false)
+    pub pixel_only: Option<bool>,
+    /// Only list fonts whose name contains this text (case-insensitive)
+    pub name_contains: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RecentOperationsParams {
+    /// Number of operations to return, newest first (default: 20)
+    pub limit: Option<usize>,
+}
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+pub async fn get_aseprite_info(server: &AsepriteServer) -> Result<String, String> {
+    let info = server
+        .aseprite_info_cache()
+        .get_or_try_init(|| probe_aseprite(server))
+        .await?;
+    Ok(info.to_string())
+}
+
+/// Run Aseprite to find its version and which optional features it supports.
+async fn probe_aseprite(server: &AsepriteServer) -> Result<serde_json::Value, String> {
+    let version = server
+        .run_cli(&["--version".to_string()])
+        .await
+        .map_err(|e| format!("Aseprite could not be run from {}: {}", server.exe(), e))?;
+    if !version.success {
+        return Err(format!("Aseprite --version failed: {}", version.result_text()));
+    }
+    let help = server
+        .run_cli(&["--help".to_string()])
+        .await
+        .map(|o| o.stdout)
+        .unwrap_or_default();
+    let cli_flags: serde_json::Map<String, serde_json::Value> = PROBED_CLI_FLAGS
+        .iter()
+        .map(|flag| (flag.to_string(), json!(help.contains(flag))))
+        .collect();
+
+    // Saving a 1x1 sprite as .webp is the most reliable way to tell if WebP export is built in
+    let webp_probe = server.temp_file_path("webp");
+    let script = format!(
+        r#"local s = Sprite(1, 1)
+pcall(function() s:saveCopyAs({probe}) end)
+s:close()
+print(json.encode({{version = tostring(app.version), api_version = app.apiVersion, tilemaps = Tileset ~= nil}}))"#,
+        probe = lua_path(&webp_probe.to_string_lossy())
+    );
+    let output = server.execute_script(&script).await?;
+    let webp = tokio::fs::remove_file(&webp_probe).await.is_ok();
+    let lua: serde_json::Value = serde_json::from_str(&output).unwrap_or_default();
+
+    Ok(json!({
+        "version": lua["version"],
+        "api_version": lua["api_version"],
+        "version_text": version.stdout.trim(),
+        "executable": server.exe().to_string(),
+        "output_dir": server.output_dir().map(|p| p.to_string_lossy().to_string()),
+        "temp_dir": server.temp_dir().to_string_lossy(),
+        "features": {
+            "tilemaps": lua["tilemaps"].as_bool().unwrap_or(false),
+            "webp": webp,
+        },
+        "cli_flags": cli_flags,
+    }))
+}
+
+/// Font file extensions worth listing; bitmap formats count as pixel fonts. Aseprite's own
+/// sprite-sheet fonts are PNGs, which are only collected from its font directories.
+const VECTOR_FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "ttc", "otc"];
+const BITMAP_FONT_EXTENSIONS: &[&str] = &["bdf", "pcf", "fon", "fnt"];
+
+/// Lowercase name fragments of common pixel fonts, for the pixel_only heuristic.
+const PIXEL_FONT_HINTS: &[&str] = &[
+    "pixel", "bitmap", "8bit", "8-bit", "pico", "m3x6", "m5x7", "m6x11", "monogram", "press start", "pressstart",
+    "silkscreen", "terminus", "unifont", "proggy", "dogica", "04b", "tiny5", "aseprite",
+];
+
+/// Directories are searched this deep, and at most this many fonts are collected.
+const FONT_SCAN_DEPTH: usize = 5;
+const FONT_SCAN_LIMIT: usize = 5000;
+
+pub async fn list_fonts(server: &AsepriteServer, p: ListFontsParams) -> Result<String, String> {
+    let all = server.font_list_cache().get_or_try_init(|| scan_fonts(server)).await?;
+    let pixel_only = p.pixel_only.unwrap_or(false);
+    let needle = p.name_contains.as_deref().map(str::to_lowercase);
+    let fonts: Vec<&serde_json::Value> = all["fonts"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|f| !pixel_only || f["pixel"] == true)
+        .filter(|f| {
+            needle
+                .as_ref()
+                .is_none_or(|n| f["name"].as_str().unwrap_or_default().to_lowercase().contains(n))
+        })
+        .collect();
+    Ok(json!({
+        "count": fonts.len(),
+        "fonts": fonts,
+        "searched": all["searched"],
+    })
+    .to_string())
+}
+
+/// Ask Aseprite where it is installed and keeps its settings, then collect font files from its
+/// font and extension directories and from the platform's font directories.
+async fn scan_fonts(server: &AsepriteServer) -> Result<serde_json::Value, String> {
+    let output = server
+        .execute_script(r#"print(json.encode({app_path = app.fs.appPath, user_config = app.fs.userConfigPath}))"#)
+        .await?;
+    let paths: serde_json::Value = serde_json::from_str(&output).map_err(|_| output.clone())?;
+
+    let mut dirs: Vec<(PathBuf, &str)> = Vec::new();
+    if let Some(app_dir) = paths["app_path"].as_str().and_then(|p| Path::new(p).parent()) {
+        // macOS bundles keep data in Contents/Resources next to Contents/MacOS
+        for data in [app_dir.join("data"), app_dir.join("../Resources/data")] {
+            dirs.push((data.join("fonts"), "aseprite"));
+            dirs.push((data.join("extensions"), "aseprite"));
+        }
+    }
+    if let Some(config) = paths["user_config"].as_str() {
+        dirs.push((Path::new(config).join("fonts"), "aseprite"));
+        dirs.push((Path::new(config).join("extensions"), "aseprite"));
+    }
+    dirs.extend(system_font_dirs().into_iter().map(|d| (d, "system")));
+
+    tokio::task::spawn_blocking(move || {
+        let mut fonts = Vec::new();
+        let mut searched = Vec::new();
+        for (dir, source) in &dirs {
+            if dir.is_dir() {
+                searched.push(dir.to_string_lossy().to_string());
+                collect_fonts(dir, source, FONT_SCAN_DEPTH, &mut fonts);
+            }
+        }
+        fonts.sort_by(|a: &serde_json::Value, b| {
+            let key = |f: &serde_json::Value| f["name"].as_str().unwrap_or_default().to_lowercase();
+            key(a).cmp(&key(b))
+        });
+        fonts.dedup_by(|a, b| a["path"] == b["path"]);
+        json!({ "fonts": fonts, "searched": searched })
+    })
+    .await
+    .map_err(|e| format!("Font scan failed: {}", e))
+}
+
+/// Standard font directories for the current platform.
+fn system_font_dirs() -> Vec<PathBuf> {
+    #[cfg(not(target_os = "windows"))]
+    let home = std::env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+
+    #[cfg(target_os = "windows")]
+    {
+        let windir = std::env::var_os("WINDIR").map_or_else(|| PathBuf::from(r"C:\Windows"), PathBuf::from);
+        dirs.push(windir.join("Fonts"));
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            dirs.push(PathBuf::from(local).join(r"Microsoft\Windows\Fonts"));
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        dirs.extend([
+            PathBuf::from("/System/Library/Fonts"),
+            PathBuf::from("/Library/Fonts"),
+            home.join("Library/Fonts"),
+        ]);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        dirs.extend([
+            PathBuf::from("/usr/share/fonts"),
+            PathBuf::from("/usr/local/share/fonts"),
+            home.join(".local/share/fonts"),
+            home.join(".fonts"),
+        ]);
+    }
+
+    dirs
+}
+
+/// Add the font files under `dir` (up to `depth` levels down) to `fonts`.
+fn collect_fonts(dir: &Path, source: &str, depth: usize, fonts: &mut Vec<serde_json::Value>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if fonts.len() >= FONT_SCAN_LIMIT {
+            return;
+        }
+        if path.is_dir() {
+            if depth > 0 {
+                collect_fonts(&path, source, depth - 1, fonts);
+            }
+            continue;
+        }
+        let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let bitmap = BITMAP_FONT_EXTENSIONS.contains(&ext.as_str()) || (ext == "png" && source == "aseprite");
+        if !bitmap && !VECTOR_FONT_EXTENSIONS.contains(&ext.as_str()) {
+            continue;
+        }
+        // Extensions also ship theme images; only PNGs inside a fonts directory are fonts
+        if ext == "png" && !path.parent().is_some_and(|p| p.ends_with("fonts")) {
+            continue;
+        }
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+        let lower = name.to_lowercase();
+        let pixel = bitmap || PIXEL_FONT_HINTS.iter().any(|hint| lower.contains(hint));
+        fonts.push(json!({
+            "name": name,
+            "path": path.to_string_lossy(),
+            "format": ext,
+            "source": source,
+            "pixel": pixel,
+        }));
+    }
+}
+
+pub async fn get_recent_operations(server: &AsepriteServer, p: RecentOperationsParams) -> Result<String, String> {
+    let operations = server.recent_operations(p.limit.unwrap_or(20));
+    Ok(json!({ "count": operations.len(), "operations": operations }).to_string())
+}
+
+pub async fn get_config(server: &AsepriteServer) -> Result<String, String> {
+    Ok(effective_config(server, server.timeout_secs(), &server.config()).to_string())
+}
+
+pub async fn set_config(server: &AsepriteServer, p: SetConfigParams) -> Result<String, String> {
+    if let Some(key) = p.other.keys().next() {
+        return Err(if LOCKED_SETTINGS.contains(&key.as_str()) {
+            format!("'{}' is locked: it can only be set by environment variable at startup", key)
+        } else {
+            format!(
+                "Unknown setting '{}'. Changeable settings: timeout_secs, auto_backup, default_color_mode, return_base64, max_pixel_read_area, max_draw_pixels, max_palette_entries",
+                key
+            )
+        });
+    }
+    if let Some(secs) = p.timeout_secs
+        && !(1..=MAX_TIMEOUT_SECS).contains(&secs)
+    {
+        return Err(format!("timeout_secs must be between 1 and {}", MAX_TIMEOUT_SECS));
+    }
+    let color_mode = p.default_color_mode.map(|mode| mode.to_lowercase());
+    if let Some(ref mode) = color_mode
+        && !matches!(mode.as_str(), "rgb" | "grayscale" | "indexed")
+    {
+        return Err("default_color_mode must be 'rgb', 'grayscale', or 'indexed'".to_string());
+    }
+    for (name, value) in [
+        ("max_pixel_read_area", p.max_pixel_read_area),
+        ("max_draw_pixels", p.max_draw_pixels),
+        ("max_palette_entries", p.max_palette_entries),
+    ] {
+        if value == Some(0) {
+            return Err(format!("{} must be greater than 0", name));
+        }
+    }
+
+    let apply = |config: &mut ServerConfig| {
+        if let Some(auto_backup) = p.auto_backup {
+            config.auto_backup = auto_backup;
+        }
+        if let Some(mode) = color_mode {
+            config.default_color_mode = mode;
+        }
+        if let Some(return_base64) = p.return_base64 {
+            config.return_base64 = return_base64;
+        }
+        if let Some(area) = p.max_pixel_read_area {
+            config.max_pixel_read_area = area;
+        }
+        if let Some(pixels) = p.max_draw_pixels {
+            config.max_draw_pixels = pixels;
+        }
+        if let Some(entries) = p.max_palette_entries {
+            config.max_palette_entries = entries;
+        }
+    };
+    if is_dry_run() {
+        // Report the config the call would produce without applying it
+        let mut config = server.config();
+        apply(&mut config);
+        let timeout_secs = p.timeout_secs.unwrap_or(server.timeout_secs());
+        let mut result = effective_config(server, timeout_secs, &config);
+        result["dry_run"] = json!(true);
+        return Ok(result.to_string());
+    }
+    if let Some(secs) = p.timeout_secs {
+        server.set_timeout_secs(secs);
+    }
+    server.update_config(apply);
+    Ok(effective_config(server, server.timeout_secs(), &server.config()).to_string())
+}
+
+/// Changeable settings (`timeout_secs` and `config`) next to the locked ones, as `get_config`
+/// and `set_config` report them.
+fn effective_config(server: &AsepriteServer, timeout_secs: u64, config: &ServerConfig) -> serde_json::Value {
+    json!({
+        "settings": {
+            "timeout_secs": timeout_secs,
+            "auto_backup": config.auto_backup,
+            "default_color_mode": config.default_color_mode,
+            "return_base64": config.return_base64,
+            "max_pixel_read_area": config.max_pixel_read_area,
+            "max_draw_pixels": config.max_draw_pixels,
+            "max_palette_entries": config.max_palette_entries,
+        },
+        "locked": {
+            "allowed_dirs": server.allowed_dirs().map(|dirs| dirs.iter().map(|d| d.to_string_lossy()).collect::<Vec<_>>()),
+            "allow_arbitrary_scripts": server.arbitrary_scripts_allowed(),
+            "output_dir": server.output_dir().map(|d| d.to_string_lossy()),
+            "workspace_dir": server.workspace_root().to_string_lossy(),
+            "scripts_dir": server.scripts_dir().map(|d| d.to_string_lossy()),
+            "temp_dir": server.temp_dir().to_string_lossy(),
+            "dry_run": server.dry_run_forced(),
+            "backup_keep": server.backup_keep(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aseprite::dry_run;
+    use crate::test_support::dry_server;
+
+    #[tokio::test]
+    async fn dry_run_set_config_reports_without_applying() {
+        let server = dry_server();
+        let before = server.config();
+        let params: SetConfigParams =
+            serde_json::from_value(json!({ "auto_backup": !before.auto_backup, "timeout_secs": 7 })).unwrap();
+        let (result, planned) = dry_run(set_config(&server, params)).await;
+        let report: serde_json::Value = serde_json::from_str(&result.unwrap()).unwrap();
+        assert!(planned.is_empty());
+        assert_eq!(report["dry_run"], true);
+        assert_eq!(report["settings"]["auto_backup"], !before.auto_backup);
+        assert_eq!(report["settings"]["timeout_secs"], 7);
+        assert_eq!(server.config().auto_backup, before.auto_backup);
+        assert_ne!(server.timeout_secs(), 7);
+    }
+}
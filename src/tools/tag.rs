@@ -2,8 +2,9 @@ use rmcp::schemars;
 use serde::Deserialize;
 
 use crate::aseprite::lua_string;
+use crate::lua_helpers::{lua_fragment_script, lua_save_target, with_saved_to};
 use crate::server::AsepriteServer;
-use crate::utils::parse_hex_color;
+use crate::utils::{check_choice, parse_color};
 
 // ============================================================================
 // Parameter Structs
@@ -13,6 +14,14 @@ use crate::utils::parse_hex_color;
 pub struct CreateTagParams {
     /// Path to the sprite file
     pub file_path: String,
+    #[serde(flatten)]
+    pub op: CreateTagOp,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CreateTagOp {
     /// Tag name
     pub name: String,
     /// First frame number (1-based)
@@ -21,8 +30,10 @@ pub struct CreateTagParams {
     pub to_frame: u32,
     /// Animation direction: "forward", "reverse", "ping_pong", "ping_pong_reverse" (default: "forward")
     pub ani_dir: Option<String>,
-    /// Tag color as hex string (e.g. "#ff0000")
+    /// Tag color as hex, CSS name, or rgb() (e.g. "#ff0000", "red")
     pub color: Option<String>,
+    /// Clamp out-of-range values and fall back to defaults for unknown names instead of failing (default: false)
+    pub lenient: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -31,6 +42,8 @@ pub struct DeleteTagParams {
     pub file_path: String,
     /// Tag name to delete
     pub name: String,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
 }
 
 // ============================================================================
@@ -55,49 +68,83 @@ print(json.encode({tags = tags, total = #tags}))"#;
 }
 
 pub async fn create_tag(server: &AsepriteServer, p: CreateTagParams) -> Result<String, String> {
-    let ani_dir = match p.ani_dir.as_deref() {
-        Some("reverse") => "AniDir.REVERSE",
-        Some("ping_pong") => "AniDir.PING_PONG",
-        Some("ping_pong_reverse") => "AniDir.PING_PONG_REVERSE",
-        _ => "AniDir.FORWARD",
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+    let script = lua_fragment_script(&create_tag_fragment(&p.op)?, &save);
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
+
+/// Lua fragment creating the tag (see `lua_fragment_script`).
+pub fn create_tag_fragment(p: &CreateTagOp) -> Result<String, String> {
+    let lenient = p.lenient.unwrap_or(false);
+    let ani_dir = check_choice(
+        "ani_dir",
+        p.ani_dir.as_deref().unwrap_or("forward"),
+        &[
+            ("forward", "AniDir.FORWARD"),
+            ("reverse", "AniDir.REVERSE"),
+            ("ping_pong", "AniDir.PING_PONG"),
+            ("ping_pong_reverse", "AniDir.PING_PONG_REVERSE"),
+        ],
+        lenient,
+    )?;
+    let from = match p.from_frame {
+        0 if lenient => 1,
+        0 => return Err("from_frame is 1-based and must be at least 1 (got 0)".to_string()),
+        n => n,
+    };
+    let to = match p.to_frame {
+        n if n >= from => n,
+        _ if lenient => from,
+        n => return Err(format!("to_frame must not be before from_frame {} (got {})", from, n)),
     };
     let color_code = if let Some(ref color) = p.color {
-        let (r, g, b) = parse_hex_color(color);
+        let (r, g, b, _) = parse_color(color).map_err(|e| format!("Invalid color '{}': {}", color, e))?;
         format!("tag.color = Color({}, {}, {})\n", r, g, b)
     } else {
         String::new()
     };
 
-    let script = format!(
-        r#"local spr = app.sprite
-local tag = spr:newTag({from}, {to})
+    Ok(format!(
+        r#"local to_f = {to}
+if to_f > #spr.frames and {lenient} then to_f = math.max(#spr.frames, {from}) end
+if to_f > #spr.frames then
+    print(json.encode({{error = "to_frame must be at most " .. #spr.frames .. ", the sprite's frame count (got {to})"}}))
+    return
+end
+local tag = spr:newTag({from}, to_f)
 tag.name = {name}
 tag.aniDir = {ani}
 {color}
-spr:saveAs(spr.filename)
-local result = {{}}
+result = {{}}
 result.name = tag.name
 result.fromFrame = tag.fromFrame.frameNumber
 result.toFrame = tag.toFrame.frameNumber
 result.aniDir = tostring(tag.aniDir)
-result.status = "created"
-print(json.encode(result))"#,
-        from = p.from_frame,
-        to = p.to_frame,
+result.status = "created""#,
+        from = from,
+        to = to,
+        lenient = lenient,
         name = lua_string(&p.name),
         ani = ani_dir,
         color = color_code
-    );
-    server.execute_script_on_file(&p.file_path, &script).await
+    ))
 }
 
 pub async fn delete_tag(server: &AsepriteServer, p: DeleteTagParams) -> Result<String, String> {
+    let (save, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
     let script = format!(
         r#"local spr = app.sprite
 spr:deleteTag({name})
-spr:saveAs(spr.filename)
+{save}
 print(json.encode({{status = "deleted", tag = {name}}}))"#,
-        name = lua_string(&p.name)
+        name = lua_string(&p.name),
+        save = save
     );
-    server.execute_script_on_file(&p.file_path, &script).await
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
 }
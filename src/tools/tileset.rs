@@ -0,0 +1,538 @@
+use rmcp::model::{CallToolResult, Content};
+use rmcp::schemars;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+
+use crate::aseprite::{lua_path, lua_string};
+use crate::lua_helpers::{LUA_FIND_LAYER, LUA_FIND_TILESET, LUA_SAVE_IN_PLACE, lua_check_frame, lua_require_layer};
+use crate::server::AsepriteServer;
+use crate::utils::{read_image_base64, write_output};
+
+// ============================================================================
+// Parameter Structs
+// ============================================================================
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetTileImageParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Tileset name or 1-based index from list_tilesets (default: the first tileset)
+    pub tileset: Option<String>,
+    /// Tile index within the tileset (0 is the empty tile)
+    pub tile_index: u32,
+    /// Also save the tile PNG to this path (otherwise it is only returned inline)
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct CreateTilesetParams {
+    /// Path to the sprite file (.aseprite; other formats can't store tilesets)
+    pub file_path: String,
+    /// Tile width in pixels
+    pub tile_width: u32,
+    /// Tile height in pixels
+    pub tile_height: u32,
+    /// Image layer to convert into a tilemap layer: it is cut into tiles on the grid and
+    /// identical tiles are stored once. If omitted, an empty tileset is created
+    pub from_layer: Option<String>,
+    /// Tileset name
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetTilemapParams {
+    /// Path to the sprite file (.aseprite)
+    pub file_path: String,
+    /// Tilemap layer to fill; created as a tilemap layer if it doesn't exist
+    pub layer: String,
+    /// Tileset name or 1-based index (default: the layer's tileset, or the first tileset)
+    pub tileset: Option<String>,
+    /// Tile indices by row: tiles[row][column], starting at the grid origin (0 is the empty tile)
+    pub tiles: Vec<Vec<u32>>,
+    /// Optional flip flags with the same shape as tiles: "" (none) or any of "x" (horizontal),
+    /// "y" (vertical), "d" (diagonal), e.g. "xy"
+    pub flags: Option<Vec<Vec<String>>>,
+    /// Target frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetTilemapParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Tilemap layer to read
+    pub layer: String,
+    /// Frame number, 1-based (if omitted, uses frame 1)
+    pub frame: Option<u32>,
+    /// Only return this part of the map, in tile coordinates
+    pub region: Option<TileRegion>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct TileRegion {
+    /// First column
+    pub x: u32,
+    /// First row
+    pub y: u32,
+    /// Number of columns
+    pub width: u32,
+    /// Number of rows
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ExportTilesetParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Tileset name or 1-based index (default: the first tileset)
+    pub tileset: Option<String>,
+    /// Output image path (e.g. "tiles.png"); a JSON sidecar with the same name is written next to it
+    pub output_path: String,
+    /// Tiles per row (default: all tiles in one row)
+    pub columns: Option<u32>,
+    /// Spacing in pixels between tiles (default: 0)
+    pub padding: Option<u32>,
+}
+
+/// Tile flag bits stored above the tile index in tilemap pixels (Aseprite's `tile_f_*`).
+const TILE_FLAG_X: u32 = 0x2000_0000;
+const TILE_FLAG_Y: u32 = 0x4000_0000;
+const TILE_FLAG_D: u32 = 0x8000_0000;
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+pub async fn list_tilesets(server: &AsepriteServer, file_path: &str) -> Result<String, String> {
+    let script = r#"local spr = app.sprite
+local tilesets = {}
+for i, ts in ipairs(spr.tilesets or {}) do
+    local size = ts.grid.tileSize
+    table.insert(tilesets, {
+        index = i,
+        name = ts.name,
+        tile_width = size.width,
+        tile_height = size.height,
+        tile_count = #ts,
+        base_index = ts.baseIndex
+    })
+end
+print(json.encode({tilesets = tilesets, total = #tilesets}))"#;
+    server.execute_script_on_file(file_path, script).await
+}
+
+pub async fn get_tile_image(server: &AsepriteServer, p: GetTileImageParams) -> Result<CallToolResult, String> {
+    let keep = p.output_path.is_some();
+    let output = match p.output_path {
+        Some(ref output) => server.resolve_output_path(output)?,
+        None => server.temp_file_path("png").to_string_lossy().to_string(),
+    };
+    let script = format!(
+        r#"local spr = app.sprite
+{find_tileset}
+local ts = find_tileset(spr, {key})
+if not ts then
+    print(json.encode({{error = "Tileset not found: " .. tostring({key})}}))
+    return
+end
+local index = {index}
+if index >= #ts then
+    print(json.encode({{error = "Tile index " .. index .. " is out of range: the tileset has " .. #ts .. " tiles (0-" .. (#ts - 1) .. ")"}}))
+    return
+end
+local img = ts:getTile(index)
+img:saveAs{{ filename = {out}, palette = spr.palettes[1] }}
+print(json.encode({{tileset = ts.name, tile_index = index, width = img.width, height = img.height}}))"#,
+        find_tileset = LUA_FIND_TILESET,
+        key = p.tileset.as_deref().map_or("nil".to_string(), lua_string),
+        index = p.tile_index,
+        out = lua_path(&output)
+    );
+    let result = async {
+        let info = server.execute_script_on_file(&p.file_path, &script).await?;
+        Ok::<_, String>((info, read_image_base64(&output).await?))
+    }
+    .await;
+    if !keep {
+        tokio::fs::remove_file(&output).await.ok();
+    }
+    let (mut text, (data, mime)) = result?;
+    if keep {
+        text.push_str(&format!("\nSaved: {}", output));
+    }
+    Ok(CallToolResult::success(vec![Content::text(text), Content::image(data, mime)]))
+}
+
+pub async fn create_tileset(server: &AsepriteServer, p: CreateTilesetParams) -> Result<String, String> {
+    if p.tile_width == 0 || p.tile_height == 0 {
+        return Err("tile_width and tile_height must be at least 1".to_string());
+    }
+    require_aseprite_file(&p.file_path)?;
+    let name = p.name.as_deref().map_or("nil".to_string(), lua_string);
+    let create = match p.from_layer {
+        Some(ref layer) => format!(
+            r#"{find_layer}
+{require_layer}
+if not layer.isImage or layer.isTilemap then
+    print(json.encode({{error = "Layer " .. {layer} .. " is not a plain image layer"}}))
+    return
+end
+-- ConvertLayer cuts the layer on the sprite grid, storing identical tiles once
+spr.gridBounds = Rectangle(0, 0, {tw}, {th})
+app.activeLayer = layer
+app.command.ConvertLayer {{ to = "tilemap" }}
+local tilemap = app.activeLayer
+local ts = tilemap.tileset
+local layer_name = tilemap.name"#,
+            require_layer = lua_require_layer("layer", layer),
+            find_layer = LUA_FIND_LAYER,
+            layer = lua_string(layer),
+            tw = p.tile_width,
+            th = p.tile_height,
+        ),
+        None => format!(
+            r#"local ts = spr:newTileset(Rectangle(0, 0, {tw}, {th}))
+local layer_name = nil"#,
+            tw = p.tile_width,
+            th = p.tile_height,
+        ),
+    };
+    let script = format!(
+        r#"local spr = app.sprite
+if not spr.newTileset then
+    print(json.encode({{error = "This Aseprite version has no tileset support (1.3 or later is required)"}}))
+    return
+end
+{create}
+local name = {name}
+if name then ts.name = name end
+{save}
+print(json.encode({{
+    status = "created",
+    name = ts.name,
+    tile_width = {tw},
+    tile_height = {th},
+    unique_tiles = #ts - 1,
+    tilemap_layer = layer_name
+}}))"#,
+        create = create,
+        name = name,
+        save = LUA_SAVE_IN_PLACE,
+        tw = p.tile_width,
+        th = p.tile_height,
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn set_tilemap(server: &AsepriteServer, p: SetTilemapParams) -> Result<String, String> {
+    require_aseprite_file(&p.file_path)?;
+    let rows = p.tiles.len();
+    let columns = p.tiles.first().map_or(0, Vec::len);
+    if rows == 0 || columns == 0 {
+        return Err("tiles must have at least one row and one column".to_string());
+    }
+    if let Some(row) = p.tiles.iter().position(|r| r.len() != columns) {
+        return Err(format!(
+            "tiles must be rectangular: row {} has {} entries, row 0 has {}",
+            row,
+            p.tiles[row].len(),
+            columns
+        ));
+    }
+    let flags = match p.flags {
+        Some(ref flags) => {
+            if flags.len() != rows || flags.iter().any(|r| r.len() != columns) {
+                return Err(format!("flags must have the same shape as tiles ({} rows x {} columns)", rows, columns));
+            }
+            flags
+                .iter()
+                .map(|row| row.iter().map(|f| parse_tile_flags(f)).collect::<Result<Vec<_>, _>>())
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        None => vec![vec![0; columns]; rows],
+    };
+    let lua_rows = |grid: Vec<Vec<u32>>| {
+        let rows: Vec<String> = grid
+            .iter()
+            .map(|row| format!("{{{}}}", row.iter().map(u32::to_string).collect::<Vec<_>>().join(",")))
+            .collect();
+        format!("{{{}}}", rows.join(",\n"))
+    };
+
+    let script = format!(
+        r#"local spr = app.sprite
+if not spr.newTileset then
+    print(json.encode({{error = "This Aseprite version has no tilemap support (1.3 or later is required)"}}))
+    return
+end
+{find_layer}
+{find_tileset}
+local tiles = {tiles}
+local flags = {flags}
+local rows, cols = {rows}, {cols}
+{check_frame}
+local frame = spr.frames[{frame}]
+local layer = find_layer(spr.layers, {layer})
+if layer and not layer.isTilemap then
+    print(json.encode({{error = "Layer " .. {layer} .. " exists but is not a tilemap layer"}}))
+    return
+end
+local ts
+if {tileset_key} ~= nil then
+    ts = find_tileset(spr, {tileset_key})
+elseif layer then
+    ts = layer.tileset
+else
+    ts = find_tileset(spr, nil)
+end
+if not ts then
+    print(json.encode({{error = "Tileset not found; create one with create_tileset first"}}))
+    return
+end
+local created = false
+if not layer then
+    app.command.NewLayer {{ name = {layer}, tilemap = true }}
+    layer = app.activeLayer
+    created = true
+end
+-- New tilemap layers get a fresh tileset, so point them at the chosen one
+if created or {tileset_key} ~= nil then
+    local ok = pcall(function() layer.tileset = ts end)
+    if not ok then
+        print(json.encode({{error = "This Aseprite version can't change the tileset of layer " .. layer.name}}))
+        return
+    end
+end
+local size = ts.grid.tileSize
+local origin = ts.grid.origin
+if origin.x + cols * size.width > spr.width or origin.y + rows * size.height > spr.height then
+    print(json.encode({{error = string.format(
+        "A %dx%d tile map of %dx%d tiles is %dx%d pixels, larger than the %dx%d canvas (at most %dx%d tiles)",
+        cols, rows, size.width, size.height, cols * size.width, rows * size.height, spr.width, spr.height,
+        (spr.width - origin.x) // size.width, (spr.height - origin.y) // size.height)}}))
+    return
+end
+local bad = {{}}
+for y = 1, rows do
+    for x = 1, cols do
+        if tiles[y][x] >= #ts then
+            bad[#bad + 1] = {{row = y - 1, column = x - 1, index = tiles[y][x]}}
+        end
+    end
+end
+if #bad > 0 then
+    local shown = {{}}
+    for i = 1, math.min(#bad, 10) do shown[i] = bad[i] end
+    print(json.encode({{error = #bad .. " tile indices are out of range: the tileset has " .. #ts .. " tiles (0-" .. (#ts - 1) .. ")", out_of_range = shown}}))
+    return
+end
+local img = Image(cols, rows, ColorMode.TILEMAP)
+local pc = app.pixelColor
+for y = 1, rows do
+    for x = 1, cols do
+        img:drawPixel(x - 1, y - 1, pc.tile(tiles[y][x], flags[y][x]))
+    end
+end
+spr:newCel(layer, frame, img, origin)
+{save}
+print(json.encode({{
+    status = "tilemap_set",
+    layer = layer.name,
+    layer_created = created,
+    tileset = ts.name,
+    frame = frame.frameNumber,
+    columns = cols,
+    rows = rows
+}}))"#,
+        check_frame = lua_check_frame(p.frame.unwrap_or(1)),
+        find_layer = LUA_FIND_LAYER,
+        find_tileset = LUA_FIND_TILESET,
+        tiles = lua_rows(p.tiles),
+        flags = lua_rows(flags),
+        rows = rows,
+        cols = columns,
+        frame = p.frame.unwrap_or(1),
+        layer = lua_string(&p.layer),
+        tileset_key = p.tileset.as_deref().map_or("nil".to_string(), lua_string),
+        save = LUA_SAVE_IN_PLACE,
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn get_tilemap(server: &AsepriteServer, p: GetTilemapParams) -> Result<String, String> {
+    let region = match p.region {
+        Some(ref r) if r.width == 0 || r.height == 0 => {
+            return Err("region width and height must be at least 1".to_string());
+        }
+        Some(ref r) => format!("{{x = {}, y = {}, w = {}, h = {}}}", r.x, r.y, r.width, r.height),
+        None => "nil".to_string(),
+    };
+    let script = format!(
+        r#"local spr = app.sprite
+{find_layer}
+{require_layer}
+if not layer.isTilemap then
+    local kind = layer.isGroup and "a group layer" or (layer.isReference and "a reference layer" or "an image layer")
+    print(json.encode({{error = "Layer " .. layer.name .. " is " .. kind .. ", not a tilemap layer"}}))
+    return
+end
+{check_frame}
+local frame = spr.frames[{frame}]
+local ts = layer.tileset
+local size = ts.grid.tileSize
+local origin = ts.grid.origin
+local cols = math.ceil((spr.width - origin.x) / size.width)
+local rows = math.ceil((spr.height - origin.y) / size.height)
+local region = {region}
+if region then
+    if region.x >= cols or region.y >= rows then
+        print(json.encode({{error = string.format("region starts outside the %dx%d tile grid", cols, rows)}}))
+        return
+    end
+    region.w = math.min(region.w, cols - region.x)
+    region.h = math.min(region.h, rows - region.y)
+else
+    region = {{x = 0, y = 0, w = cols, h = rows}}
+end
+-- The cel image holds one pixel per tile, placed at the cel position on the grid
+local cel = layer:cel(frame)
+local img, cx, cy
+if cel then
+    img = cel.image
+    cx = (cel.position.x - origin.x) // size.width
+    cy = (cel.position.y - origin.y) // size.height
+end
+local pc = app.pixelColor
+local tiles, flags, flagged = {{}}, {{}}, false
+for row = region.y, region.y + region.h - 1 do
+    local tile_row, flag_row = {{}}, {{}}
+    for col = region.x, region.x + region.w - 1 do
+        local value = 0
+        if img then
+            local ix, iy = col - cx, row - cy
+            if ix >= 0 and iy >= 0 and ix < img.width and iy < img.height then
+                value = img:getPixel(ix, iy)
+            end
+        end
+        local f = pc.tileF(value)
+        local flag = ""
+        if f & {flag_x} ~= 0 then flag = flag .. "x" end
+        if f & {flag_y} ~= 0 then flag = flag .. "y" end
+        if f & {flag_d} ~= 0 then flag = flag .. "d" end
+        if flag ~= "" then flagged = true end
+        tile_row[#tile_row + 1] = pc.tileI(value)
+        flag_row[#flag_row + 1] = flag
+    end
+    tiles[#tiles + 1] = tile_row
+    flags[#flags + 1] = flag_row
+end
+local result = {{
+    layer = layer.name,
+    tileset = ts.name,
+    frame = frame.frameNumber,
+    grid = {{columns = cols, rows = rows, tile_width = size.width, tile_height = size.height, origin = {{x = origin.x, y = origin.y}}}},
+    region = {{x = region.x, y = region.y, width = region.w, height = region.h}},
+    tiles = tiles
+}}
+if flagged then result.flags = flags end
+print(json.encode(result))"#,
+        check_frame = lua_check_frame(p.frame.unwrap_or(1)),
+        require_layer = lua_require_layer("layer", &p.layer),
+        find_layer = LUA_FIND_LAYER,
+        frame = p.frame.unwrap_or(1),
+        region = region,
+        flag_x = TILE_FLAG_X,
+        flag_y = TILE_FLAG_Y,
+        flag_d = TILE_FLAG_D,
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn export_tileset(server: &AsepriteServer, p: ExportTilesetParams) -> Result<String, String> {
+    if p.columns == Some(0) {
+        return Err("columns must be at least 1".to_string());
+    }
+    let output = server.resolve_output_path(&p.output_path)?;
+    let sidecar = Path::new(&output).with_extension("json");
+    let padding = p.padding.unwrap_or(0);
+    let script = format!(
+        r#"local spr = app.sprite
+{find_tileset}
+local ts = find_tileset(spr, {key})
+if not ts then
+    print(json.encode({{error = "Tileset not found: " .. tostring({key})}}))
+    return
+end
+local size = ts.grid.tileSize
+local count = #ts
+local cols = math.min({columns}, count)
+local rows = math.ceil(count / cols)
+local pad = {padding}
+local sheet = Image(cols * size.width + (cols - 1) * pad, rows * size.height + (rows - 1) * pad, spr.colorMode)
+for i = 0, count - 1 do
+    local x = (i % cols) * (size.width + pad)
+    local y = (i // cols) * (size.height + pad)
+    sheet:drawImage(ts:getTile(i), Point(x, y))
+end
+sheet:saveAs{{ filename = {out}, palette = spr.palettes[1] }}
+print(json.encode({{
+    tileset = ts.name,
+    tile_width = size.width,
+    tile_height = size.height,
+    tile_count = count,
+    columns = cols,
+    rows = rows,
+    padding = pad,
+    width = sheet.width,
+    height = sheet.height
+}}))"#,
+        find_tileset = LUA_FIND_TILESET,
+        key = p.tileset.as_deref().map_or("nil".to_string(), lua_string),
+        columns = p.columns.map_or("count".to_string(), |c| c.to_string()),
+        padding = padding,
+        out = lua_path(&output),
+    );
+    let info: serde_json::Value = serde_json::from_str(&server.execute_script_on_file(&p.file_path, &script).await?)
+        .map_err(|e| format!("Unexpected output from the tileset export: {}", e))?;
+
+    // Sidecar for engine importers; tile i of the sheet is tileset index i (0 is the empty tile)
+    let image_name = Path::new(&output).file_name().map(|n| n.to_string_lossy().to_string());
+    let mut meta = info.clone();
+    meta["image"] = json!(image_name);
+    meta["first_index"] = json!(0);
+    let meta_text = serde_json::to_string_pretty(&meta).map_err(|e| e.to_string())?;
+    write_output(&sidecar, meta_text)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", sidecar.display(), e))?;
+
+    let mut result = info;
+    result["status"] = json!("exported");
+    result["output"] = json!(output);
+    result["metadata"] = json!(sidecar.to_string_lossy());
+    Ok(result.to_string())
+}
+
+/// Parse a flip flag string ("", "x", "y", "d" or combinations) into tile flag bits.
+fn parse_tile_flags(flags: &str) -> Result<u32, String> {
+    flags.chars().try_fold(0, |bits, c| match c.to_ascii_lowercase() {
+        'x' => Ok(bits | TILE_FLAG_X),
+        'y' => Ok(bits | TILE_FLAG_Y),
+        'd' => Ok(bits | TILE_FLAG_D),
+        _ => Err(format!("Invalid tile flags '{}': use any of x, y, d", flags)),
+    })
+}
+
+/// Tilesets and tilemap layers only survive in .aseprite/.ase files.
+fn require_aseprite_file(file_path: &str) -> Result<(), String> {
+    let is_sprite = Path::new(file_path)
+        .extension()
+        .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "aseprite" | "ase"));
+    if !is_sprite {
+        return Err(format!(
+            "{} is not an .aseprite file; tilesets can only be saved in .aseprite/.ase files",
+            file_path
+        ));
+    }
+    Ok(())
+}
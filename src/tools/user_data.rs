@@ -0,0 +1,226 @@
+use rmcp::schemars;
+use serde::Deserialize;
+
+use crate::aseprite::lua_string;
+use crate::lua_helpers::{LUA_FIND_LAYER, lua_find_slice, lua_require_layer, lua_require_tag, lua_save_target, with_saved_to};
+use crate::server::AsepriteServer;
+use crate::utils::parse_color;
+
+// ============================================================================
+// Parameter Structs
+// ============================================================================
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct GetUserDataParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Kind of object: "sprite", "layer", "tag", "slice", or "cel"
+    pub object_type: String,
+    /// Name of the layer, tag, or slice (not used for "sprite" and "cel")
+    pub name: Option<String>,
+    /// Layer of the cel (object_type "cel")
+    pub layer: Option<String>,
+    /// Frame of the cel, 1-based (object_type "cel")
+    pub frame: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct SetUserDataParams {
+    /// Path to the sprite file
+    pub file_path: String,
+    /// Kind of object: "sprite", "layer", "tag", "slice", or "cel"
+    pub object_type: String,
+    /// Name of the layer, tag, or slice (not used for "sprite" and "cel")
+    pub name: Option<String>,
+    /// Layer of the cel (object_type "cel")
+    pub layer: Option<String>,
+    /// Frame of the cel, 1-based (object_type "cel")
+    pub frame: Option<u32>,
+    /// User data string to store (e.g. JSON metadata for a game engine)
+    pub data: Option<String>,
+    /// User data color as hex, CSS name, or rgb()/rgba() (e.g. "#ff000080")
+    pub color: Option<String>,
+    /// Typed key/value properties to merge into the object's `properties` (Aseprite 1.3+).
+    /// On older versions they are stored JSON-encoded in the data string instead.
+    pub properties: Option<serde_json::Map<String, serde_json::Value>>,
+    /// Save to a different path (if omitted, overwrites the original)
+    pub output_path: Option<String>,
+}
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+/// Lua code resolving the selected object into `obj` and its display name into `label`.
+/// Prints an error JSON and returns if it doesn't exist.
+fn lua_find_object(
+    object_type: &str,
+    name: Option<&str>,
+    layer: Option<&str>,
+    frame: Option<u32>,
+) -> Result<String, String> {
+    let require_name = || name.ok_or_else(|| format!("name is required for object_type \"{}\"", object_type));
+    let code = match object_type {
+        "sprite" => "local obj = spr\nlocal label = app.fs.fileTitle(spr.filename)".to_string(),
+        "layer" => format!(
+            r#"{find_layer}
+{require_layer}
+local label = obj.name"#,
+            require_layer = lua_require_layer("obj", require_name()?),
+            find_layer = LUA_FIND_LAYER,
+        ),
+        "tag" => format!(
+            r#"{require_tag}
+local label = obj.name"#,
+            require_tag = lua_require_tag("obj", require_name()?)
+        ),
+        "slice" => format!(
+            "{find_slice}\nlocal obj = target_slice\nlocal label = obj.name",
+            find_slice = lua_find_slice(require_name()?)
+        ),
+        "cel" => {
+            let layer = layer.ok_or("layer is required for object_type \"cel\"")?;
+            let frame = frame.ok_or("frame is required for object_type \"cel\"")?;
+            format!(
+                r#"{find_layer}
+{require_layer}
+local obj = cel_layer:cel({frame})
+if not obj then
+    print(json.encode({{error = "No cel at frame {frame} on layer " .. {name}}}))
+    return
+end
+local label = cel_layer.name .. " @ frame {frame}""#,
+                require_layer = lua_require_layer("cel_layer", layer),
+                find_layer = LUA_FIND_LAYER,
+                name = lua_string(layer),
+                frame = frame
+            )
+        }
+        other => {
+            return Err(format!(
+                "Unknown object_type '{}'. Use \"sprite\", \"layer\", \"tag\", \"slice\", or \"cel\"",
+                other
+            ));
+        }
+    };
+    Ok(code)
+}
+
+/// Lua function reading `obj.properties` into a plain table, or nil when the running
+/// Aseprite has no typed properties (before 1.3).
+const LUA_READ_PROPERTIES: &str = r#"
+local function read_properties(obj)
+    local ok, props = pcall(function()
+        local t = {}
+        for k, v in pairs(obj.properties) do t[k] = v end
+        return t
+    end)
+    if ok then return props end
+    return nil
+end"#;
+
+pub async fn get_user_data(server: &AsepriteServer, p: GetUserDataParams) -> Result<String, String> {
+    let find = lua_find_object(&p.object_type, p.name.as_deref(), p.layer.as_deref(), p.frame)?;
+    let script = format!(
+        r##"local spr = app.sprite
+{find}
+{read_properties}
+local result = {{}}
+result.object_type = {object_type}
+result.name = label
+result.data = obj.data or ""
+local ok, c = pcall(function() return obj.color end)
+if ok and c then
+    result.color = string.format("#%02x%02x%02x%02x", c.red, c.green, c.blue, c.alpha)
+end
+local props = read_properties(obj)
+result.properties_supported = props ~= nil
+if props and next(props) then result.properties = props end
+print(json.encode(result))"##,
+        find = find,
+        read_properties = LUA_READ_PROPERTIES,
+        object_type = lua_string(&p.object_type),
+    );
+    server.execute_script_on_file(&p.file_path, &script).await
+}
+
+pub async fn set_user_data(server: &AsepriteServer, p: SetUserDataParams) -> Result<String, String> {
+    if p.data.is_none() && p.color.is_none() && p.properties.is_none() {
+        return Err("Nothing to set: provide data, color, or properties".to_string());
+    }
+    let find = lua_find_object(&p.object_type, p.name.as_deref(), p.layer.as_deref(), p.frame)?;
+    let (save_code, saved_to) = lua_save_target(server, &p.file_path, p.output_path.as_deref())?;
+
+    let mut set_code = String::new();
+    if let Some(ref color) = p.color {
+        let (r, g, b, a) = parse_color(color).map_err(|e| format!("Invalid color '{}': {}", color, e))?;
+        set_code.push_str(&format!(
+            r#"if not pcall(function() obj.color = Color({r}, {g}, {b}, {a}) end) then
+    print(json.encode({{error = "This Aseprite version can't store a user data color on a " .. {object_type}}}))
+    return
+end
+"#,
+            r = r,
+            g = g,
+            b = b,
+            a = a,
+            object_type = lua_string(&p.object_type)
+        ));
+    }
+    if let Some(ref data) = p.data {
+        set_code.push_str(&format!("obj.data = {}\n", lua_string(data)));
+    }
+    // Typed properties need Aseprite 1.3; older versions get the JSON in the data string,
+    // which only works when the caller isn't also setting `data` explicitly.
+    if let Some(ref props) = p.properties {
+        let encoded = serde_json::Value::Object(props.clone()).to_string();
+        set_code.push_str(&format!(
+            r#"local new_props = json.decode({encoded})
+local stored = pcall(function()
+    for k, v in pairs(new_props) do obj.properties[k] = v end
+end)
+if stored then
+    storage = "properties"
+elseif {has_data} then
+    print(json.encode({{error = "This Aseprite version has no typed properties, and data is already being set; pass the properties as JSON in data instead"}}))
+    return
+else
+    obj.data = {encoded}
+end
+"#,
+            encoded = lua_string(&encoded),
+            has_data = p.data.is_some()
+        ));
+    }
+
+    let script = format!(
+        r##"local spr = app.sprite
+{find}
+{read_properties}
+local storage = "data"
+{set_code}
+{save}
+local result = {{}}
+result.object_type = {object_type}
+result.name = label
+result.data = obj.data or ""
+local ok, c = pcall(function() return obj.color end)
+if ok and c then
+    result.color = string.format("#%02x%02x%02x%02x", c.red, c.green, c.blue, c.alpha)
+end
+local props = read_properties(obj)
+if props and next(props) then result.properties = props end
+result.storage = storage
+result.status = "updated"
+print(json.encode(result))"##,
+        find = find,
+        read_properties = LUA_READ_PROPERTIES,
+        set_code = set_code,
+        object_type = lua_string(&p.object_type),
+        save = save_code,
+    );
+    server
+        .execute_script_on_file(&p.file_path, &script)
+        .await
+        .map(|output| with_saved_to(output, &saved_to))
+}
@@ -0,0 +1,241 @@
+use rmcp::schemars;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::aseprite::is_dry_run;
+use crate::backup;
+use crate::server::AsepriteServer;
+
+// ============================================================================
+// Parameter Structs
+// ============================================================================
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct ListFilesParams {
+    /// Glob relative to the workspace root, e.g. "sprites/*.aseprite" (default: "**/*", everything)
+    pub pattern: Option<String>,
+    /// Only list files with these extensions, e.g. [".aseprite", ".png"] (case-insensitive)
+    pub extensions: Option<Vec<String>>,
+    /// Maximum number of files to return (default: 100, max: 1000)
+    pub limit: Option<usize>,
+    /// Number of matching files to skip, for paging through large folders (default: 0)
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct StatFileParams {
+    /// File or directory to check. Relative paths are looked up like tool inputs
+    /// (workspace dir, output dir, then working directory)
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct RestoreBackupParams {
+    /// The file whose backups to list or restore
+    pub file_path: String,
+    /// Backup to restore (file name or path from the listing). Omit to just list backups
+    pub backup: Option<String>,
+}
+
+// ============================================================================
+// Tool Implementations
+// ============================================================================
+
+pub async fn list_files(server: &AsepriteServer, p: ListFilesParams) -> Result<String, String> {
+    let pattern = p.pattern.unwrap_or_else(|| "**/*".to_string());
+    let pattern_path = Path::new(&pattern);
+    if !pattern_path.is_relative() || pattern_path.components().any(|c| c == Component::ParentDir) {
+        return Err("pattern must be relative to the workspace root and cannot contain '..'".to_string());
+    }
+    let limit = p.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = p.offset.unwrap_or(0);
+    let extensions: Vec<String> = p
+        .extensions
+        .unwrap_or_default()
+        .iter()
+        .map(|ext| ext.trim_start_matches('.').to_lowercase())
+        .collect();
+
+    let root = server.workspace_root();
+    server.check_path_allowed(&root)?;
+    let canonical_root = root
+        .canonicalize()
+        .map_err(|e| format!("Workspace root {} is not accessible: {}", root.display(), e))?;
+    let full_pattern = canonical_root.join(&pattern).to_string_lossy().to_string();
+    let entries = glob::glob(&full_pattern).map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+
+    let mut files: Vec<(String, std::fs::Metadata)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|path| {
+            extensions.is_empty()
+                || path
+                    .extension()
+                    .is_some_and(|ext| extensions.contains(&ext.to_string_lossy().to_lowercase()))
+        })
+        .filter(|path| !path.components().any(|c| c.as_os_str() == backup::BACKUP_DIR))
+        // Symlinks pointing outside the root are skipped
+        .filter(|path| path.canonicalize().is_ok_and(|real| real.starts_with(&canonical_root)))
+        .filter_map(|path| {
+            let meta = std::fs::metadata(&path).ok().filter(|m| m.is_file())?;
+            let relative = path.strip_prefix(&canonical_root).ok()?.to_string_lossy().replace('\\', "/");
+            Some((relative, meta))
+        })
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let total = files.len();
+    let page: Vec<serde_json::Value> = files
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(path, meta)| {
+            json!({
+                "path": path,
+                "size": meta.len(),
+                "modified": modified_secs(meta),
+            })
+        })
+        .collect();
+    let has_more = offset + page.len() < total;
+    Ok(json!({
+        "root": root.to_string_lossy(),
+        "total": total,
+        "offset": offset,
+        "count": page.len(),
+        "has_more": has_more,
+        "files": page,
+    })
+    .to_string())
+}
+
+pub async fn stat_file(server: &AsepriteServer, p: StatFileParams) -> Result<String, String> {
+    let path = Path::new(&p.path);
+    let candidates: Vec<PathBuf> = if path.is_relative() {
+        server.input_search_dirs().iter().map(|dir| dir.join(path)).collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    // Check the sandbox before touching the disk, so whether a path outside it exists stays hidden
+    let allowed: Vec<&PathBuf> = candidates.iter().filter(|c| server.check_path_allowed(c).is_ok()).collect();
+    if allowed.is_empty() {
+        server.check_path_allowed(&candidates[0])?;
+    }
+    let Some(found) = allowed.into_iter().find(|candidate| candidate.exists()) else {
+        let mut result = json!({ "path": p.path, "exists": false });
+        if path.is_relative() {
+            let searched: Vec<String> = server
+                .input_search_dirs()
+                .iter()
+                .map(|d| d.display().to_string())
+                .collect();
+            result["searched"] = json!(searched);
+        }
+        return Ok(result.to_string());
+    };
+    let meta = std::fs::metadata(found).map_err(|e| format!("Failed to read {}: {}", found.display(), e))?;
+    Ok(json!({
+        "path": found.to_string_lossy(),
+        "exists": true,
+        "is_dir": meta.is_dir(),
+        "size": meta.len(),
+        "modified": modified_secs(&meta),
+    })
+    .to_string())
+}
+
+pub async fn restore_backup(server: &AsepriteServer, p: RestoreBackupParams) -> Result<String, String> {
+    // The file itself may be gone, so resolve it like an output path
+    let file_path = match server.resolve_input_path(&p.file_path) {
+        Ok(path) => path,
+        Err(_) => server.resolve_output_path(&p.file_path)?,
+    };
+    let path = Path::new(&file_path);
+    let backups = backup::list_backups(path);
+
+    let Some(wanted) = p.backup else {
+        let listing: Vec<serde_json::Value> = backups
+            .iter()
+            .map(|(backup, millis)| {
+                json!({
+                    "name": backup.file_name().map(|n| n.to_string_lossy().to_string()),
+                    "path": backup.to_string_lossy(),
+                    "created": (millis / 1000) as u64,
+                    "size": std::fs::metadata(backup).map(|m| m.len()).ok(),
+                })
+            })
+            .collect();
+        return Ok(json!({ "file": file_path, "backups": listing }).to_string());
+    };
+    let chosen = backups
+        .iter()
+        .find(|(backup, _)| {
+            backup.file_name().is_some_and(|n| n.to_string_lossy() == wanted.as_str())
+                || backup.to_string_lossy() == wanted.as_str()
+        })
+        .map(|(backup, _)| backup.clone())
+        .ok_or_else(|| format!("No backup named '{}' for {}", wanted, file_path))?;
+
+    if is_dry_run() {
+        return Ok(json!({
+            "status": "dry_run",
+            "file": file_path,
+            "would_restore_from": chosen.to_string_lossy(),
+            "note": "Nothing was restored or backed up.",
+        })
+        .to_string());
+    }
+    // Read the backup first: backing up the current file may prune it
+    let contents = tokio::fs::read(&chosen)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", chosen.display(), e))?;
+    let _guards = server.lock_files([path]).await;
+    let previous = if path.is_file() {
+        Some(backup::backup_file(path, server.backup_keep()).await?)
+    } else {
+        None
+    };
+    tokio::fs::write(path, contents)
+        .await
+        .map_err(|e| format!("Failed to restore {}: {}", chosen.display(), e))?;
+    Ok(json!({
+        "status": "restored",
+        "file": file_path,
+        "restored_from": chosen.to_string_lossy(),
+        "previous_version_backup": previous.map(|b| b.to_string_lossy().to_string()),
+    })
+    .to_string())
+}
+
+/// Modification time as Unix seconds, if the platform reports one.
+fn modified_secs(meta: &std::fs::Metadata) -> Option<u64> {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, scratch_dir};
+
+    #[tokio::test]
+    async fn stat_file_hides_whether_paths_outside_the_sandbox_exist() {
+        let dir = scratch_dir("stat_sandbox").canonicalize().unwrap();
+        let mut server = dry_server();
+        server.set_allowed_dirs(vec![dir.join("inside")]);
+        std::fs::create_dir_all(dir.join("inside")).unwrap();
+        std::fs::write(dir.join("outside.png"), "png").unwrap();
+        let stat = |path: PathBuf| stat_file(&server, StatFileParams { path: path.to_string_lossy().to_string() });
+
+        let existing = stat(dir.join("outside.png")).await.unwrap_err();
+        let missing = stat(dir.join("missing.png")).await.unwrap_err();
+        assert!(existing.starts_with("Access denied"), "{}", existing);
+        assert!(missing.starts_with("Access denied"), "{}", missing);
+
+        let result: serde_json::Value = serde_json::from_str(&stat(dir.join("inside/missing.png")).await.unwrap()).unwrap();
+        assert_eq!(result["exists"], false);
+    }
+}
@@ -1,39 +1,686 @@
-/// Validate a hex color string format. Returns Ok(()) if valid, Err with message otherwise.
-pub fn validate_hex_color(hex: &str) -> Result<(), String> {
-    let h = hex.trim_start_matches('#');
-    if h.len() != 6 && h.len() != 8 {
-        return Err(format!(
-            "expected 6 or 8 hex digits (got {}), format: #rrggbb or #rrggbbaa",
-            h.len()
-        ));
+use std::path::{Path, PathBuf};
+
+use crate::aseprite::is_dry_run;
+use crate::server::AsepriteServer;
+
+/// Parse a color given as hex (`#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`; the 6- and 8-digit forms may omit `#`),
+/// a CSS color name (`red`, `cornflowerblue`, `transparent`), or a CSS functional form
+/// (`rgb(255, 0, 0)`, `rgba(255 0 0 / 50%)`, `rgba(100%, 0%, 0%, 0.5)`) into (r, g, b, a).
+pub fn parse_color(color: &str) -> Result<(u8, u8, u8, u8), String> {
+    let s = color.trim().to_ascii_lowercase();
+    if let Some(args) = s
+        .strip_prefix("rgba(")
+        .or_else(|| s.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        return parse_rgb_function(args);
+    }
+    if s == "transparent" {
+        return Ok((0, 0, 0, 0));
+    }
+    if let Some(&(_, rgb)) = CSS_COLOR_NAMES.iter().find(|(name, _)| *name == s) {
+        return Ok(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255));
+    }
+
+    let hex = s.strip_prefix('#').unwrap_or(&s);
+    // Short forms need the '#', so words like "add" or "beef" aren't read as colors
+    if hex.len() == s.len() && (hex.len() == 3 || hex.len() == 4) {
+        return Err(format!("'{}' is not a color; write short hex colors with a '#' (e.g. \"#{}\")", color.trim(), hex));
+    }
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(
+            "not a hex color, CSS color name, or rgb()/rgba() value (e.g. \"#ff8800\", \"orange\", \"rgb(255, 136, 0)\")"
+                .to_string(),
+        );
     }
-    if !h.chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err("contains non-hex characters".to_string());
+    let digit = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).unwrap_or(0) * 17;
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    match hex.len() {
+        3 => Ok((digit(0), digit(1), digit(2), 255)),
+        4 => Ok((digit(0), digit(1), digit(2), digit(3))),
+        6 => Ok((byte(0), byte(2), byte(4), 255)),
+        8 => Ok((byte(0), byte(2), byte(4), byte(6))),
+        n => Err(format!(
+            "expected 3, 4, 6, or 8 hex digits (got {}), format: #rgb, #rrggbb, or #rrggbbaa",
+            n
+        )),
     }
-    Ok(())
 }
 
-/// Parse a hex color string (#rrggbb) to (r, g, b).
-/// Assumes input has been validated with `validate_hex_color`.
-pub fn parse_hex_color(hex: &str) -> (u8, u8, u8) {
-    let hex = hex.trim_start_matches('#');
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-    (r, g, b)
+/// Parse the arguments of `rgb()`/`rgba()`: three channels (0-255 or percentages) and an optional
+/// alpha (0-1 or a percentage), separated by commas or spaces, with `/` before the alpha allowed.
+fn parse_rgb_function(args: &str) -> Result<(u8, u8, u8, u8), String> {
+    let parts: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 3 && parts.len() != 4 {
+        return Err(format!("rgb()/rgba() needs 3 or 4 values (got {})", parts.len()));
+    }
+    let number = |p: &str| -> Result<f64, String> {
+        p.trim_end_matches('%')
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("'{}' is not a number", p))
+    };
+    let mut channels = [0u8; 3];
+    for (channel, p) in channels.iter_mut().zip(&parts) {
+        let v = number(p)?;
+        let v = if p.ends_with('%') { v * 255.0 / 100.0 } else { v };
+        if !(0.0..=255.0).contains(&v) {
+            return Err(format!("channel '{}' is outside 0-255 (or 0%-100%)", p));
+        }
+        *channel = v.round() as u8;
+    }
+    let alpha = match parts.get(3) {
+        Some(p) => {
+            let v = number(p)?;
+            let v = if p.ends_with('%') { v / 100.0 } else { v };
+            if !(0.0..=1.0).contains(&v) {
+                return Err(format!("alpha '{}' is outside 0-1 (or 0%-100%)", p));
+            }
+            (v * 255.0).round() as u8
+        }
+        None => 255,
+    };
+    Ok((channels[0], channels[1], channels[2], alpha))
 }
 
-/// Parse a hex color string (#rrggbb or #rrggbbaa) to (r, g, b, a).
-/// Assumes input has been validated with `validate_hex_color`.
-pub fn parse_hex_color_with_alpha(hex: &str) -> (u8, u8, u8, u8) {
-    let hex = hex.trim_start_matches('#');
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-    let a = if hex.len() >= 8 {
-        u8::from_str_radix(&hex[6..8], 16).unwrap_or(255)
+/// CSS named colors (CSS Color Module Level 4) as 0xRRGGBB.
+const CSS_COLOR_NAMES: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff), ("antiquewhite", 0xfaebd7), ("aqua", 0x00ffff), ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff), ("beige", 0xf5f5dc), ("bisque", 0xffe4c4), ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd), ("blue", 0x0000ff), ("blueviolet", 0x8a2be2), ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887), ("cadetblue", 0x5f9ea0), ("chartreuse", 0x7fff00), ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50), ("cornflowerblue", 0x6495ed), ("cornsilk", 0xfff8dc), ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff), ("darkblue", 0x00008b), ("darkcyan", 0x008b8b), ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9), ("darkgreen", 0x006400), ("darkgrey", 0xa9a9a9), ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b), ("darkolivegreen", 0x556b2f), ("darkorange", 0xff8c00), ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000), ("darksalmon", 0xe9967a), ("darkseagreen", 0x8fbc8f), ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f), ("darkslategrey", 0x2f4f4f), ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3), ("deeppink", 0xff1493), ("deepskyblue", 0x00bfff), ("dimgray", 0x696969),
+    ("dimgrey", 0x696969), ("dodgerblue", 0x1e90ff), ("firebrick", 0xb22222), ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22), ("fuchsia", 0xff00ff), ("gainsboro", 0xdcdcdc), ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700), ("goldenrod", 0xdaa520), ("gray", 0x808080), ("green", 0x008000),
+    ("greenyellow", 0xadff2f), ("grey", 0x808080), ("honeydew", 0xf0fff0), ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c), ("indigo", 0x4b0082), ("ivory", 0xfffff0), ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa), ("lavenderblush", 0xfff0f5), ("lawngreen", 0x7cfc00), ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6), ("lightcoral", 0xf08080), ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2), ("lightgray", 0xd3d3d3), ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3), ("lightpink", 0xffb6c1), ("lightsalmon", 0xffa07a), ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa), ("lightslategray", 0x778899), ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de), ("lightyellow", 0xffffe0), ("lime", 0x00ff00), ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6), ("magenta", 0xff00ff), ("maroon", 0x800000), ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd), ("mediumorchid", 0xba55d3), ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371), ("mediumslateblue", 0x7b68ee), ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc), ("mediumvioletred", 0xc71585), ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa), ("mistyrose", 0xffe4e1), ("moccasin", 0xffe4b5), ("navajowhite", 0xffdead),
+    ("navy", 0x000080), ("oldlace", 0xfdf5e6), ("olive", 0x808000), ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500), ("orangered", 0xff4500), ("orchid", 0xda70d6), ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98), ("paleturquoise", 0xafeeee), ("palevioletred", 0xdb7093), ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9), ("peru", 0xcd853f), ("pink", 0xffc0cb), ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6), ("purple", 0x800080), ("rebeccapurple", 0x663399), ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f), ("royalblue", 0x4169e1), ("saddlebrown", 0x8b4513), ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460), ("seagreen", 0x2e8b57), ("seashell", 0xfff5ee), ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0), ("skyblue", 0x87ceeb), ("slateblue", 0x6a5acd), ("slategray", 0x708090),
+    ("slategrey", 0x708090), ("snow", 0xfffafa), ("springgreen", 0x00ff7f), ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c), ("teal", 0x008080), ("thistle", 0xd8bfd8), ("tomato", 0xff6347), ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee), ("wheat", 0xf5deb3), ("white", 0xffffff), ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00), ("yellowgreen", 0x9acd32),
+];
+
+/// Convert RGB to HSL, each component in 0-1.
+pub fn rgb_to_hsl(r: u8, g: u8, b: u8) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    let d = max - min;
+    if d == 0.0 {
+        return (0.0, 0.0, l);
+    }
+    let s = if l > 0.5 { d / (2.0 - max - min) } else { d / (max + min) };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
     } else {
-        255
+        (r - g) / d + 4.0
     };
-    (r, g, b, a)
+    (h / 6.0, s, l)
+}
+
+/// Convert HSL (each component in 0-1, hue wrapping) to RGB.
+pub fn hsl_to_rgb(h: f64, s: f64, l: f64) -> (u8, u8, u8) {
+    let to_u8 = |v: f64| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+    if s == 0.0 {
+        return (to_u8(l), to_u8(l), to_u8(l));
+    }
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let hue = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        }
+    };
+    (to_u8(hue(h + 1.0 / 3.0)), to_u8(hue(h)), to_u8(hue(h - 1.0 / 3.0)))
+}
+
+/// Check a numeric parameter against its accepted range. With `lenient` an out-of-range value is
+/// clamped instead; otherwise the error names the parameter, the value, and the range, so a
+/// client can correct the call.
+pub fn check_range<T: PartialOrd + Copy + std::fmt::Display>(
+    name: &str,
+    value: T,
+    range: std::ops::RangeInclusive<T>,
+    lenient: bool,
+) -> Result<T, String> {
+    let (min, max) = (*range.start(), *range.end());
+    if range.contains(&value) {
+        Ok(value)
+    } else if lenient {
+        Ok(if value < min { min } else { max })
+    } else {
+        Err(format!("{} must be between {} and {} (got {})", name, min, max, value))
+    }
+}
+
+/// Map a named option (case-insensitive) to its value. With `lenient` an unknown name falls back
+/// to the first choice, the default; otherwise the error lists the accepted names.
+pub fn check_choice<T: Copy>(name: &str, value: &str, choices: &[(&str, T)], lenient: bool) -> Result<T, String> {
+    if let Some((_, mapped)) = choices.iter().find(|(choice, _)| choice.eq_ignore_ascii_case(value)) {
+        return Ok(*mapped);
+    }
+    match choices.first() {
+        Some((_, default)) if lenient => Ok(*default),
+        _ => Err(format!(
+            "Invalid {} '{}'. Must be one of: {}",
+            name,
+            value,
+            choices.iter().map(|(choice, _)| *choice).collect::<Vec<_>>().join(", ")
+        )),
+    }
+}
+
+/// Easing curve for interpolating between two keyframes (quadratic in and out).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Parse "linear", "ease_in", "ease_out", or "ease_in_out".
+    pub fn parse(name: &str) -> Result<Self, String> {
+        check_choice(
+            "easing",
+            name,
+            &[
+                ("linear", Easing::Linear),
+                ("ease_in", Easing::EaseIn),
+                ("ease_out", Easing::EaseOut),
+                ("ease_in_out", Easing::EaseInOut),
+            ],
+            false,
+        )
+    }
+
+    /// Map linear progress `t` (0-1) to eased progress.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut if t < 0.5 => 2.0 * t * t,
+            Easing::EaseInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+        }
+    }
+}
+
+/// Return the error a Lua script reported by printing an `{"error": ...}` JSON object, which it
+/// does before returning early. The object may follow other printed lines. When it carries more
+/// fields than the message (e.g. partial results), they are appended as JSON.
+pub fn script_error(output: &str) -> Option<String> {
+    let output = output.trim();
+    let json = serde_json::from_str::<serde_json::Value>(output)
+        .ok()
+        .or_else(|| serde_json::from_str(output.lines().last()?.trim()).ok())?;
+    let obj = json.as_object()?;
+    let message = match obj.get("error")? {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    if obj.len() == 1 {
+        Some(message)
+    } else {
+        Some(format!("{}\n{}", message, json))
+    }
+}
+
+/// Decode a hex string ("ff00a0...") printed by a Lua script into bytes.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) {
+        return Err("Invalid pixel data from Aseprite (odd hex length)".to_string());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| "Invalid pixel data from Aseprite".to_string()))
+        .collect()
+}
+
+/// Make a name safe to use as a file name on every platform (Windows being the strictest):
+/// replaces reserved characters with '_', trims trailing dots/spaces, and avoids reserved device names.
+pub fn sanitize_file_name(name: &str) -> String {
+    let mut safe: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let trimmed_len = safe.trim_end_matches(['.', ' ']).len();
+    safe.truncate(trimmed_len);
+    if safe.is_empty() {
+        return "_".to_string();
+    }
+    let stem = safe.split('.').next().unwrap_or("").to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit());
+    if reserved {
+        safe.insert(0, '_');
+    }
+    safe
+}
+
+/// Read pixel dimensions from the header of a PNG, GIF, BMP, JPEG, WebP, or Aseprite file.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32);
+    let le24 = |i: usize| Some(le16(i)? | (*bytes.get(i + 2)? as u32) << 16);
+    let be32 = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+    let le32 = |i: usize| Some(u32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.get(4..6) == Some(&[0xe0, 0xa5]) {
+        // Aseprite header: file size, magic 0xA5E0, frame count, width, height
+        return Some((le16(8)?, le16(10)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if bytes.starts_with(b"BM") {
+        return Some(((le32(18)? as i32).unsigned_abs(), (le32(22)? as i32).unsigned_abs()));
+    }
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return match bytes.get(12..16)? {
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = le32(21)?;
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            _ => None,
+        };
+    }
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        // Walk JPEG segments until a start-of-frame marker
+        let mut i = 2;
+        while i + 4 <= bytes.len() {
+            if bytes[i] != 0xff {
+                return None;
+            }
+            let marker = bytes[i + 1];
+            if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                return Some((be16(i + 7)?, be16(i + 5)?));
+            }
+            i += 2 + be16(i + 2)? as usize;
+        }
+    }
+    None
+}
+
+/// Describe an output file as `{path, exists, bytes, width, height}` (dimensions when readable).
+pub async fn describe_output_file(path: &str) -> serde_json::Value {
+    match tokio::fs::read(path).await {
+        Ok(bytes) => {
+            let dims = image_dimensions(&bytes);
+            serde_json::json!({
+                "path": path,
+                "exists": true,
+                "bytes": bytes.len(),
+                "width": dims.map(|d| d.0),
+                "height": dims.map(|d| d.1),
+            })
+        }
+        Err(_) => serde_json::json!({
+            "path": path,
+            "exists": false,
+            "bytes": 0,
+            "width": null,
+            "height": null,
+        }),
+    }
+}
+
+/// Maximum size of an image returned inline (base64) in a tool result.
+pub const MAX_INLINE_IMAGE_BYTES: u64 = 2 * 1024 * 1024;
+
+/// Return the MIME type for a raster image path based on its extension, if it can be inlined.
+pub fn image_mime_type(path: &str) -> Option<&'static str> {
+    let ext = std::path::Path::new(path)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    match ext.as_str() {
+        "png" => Some("image/png"),
+        "gif" => Some("image/gif"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "bmp" => Some("image/bmp"),
+        _ => None,
+    }
+}
+
+/// Read an image file and encode it as base64 for inline MCP image content.
+/// Returns `Err` with a human-readable reason when the file can't be inlined.
+pub async fn read_image_base64(path: &str) -> Result<(String, &'static str), String> {
+    use base64::Engine;
+    let mime = image_mime_type(path)
+        .ok_or_else(|| format!("'{}' is not a raster format that can be returned inline", path))?;
+    let size = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("Failed to read '{}': {}", path, e))?
+        .len();
+    if size > MAX_INLINE_IMAGE_BYTES {
+        return Err(format!(
+            "'{}' is {} bytes, over the {} byte inline limit",
+            path, size, MAX_INLINE_IMAGE_BYTES
+        ));
+    }
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+    Ok((base64::engine::general_purpose::STANDARD.encode(bytes), mime))
+}
+
+/// Expand input paths, treating entries with glob characters as patterns. Keeps order, drops duplicates.
+pub fn expand_inputs(server: &AsepriteServer, inputs: &[String]) -> Result<Vec<String>, String> {
+    let mut files: Vec<String> = Vec::new();
+    for input in inputs {
+        if input.contains(['*', '?', '[']) {
+            // Relative globs are matched in the same directories as other relative inputs
+            let patterns: Vec<String> = if Path::new(input).is_relative() {
+                server
+                    .input_search_dirs()
+                    .iter()
+                    .map(|dir| dir.join(input).to_string_lossy().to_string())
+                    .collect()
+            } else {
+                vec![input.clone()]
+            };
+            let mut matched: Vec<String> = Vec::new();
+            for pattern in &patterns {
+                let paths = glob::glob(pattern).map_err(|e| format!("Invalid glob '{}': {}", input, e))?;
+                matched = paths
+                    .filter_map(|entry| entry.ok())
+                    .filter(|path| path.is_file())
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect();
+                if !matched.is_empty() {
+                    break;
+                }
+            }
+            if matched.is_empty() {
+                return Err(format!("No files match '{}' (searched: {})", input, patterns.join(", ")));
+            }
+            for path in &matched {
+                server.check_path_allowed(Path::new(path))?;
+            }
+            matched.sort();
+            files.extend(matched);
+        } else {
+            let path = server.resolve_input_path(input)?;
+            if !Path::new(&path).is_file() {
+                return Err(format!("Input not found: {}", input));
+            }
+            files.push(path);
+        }
+    }
+    // The same file reached through different relative paths or symlinks is processed once
+    let mut seen = std::collections::HashSet::new();
+    files.retain(|f| seen.insert(std::fs::canonicalize(f).unwrap_or_else(|_| PathBuf::from(f))));
+    if files.is_empty() {
+        return Err("No inputs given".to_string());
+    }
+    Ok(files)
+}
+
+/// Files a multi-file tool works on: the one `file_path` it was always given, or the expanded
+/// `file_paths` / `glob` (see `resolve_file_list`).
+pub enum FileTargets {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Resolve exactly one of `file_path`, `file_paths`, or `glob` into the files to process. A
+/// pattern that matches nothing is an error. `output_path` only works with a single file, since
+/// every file of a list is saved in place.
+pub fn resolve_file_list(
+    server: &AsepriteServer,
+    file_path: Option<&str>,
+    file_paths: Option<&[String]>,
+    glob: Option<&str>,
+    output_path: Option<&str>,
+) -> Result<FileTargets, String> {
+    match (file_path, file_paths, glob) {
+        (Some(path), None, None) => Ok(FileTargets::One(path.to_string())),
+        (None, Some(_), None) | (None, None, Some(_)) if output_path.is_some() => {
+            Err("output_path only works with file_path; files from file_paths or glob are saved in place".to_string())
+        }
+        (None, Some(paths), None) => expand_inputs(server, paths).map(FileTargets::Many),
+        (None, None, Some(pattern)) => expand_inputs(server, &[pattern.to_string()]).map(FileTargets::Many),
+        (None, None, None) => Err("Provide file_path, file_paths, or glob".to_string()),
+        _ => Err("Use only one of file_path, file_paths, or glob".to_string()),
+    }
+}
+
+impl FileTargets {
+    /// Run `op` on each file in turn. A single file returns `op`'s own result; a list keeps
+    /// going past failures and reports every file's result or error, failing only when no
+    /// file succeeded.
+    pub async fn run<F, Fut>(self, mut op: F) -> Result<String, String>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<String, String>>,
+    {
+        let files = match self {
+            FileTargets::One(path) => return op(path).await,
+            FileTargets::Many(files) => files,
+        };
+        let mut results = Vec::with_capacity(files.len());
+        let mut failed = 0;
+        for file in files {
+            let entry = match op(file.clone()).await {
+                Ok(output) => {
+                    let result = serde_json::from_str(&output).unwrap_or(serde_json::Value::String(output));
+                    serde_json::json!({ "file_path": file, "result": result })
+                }
+                Err(e) => {
+                    failed += 1;
+                    serde_json::json!({ "file_path": file, "error": e })
+                }
+            };
+            results.push(entry);
+        }
+        let summary = serde_json::json!({
+            "files": results.len(),
+            "succeeded": results.len() - failed,
+            "failed": failed,
+            "results": results,
+        })
+        .to_string();
+        if failed == results.len() { Err(summary) } else { Ok(summary) }
+    }
+}
+
+/// Write a file a tool produces. A dry run writes nothing (see `aseprite::dry_run`), so every
+/// Rust-side write of outputs goes through here or `create_output_dir`.
+pub async fn write_output(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    if is_dry_run() {
+        return Ok(());
+    }
+    tokio::fs::write(path, contents).await
+}
+
+/// Create a directory (and its parents) for a tool's outputs, unless this is a dry run.
+pub async fn create_output_dir(path: impl AsRef<Path>) -> std::io::Result<()> {
+    if is_dry_run() {
+        return Ok(());
+    }
+    tokio::fs::create_dir_all(path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dry_server, scratch_dir};
+
+    #[test]
+    fn easing_curves_meet_the_keyframes_and_bend_the_right_way() {
+        for easing in [Easing::Linear, Easing::EaseIn, Easing::EaseOut, Easing::EaseInOut] {
+            assert_eq!(easing.apply(0.0), 0.0, "{:?}", easing);
+            assert_eq!(easing.apply(1.0), 1.0, "{:?}", easing);
+            assert_eq!(easing.apply(-0.5), 0.0, "{:?} clamps below 0", easing);
+            assert_eq!(easing.apply(1.5), 1.0, "{:?} clamps above 1", easing);
+        }
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert_eq!(Easing::EaseIn.apply(0.5), 0.25);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.75);
+        assert_eq!(Easing::EaseInOut.apply(0.25), 0.125);
+        assert_eq!(Easing::EaseInOut.apply(0.5), 0.5);
+        assert_eq!(Easing::EaseInOut.apply(0.75), 0.875);
+        assert_eq!(Easing::parse("ease_in_out"), Ok(Easing::EaseInOut));
+        assert!(Easing::parse("bounce").is_err());
+    }
+
+    #[test]
+    fn parse_color_accepts_short_hex_names_and_rgb_functions() {
+        assert_eq!(parse_color("#f80"), Ok((255, 136, 0, 255)));
+        assert_eq!(parse_color("#f808"), Ok((255, 136, 0, 136)));
+        assert_eq!(parse_color("  #FF8800  "), Ok((255, 136, 0, 255)));
+        assert_eq!(parse_color("ff880080"), Ok((255, 136, 0, 128)));
+        assert_eq!(parse_color(" Orange "), Ok((255, 165, 0, 255)));
+        assert_eq!(parse_color("rgb( 255 , 136 , 0 )"), Ok((255, 136, 0, 255)));
+        assert_eq!(parse_color("rgba(100%, 0%, 50%, 50%)"), Ok((255, 0, 128, 128)));
+        assert_eq!(parse_color("rgb(255 136 0 / 0.5)"), Ok((255, 136, 0, 128)));
+    }
+
+    #[test]
+    fn parse_color_rejects_bare_short_hex_and_out_of_range_values() {
+        assert!(parse_color("add").is_err());
+        assert!(parse_color("beef").is_err());
+        assert!(parse_color("#12345").is_err());
+        assert!(parse_color("rgb(256, 0, 0)").is_err());
+        assert!(parse_color("rgb(101%, 0%, 0%)").is_err());
+        assert!(parse_color("rgba(0, 0, 0, 150%)").is_err());
+    }
+
+    fn resolve(
+        file_path: Option<&str>,
+        file_paths: Option<&[String]>,
+        glob: Option<&str>,
+        output_path: Option<&str>,
+    ) -> Result<FileTargets, String> {
+        resolve_file_list(&dry_server(), file_path, file_paths, glob, output_path)
+    }
+
+    #[test]
+    fn glob_matching_nothing_is_an_error() {
+        let dir = scratch_dir("glob_no_match");
+        let pattern = dir.join("*.aseprite").to_string_lossy().to_string();
+        let err = resolve(None, None, Some(&pattern), None).err().unwrap();
+        assert!(err.starts_with("No files match"), "{}", err);
+    }
+
+    #[test]
+    fn glob_matches_are_sorted_and_deduplicated() {
+        let dir = scratch_dir("glob_matches");
+        for name in ["b.png", "a.png", "c.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let a = dir.join("a.png").to_string_lossy().to_string();
+        let pattern = dir.join("*.png").to_string_lossy().to_string();
+        let files = expand_inputs(&dry_server(), &[pattern, a.clone()]).unwrap();
+        assert_eq!(files, [a, dir.join("b.png").to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn missing_listed_file_is_an_error() {
+        let dir = scratch_dir("missing_listed");
+        let paths = [dir.join("gone.png").to_string_lossy().to_string()];
+        assert!(resolve(None, Some(&paths), None, None).is_err());
+    }
+
+    #[test]
+    fn file_arguments_are_mutually_exclusive() {
+        let paths = ["a.png".to_string()];
+        let both = resolve(Some("a.png"), None, Some("*.png"), None).err().unwrap();
+        assert_eq!(both, "Use only one of file_path, file_paths, or glob");
+        let both = resolve(Some("a.png"), Some(&paths), None, None).err().unwrap();
+        assert_eq!(both, "Use only one of file_path, file_paths, or glob");
+        let none = resolve(None, None, None, None).err().unwrap();
+        assert_eq!(none, "Provide file_path, file_paths, or glob");
+        let with_output = resolve(None, Some(&paths), None, Some("out.png")).err().unwrap();
+        assert!(with_output.starts_with("output_path only works with file_path"));
+    }
+
+    #[tokio::test]
+    async fn many_files_continue_past_failures() {
+        let files = FileTargets::Many(vec!["good.png".to_string(), "bad.png".to_string()]);
+        let summary = files
+            .run(|file| async move {
+                match file.as_str() {
+                    "good.png" => Ok(r#"{"status":"done"}"#.to_string()),
+                    _ => Err("broken".to_string()),
+                }
+            })
+            .await
+            .unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&summary).unwrap();
+        assert_eq!((summary["files"].as_u64(), summary["succeeded"].as_u64(), summary["failed"].as_u64()), (Some(2), Some(1), Some(1)));
+        assert_eq!(summary["results"][0]["result"]["status"], "done");
+        assert_eq!(summary["results"][1]["error"], "broken");
+    }
+
+    #[tokio::test]
+    async fn many_files_all_failing_is_an_error() {
+        let files = FileTargets::Many(vec!["a.png".to_string(), "b.png".to_string()]);
+        let err = files.run(|_| async { Err::<String, _>("broken".to_string()) }).await.err().unwrap();
+        let summary: serde_json::Value = serde_json::from_str(&err).unwrap();
+        assert_eq!(summary["failed"].as_u64(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn one_file_returns_its_own_result() {
+        let result = FileTargets::One("a.png".to_string()).run(|file| async move { Err::<String, _>(file) }).await;
+        assert_eq!(result, Err("a.png".to_string()));
+    }
 }